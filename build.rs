@@ -0,0 +1,24 @@
+//! Records the commit and compiler a binary was built from, for
+//! `server/info`. Builds outside a git checkout simply go without.
+
+use std::{env, process::Command};
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    if let Some(commit) = run("git", &["rev-parse", "--short=12", "HEAD"]) {
+        println!("cargo:rustc-env=EDITOR_SERVER_COMMIT={commit}");
+    }
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    if let Some(version) = run(&rustc, &["--version"]) {
+        println!("cargo:rustc-env=EDITOR_SERVER_RUSTC={version}");
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
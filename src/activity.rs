@@ -0,0 +1,167 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{Read, Seek, SeekFrom},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use crate::clock;
+
+/// Events kept for `activity/list`; older ones are dropped.
+const MAX_EVENTS: usize = 1000;
+/// Live events buffered per connection before a slow one starts missing them.
+const LIVE_BUFFER: usize = 256;
+/// How often the git reflog is checked for new commits and checkouts.
+const GIT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityEvent {
+    /// Increases by one per event, so clients can page with `since`.
+    pub id: u64,
+    /// Unix seconds.
+    pub time: u64,
+    /// Dotted event kind, e.g. `file.saved`, `git.commit`, `task.exited`.
+    pub kind: String,
+    /// Connection that caused the event, if any.
+    pub connection_id: Option<u64>,
+    pub details: Value,
+}
+
+struct Feed {
+    events: Mutex<VecDeque<ActivityEvent>>,
+    next_id: AtomicU64,
+    live: broadcast::Sender<ActivityEvent>,
+}
+
+/// Workspace-wide log of saves, git operations, task runs and connections,
+/// shared by everyone connected to the server.
+#[derive(Clone)]
+pub struct ActivityFeed {
+    feed: Arc<Feed>,
+}
+
+impl Default for ActivityFeed {
+    fn default() -> Self {
+        Self {
+            feed: Arc::new(Feed {
+                events: Mutex::default(),
+                next_id: AtomicU64::new(1),
+                live: broadcast::Sender::new(LIVE_BUFFER),
+            }),
+        }
+    }
+}
+
+impl ActivityFeed {
+    pub fn record(&self, kind: &str, connection_id: Option<u64>, details: Value) {
+        let mut events = self.lock();
+        // Allocated under the lock so ids are in list order.
+        let event = ActivityEvent {
+            id: self.feed.next_id.fetch_add(1, Ordering::Relaxed),
+            time: clock::unix_secs(),
+            kind: kind.to_string(),
+            connection_id,
+            details,
+        };
+        debug!(id = event.id, kind = %event.kind, "Activity recorded");
+        if events.len() == MAX_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event.clone());
+        // Only fails when nobody is connected.
+        let _ = self.feed.live.send(event);
+    }
+
+    /// Events after `since` (an event id), oldest first, optionally limited
+    /// to kinds starting with `kind`.
+    pub fn list(&self, since: u64, kind: Option<&str>, limit: usize) -> Vec<ActivityEvent> {
+        self.lock()
+            .iter()
+            .filter(|event| event.id > since)
+            .filter(|event| kind.is_none_or(|kind| event.kind.starts_with(kind)))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ActivityEvent> {
+        self.feed.live.subscribe()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<ActivityEvent>> {
+        self.feed.events.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Records git operations by tailing `.git/logs/HEAD`, which catches commits
+/// and checkouts made from terminals as well as through the API.
+pub fn watch_git(root: PathBuf, feed: ActivityFeed) {
+    let reflog = root.join(".git").join("logs").join("HEAD");
+    tokio::spawn(async move {
+        let mut offset = fs::metadata(&reflog).map(|m| m.len()).unwrap_or(0);
+        let mut interval = tokio::time::interval(GIT_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Ok(len) = fs::metadata(&reflog).map(|m| m.len()) else {
+                continue;
+            };
+            if len < offset {
+                offset = 0; // Reflog was rewritten (e.g. `git reflog expire`)
+            }
+            if len == offset {
+                continue;
+            }
+            match read_from(&reflog, offset) {
+                Ok(appended) => {
+                    // Only whole lines; a partial one is re-read next time.
+                    let complete = appended.rfind('\n').map_or(0, |end| end + 1);
+                    for line in appended[..complete].lines() {
+                        if let Some((kind, details)) = parse_reflog_line(line) {
+                            feed.record(&kind, None, details);
+                        }
+                    }
+                    offset += complete as u64;
+                }
+                Err(e) => warn!(error = %e, "Failed to read git reflog"),
+            }
+        }
+    });
+}
+
+fn read_from(path: &std::path::Path, offset: u64) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut appended = String::new();
+    file.read_to_string(&mut appended)?;
+    Ok(appended)
+}
+
+/// Parses `<old> <new> <name> <<email>> <time> <tz>\t<action>: <message>`.
+fn parse_reflog_line(line: &str) -> Option<(String, Value)> {
+    let (header, message) = line.split_once('\t')?;
+    let mut fields = header.splitn(3, ' ');
+    let (old, new, identity) = (fields.next()?, fields.next()?, fields.next()?);
+    let author = identity.split(" <").next().unwrap_or(identity);
+    let (action, summary) = message.split_once(": ").unwrap_or((message, ""));
+    // "commit (amend)" and "commit (initial)" are both commits.
+    let action = action.split([' ', '(']).next().unwrap_or(action);
+    Some((
+        format!("git.{action}"),
+        json!({
+            "old": old,
+            "new": new,
+            "author": author,
+            "message": summary,
+        }),
+    ))
+}
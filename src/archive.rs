@@ -0,0 +1,36 @@
+use std::{
+    fs,
+    io::{self, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+/// Files under `start` (a file or directory) that belong in an archive of
+/// the workspace: ignore files are honoured, and `.git` and the server's
+/// data directory are skipped.
+pub fn workspace_files(start: &Path, data_path: &Path) -> Vec<PathBuf> {
+    ignore::WalkBuilder::new(start)
+        .hidden(false)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(ignore::DirEntry::into_path)
+        .filter(|file| {
+            !file.starts_with(data_path) && !file.components().any(|c| c.as_os_str() == ".git")
+        })
+        .collect()
+}
+
+/// Writes `files` into a zip archive, naming each entry by its path
+/// relative to `root`.
+pub fn write_zip<W: Write + Seek>(writer: W, root: &Path, files: &[PathBuf]) -> io::Result<W> {
+    let mut archive = zip::ZipWriter::new(writer);
+    let options = zip::write::SimpleFileOptions::default();
+    for file in files {
+        let name = file.strip_prefix(root).unwrap_or(file).to_string_lossy();
+        archive
+            .start_file(name, options)
+            .map_err(io::Error::other)?;
+        io::copy(&mut fs::File::open(file)?, &mut archive)?;
+    }
+    archive.finish().map_err(io::Error::other)
+}
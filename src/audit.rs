@@ -0,0 +1,146 @@
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+use tracing::{debug, info, warn};
+
+use crate::clock;
+
+/// Entries kept in memory for `audit/query`; the log file keeps them all.
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// Increases by one per entry, so clients can page with `since`.
+    pub id: u64,
+    /// Unix seconds.
+    pub time: u64,
+    pub connection_id: u64,
+    /// Name of the authenticated identity, if the connection used a key.
+    pub identity: Option<String>,
+    pub method: String,
+    /// Paths named in the request.
+    pub paths: Vec<PathBuf>,
+    /// Bytes written, or the size of the file afterwards when the request
+    /// carried no content.
+    pub size: Option<u64>,
+}
+
+struct Log {
+    entries: VecDeque<AuditEntry>,
+    next_id: u64,
+    file: Option<File>,
+}
+
+/// Append-only record of every completed mutating request, written as
+/// JSON lines to the `auditLog` file when one is configured.
+pub struct AuditLog {
+    log: Mutex<Log>,
+}
+
+impl AuditLog {
+    pub fn new(path: Option<&Path>) -> Result<Self, String> {
+        // Ids carry on from a previous run's log.
+        let next_id = path.map_or(1, |path| last_id(path) + 1);
+        let file = match path {
+            Some(path) => {
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    fs::create_dir_all(parent).map_err(|e| {
+                        format!(
+                            "failed to create audit log directory {}: {e}",
+                            parent.display()
+                        )
+                    })?;
+                }
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("failed to open audit log {}: {e}", path.display()))?;
+                info!(path = %path.display(), "Writing audit log");
+                Some(file)
+            }
+            None => None,
+        };
+        Ok(Self {
+            log: Mutex::new(Log {
+                entries: VecDeque::new(),
+                next_id,
+                file,
+            }),
+        })
+    }
+
+    pub fn record(
+        &self,
+        connection_id: u64,
+        identity: Option<&str>,
+        method: &str,
+        paths: Vec<PathBuf>,
+        size: Option<u64>,
+    ) {
+        let mut log = self.lock();
+        let entry = AuditEntry {
+            id: log.next_id,
+            time: clock::unix_secs(),
+            connection_id,
+            identity: identity.map(str::to_string),
+            method: method.to_string(),
+            paths,
+            size,
+        };
+        log.next_id += 1;
+        debug!(id = entry.id, method, "Audit entry recorded");
+        if let Some(file) = log.file.as_mut() {
+            let line = json!(entry).to_string();
+            if let Err(e) = writeln!(file, "{line}").and_then(|_| file.flush()) {
+                warn!(error = %e, "Failed to write audit log");
+            }
+        }
+        if log.entries.len() == MAX_ENTRIES {
+            log.entries.pop_front();
+        }
+        log.entries.push_back(entry);
+    }
+
+    /// Entries after `since` (an entry id), oldest first, optionally
+    /// limited to one method and to those naming a path under `path`.
+    pub fn query(
+        &self,
+        since: u64,
+        method: Option<&str>,
+        path: Option<&Path>,
+        limit: usize,
+    ) -> Vec<AuditEntry> {
+        self.lock()
+            .entries
+            .iter()
+            .filter(|entry| entry.id > since)
+            .filter(|entry| method.is_none_or(|method| entry.method == method))
+            .filter(|entry| path.is_none_or(|path| entry.paths.iter().any(|p| p.starts_with(path))))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Log> {
+        self.log.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn last_id(path: &Path) -> u64 {
+    let Ok(text) = fs::read_to_string(path) else {
+        return 0;
+    };
+    text.lines()
+        .rev()
+        .find_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .and_then(|entry| entry["id"].as_u64())
+        .unwrap_or(0)
+}
@@ -0,0 +1,538 @@
+//! Runs protocol-level checks against a running server and prints a report.
+//!
+//! ```text
+//! conformance [--url <ws-url>] [--only <check>[,<check>...]] [--list]
+//! ```
+//!
+//! Each check opens its own connection, so one failure cannot leave state
+//! behind for the next. The checks only rely on behaviour clients depend
+//! on — JSON-RPC framing, error codes, notification and cancellation
+//! semantics, message limits — so they apply to any implementation of the
+//! protocol, not just this crate. Exits non-zero if any check fails.
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use std::{collections::HashSet, process, time::Duration};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// How long to wait for a response before failing a check.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a server must stay quiet for "no response" to hold.
+const SILENCE: Duration = Duration::from_millis(300);
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const PAYLOAD_TOO_LARGE: i64 = -32014;
+const UNSUPPORTED_PROTOCOL: i64 = -32016;
+const REQUEST_CANCELLED: i64 = -32800;
+
+const CHECKS: &[(&str, &str)] = &[
+    (
+        "parse-error",
+        "malformed JSON gets PARSE_ERROR with a null id",
+    ),
+    (
+        "invalid-request",
+        "well-formed JSON that is not a request gets INVALID_REQUEST",
+    ),
+    (
+        "empty-batch",
+        "an empty array gets a single INVALID_REQUEST",
+    ),
+    ("method-not-found", "unknown methods get METHOD_NOT_FOUND"),
+    ("invalid-params", "bad params get INVALID_PARAMS"),
+    ("id-echo", "string and numeric ids are echoed unchanged"),
+    (
+        "response-shape",
+        "responses carry jsonrpc 2.0 and exactly one of result and error",
+    ),
+    ("notifications", "requests without an id are never answered"),
+    (
+        "initialize",
+        "initialize reports a protocol version and methods",
+    ),
+    (
+        "unsupported-protocol",
+        "initialize refuses an unknown major version",
+    ),
+    (
+        "cancel-unknown",
+        "cancelling an unknown id is silently ignored",
+    ),
+    (
+        "cancel-in-flight",
+        "a cancelled request is answered exactly once",
+    ),
+    (
+        "concurrent-ids",
+        "pipelined requests each get their own response",
+    ),
+    (
+        "message-limit",
+        "messages over maxMessageBytes get PAYLOAD_TOO_LARGE",
+    ),
+    (
+        "error-catalog",
+        "server/errorCatalog lists the standard codes",
+    ),
+];
+
+enum Outcome {
+    Pass,
+    Skip(String),
+}
+
+type CheckResult = Result<Outcome, String>;
+
+struct Args {
+    url: String,
+    only: Option<Vec<String>>,
+    list: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!("usage: conformance [--url <ws-url>] [--only <check>[,<check>...]] [--list]");
+            process::exit(2);
+        }
+    };
+    if args.list {
+        for (name, description) in CHECKS {
+            println!("{name:<22} {description}");
+        }
+        return;
+    }
+
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for (name, description) in CHECKS {
+        if args
+            .only
+            .as_ref()
+            .is_some_and(|only| !only.iter().any(|only| only == name))
+        {
+            continue;
+        }
+        match run_check(name, &args.url).await {
+            Ok(Outcome::Pass) => {
+                passed += 1;
+                println!("PASS {name}: {description}");
+            }
+            Ok(Outcome::Skip(reason)) => {
+                skipped += 1;
+                println!("SKIP {name}: {reason}");
+            }
+            Err(e) => {
+                failed += 1;
+                println!("FAIL {name}: {e}");
+            }
+        }
+    }
+    println!("{passed} passed, {failed} failed, {skipped} skipped");
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut url = "ws://127.0.0.1:3000/ws".to_string();
+    let mut only = None;
+    let mut list = false;
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or_else(|| format!("{flag} needs a value"));
+        match arg.as_str() {
+            "--url" => url = value("--url")?,
+            "--only" => {
+                let names: Vec<String> = value("--only")?.split(',').map(str::to_string).collect();
+                if let Some(unknown) = names
+                    .iter()
+                    .find(|name| !CHECKS.iter().any(|(check, _)| check == name))
+                {
+                    return Err(format!("unknown check: {unknown}"));
+                }
+                only = Some(names);
+            }
+            "--list" => list = true,
+            _ => return Err(format!("unexpected argument: {arg}")),
+        }
+    }
+    Ok(Args { url, only, list })
+}
+
+async fn run_check(name: &str, url: &str) -> CheckResult {
+    let mut client = Client::connect(url).await?;
+    let result = match name {
+        "parse-error" => parse_error(&mut client).await,
+        "invalid-request" => invalid_request(&mut client).await,
+        "empty-batch" => empty_batch(&mut client).await,
+        "method-not-found" => method_not_found(&mut client).await,
+        "invalid-params" => invalid_params(&mut client).await,
+        "id-echo" => id_echo(&mut client).await,
+        "response-shape" => response_shape(&mut client).await,
+        "notifications" => notifications(&mut client).await,
+        "initialize" => initialize(&mut client).await,
+        "unsupported-protocol" => unsupported_protocol(&mut client).await,
+        "cancel-unknown" => cancel_unknown(&mut client).await,
+        "cancel-in-flight" => cancel_in_flight(&mut client).await,
+        "concurrent-ids" => concurrent_ids(&mut client).await,
+        "message-limit" => message_limit(&mut client).await,
+        "error-catalog" => error_catalog(&mut client).await,
+        _ => Err(format!("no such check: {name}")),
+    };
+    client.close().await;
+    result
+}
+
+async fn parse_error(client: &mut Client) -> CheckResult {
+    client.send("{\"jsonrpc\": \"2.0\", \"id\": 1,").await?;
+    expect_error(&client.response().await?, PARSE_ERROR, &Value::Null)?;
+    Ok(Outcome::Pass)
+}
+
+async fn invalid_request(client: &mut Client) -> CheckResult {
+    let cases = [
+        ("42", Value::Null),
+        (
+            r#"{"jsonrpc":"1.0","id":1,"method":"initialize"}"#,
+            json!(1),
+        ),
+        (r#"{"jsonrpc":"2.0","id":2}"#, json!(2)),
+        (r#"{"jsonrpc":"2.0","id":3,"method":""}"#, json!(3)),
+        (
+            r#"{"jsonrpc":"2.0","id":4,"method":"initialize","params":5}"#,
+            json!(4),
+        ),
+    ];
+    for (text, id) in cases {
+        client.send(text).await?;
+        expect_error(&client.response().await?, INVALID_REQUEST, &id)
+            .map_err(|e| format!("{text}: {e}"))?;
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn empty_batch(client: &mut Client) -> CheckResult {
+    client.send("[]").await?;
+    let response = client.response().await?;
+    if response.is_array() {
+        return Err("an empty batch was answered with an array".to_string());
+    }
+    expect_error(&response, INVALID_REQUEST, &Value::Null)?;
+    Ok(Outcome::Pass)
+}
+
+async fn method_not_found(client: &mut Client) -> CheckResult {
+    let response = client
+        .request(json!("missing"), "conformance/noSuchMethod", json!({}))
+        .await?;
+    expect_error(&response, METHOD_NOT_FOUND, &json!("missing"))?;
+    Ok(Outcome::Pass)
+}
+
+async fn invalid_params(client: &mut Client) -> CheckResult {
+    let response = client
+        .request(json!(1), "readFile", json!({ "path": 42 }))
+        .await?;
+    expect_error(&response, INVALID_PARAMS, &json!(1))?;
+    Ok(Outcome::Pass)
+}
+
+async fn id_echo(client: &mut Client) -> CheckResult {
+    for id in [
+        json!("abc"),
+        json!(""),
+        json!(0),
+        json!(-7),
+        json!(u32::MAX),
+    ] {
+        let response = client
+            .request(id.clone(), "server/capabilities", Value::Null)
+            .await?;
+        if response["id"] != id {
+            return Err(format!("sent id {id}, got {}", response["id"]));
+        }
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn response_shape(client: &mut Client) -> CheckResult {
+    let success = client
+        .request(json!(1), "server/capabilities", Value::Null)
+        .await?;
+    let failure = client
+        .request(json!(2), "conformance/noSuchMethod", Value::Null)
+        .await?;
+    for response in [&success, &failure] {
+        if response["jsonrpc"] != "2.0" {
+            return Err(format!("missing jsonrpc 2.0: {response}"));
+        }
+        let has_result = !response["result"].is_null();
+        let has_error = !response["error"].is_null();
+        if has_result == has_error {
+            return Err(format!(
+                "expected exactly one of result and error: {response}"
+            ));
+        }
+    }
+    let error = &failure["error"];
+    if !error["code"].is_i64() || !error["message"].is_string() {
+        return Err(format!(
+            "error needs an integer code and a message: {error}"
+        ));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn notifications(client: &mut Client) -> CheckResult {
+    client
+        .send(r#"{"jsonrpc":"2.0","method":"server/capabilities"}"#)
+        .await?;
+    client
+        .send(r#"{"jsonrpc":"2.0","method":"conformance/noSuchMethod"}"#)
+        .await?;
+    client
+        .send(r#"{"jsonrpc":"2.0","method":"readFile","params":{"path":42}}"#)
+        .await?;
+    client.expect_silence().await?;
+    let response = client
+        .request(json!("after"), "server/capabilities", Value::Null)
+        .await?;
+    expect_result(&response)?;
+    Ok(Outcome::Pass)
+}
+
+async fn initialize(client: &mut Client) -> CheckResult {
+    let response = client
+        .request(json!(1), "initialize", json!({ "protocolVersion": "1.0" }))
+        .await?;
+    let result = expect_result(&response)?;
+    if !result["protocolVersion"].is_string() {
+        return Err(format!("no protocolVersion in {result}"));
+    }
+    if !result["methods"].is_array() {
+        return Err(format!("no methods in {result}"));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn unsupported_protocol(client: &mut Client) -> CheckResult {
+    let response = client
+        .request(
+            json!(1),
+            "initialize",
+            json!({ "protocolVersion": "999.0" }),
+        )
+        .await?;
+    expect_error(&response, UNSUPPORTED_PROTOCOL, &json!(1))?;
+    Ok(Outcome::Pass)
+}
+
+async fn cancel_unknown(client: &mut Client) -> CheckResult {
+    client
+        .send(r#"{"jsonrpc":"2.0","method":"$/cancelRequest","params":{"id":"never-sent"}}"#)
+        .await?;
+    client.expect_silence().await?;
+    let response = client
+        .request(json!(1), "server/capabilities", Value::Null)
+        .await?;
+    expect_result(&response)?;
+    Ok(Outcome::Pass)
+}
+
+async fn cancel_in_flight(client: &mut Client) -> CheckResult {
+    client
+        .send(r#"{"jsonrpc":"2.0","id":"slow","method":"listFiles","params":{"path":"."}}"#)
+        .await?;
+    client
+        .send(r#"{"jsonrpc":"2.0","method":"$/cancelRequest","params":{"id":"slow"}}"#)
+        .await?;
+    let response = client.response().await?;
+    if response["id"] != "slow" {
+        return Err(format!("expected the response to \"slow\", got {response}"));
+    }
+    // Finishing before the cancellation arrived is fine; two answers are not.
+    if !response["error"].is_null() {
+        expect_error(&response, REQUEST_CANCELLED, &json!("slow"))?;
+    }
+    client.expect_silence().await?;
+    Ok(Outcome::Pass)
+}
+
+async fn concurrent_ids(client: &mut Client) -> CheckResult {
+    let ids: HashSet<i64> = (1..=10).collect();
+    for id in &ids {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": "server/capabilities" });
+        client.send(&request.to_string()).await?;
+    }
+    let mut answered = HashSet::new();
+    for _ in &ids {
+        let response = client.response().await?;
+        let id = response["id"]
+            .as_i64()
+            .ok_or_else(|| format!("unexpected response {response}"))?;
+        if !answered.insert(id) {
+            return Err(format!("id {id} was answered twice"));
+        }
+    }
+    if answered != ids {
+        return Err(format!("answered {answered:?} instead of 1 to 10"));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn message_limit(client: &mut Client) -> CheckResult {
+    let response = client
+        .request(json!(1), "initialize", json!({ "protocolVersion": "1.0" }))
+        .await?;
+    let Some(limit) = expect_result(&response)?["limits"]["maxMessageBytes"].as_u64() else {
+        return Ok(Outcome::Skip(
+            "initialize does not report limits.maxMessageBytes".to_string(),
+        ));
+    };
+    let padding = "x".repeat(limit as usize + 1);
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "server/capabilities",
+        "params": { "padding": padding },
+    });
+    client.send(&request.to_string()).await?;
+    let response = client.response().await?;
+    if response["id"] != json!(2) && !response["id"].is_null() {
+        return Err(format!("unexpected response {response}"));
+    }
+    if response["error"]["code"] != PAYLOAD_TOO_LARGE {
+        return Err(format!("expected PAYLOAD_TOO_LARGE, got {response}"));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn error_catalog(client: &mut Client) -> CheckResult {
+    let response = client
+        .request(json!(1), "server/errorCatalog", Value::Null)
+        .await?;
+    let result = expect_result(&response)?;
+    let codes: HashSet<i64> = result["errors"]
+        .as_array()
+        .ok_or_else(|| format!("no errors array in {result}"))?
+        .iter()
+        .filter_map(|entry| entry["code"].as_i64())
+        .collect();
+    let required = [
+        PARSE_ERROR,
+        INVALID_REQUEST,
+        METHOD_NOT_FOUND,
+        INVALID_PARAMS,
+        INTERNAL_ERROR,
+    ];
+    let missing: Vec<_> = required
+        .iter()
+        .filter(|code| !codes.contains(code))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("catalog is missing {missing:?}"));
+    }
+    Ok(Outcome::Pass)
+}
+
+fn expect_error(response: &Value, code: i64, id: &Value) -> Result<(), String> {
+    if response["error"]["code"] != code {
+        return Err(format!("expected error {code}, got {response}"));
+    }
+    if response["id"] != *id {
+        return Err(format!("expected id {id}, got {}", response["id"]));
+    }
+    Ok(())
+}
+
+fn expect_result(response: &Value) -> Result<&Value, String> {
+    if !response["error"].is_null() {
+        return Err(format!("expected a result, got {response}"));
+    }
+    Ok(&response["result"])
+}
+
+struct Client {
+    socket: Socket,
+}
+
+impl Client {
+    async fn connect(url: &str) -> Result<Self, String> {
+        let (socket, _) = connect_async(url)
+            .await
+            .map_err(|e| format!("connecting to {url}: {e}"))?;
+        Ok(Self { socket })
+    }
+
+    async fn send(&mut self, text: &str) -> Result<(), String> {
+        self.socket
+            .send(Message::Text(text.into()))
+            .await
+            .map_err(|e| format!("sending: {e}"))
+    }
+
+    async fn request(&mut self, id: Value, method: &str, params: Value) -> Result<Value, String> {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        self.send(&request.to_string()).await?;
+        let response = self.response().await?;
+        if response["id"] != id {
+            return Err(format!("sent id {id}, got response {response}"));
+        }
+        Ok(response)
+    }
+
+    /// Next response, skipping server notifications.
+    async fn response(&mut self) -> Result<Value, String> {
+        match tokio::time::timeout(RESPONSE_TIMEOUT, self.next_response()).await {
+            Ok(response) => response,
+            Err(_) => Err(format!(
+                "no response within {}s",
+                RESPONSE_TIMEOUT.as_secs()
+            )),
+        }
+    }
+
+    /// Fails if a response arrives within [`SILENCE`].
+    async fn expect_silence(&mut self) -> Result<(), String> {
+        match tokio::time::timeout(SILENCE, self.next_response()).await {
+            Ok(Ok(response)) => Err(format!("expected no response, got {response}")),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Ok(()),
+        }
+    }
+
+    async fn next_response(&mut self) -> Result<Value, String> {
+        loop {
+            let message = self
+                .socket
+                .next()
+                .await
+                .ok_or("connection closed")?
+                .map_err(|e| format!("receiving: {e}"))?;
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(frame) => return Err(format!("connection closed: {frame:?}")),
+                _ => continue,
+            };
+            let message: Value = serde_json::from_str(&text)
+                .map_err(|e| format!("invalid JSON from server: {e}"))?;
+            if message.get("method").is_none() || message.is_array() {
+                return Ok(message);
+            }
+        }
+    }
+
+    async fn close(mut self) {
+        let _ = self.socket.close(None).await;
+    }
+}
@@ -0,0 +1,278 @@
+//! Re-drives a session recorded with `editor-server --record <dir>` against a
+//! fresh server and reports responses that differ from the recording.
+//!
+//! ```text
+//! replay <recording.jsonl> [--workspace <dir>] [--server <path>] [--port <n>] [--fast]
+//! ```
+//!
+//! The server runs on a temporary copy of `--workspace` (an empty directory
+//! if omitted), and absolute paths under the recorded root are rewritten to
+//! point into it. Requests are sent with their recorded spacing unless
+//! `--fast` is given. Responses are matched by id; notifications are not
+//! compared since their interleaving depends on timing.
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    time::{Duration, Instant},
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type Stream = futures_util::stream::SplitStream<Socket>;
+
+/// How long to wait for outstanding responses after the last request.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Args {
+    recording: PathBuf,
+    workspace: Option<PathBuf>,
+    server: PathBuf,
+    port: u16,
+    fast: bool,
+}
+
+struct Frame {
+    at: Duration,
+    inbound: bool,
+    text: String,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!(
+                "usage: replay <recording.jsonl> [--workspace <dir>] [--server <path>] [--port <n>] [--fast]"
+            );
+            process::exit(2);
+        }
+    };
+    match run(&args).await {
+        Ok(0) => println!("replay matched the recording"),
+        Ok(mismatches) => {
+            println!("{mismatches} response(s) differ from the recording");
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("replay failed: {e}");
+            process::exit(2);
+        }
+    }
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut recording = None;
+    let mut workspace = None;
+    let mut server = None;
+    let mut port = 3900;
+    let mut fast = false;
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or_else(|| format!("{flag} needs a value"));
+        match arg.as_str() {
+            "--workspace" => workspace = Some(PathBuf::from(value("--workspace")?)),
+            "--server" => server = Some(PathBuf::from(value("--server")?)),
+            "--port" => {
+                let port_value = value("--port")?;
+                port = port_value
+                    .parse()
+                    .map_err(|_| format!("invalid --port value: {port_value}"))?;
+            }
+            "--fast" => fast = true,
+            _ if recording.is_none() && !arg.starts_with("--") => {
+                recording = Some(PathBuf::from(arg))
+            }
+            _ => return Err(format!("unexpected argument: {arg}")),
+        }
+    }
+    // The server binary is built next to this one.
+    let server = match server {
+        Some(server) => server,
+        None => std::env::current_exe()
+            .map_err(|e| e.to_string())?
+            .with_file_name("editor-server"),
+    };
+    Ok(Args {
+        recording: recording.ok_or("missing recording file")?,
+        workspace,
+        server,
+        port,
+        fast,
+    })
+}
+
+async fn run(args: &Args) -> Result<usize, String> {
+    let (recorded_root, frames) = load_recording(&args.recording)?;
+
+    let workspace = std::env::temp_dir().join(format!("editor-replay-{}", process::id()));
+    if let Some(source) = &args.workspace {
+        copy_dir(source, &workspace).map_err(|e| format!("copying workspace: {e}"))?;
+    } else {
+        fs::create_dir_all(&workspace).map_err(|e| e.to_string())?;
+    }
+    let workspace = fs::canonicalize(&workspace).map_err(|e| e.to_string())?;
+    let rewrite = |text: &str| match &recorded_root {
+        Some(root) => text.replace(root.as_str(), &workspace.to_string_lossy()),
+        None => text.to_string(),
+    };
+
+    let mut server = Command::new(&args.server)
+        .arg("--root")
+        .arg(&workspace)
+        .arg("--port")
+        .arg(args.port.to_string())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("starting {}: {e}", args.server.display()))?;
+
+    let result = replay(args.port, args.fast, &frames, &rewrite).await;
+
+    let _ = server.kill();
+    let _ = server.wait();
+    let _ = fs::remove_dir_all(&workspace);
+    result
+}
+
+fn load_recording(path: &Path) -> Result<(Option<String>, Vec<Frame>), String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("reading {}: {e}", path.display()))?;
+    let mut root = None;
+    let mut frames = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Value = serde_json::from_str(line)
+            .map_err(|e| format!("{}:{}: {e}", path.display(), number + 1))?;
+        if entry["type"] == "session" {
+            root = entry["root"].as_str().map(str::to_string);
+            continue;
+        }
+        frames.push(Frame {
+            at: Duration::from_millis(entry["t"].as_u64().unwrap_or(0)),
+            inbound: entry["direction"] == "in",
+            text: entry["text"].as_str().unwrap_or_default().to_string(),
+        });
+    }
+    Ok((root, frames))
+}
+
+async fn replay(
+    port: u16,
+    fast: bool,
+    frames: &[Frame],
+    rewrite: &dyn Fn(&str) -> String,
+) -> Result<usize, String> {
+    let url = format!("ws://127.0.0.1:{port}/ws");
+    let socket = connect_with_retry(&url).await?;
+    let (mut sink, mut stream) = socket.split();
+
+    // Recorded responses keyed by id, with paths rewritten like the requests.
+    let mut expected: HashMap<String, Value> = frames
+        .iter()
+        .filter(|frame| !frame.inbound)
+        .filter_map(|frame| serde_json::from_str::<Value>(&rewrite(&frame.text)).ok())
+        .filter(is_response)
+        .map(|response| (response["id"].to_string(), response))
+        .collect();
+
+    let started = Instant::now();
+    let mut received = HashMap::new();
+    for frame in frames.iter().filter(|frame| frame.inbound) {
+        if !fast && let Some(wait) = frame.at.checked_sub(started.elapsed()) {
+            drain_until(&mut stream, &mut received, Instant::now() + wait).await;
+        }
+        sink.send(Message::Text(rewrite(&frame.text).into()))
+            .await
+            .map_err(|e| format!("sending request: {e}"))?;
+    }
+    let deadline = Instant::now() + RESPONSE_TIMEOUT;
+    while expected.keys().any(|id| !received.contains_key(id)) && Instant::now() < deadline {
+        drain_until(
+            &mut stream,
+            &mut received,
+            deadline.min(Instant::now() + Duration::from_millis(50)),
+        )
+        .await;
+    }
+    let _ = sink.close().await;
+
+    let mut mismatches = 0;
+    let mut ids: Vec<_> = expected.keys().cloned().collect();
+    ids.sort();
+    for id in ids {
+        let recorded = expected.remove(&id).unwrap_or_default();
+        match received.get(&id) {
+            Some(actual) if *actual == recorded => {}
+            Some(actual) => {
+                mismatches += 1;
+                println!("id {id}: response differs");
+                println!("  recorded: {recorded}");
+                println!("  replayed: {actual}");
+            }
+            None => {
+                mismatches += 1;
+                println!("id {id}: no response (recorded: {recorded})");
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Collects responses until `deadline` or the connection closes.
+async fn drain_until(
+    stream: &mut Stream,
+    received: &mut HashMap<String, Value>,
+    deadline: Instant,
+) {
+    let deadline = tokio::time::Instant::from_std(deadline);
+    while let Ok(Some(Ok(message))) = tokio::time::timeout_at(deadline, stream.next()).await {
+        if let Message::Text(text) = message
+            && let Ok(value) = serde_json::from_str::<Value>(&text)
+            && is_response(&value)
+        {
+            received.insert(value["id"].to_string(), value);
+        }
+    }
+}
+
+fn is_response(message: &Value) -> bool {
+    message.get("id").is_some_and(|id| !id.is_null())
+        && (message.get("result").is_some() || message.get("error").is_some())
+}
+
+async fn connect_with_retry(url: &str) -> Result<Socket, String> {
+    let mut attempts = 0;
+    loop {
+        match connect_async(url).await {
+            Ok((socket, _)) => return Ok(socket),
+            Err(e) if attempts >= 50 => return Err(format!("connecting to {url}: {e}")),
+            Err(_) => {
+                attempts += 1;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+fn copy_dir(source: &Path, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let destination = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &destination)?;
+        } else {
+            fs::copy(entry.path(), destination)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tracing::{debug, info};
+
+static UPLOAD_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+struct Upload {
+    connection_id: u64,
+    temp_path: PathBuf,
+    file: fs::File,
+    hasher: blake3::Hasher,
+    size: u64,
+}
+
+/// Deduplicated content storage keyed by BLAKE3 hash, laid out as
+/// `<data dir>/blobs/<first two hex chars>/<hash>`.
+pub struct BlobStore {
+    dir: PathBuf,
+    uploads: Mutex<HashMap<u64, Upload>>,
+}
+
+impl BlobStore {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            dir: data_dir.join("blobs"),
+            uploads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn blob_path(&self, hash: &str) -> Option<PathBuf> {
+        let valid = hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit());
+        valid.then(|| self.dir.join(&hash[..2]).join(hash))
+    }
+
+    fn temp_path(&self, id: u64) -> PathBuf {
+        self.dir
+            .join("tmp")
+            .join(format!("{}-{id}", std::process::id()))
+    }
+
+    pub fn put_bytes(&self, bytes: &[u8]) -> std::io::Result<String> {
+        let hash = blake3::hash(bytes).to_hex().to_string();
+        let path = self.blob_path(&hash).expect("hash is valid hex");
+        if path.exists() {
+            debug!(hash = %hash, "Blob already stored");
+            return Ok(hash);
+        }
+        let temp_path = self.temp_path(UPLOAD_COUNTER.fetch_add(1, Ordering::Relaxed));
+        fs::create_dir_all(temp_path.parent().expect("temp path has parent"))?;
+        fs::write(&temp_path, bytes)?;
+        self.commit(&temp_path, &path)?;
+        Ok(hash)
+    }
+
+    pub fn put_file(&self, source: &Path) -> std::io::Result<String> {
+        self.put_bytes(&fs::read(source)?)
+    }
+
+    /// Stores what `fill` writes into a fresh file, without holding it in
+    /// memory, returning its hash and size.
+    pub fn put_with(
+        &self,
+        fill: impl FnOnce(fs::File) -> std::io::Result<fs::File>,
+    ) -> std::io::Result<(String, u64)> {
+        let temp_path = self.temp_path(UPLOAD_COUNTER.fetch_add(1, Ordering::Relaxed));
+        fs::create_dir_all(temp_path.parent().expect("temp path has parent"))?;
+        let stored = fill(fs::File::create(&temp_path)?).and_then(|mut file| {
+            file.flush()?;
+            drop(file);
+            let mut hasher = blake3::Hasher::new();
+            let size = std::io::copy(&mut fs::File::open(&temp_path)?, &mut hasher)?;
+            let hash = hasher.finalize().to_hex().to_string();
+            let path = self.blob_path(&hash).expect("hash is valid hex");
+            if path.exists() {
+                fs::remove_file(&temp_path)?;
+            } else {
+                self.commit(&temp_path, &path)?;
+            }
+            Ok((hash, size))
+        });
+        if stored.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        stored
+    }
+
+    pub fn open(&self, hash: &str) -> std::io::Result<fs::File> {
+        fs::File::open(self.existing_path(hash)?)
+    }
+
+    pub fn size(&self, hash: &str) -> std::io::Result<u64> {
+        Ok(fs::metadata(self.existing_path(hash)?)?.len())
+    }
+
+    pub fn read_range(&self, hash: &str, offset: u64, length: usize) -> std::io::Result<Vec<u8>> {
+        let mut file = fs::File::open(self.existing_path(hash)?)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buffer = Vec::with_capacity(length);
+        file.take(length as u64).read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Writes the blob to `dest`, replacing whatever is there. The copy is
+    /// made beside `dest` and renamed over it, so readers never see half a
+    /// file.
+    pub fn copy_to(&self, hash: &str, dest: &Path) -> std::io::Result<u64> {
+        let source = self.existing_path(hash)?;
+        let name = dest.file_name().unwrap_or_default().to_string_lossy();
+        let id = UPLOAD_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp_path = dest.with_file_name(format!(".{name}.{}-{id}.part", std::process::id()));
+        let copied = fs::copy(&source, &temp_path).and_then(|size| {
+            fs::rename(&temp_path, dest)?;
+            Ok(size)
+        });
+        if copied.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        copied
+    }
+
+    fn existing_path(&self, hash: &str) -> std::io::Result<PathBuf> {
+        self.blob_path(hash)
+            .filter(|path| path.exists())
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Blob not found"))
+    }
+
+    pub fn begin_upload(&self, connection_id: u64) -> std::io::Result<u64> {
+        let id = UPLOAD_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp_path = self.temp_path(id);
+        fs::create_dir_all(temp_path.parent().expect("temp path has parent"))?;
+        let file = fs::File::create(&temp_path)?;
+        self.lock().insert(
+            id,
+            Upload {
+                connection_id,
+                temp_path,
+                file,
+                hasher: blake3::Hasher::new(),
+                size: 0,
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn append(&self, connection_id: u64, upload_id: u64, chunk: &[u8]) -> Result<u64, String> {
+        let mut uploads = self.lock();
+        let upload = uploads
+            .get_mut(&upload_id)
+            .filter(|upload| upload.connection_id == connection_id)
+            .ok_or_else(|| format!("Upload {upload_id} not found"))?;
+        upload
+            .file
+            .write_all(chunk)
+            .map_err(|e| format!("Failed to write upload chunk: {e}"))?;
+        upload.hasher.update(chunk);
+        upload.size += chunk.len() as u64;
+        Ok(upload.size)
+    }
+
+    /// Finalizes an upload, returning its hash and size.
+    pub fn finish(&self, connection_id: u64, upload_id: u64) -> Result<(String, u64), String> {
+        let upload = {
+            let mut uploads = self.lock();
+            match uploads.get(&upload_id) {
+                Some(upload) if upload.connection_id == connection_id => {
+                    uploads.remove(&upload_id).expect("upload is present")
+                }
+                _ => return Err(format!("Upload {upload_id} not found")),
+            }
+        };
+
+        let Upload {
+            temp_path,
+            mut file,
+            hasher,
+            size,
+            ..
+        } = upload;
+        file.flush()
+            .and_then(|_| file.sync_all())
+            .map_err(|e| format!("Failed to flush upload: {e}"))?;
+        drop(file);
+
+        let hash = hasher.finalize().to_hex().to_string();
+        let path = self.blob_path(&hash).expect("hash is valid hex");
+        if path.exists() {
+            let _ = fs::remove_file(&temp_path);
+        } else {
+            self.commit(&temp_path, &path)
+                .map_err(|e| format!("Failed to store blob: {e}"))?;
+        }
+        info!(hash = %hash, size, "Blob upload completed");
+        Ok((hash, size))
+    }
+
+    /// Discards a connection's unfinished uploads; called when it closes.
+    pub fn close_connection(&self, connection_id: u64) {
+        self.lock().retain(|_, upload| {
+            if upload.connection_id == connection_id {
+                let _ = fs::remove_file(&upload.temp_path);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    fn commit(&self, temp_path: &Path, path: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(path.parent().expect("blob path has parent"))?;
+        fs::rename(temp_path, path)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Upload>> {
+        self.uploads.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,208 @@
+//! Text encodings for files that are not UTF-8.
+//!
+//! Detection looks for a byte order mark first, then recognises BOM-less
+//! UTF-16 by the zero bytes ASCII text leaves in every other position,
+//! then accepts valid UTF-8. Anything else is taken as Latin-1, which decodes
+//! every byte, so legacy files always open.
+
+use serde::{Deserialize, Serialize};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Share of code units with a zero high byte, and no more than a tenth of
+/// that with a zero low byte, for BOM-less text to be taken as UTF-16.
+const MIN_UTF16_ZERO_SHARE: f64 = 0.3;
+
+/// Most control characters, other than whitespace and escape, that a file
+/// may hold per thousand bytes and still be taken as text.
+const MAX_CONTROL_PER_MILLE: usize = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    #[serde(rename = "utf-8", alias = "utf8")]
+    Utf8,
+    #[serde(rename = "utf-16le", alias = "utf-16")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    #[serde(rename = "latin1", alias = "latin-1", alias = "iso-8859-1")]
+    Latin1,
+}
+
+impl Charset {
+    pub const ALL: [Charset; 4] = [
+        Charset::Utf8,
+        Charset::Utf16Le,
+        Charset::Utf16Be,
+        Charset::Latin1,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+            Charset::Utf16Le => "utf-16le",
+            Charset::Utf16Be => "utf-16be",
+            Charset::Latin1 => "latin1",
+        }
+    }
+
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Charset::Utf8 => UTF8_BOM,
+            Charset::Utf16Le => UTF16LE_BOM,
+            Charset::Utf16Be => UTF16BE_BOM,
+            Charset::Latin1 => &[],
+        }
+    }
+}
+
+pub struct Decoded {
+    pub text: String,
+    pub charset: Charset,
+    /// Whether the bytes started with a byte order mark, which is not part
+    /// of `text`.
+    pub bom: bool,
+}
+
+/// Decodes `bytes` as `charset`, or as whatever they look like when it is
+/// `None`.
+pub fn decode(bytes: &[u8], charset: Option<Charset>) -> Result<Decoded, String> {
+    let Some(charset) = charset else {
+        return Ok(detect(bytes));
+    };
+    let bom = !charset.bom().is_empty() && bytes.starts_with(charset.bom());
+    let body = if bom {
+        &bytes[charset.bom().len()..]
+    } else {
+        bytes
+    };
+    let text = match charset {
+        Charset::Utf8 => String::from_utf8(body.to_vec()).ok(),
+        Charset::Utf16Le => utf16(body, u16::from_le_bytes),
+        Charset::Utf16Be => utf16(body, u16::from_be_bytes),
+        Charset::Latin1 => Some(body.iter().map(|&byte| byte as char).collect()),
+    };
+    let text = text.ok_or_else(|| format!("Content is not valid {}", charset.name()))?;
+    Ok(Decoded { text, charset, bom })
+}
+
+/// `text` in `charset`, preceded by its byte order mark if `bom` is set.
+pub fn encode(text: &str, charset: Charset, bom: bool) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len() + 3);
+    if bom {
+        bytes.extend_from_slice(charset.bom());
+    }
+    match charset {
+        Charset::Utf8 => bytes.extend_from_slice(text.as_bytes()),
+        Charset::Utf16Le => text
+            .encode_utf16()
+            .for_each(|unit| bytes.extend(unit.to_le_bytes())),
+        Charset::Utf16Be => text
+            .encode_utf16()
+            .for_each(|unit| bytes.extend(unit.to_be_bytes())),
+        Charset::Latin1 => {
+            for (offset, c) in text.char_indices() {
+                let Ok(byte) = u8::try_from(u32::from(c)) else {
+                    return Err(format!(
+                        "{c:?} at byte {offset} cannot be written as latin1"
+                    ));
+                };
+                bytes.push(byte);
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+fn detect(bytes: &[u8]) -> Decoded {
+    for charset in [Charset::Utf8, Charset::Utf16Le, Charset::Utf16Be] {
+        if bytes.starts_with(charset.bom())
+            && let Ok(decoded) = decode(bytes, Some(charset))
+        {
+            return decoded;
+        }
+    }
+    // Before UTF-8, which ASCII text in UTF-16 also is.
+    if let Some(charset) = utf16_without_bom(bytes)
+        && let Ok(decoded) = decode(bytes, Some(charset))
+    {
+        return decoded;
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Decoded {
+            text: text.to_string(),
+            charset: Charset::Utf8,
+            bom: false,
+        };
+    }
+    Decoded {
+        text: bytes.iter().map(|&byte| byte as char).collect(),
+        charset: Charset::Latin1,
+        bom: false,
+    }
+}
+
+/// The charset of text starting with `head`, or `None` if it looks like
+/// binary data instead. `partial` is whether the text goes on past `head`,
+/// which may then end partway through a character.
+pub fn sniff(head: &[u8], partial: bool) -> Option<Charset> {
+    for charset in [Charset::Utf8, Charset::Utf16Le, Charset::Utf16Be] {
+        if head.starts_with(charset.bom()) {
+            return Some(charset);
+        }
+    }
+    let even = &head[..head.len() & !1];
+    if let Some(charset) = utf16_without_bom(even)
+        && decode(even, Some(charset)).is_ok()
+    {
+        return Some(charset);
+    }
+    let control = head
+        .iter()
+        .filter(|&&byte| byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    if head.contains(&0) || control * 1000 > head.len() * MAX_CONTROL_PER_MILLE {
+        return None;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => Some(Charset::Utf8),
+        Err(e) if partial && e.error_len().is_none() => Some(Charset::Utf8),
+        Err(_) => Some(Charset::Latin1),
+    }
+}
+
+fn utf16_without_bom(bytes: &[u8]) -> Option<Charset> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let units = (bytes.len() / 2) as f64;
+    let zeros = |position: usize| {
+        bytes
+            .chunks_exact(2)
+            .filter(|unit| unit[position] == 0)
+            .count() as f64
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    let looks_like =
+        |high: f64, low: f64| high / units >= MIN_UTF16_ZERO_SHARE && low * 10.0 <= high;
+    if looks_like(odd, even) {
+        Some(Charset::Utf16Le)
+    } else if looks_like(even, odd) {
+        Some(Charset::Utf16Be)
+    } else {
+        None
+    }
+}
+
+fn utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Option<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| unit([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).ok()
+}
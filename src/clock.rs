@@ -0,0 +1,38 @@
+//! Time source for everything the server reports to clients.
+//!
+//! Built with the `deterministic` feature the clock is frozen: elapsed
+//! times read as zero and wall-clock timestamps as a fixed instant, so full
+//! protocol exchanges can be snapshot-tested without spurious diffs.
+
+use std::time::Instant;
+
+/// Whether the server was built for reproducible output.
+pub const DETERMINISTIC: bool = cfg!(feature = "deterministic");
+
+/// What [`unix_secs`] reports in deterministic builds.
+#[cfg(feature = "deterministic")]
+const FROZEN_UNIX_SECS: u64 = 1_700_000_000;
+
+#[cfg(not(feature = "deterministic"))]
+pub fn now() -> Instant {
+    Instant::now()
+}
+
+#[cfg(feature = "deterministic")]
+pub fn now() -> Instant {
+    static FROZEN: std::sync::LazyLock<Instant> = std::sync::LazyLock::new(Instant::now);
+    *FROZEN
+}
+
+#[cfg(not(feature = "deterministic"))]
+pub fn unix_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(feature = "deterministic")]
+pub fn unix_secs() -> u64 {
+    FROZEN_UNIX_SECS
+}
@@ -0,0 +1,776 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::PathBuf, time::Duration};
+
+use crate::line_ending::LineEndingPolicy;
+
+/// Server configuration, loaded from an optional JSON file (`--config <path>`)
+/// and overridden by command line flags.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Config {
+    /// Workspace root that relative config globs are evaluated against.
+    pub root: PathBuf,
+    /// Name of the root in `workspace://` URIs; defaults to the root
+    /// directory's name.
+    pub root_name: Option<String>,
+    /// Further roots served next to `root`, by name. Once any are set,
+    /// request paths must name their workspace, as `name:relative/path` or
+    /// a `workspace://` URI. Also set by repeated `--workspace name=path`.
+    pub workspaces: BTreeMap<String, WorkspaceDefinition>,
+    /// TCP port to listen on.
+    pub port: u16,
+    /// When set, every connection's frames are recorded to a file in this
+    /// directory for later replay.
+    pub record_dir: Option<PathBuf>,
+    /// `text` for human-readable logs, `json` for one JSON object per line
+    /// with the fields of every enclosing span. Also set by `--log-format`.
+    pub log_format: LogFormat,
+    /// What is logged, in `RUST_LOG` syntax (`debug`,
+    /// `info,editor_server::lsp=trace`). When unset `RUST_LOG` decides,
+    /// and failing that `info`.
+    pub log_level: Option<String>,
+    /// Also write logs to a file, rotated by size and time. Also set by
+    /// `--log-file`.
+    pub log_file: LogFileConfig,
+    /// Directory of the web editor UI, served at `/` with paths that match
+    /// no file falling back to its `index.html`. Also set by `--static-dir`.
+    pub static_dir: Option<PathBuf>,
+    /// Origins, such as `https://editor.example.com`, whose pages may open
+    /// sockets and call the HTTP endpoints besides the server's own; `*`
+    /// allows any. Requests without an `Origin` header are not affected.
+    pub allowed_origins: Vec<String>,
+    /// JSON lines file that every completed mutating request is appended to.
+    pub audit_log: Option<PathBuf>,
+    /// Directory for server-owned data (blobs, history), relative to the root.
+    pub data_dir: PathBuf,
+    /// Directory of `createFromTemplate` templates, relative to the root;
+    /// `templates` in the data directory by default.
+    pub templates_dir: Option<PathBuf>,
+    /// Globs whose overwrite or deletion requires an explicit `force: true`.
+    pub protected_paths: Vec<String>,
+    /// Globs (gitignore syntax) that listings and searches leave out along
+    /// with whatever `.gitignore` and `.ignore` files exclude.
+    pub exclude: Vec<String>,
+    /// Access granted per path, checked before every file operation. The
+    /// first rule whose glob matches decides; unmatched paths are writable.
+    pub permissions: Vec<PermissionRule>,
+    /// Refuse every method that modifies the workspace, for browsing-only
+    /// deployments. Also set by `--read-only`.
+    pub read_only: bool,
+    /// Whether request paths may pass through symlinks. Symlinks leading
+    /// out of the workspace are refused regardless.
+    pub follow_symlinks: bool,
+    /// Keep accepting host paths in `readFile`, `writeFile` and `listFiles`
+    /// as protocol v1 clients send them; when off those methods only take
+    /// `workspace://` URIs.
+    pub raw_paths: bool,
+    /// What `writeFile` and `createFromTemplate` do with the line endings
+    /// of content they write, unless a request picks its own `lineEnding`:
+    /// `asIs`, `normalize` (one style throughout, whichever the content
+    /// mostly uses), `preserve` (the style of the file being replaced),
+    /// `lf` or `crlf`.
+    pub line_endings: LineEndingPolicy,
+    /// Named commands runnable through `task/run`.
+    pub tasks: BTreeMap<String, TaskDefinition>,
+    /// Upper bound on tasks running at once across all connections.
+    pub max_concurrent_tasks: usize,
+    /// Commands and built-in jobs run on a cron schedule while the server
+    /// is up.
+    pub jobs: BTreeMap<String, JobDefinition>,
+    /// Actions external systems may trigger over HTTP, keyed by the name
+    /// in `POST /hooks/<name>`.
+    pub webhooks: BTreeMap<String, WebhookDefinition>,
+    /// Language server commands keyed by language id (`rust`, `typescript`).
+    pub language_servers: BTreeMap<String, LanguageServerDefinition>,
+    /// Debug adapter commands keyed by the name `dap/start` is given
+    /// (`debugpy`, `lldb`). Adapters must speak DAP on stdin and stdout.
+    pub debug_adapters: BTreeMap<String, DebugAdapterDefinition>,
+    /// Formatter commands keyed by file extension. `{path}` in args is
+    /// replaced with the document path.
+    pub formatters: BTreeMap<String, FormatterDefinition>,
+    /// Interpreters `snippet/run` executes code with, and the limits it
+    /// runs under.
+    pub snippets: SnippetConfig,
+    /// Results larger than this many bytes are compressed for clients that
+    /// negotiated an encoding in `initialize`.
+    pub compression_threshold: usize,
+    /// Seconds between WebSocket pings, which measure round-trip time and
+    /// keep live clients from looking idle; zero disables them.
+    pub ping_interval_secs: u64,
+    /// Seconds without any frame (pongs included) from the client after
+    /// which the connection is closed; zero disables reaping.
+    pub idle_timeout_secs: u64,
+    /// Seconds a resumable session outlives its socket, keeping terminals,
+    /// open documents and subscriptions for a reconnect; zero disables
+    /// session resume.
+    pub session_grace_secs: u64,
+    /// Messages kept per session for replay after a reconnect.
+    pub session_replay_limit: usize,
+    /// WebSocket connections served at once; further upgrades get 503.
+    /// Zero means no limit.
+    pub max_connections: usize,
+    /// Requests a single connection may have running at once on each lane,
+    /// interactive and background.
+    pub max_concurrent_requests: usize,
+    /// Interactive requests (reads, edits, navigation) allowed to run at once.
+    pub interactive_workers: usize,
+    /// Threads background requests (scans, blame, bulk transfers) run on,
+    /// kept few so they cannot starve interactive ones.
+    pub background_workers: usize,
+    /// Background requests allowed to wait for a thread, server-wide;
+    /// more are refused with `SERVER_BUSY` until the queue drains. Zero
+    /// runs them only while a thread is free.
+    pub background_queue: usize,
+    /// Per-connection request rate limit.
+    pub rate_limit: RateLimitConfig,
+    /// Size limits on incoming messages and file payloads.
+    pub limits: PayloadLimits,
+    /// How long requests may run before failing with TIMEOUT.
+    pub timeouts: TimeoutConfig,
+    /// When open documents with unsaved changes are written to disk
+    /// without a `document/save`.
+    pub auto_save: AutoSaveConfig,
+    /// Copies of files' previous content kept when they are overwritten.
+    pub history: HistoryConfig,
+    /// Identities and the usage limits of their tiers.
+    pub policy: PolicyConfig,
+    /// Simulated misbehaviour, honoured only by builds with the `faults`
+    /// feature.
+    pub faults: FaultConfig,
+    /// OpenTelemetry export of spans and request metrics, honoured only by
+    /// builds with the `otel` feature.
+    pub telemetry: TelemetryConfig,
+    /// Recent requests and responses kept in memory for
+    /// `debug/recentRequests`.
+    pub request_log: RequestLogConfig,
+    /// The background index of file contents and symbols behind
+    /// `searchInFiles` and `workspaceSymbols`.
+    pub index: IndexConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RateLimitConfig {
+    /// Sustained requests per second; zero disables limiting.
+    pub requests_per_second: f64,
+    /// Requests a client may send in a burst before being limited.
+    pub burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 200.0,
+            burst: 500,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PayloadLimits {
+    /// Largest WebSocket message accepted, in bytes.
+    pub max_message_bytes: usize,
+    /// Largest `writeFile` content, in bytes.
+    pub max_write_bytes: usize,
+    /// Largest file `readFile` will return, in bytes.
+    pub max_read_bytes: u64,
+    /// Largest file `uploadFile` places, and largest `POST /upload` body,
+    /// in bytes.
+    pub max_upload_bytes: u64,
+    /// Bytes of responses and notifications a connection may have waiting
+    /// to be sent. A client that falls this far behind is disconnected.
+    pub max_outbound_bytes: usize,
+}
+
+impl Default for PayloadLimits {
+    fn default() -> Self {
+        Self {
+            max_message_bytes: 16 * 1024 * 1024,
+            max_write_bytes: 16 * 1024 * 1024,
+            max_read_bytes: 32 * 1024 * 1024,
+            max_upload_bytes: 512 * 1024 * 1024,
+            max_outbound_bytes: 128 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TimeoutConfig {
+    /// Milliseconds a request may run; zero disables the timeout.
+    pub default_ms: u64,
+    /// Per-method overrides of `default_ms`, e.g. `{"git/blame": 120000}`.
+    pub methods: BTreeMap<String, u64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            default_ms: 60_000,
+            methods: BTreeMap::new(),
+        }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn for_method(&self, method: &str) -> Option<Duration> {
+        let millis = self.methods.get(method).copied().unwrap_or(self.default_ms);
+        (millis > 0).then(|| Duration::from_millis(millis))
+    }
+}
+
+/// Either condition saves a dirty document; with neither, documents are
+/// only saved on request.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AutoSaveConfig {
+    /// Save dirty documents every this many seconds.
+    pub interval_secs: Option<u64>,
+    /// Save a dirty document once it has gone this many milliseconds
+    /// without an edit.
+    pub idle_ms: Option<u64>,
+}
+
+impl AutoSaveConfig {
+    pub fn enabled(&self) -> bool {
+        self.interval_secs.is_some_and(|secs| secs > 0) || self.idle_ms.is_some_and(|ms| ms > 0)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HistoryConfig {
+    /// Snapshot a file into `history` in the data directory before
+    /// `writeFile` overwrites it.
+    pub enabled: bool,
+    /// Snapshots kept per file; older ones are removed as new ones are taken.
+    pub max_versions: usize,
+    /// Snapshots older than this many days are removed; zero keeps them
+    /// until `maxVersions` pushes them out.
+    pub max_age_days: u64,
+    /// Files larger than this many bytes are overwritten without a snapshot.
+    pub max_file_bytes: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_versions: 20,
+            max_age_days: 30,
+            max_file_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PolicyConfig {
+    /// Tier of connections that present no key; unlimited when unset.
+    pub default_tier: Option<String>,
+    pub tiers: BTreeMap<String, TierLimits>,
+    /// API keys, sent as `Authorization: Bearer <key>` or `?token=<key>`
+    /// on the WebSocket upgrade, mapped to the identity they authenticate.
+    pub keys: BTreeMap<String, IdentityDefinition>,
+    /// A JSON file of more keys, shaped like `keys`, for deployments that
+    /// provision users elsewhere. It is read again whenever it changes, so
+    /// keys can be issued and revoked without a restart, though revoking
+    /// one leaves its open connections be. A key in both places
+    /// authenticates as its `keys` entry.
+    pub keys_file: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RequestLogConfig {
+    /// Requests kept, oldest dropped first; zero keeps none.
+    pub entries: usize,
+    /// Request and response bodies are cut to this many bytes.
+    pub max_body_bytes: usize,
+}
+
+impl Default for RequestLogConfig {
+    fn default() -> Self {
+        Self {
+            entries: 200,
+            max_body_bytes: 2048,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct IndexConfig {
+    /// Index file contents, which takes memory on the order of their
+    /// size; without it every search reads every file.
+    pub contents: bool,
+    /// Index the definitions in source files for `workspaceSymbols`.
+    pub symbols: bool,
+    /// Larger files are left out of the content index and read by every
+    /// search instead, and left out of the symbol index.
+    pub max_file_bytes: u64,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            contents: true,
+            symbols: true,
+            max_file_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LogFileConfig {
+    /// File logs are appended to, relative to the working directory;
+    /// nothing is written to a file without one.
+    pub path: Option<PathBuf>,
+    /// Format of the file, independent of `logFormat`.
+    pub format: LogFormat,
+    /// Rotate before the file would grow past this many bytes. Zero means
+    /// no size limit.
+    pub max_bytes: u64,
+    /// Also rotate when the hour or day (UTC) changes.
+    pub rotate: LogRotation,
+    /// Rotated files kept besides the current one; zero truncates instead.
+    pub keep: usize,
+}
+
+impl Default for LogFileConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            format: LogFormat::Json,
+            max_bytes: 10 * 1024 * 1024,
+            rotate: LogRotation::Daily,
+            keep: 7,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    Daily,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TelemetryConfig {
+    /// Base URL of an OTLP/HTTP collector, such as `http://localhost:4318`;
+    /// nothing is exported without one. Only plain `http` is supported.
+    pub endpoint: Option<String>,
+    /// `service.name` the spans and metrics are reported under.
+    pub service_name: String,
+    /// Fraction of traces exported, from 0 to 1. Each request is a trace
+    /// of its own, so this samples requests.
+    pub sample_ratio: f64,
+    /// Seconds between exports.
+    pub export_interval_secs: u64,
+    /// Also export request counts and durations per method.
+    pub metrics: bool,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            service_name: "editor-server".to_string(),
+            sample_ratio: 1.0,
+            export_interval_secs: 5,
+            metrics: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FaultConfig {
+    /// Rules keyed by method name; `*` applies to methods without a rule.
+    pub methods: BTreeMap<String, FaultRule>,
+    /// Rules keyed by activity event kind (`file.saved`), with the same
+    /// `*` fallback. Events are delayed or dropped, never failed.
+    pub events: BTreeMap<String, FaultRule>,
+}
+
+/// Chances, from 0 to 1, of each kind of misbehaviour.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FaultRule {
+    /// Chance of holding the message back for up to `maxDelayMs`.
+    pub delay_rate: f64,
+    pub max_delay_ms: u64,
+    /// Chance of answering with an injected INTERNAL_ERROR instead of
+    /// running the handler.
+    pub fail_rate: f64,
+    /// Chance of never answering, or of not delivering the event.
+    pub drop_rate: f64,
+    /// Chance of the handler panicking instead of running, for checking
+    /// that only the request fails. Events are dropped instead.
+    pub panic_rate: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityDefinition {
+    pub name: String,
+    pub tier: String,
+    /// Confines the key's connections to one directory: the name of a
+    /// `workspaces` entry, or a path. Unconfined when unset.
+    #[serde(default)]
+    pub workspace: Option<String>,
+    /// Applied on top of the server's rules, with globs relative to
+    /// `workspace`.
+    #[serde(default)]
+    pub permissions: Vec<PermissionRule>,
+}
+
+/// Limits of one tier; anything unset is unlimited.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TierLimits {
+    /// Terminals a connection may have open at once.
+    pub max_terminals: Option<usize>,
+    /// Background requests (searches, scans, exports, blame) a connection
+    /// may have running at once.
+    pub max_search_concurrency: Option<usize>,
+    /// Writes are refused once the workspace holds this many bytes.
+    pub max_workspace_bytes: Option<u64>,
+    /// Method namespaces (the part before `/`, e.g. `git`, `terminal`)
+    /// the tier may call. Core methods without a namespace, `$/` and
+    /// `server/` are always allowed.
+    pub allowed_namespaces: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceDefinition {
+    pub path: PathBuf,
+    /// Like the top-level `permissions`, with globs relative to this root.
+    #[serde(default)]
+    pub permissions: Vec<PermissionRule>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PermissionRule {
+    /// Glob relative to the workspace root, e.g. `**/.env`.
+    pub glob: String,
+    pub access: Access,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Ordered from least to most permissive.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum Access {
+    /// Neither readable nor writable, and left out of listings.
+    Deny,
+    Read,
+    Write,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BuiltinJob {
+    /// Zips the workspace (honouring ignore files) into the data directory.
+    Snapshot,
+    /// Runs the secret and license scans, refreshing the problems store.
+    Scan,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobDefinition {
+    /// Five-field cron expression, evaluated in UTC.
+    pub schedule: String,
+    /// Built-in job to run; exactly one of `builtin` and `command` is set.
+    pub builtin: Option<BuiltinJob>,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Working directory, relative to the workspace root.
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDefinition {
+    /// Shared secret callers send as `Authorization: Bearer <token>`.
+    pub token: String,
+    pub action: WebhookAction,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum WebhookAction {
+    /// Rescans the workspace and refreshes the problems store.
+    RefreshIndex,
+    /// Fast-forwards the checked out branch from its remote.
+    PullLatest {
+        remote: Option<String>,
+        branch: Option<String>,
+    },
+    /// Shows a message in every connected editor. A `message` in the
+    /// request body takes precedence over the configured one.
+    Broadcast { message: Option<String> },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskDefinition {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Working directory, relative to the workspace root.
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageServerDefinition {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugAdapterDefinition {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatterDefinition {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SnippetConfig {
+    /// Keyed by the language `snippet/run` is asked for.
+    pub interpreters: BTreeMap<String, InterpreterDefinition>,
+    /// Wall-clock time a snippet may run before it is killed.
+    pub timeout_ms: u64,
+    /// CPU time a snippet may use. Zero means no limit; Linux only.
+    pub cpu_secs: u64,
+    /// Heap and other private writable memory a snippet may use, in bytes.
+    /// Zero means no limit; Linux only.
+    pub memory_bytes: u64,
+    /// Output kept from each of stdout and stderr; the rest is discarded.
+    pub max_output_bytes: usize,
+}
+
+impl Default for SnippetConfig {
+    fn default() -> Self {
+        let interpreter = |command: &str, extension: &str| InterpreterDefinition {
+            command: command.to_string(),
+            args: vec!["{file}".to_string()],
+            extension: Some(extension.to_string()),
+        };
+        Self {
+            interpreters: BTreeMap::from([
+                ("javascript".to_string(), interpreter("node", "js")),
+                ("python".to_string(), interpreter("python3", "py")),
+                ("shell".to_string(), interpreter("sh", "sh")),
+            ]),
+            timeout_ms: 10_000,
+            cpu_secs: 5,
+            memory_bytes: 512 * 1024 * 1024,
+            max_output_bytes: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InterpreterDefinition {
+    pub command: String,
+    /// `{file}` is replaced by the path of a file holding the snippet;
+    /// without it the snippet is fed on stdin instead.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Given to the snippet's file, for interpreters that go by it.
+    pub extension: Option<String>,
+}
+
+fn default_formatters() -> BTreeMap<String, FormatterDefinition> {
+    let formatter = |command: &str, args: &[&str]| FormatterDefinition {
+        command: command.to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+    };
+    let prettier = formatter("prettier", &["--stdin-filepath", "{path}"]);
+
+    let mut formatters = BTreeMap::new();
+    formatters.insert(
+        "rs".to_string(),
+        formatter("rustfmt", &["--edition", "2024"]),
+    );
+    formatters.insert("go".to_string(), formatter("gofmt", &[]));
+    for extension in [
+        "js", "jsx", "ts", "tsx", "json", "css", "scss", "html", "md", "yaml",
+    ] {
+        formatters.insert(extension.to_string(), prettier.clone());
+    }
+    formatters
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("."),
+            root_name: None,
+            workspaces: BTreeMap::new(),
+            port: 3000,
+            record_dir: None,
+            log_format: LogFormat::Text,
+            log_file: LogFileConfig::default(),
+            log_level: None,
+            static_dir: None,
+            allowed_origins: Vec::new(),
+            audit_log: None,
+            data_dir: PathBuf::from(".editor-server"),
+            templates_dir: None,
+            protected_paths: vec![
+                ".git/**".to_string(),
+                "Cargo.lock".to_string(),
+                ".github/workflows/**".to_string(),
+            ],
+            exclude: Vec::new(),
+            permissions: Vec::new(),
+            read_only: false,
+            follow_symlinks: true,
+            raw_paths: true,
+            line_endings: LineEndingPolicy::AsIs,
+            tasks: BTreeMap::new(),
+            jobs: BTreeMap::new(),
+            webhooks: BTreeMap::new(),
+            max_concurrent_tasks: 4,
+            language_servers: BTreeMap::new(),
+            debug_adapters: BTreeMap::new(),
+            formatters: default_formatters(),
+            snippets: SnippetConfig::default(),
+            compression_threshold: 64 * 1024,
+            ping_interval_secs: 30,
+            idle_timeout_secs: 120,
+            session_grace_secs: 60,
+            session_replay_limit: 1000,
+            max_connections: 1000,
+            max_concurrent_requests: 16,
+            interactive_workers: 32,
+            background_workers: 2,
+            background_queue: 64,
+            rate_limit: RateLimitConfig::default(),
+            limits: PayloadLimits::default(),
+            timeouts: TimeoutConfig::default(),
+            auto_save: AutoSaveConfig::default(),
+            history: HistoryConfig::default(),
+            policy: PolicyConfig::default(),
+            faults: FaultConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            request_log: RequestLogConfig::default(),
+            index: IndexConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn data_path(&self) -> PathBuf {
+        self.root.join(&self.data_dir)
+    }
+
+    pub fn templates_path(&self) -> PathBuf {
+        match &self.templates_dir {
+            Some(dir) => self.root.join(dir),
+            None => self.data_path().join("templates"),
+        }
+    }
+
+    pub fn from_args() -> Result<Self, String> {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+
+        let mut config = match flag_value(&args, "--config") {
+            Some(path) => {
+                let text = fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read config file {path}: {e}"))?;
+                serde_json::from_str(&text)
+                    .map_err(|e| format!("failed to parse config file {path}: {e}"))?
+            }
+            None => Config::default(),
+        };
+
+        if let Some(root) = flag_value(&args, "--root") {
+            config.root = PathBuf::from(root);
+        }
+        if let Some(port) = flag_value(&args, "--port") {
+            config.port = port
+                .parse()
+                .map_err(|_| format!("invalid --port value: {port}"))?;
+        }
+        if let Some(dir) = flag_value(&args, "--record") {
+            config.record_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(format) = flag_value(&args, "--log-format") {
+            config.log_format = match format {
+                "text" => LogFormat::Text,
+                "json" => LogFormat::Json,
+                _ => return Err(format!("invalid --log-format value: {format}")),
+            };
+        }
+        if let Some(path) = flag_value(&args, "--log-file") {
+            config.log_file.path = Some(PathBuf::from(path));
+        }
+        if let Some(dir) = flag_value(&args, "--static-dir") {
+            config.static_dir = Some(PathBuf::from(dir));
+        }
+        for workspace in flag_values(&args, "--workspace") {
+            let Some((name, path)) = workspace.split_once('=') else {
+                return Err(format!(
+                    "invalid --workspace value, expected name=path: {workspace}"
+                ));
+            };
+            config.workspaces.insert(
+                name.to_string(),
+                WorkspaceDefinition {
+                    path: PathBuf::from(path),
+                    permissions: Vec::new(),
+                },
+            );
+        }
+        if args.iter().any(|arg| arg == "--read-only") {
+            config.read_only = true;
+        }
+
+        Ok(config)
+    }
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    flag_values(args, flag).next()
+}
+
+/// Values of every occurrence of a repeatable flag.
+fn flag_values<'a>(args: &'a [String], flag: &str) -> impl Iterator<Item = &'a str> {
+    args.windows(2)
+        .filter(move |pair| pair[0] == flag)
+        .map(|pair| pair[1].as_str())
+}
@@ -0,0 +1,169 @@
+//! Five-field cron expressions (`minute hour day-of-month month
+//! day-of-week`), evaluated in UTC.
+//!
+//! Each field accepts `*`, numbers, ranges (`1-5`), lists (`1,15`) and
+//! steps (`*/10`, `8-18/2`). Day of week is 0-6 with 0 (or 7) for Sunday.
+//! As in classic cron, when both day fields are restricted a time matches
+//! if either does. The shorthands `@hourly`, `@daily`, `@weekly`,
+//! `@monthly` and `@yearly` are also accepted.
+
+/// A parsed expression; bit `n` of a field is set when value `n` matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether each day field was `*`, needed for the either-day rule.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// A UTC calendar minute, as needed for matching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CivilTime {
+    pub minute: u32,
+    pub hour: u32,
+    pub day: u32,
+    pub month: u32,
+    /// 0 = Sunday.
+    pub weekday: u32,
+}
+
+impl CivilTime {
+    pub fn from_unix(secs: u64) -> Self {
+        let days = secs / 86_400;
+        let seconds_of_day = secs % 86_400;
+        let (_, month, day) = civil_from_days(days as i64);
+        Self {
+            minute: (seconds_of_day / 60 % 60) as u32,
+            hour: (seconds_of_day / 3600) as u32,
+            day,
+            month,
+            // 1970-01-01 was a Thursday.
+            weekday: ((days + 4) % 7) as u32,
+        }
+    }
+}
+
+/// UTC `(year, month, day)` of a Unix timestamp.
+pub fn civil_date(secs: u64) -> (i64, u32, u32) {
+    civil_from_days((secs / 86_400) as i64)
+}
+
+/// Howard Hinnant's days-to-civil conversion.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "expected 5 fields in cron expression, found {}",
+                fields.len()
+            ));
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1; // 7 is another name for Sunday
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    pub fn matches(&self, time: &CivilTime) -> bool {
+        let bit = |field: u64, value: u32| field & (1 << value) != 0;
+        let day = bit(self.days, time.day);
+        let weekday = bit(self.weekdays, time.weekday);
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        bit(self.minutes, time.minute)
+            && bit(self.hours, time.hour)
+            && bit(self.months, time.month)
+            && day_matches
+    }
+
+    /// The first minute strictly after `after` (unix seconds) that matches,
+    /// searching up to about four years ahead.
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        let mut minute = after / 60 + 1;
+        let limit = minute + 4 * 366 * 24 * 60;
+        while minute < limit {
+            if self.matches(&CivilTime::from_unix(minute * 60)) {
+                return Some(minute * 60);
+            }
+            minute += 1;
+        }
+        None
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step in cron field: {part}"))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, min, max)?, parse_value(end, min, max)?)
+        } else {
+            let value = parse_value(range, min, max)?;
+            // `5/15` means from 5 to the end in steps of 15.
+            (value, if step > 1 { max } else { value })
+        };
+        if start > end {
+            return Err(format!("invalid range in cron field: {part}"));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .ok_or_else(|| format!("cron value out of range {min}-{max}: {value}"))
+}
@@ -0,0 +1,403 @@
+//! Debug adapters bridged onto WebSocket connections, as `lsp` does for
+//! language servers. A client starts a session with `dap/start`, sends DAP
+//! requests through `dap/request` and gets the adapter's events and
+//! reverse requests (`runInTerminal`) as notifications. Sessions belong to
+//! the connection that started them and end when it closes.
+
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::Stdio,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+    },
+};
+use tokio::{
+    io::{AsyncRead, AsyncWriteExt, BufReader},
+    process::{ChildStdin, Command},
+    sync::{mpsc, oneshot},
+};
+use tracing::{Instrument, debug, info, info_span, warn};
+
+use crate::{
+    config::DebugAdapterDefinition,
+    lsp,
+    rpc::{
+        context::Notifier,
+        error::{DEBUG_ADAPTER_ERROR_CODE, create_error_response_with_data},
+        request::JsonRpcResponse,
+    },
+};
+
+/// Client request ids, the correlation ids of those requests and the DAP
+/// command sent, waiting on a response, keyed by the `seq` we sent.
+type Pending = Arc<Mutex<HashMap<i64, (Value, String, String)>>>;
+
+/// Commands of reverse requests the client has yet to answer, by the
+/// adapter's `seq`.
+type Reverse = Arc<Mutex<HashMap<i64, String>>>;
+
+static SESSION_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+struct DapSession {
+    connection_id: u64,
+    outbound: mpsc::UnboundedSender<Value>,
+    pending: Pending,
+    reverse: Reverse,
+    next_seq: AtomicI64,
+    stop: Option<oneshot::Sender<()>>,
+}
+
+type Sessions = HashMap<u64, DapSession>;
+
+/// Debug adapter processes by session id. Unlike language servers a
+/// connection may run several sessions of one adapter, such as a program
+/// and the child process it spawned.
+#[derive(Default)]
+pub struct DapBridge {
+    sessions: Arc<Mutex<Sessions>>,
+}
+
+impl DapBridge {
+    /// Launches the adapter and returns the new session's id. The adapter
+    /// is only started; the client sends `initialize` and `launch` itself.
+    pub fn start(
+        &self,
+        connection_id: u64,
+        adapter: &str,
+        definition: &DebugAdapterDefinition,
+        root: &Path,
+        notifier: &Notifier,
+    ) -> Result<u64, String> {
+        let mut child = Command::new(&definition.command)
+            .args(&definition.args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start debug adapter {adapter}: {e}"))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (outbound, outbound_rx) = mpsc::unbounded_channel();
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let pending: Pending = Default::default();
+        let reverse: Reverse = Default::default();
+        let session_id = SESSION_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        let span = info_span!("dap_session", connection_id, session_id, adapter = %adapter);
+        tokio::spawn(write_frames(stdin, outbound_rx).instrument(span.clone()));
+        let reader = tokio::spawn(
+            read_frames(
+                stdout,
+                session_id,
+                Arc::clone(&pending),
+                Arc::clone(&reverse),
+                notifier.clone(),
+            )
+            .instrument(span.clone()),
+        );
+
+        // Registered before the supervisor can see the adapter exit.
+        self.lock().insert(
+            session_id,
+            DapSession {
+                connection_id,
+                outbound,
+                pending,
+                reverse,
+                next_seq: AtomicI64::new(1),
+                stop: Some(stop_tx),
+            },
+        );
+        let sessions = Arc::clone(&self.sessions);
+        let notifier = notifier.clone();
+        tokio::spawn(
+            async move {
+                let status = tokio::select! {
+                    status = child.wait() => {
+                        info!(status = ?status.as_ref().ok(), "Debug adapter exited");
+                        status.ok().and_then(|status| status.code())
+                    }
+                    _ = stop_rx => {
+                        let _ = child.kill().await;
+                        info!("Debug adapter stopped");
+                        None
+                    }
+                };
+                sessions
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(&session_id);
+                // Pending requests are failed first, so the client hears
+                // of them before `dap/exited`.
+                let _ = reader.await;
+                notifier.notify(
+                    "dap/exited",
+                    json!({ "sessionId": session_id, "exitCode": status }),
+                );
+            }
+            .instrument(span),
+        );
+
+        info!(connection_id, session_id, adapter = %adapter, command = %definition.command, "Debug adapter started");
+        Ok(session_id)
+    }
+
+    /// Sends a DAP request and remembers `client_id` so the adapter's
+    /// response is delivered as the reply to the client's `dap/request`.
+    pub fn request(
+        &self,
+        connection_id: u64,
+        session_id: u64,
+        command: &str,
+        arguments: Value,
+        client_id: Value,
+        correlation_id: &str,
+    ) -> Result<(), String> {
+        let sessions = self.lock();
+        let session = owned(&sessions, connection_id, session_id)?;
+        let seq = session.next_seq.fetch_add(1, Ordering::Relaxed);
+        session
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                seq,
+                (client_id, correlation_id.to_string(), command.to_string()),
+            );
+        let mut message = json!({ "seq": seq, "type": "request", "command": command });
+        if !arguments.is_null() {
+            message["arguments"] = arguments;
+        }
+        session.send(message)
+    }
+
+    /// Answers a reverse request the adapter sent to the client.
+    pub fn respond(
+        &self,
+        connection_id: u64,
+        session_id: u64,
+        request_seq: i64,
+        success: bool,
+        body: Value,
+        message: Option<String>,
+    ) -> Result<(), String> {
+        let sessions = self.lock();
+        let session = owned(&sessions, connection_id, session_id)?;
+        let command = session
+            .reverse
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&request_seq)
+            .ok_or_else(|| format!("No request {request_seq} from the debug adapter is waiting"))?;
+        let seq = session.next_seq.fetch_add(1, Ordering::Relaxed);
+        let mut response = json!({
+            "seq": seq,
+            "type": "response",
+            "request_seq": request_seq,
+            "success": success,
+            "command": command,
+        });
+        if !body.is_null() {
+            response["body"] = body;
+        }
+        if let Some(message) = message {
+            response["message"] = message.into();
+        }
+        session.send(response)
+    }
+
+    pub fn stop(&self, connection_id: u64, session_id: u64) -> Result<(), String> {
+        let mut sessions = self.lock();
+        owned(&sessions, connection_id, session_id)?;
+        let mut session = sessions.remove(&session_id).expect("session was found");
+        if let Some(stop) = session.stop.take() {
+            let _ = stop.send(());
+        }
+        Ok(())
+    }
+
+    /// Stops every debug adapter owned by a connection; called when it closes.
+    pub fn close_connection(&self, connection_id: u64) {
+        self.lock().retain(|session_id, session| {
+            if session.connection_id != connection_id {
+                return true;
+            }
+            if let Some(stop) = session.stop.take() {
+                debug!(
+                    connection_id,
+                    session_id, "Stopping debug adapter of closed connection"
+                );
+                let _ = stop.send(());
+            }
+            false
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Sessions> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Another connection's session is reported as missing, not as someone
+/// else's.
+fn owned(sessions: &Sessions, connection_id: u64, session_id: u64) -> Result<&DapSession, String> {
+    sessions
+        .get(&session_id)
+        .filter(|session| session.connection_id == connection_id)
+        .ok_or_else(|| format!("No debug session {session_id}"))
+}
+
+impl DapSession {
+    fn send(&self, message: Value) -> Result<(), String> {
+        self.outbound
+            .send(message)
+            .map_err(|_| "Debug adapter is no longer running".to_string())
+    }
+}
+
+/// Writes messages to the adapter with the `Content-Length` framing DAP
+/// shares with LSP.
+async fn write_frames(mut stdin: ChildStdin, mut outbound: mpsc::UnboundedReceiver<Value>) {
+    while let Some(message) = outbound.recv().await {
+        let body = message.to_string();
+        let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        if let Err(e) = stdin.write_all(frame.as_bytes()).await {
+            warn!(error = %e, "Failed to write to debug adapter");
+            return;
+        }
+    }
+}
+
+/// Reads framed messages from the adapter and routes them to the client:
+/// responses become replies to the pending `dap/request`, events become
+/// `dap/event` notifications and reverse requests `dap/reverseRequest`.
+async fn read_frames(
+    stdout: impl AsyncRead + Unpin,
+    session_id: u64,
+    pending: Pending,
+    reverse: Reverse,
+    notifier: Notifier,
+) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let message = match lsp::read_frame(&mut reader).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                warn!(error = %e, "Malformed frame from debug adapter");
+                break;
+            }
+        };
+
+        match message.get("type").and_then(Value::as_str) {
+            Some("response") => {
+                let waiting = message
+                    .get("request_seq")
+                    .and_then(Value::as_i64)
+                    .and_then(|seq| {
+                        pending
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .remove(&seq)
+                    });
+                let Some((client_id, correlation_id, _)) = waiting else {
+                    debug!(request_seq = ?message.get("request_seq"), "Dropping response for unknown request");
+                    continue;
+                };
+                let response =
+                    dap_response_to_client(&message, client_id).correlated(&correlation_id);
+                match serde_json::to_string(&response) {
+                    Ok(text) => {
+                        notifier.send(text);
+                    }
+                    Err(e) => warn!(error = %e, "Failed to serialize debug adapter response"),
+                }
+            }
+            Some("event") => {
+                notifier.notify(
+                    "dap/event",
+                    json!({
+                        "sessionId": session_id,
+                        "event": message.get("event"),
+                        "body": message.get("body"),
+                    }),
+                );
+            }
+            Some("request") => {
+                let (Some(seq), Some(command)) = (
+                    message.get("seq").and_then(Value::as_i64),
+                    message.get("command").and_then(Value::as_str),
+                ) else {
+                    debug!("Ignoring debug adapter request without seq or command");
+                    continue;
+                };
+                reverse
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(seq, command.to_string());
+                notifier.notify(
+                    "dap/reverseRequest",
+                    json!({
+                        "sessionId": session_id,
+                        "seq": seq,
+                        "command": command,
+                        "arguments": message.get("arguments"),
+                    }),
+                );
+            }
+            _ => debug!("Ignoring debug adapter message of unknown type"),
+        }
+    }
+
+    // Fail whatever is still waiting so clients are not left hanging.
+    let orphaned: Vec<(Value, String, String)> = pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain()
+        .map(|(_, waiting)| waiting)
+        .collect();
+    for (client_id, correlation_id, command) in orphaned {
+        let response = create_error_response_with_data(
+            DEBUG_ADAPTER_ERROR_CODE,
+            "Debug adapter exited before responding",
+            Some(json!({ "command": command })),
+            client_id,
+        )
+        .correlated(&correlation_id);
+        if let Ok(text) = serde_json::to_string(&response) {
+            notifier.send(text);
+        }
+    }
+}
+
+/// A successful response's `body` becomes the result; a failed one an
+/// error carrying the adapter's message and structured `error` body.
+fn dap_response_to_client(message: &Value, client_id: Value) -> JsonRpcResponse {
+    if message.get("success").and_then(Value::as_bool) == Some(false) {
+        let body_error = message.get("body").and_then(|body| body.get("error"));
+        let text = message
+            .get("message")
+            .and_then(Value::as_str)
+            .or_else(|| body_error?.get("format")?.as_str())
+            .unwrap_or("Debug adapter request failed");
+        return create_error_response_with_data(
+            DEBUG_ADAPTER_ERROR_CODE,
+            text,
+            Some(json!({ "command": message.get("command"), "error": body_error })),
+            client_id,
+        );
+    }
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        result: Some(message.get("body").cloned().unwrap_or(Value::Null)),
+        error: None,
+        id: client_id,
+        correlation_id: None,
+    }
+}
@@ -0,0 +1,158 @@
+//! rsync-style delta encoding. The client splits its copy of a file into
+//! fixed-size blocks and sends a signature per block; the server scans the
+//! current file with a rolling checksum and answers with block references
+//! plus literal bytes for whatever the client does not have.
+//!
+//! Signatures, which clients must compute identically:
+//! - `weak`: rsync rolling checksum, `a | (b << 16)` where `a` is the sum of
+//!   the block's bytes and `b` is the sum of `(len - i) * byte[i]`, both
+//!   modulo 2^16.
+//! - `strong`: first 16 bytes of the block's BLAKE3 hash, lowercase hex.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Deserialize, Debug)]
+pub struct BlockSignature {
+    pub weak: u32,
+    pub strong: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum DeltaOp {
+    /// Reuse `count` consecutive client blocks starting at `start`.
+    Copy { start: usize, count: usize },
+    /// Bytes the client does not have.
+    Literal(Vec<u8>),
+}
+
+pub fn weak_checksum(block: &[u8]) -> u32 {
+    let len = block.len() as u32;
+    let (mut a, mut b) = (0u32, 0u32);
+    for (i, &byte) in block.iter().enumerate() {
+        a = a.wrapping_add(byte as u32);
+        b = b.wrapping_add((len - i as u32).wrapping_mul(byte as u32));
+    }
+    (a & 0xffff) | ((b & 0xffff) << 16)
+}
+
+pub fn strong_checksum(block: &[u8]) -> String {
+    let hash = blake3::hash(block);
+    hash.as_bytes()[..16]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Rolling form of [`weak_checksum`] that can slide the window one byte at a time.
+struct Rolling {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl Rolling {
+    fn new(window: &[u8]) -> Self {
+        let checksum = weak_checksum(window);
+        Self {
+            a: checksum & 0xffff,
+            b: checksum >> 16,
+            len: window.len() as u32,
+        }
+    }
+
+    fn value(&self) -> u32 {
+        (self.a & 0xffff) | ((self.b & 0xffff) << 16)
+    }
+
+    fn roll(&mut self, out: u8, into: u8) {
+        self.a = self.a.wrapping_sub(out as u32).wrapping_add(into as u32) & 0xffff;
+        self.b = self
+            .b
+            .wrapping_sub(self.len.wrapping_mul(out as u32))
+            .wrapping_add(self.a)
+            & 0xffff;
+    }
+}
+
+pub fn compute_delta(
+    data: &[u8],
+    block_size: usize,
+    signatures: &[BlockSignature],
+) -> Vec<DeltaOp> {
+    let mut by_weak: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (index, signature) in signatures.iter().enumerate() {
+        by_weak.entry(signature.weak).or_default().push(index);
+    }
+
+    let mut ops = Vec::new();
+    let mut literal = Vec::new();
+    let find_block = |window: &[u8], weak: u32| -> Option<usize> {
+        let candidates = by_weak.get(&weak)?;
+        let strong = strong_checksum(window);
+        candidates
+            .iter()
+            .copied()
+            .find(|&index| signatures[index].strong == strong)
+    };
+
+    let mut position = 0;
+    let mut rolling = (data.len() >= block_size).then(|| Rolling::new(&data[..block_size]));
+    while position + block_size <= data.len() {
+        let window = &data[position..position + block_size];
+        let weak = rolling.as_ref().expect("window is full").value();
+
+        if let Some(index) = find_block(window, weak) {
+            push_literal(&mut ops, &mut literal);
+            push_copy(&mut ops, index);
+            position += block_size;
+            rolling = (position + block_size <= data.len())
+                .then(|| Rolling::new(&data[position..position + block_size]));
+            continue;
+        }
+
+        literal.push(data[position]);
+        if position + block_size < data.len() {
+            rolling
+                .as_mut()
+                .expect("window is full")
+                .roll(data[position], data[position + block_size]);
+        }
+        position += 1;
+    }
+
+    // The client's last block may be shorter than `block_size`.
+    let tail = &data[position..];
+    if !tail.is_empty() {
+        match find_block(tail, weak_checksum(tail)) {
+            Some(index) => {
+                push_literal(&mut ops, &mut literal);
+                push_copy(&mut ops, index);
+            }
+            None => literal.extend_from_slice(tail),
+        }
+    }
+    push_literal(&mut ops, &mut literal);
+    ops
+}
+
+fn push_literal(ops: &mut Vec<DeltaOp>, literal: &mut Vec<u8>) {
+    if !literal.is_empty() {
+        ops.push(DeltaOp::Literal(std::mem::take(literal)));
+    }
+}
+
+/// Appends a block reference, extending the previous copy when contiguous.
+fn push_copy(ops: &mut Vec<DeltaOp>, index: usize) {
+    if let Some(DeltaOp::Copy { start, count }) = ops.last_mut()
+        && *start + *count == index
+    {
+        *count += 1;
+        return;
+    }
+    ops.push(DeltaOp::Copy {
+        start: index,
+        count: 1,
+    });
+}
@@ -0,0 +1,502 @@
+//! Line diffs with Myers' algorithm, grouped into hunks and rendered as
+//! unified diffs, and the reverse: parsing unified diffs and applying
+//! their hunks.
+//!
+//! Lines keep their endings while they are compared, so a file that only
+//! changed from LF to CRLF differs on every line, and a missing final
+//! newline shows up the way `diff -u` reports it.
+
+use serde::Serialize;
+
+/// Edit distance beyond which the changed middle of the inputs is reported
+/// as one removal and one addition instead of searching for a minimal
+/// diff. Keeps time and memory bounded on unrelated inputs.
+const MAX_EDIT_DISTANCE: usize = 1000;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct HunkLine {
+    pub kind: LineKind,
+    /// The line without its `\n`.
+    pub text: String,
+    /// Set on a last line that has no newline.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub no_newline: bool,
+}
+
+/// A run of changes with its surrounding context. Starts are one-based, as
+/// in the `@@` header; a side with no lines starts at the line before.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+/// One step of an edit script, with the number of old and new lines that
+/// precede it.
+#[derive(Debug, Clone, Copy)]
+struct Op {
+    kind: LineKind,
+    old: usize,
+    new: usize,
+}
+
+/// The lines of `text`, each with its line ending.
+pub fn lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Hunks turning `old` into `new`, with `context` unchanged lines around
+/// each change.
+pub fn hunks(old: &str, new: &str, context: usize) -> Vec<Hunk> {
+    let (a, b) = (lines(old), lines(new));
+    let ops = edit_script(&a, &b);
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| op.kind != LineKind::Context)
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for &i in &changes {
+        let start = i.saturating_sub(context);
+        let end = (i + 1 + context).min(ops.len());
+        match groups.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => groups.push((start, end)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(start, end)| {
+            let ops = &ops[start..end];
+            let old_lines = ops.iter().filter(|op| op.kind != LineKind::Added).count();
+            let new_lines = ops.iter().filter(|op| op.kind != LineKind::Removed).count();
+            let lines = ops
+                .iter()
+                .map(|op| {
+                    let line = match op.kind {
+                        LineKind::Added => b[op.new],
+                        _ => a[op.old],
+                    };
+                    let text = line.strip_suffix('\n');
+                    HunkLine {
+                        kind: op.kind,
+                        text: text.unwrap_or(line).to_string(),
+                        no_newline: text.is_none(),
+                    }
+                })
+                .collect();
+            Hunk {
+                old_start: ops[0].old + usize::from(old_lines > 0),
+                old_lines,
+                new_start: ops[0].new + usize::from(new_lines > 0),
+                new_lines,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Indices of the lines `a` and `b` have in common, paired in order, as
+/// their diff matches them up.
+pub fn common_lines(a: &[&str], b: &[&str]) -> Vec<(usize, usize)> {
+    edit_script(a, b)
+        .into_iter()
+        .filter(|op| op.kind == LineKind::Context)
+        .map(|op| (op.old, op.new))
+        .collect()
+}
+
+/// Renders hunks as a unified diff between `old_name` and `new_name`.
+/// Identical inputs give an empty string.
+pub fn unified(old_name: &str, new_name: &str, hunks: &[Hunk]) -> String {
+    if hunks.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("--- {old_name}\n+++ {new_name}\n");
+    for hunk in hunks {
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            header_range(hunk.old_start, hunk.old_lines),
+            header_range(hunk.new_start, hunk.new_lines)
+        ));
+        for line in &hunk.lines {
+            out.push(match line.kind {
+                LineKind::Context => ' ',
+                LineKind::Added => '+',
+                LineKind::Removed => '-',
+            });
+            out.push_str(&line.text);
+            out.push('\n');
+            if line.no_newline {
+                out.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// One file's part of a patch. A missing path is `/dev/null`: the file is
+/// created or deleted.
+#[derive(Debug)]
+pub struct FilePatch {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// Outcome of applying one hunk.
+#[derive(Debug, Clone, Copy)]
+pub enum HunkOutcome {
+    /// Applied with its first line at `line` (one-based), `offset` lines
+    /// from where the hunk said.
+    Applied { line: usize, offset: isize },
+    /// Its removed and context lines were found nowhere after the
+    /// previous hunk.
+    Conflict,
+}
+
+/// Parses a unified diff, as written by `diff -u` or `git diff`. Anything
+/// outside the file headers and hunks, such as `index` lines, is skipped.
+/// `a/` and `b/` prefixes are stripped when both headers carry them.
+pub fn parse_patch(text: &str) -> Result<Vec<FilePatch>, String> {
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut input = text
+        .split_inclusive('\n')
+        .map(|line| line.strip_suffix('\n').unwrap_or(line));
+    let mut number = 0;
+    let mut old_path = None;
+    while let Some(line) = input.next() {
+        number += 1;
+        if let Some(path) = line.strip_prefix("--- ") {
+            old_path = Some(header_path(path));
+            continue;
+        }
+        if let Some(path) = line.strip_prefix("+++ ") {
+            let Some(old) = old_path.take() else {
+                return Err(format!("Line {number}: +++ without a preceding ---"));
+            };
+            let (old, new) = strip_prefixes(old, header_path(path));
+            patches.push(FilePatch {
+                old_path: old,
+                new_path: new,
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+        if !line.starts_with("@@") {
+            continue;
+        }
+        let Some(patch) = patches.last_mut() else {
+            return Err(format!("Line {number}: hunk before any file header"));
+        };
+        let Some((old_start, old_lines, new_start, new_lines)) = parse_hunk_header(line) else {
+            return Err(format!("Line {number}: malformed hunk header {line}"));
+        };
+        let mut hunk = Hunk {
+            old_start,
+            old_lines,
+            new_start,
+            new_lines,
+            lines: Vec::new(),
+        };
+        let (mut old_seen, mut new_seen) = (0, 0);
+        while old_seen < old_lines || new_seen < new_lines {
+            let Some(line) = input.next() else {
+                return Err(format!("Line {number}: hunk ends early"));
+            };
+            number += 1;
+            let (kind, text) = match line.split_at_checked(1) {
+                Some((" ", text)) => (LineKind::Context, text),
+                Some(("-", text)) => (LineKind::Removed, text),
+                Some(("+", text)) => (LineKind::Added, text),
+                // Some tools drop the space of an empty context line.
+                _ if line.is_empty() => (LineKind::Context, ""),
+                Some(("\\", _)) => {
+                    if let Some(last) = hunk.lines.last_mut() {
+                        last.no_newline = true;
+                    }
+                    continue;
+                }
+                _ => return Err(format!("Line {number}: unexpected line in hunk")),
+            };
+            match kind {
+                LineKind::Context => (old_seen, new_seen) = (old_seen + 1, new_seen + 1),
+                LineKind::Removed => old_seen += 1,
+                LineKind::Added => new_seen += 1,
+            }
+            if old_seen > old_lines || new_seen > new_lines {
+                return Err(format!(
+                    "Line {number}: hunk is longer than its header says"
+                ));
+            }
+            hunk.lines.push(HunkLine {
+                kind,
+                text: text.to_string(),
+                no_newline: false,
+            });
+        }
+        // A marker right after the last line belongs to this hunk.
+        let mut rest = input.clone();
+        if rest.next().is_some_and(|line| line.starts_with('\\')) {
+            input.next();
+            number += 1;
+            if let Some(last) = hunk.lines.last_mut() {
+                last.no_newline = true;
+            }
+        }
+        patch.hunks.push(hunk);
+    }
+    if patches.is_empty() {
+        return Err("No file headers found; expected a unified diff".to_string());
+    }
+    Ok(patches)
+}
+
+/// Applies `hunks` in order to `content`. Each is looked for where it says
+/// it belongs, shifted by the offset the previous hunk was found at, and
+/// then at the nearest position either side. Hunks that do not match are
+/// left out; the result holds every other.
+pub fn apply_hunks(content: &str, hunks: &[Hunk]) -> (String, Vec<HunkOutcome>) {
+    let lines = lines(content);
+    let mut output = String::with_capacity(content.len());
+    let mut outcomes = Vec::with_capacity(hunks.len());
+    let mut cursor = 0;
+    let mut shift: isize = 0;
+    for hunk in hunks {
+        let side = |skip: LineKind| -> Vec<String> {
+            hunk.lines
+                .iter()
+                .filter(|line| line.kind != skip)
+                .map(|line| {
+                    let mut text = line.text.clone();
+                    if !line.no_newline {
+                        text.push('\n');
+                    }
+                    text
+                })
+                .collect()
+        };
+        let (expected, replacement) = (side(LineKind::Added), side(LineKind::Removed));
+        // A hunk without old lines inserts after its start line.
+        let stated = if expected.is_empty() {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        } as isize;
+        let matches = |at: usize| {
+            at + expected.len() <= lines.len()
+                && lines[at..at + expected.len()]
+                    .iter()
+                    .zip(&expected)
+                    .all(|(line, expected)| line == expected)
+        };
+        let target = (stated + shift).clamp(cursor as isize, lines.len() as isize) as usize;
+        let found = (0..=lines.len()).find_map(|distance| {
+            [target.checked_add(distance), target.checked_sub(distance)]
+                .into_iter()
+                .flatten()
+                .find(|&at| at >= cursor && matches(at))
+        });
+        let Some(at) = found else {
+            outcomes.push(HunkOutcome::Conflict);
+            continue;
+        };
+        lines[cursor..at]
+            .iter()
+            .for_each(|line| output.push_str(line));
+        replacement.iter().for_each(|line| output.push_str(line));
+        cursor = at + expected.len();
+        shift = at as isize - stated;
+        outcomes.push(HunkOutcome::Applied {
+            line: at + 1,
+            offset: shift,
+        });
+    }
+    lines[cursor..]
+        .iter()
+        .for_each(|line| output.push_str(line));
+    (output, outcomes)
+}
+
+/// The path in a `---` or `+++` header, without a trailing timestamp.
+fn header_path(header: &str) -> Option<String> {
+    let path = header.split('\t').next().unwrap_or(header).trim_end();
+    (path != "/dev/null").then(|| path.to_string())
+}
+
+fn strip_prefixes(old: Option<String>, new: Option<String>) -> (Option<String>, Option<String>) {
+    let prefixed = old.as_deref().is_none_or(|path| path.starts_with("a/"))
+        && new.as_deref().is_none_or(|path| path.starts_with("b/"));
+    if !prefixed {
+        return (old, new);
+    }
+    let strip = |path: Option<String>| path.map(|path| path[2..].to_string());
+    (strip(old), strip(new))
+}
+
+/// `@@ -old_start,old_lines +new_start,new_lines @@`, where a missing
+/// count means one line.
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize, usize)> {
+    let ranges = line.strip_prefix("@@ -")?;
+    let (ranges, _) = ranges.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, lines)) => Some((start.parse().ok()?, lines.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_lines) = range(old)?;
+    let (new_start, new_lines) = range(new)?;
+    Some((old_start, old_lines, new_start, new_lines))
+}
+
+fn header_range(start: usize, lines: usize) -> String {
+    if lines == 1 {
+        start.to_string()
+    } else {
+        format!("{start},{lines}")
+    }
+}
+
+/// Shortest edit script from `a` to `b`. Common leading and trailing lines
+/// are matched up front so only the changed middle is searched.
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (middle_a, middle_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut kinds = vec![LineKind::Context; prefix];
+    match myers(middle_a, middle_b) {
+        Some(middle) => kinds.extend(middle),
+        None => {
+            kinds.extend(std::iter::repeat_n(LineKind::Removed, middle_a.len()));
+            kinds.extend(std::iter::repeat_n(LineKind::Added, middle_b.len()));
+        }
+    }
+    kinds.extend(std::iter::repeat_n(LineKind::Context, suffix));
+
+    let (mut old, mut new) = (0, 0);
+    kinds
+        .into_iter()
+        .map(|kind| {
+            let op = Op { kind, old, new };
+            if kind != LineKind::Added {
+                old += 1;
+            }
+            if kind != LineKind::Removed {
+                new += 1;
+            }
+            op
+        })
+        .collect()
+}
+
+/// Myers' O(ND) diff, or `None` once the distance passes
+/// [`MAX_EDIT_DISTANCE`]. Keeps the furthest-reaching frontier of every
+/// step, `2d + 1` entries for step `d`, to walk back along afterwards.
+fn myers(a: &[&str], b: &[&str]) -> Option<Vec<LineKind>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let snake = |mut x: isize, mut y: isize| {
+        while x < n && y < m && a[x as usize] == b[y as usize] {
+            x += 1;
+            y += 1;
+        }
+        x
+    };
+
+    let mut frontiers: Vec<Vec<isize>> = vec![vec![snake(0, 0)]];
+    let max = (a.len() + b.len()).min(MAX_EDIT_DISTANCE);
+    while frontiers.last().is_some_and(|last| !reached(last, n, m)) {
+        let d = frontiers.len();
+        if d > max {
+            return None;
+        }
+        let previous = &frontiers[d - 1];
+        let frontier = (0..=2 * d)
+            .map(|i| {
+                let k = i as isize - d as isize;
+                match step(previous, k, n, m) {
+                    Some((x, _)) if i % 2 == 0 => snake(x, x - k),
+                    _ => UNREACHED,
+                }
+            })
+            .collect();
+        frontiers.push(frontier);
+    }
+
+    let mut kinds = Vec::with_capacity(a.len() + b.len());
+    let (mut x, mut y) = (n, m);
+    for d in (1..frontiers.len()).rev() {
+        let (start_x, down) = step(&frontiers[d - 1], x - y, n, m)?;
+        let start_y = start_x - (x - y);
+        while x > start_x && y > start_y {
+            kinds.push(LineKind::Context);
+            x -= 1;
+            y -= 1;
+        }
+        if down {
+            kinds.push(LineKind::Added);
+            y -= 1;
+        } else {
+            kinds.push(LineKind::Removed);
+            x -= 1;
+        }
+    }
+    kinds.extend(std::iter::repeat_n(LineKind::Context, x as usize));
+    kinds.reverse();
+    Some(kinds)
+}
+
+/// Frontier entry for a diagonal no path has reached.
+const UNREACHED: isize = -1;
+
+/// Whether a frontier reached the end of both inputs. Step `d`'s frontier
+/// holds diagonal `k` at index `k + d`.
+fn reached(frontier: &[isize], n: isize, m: isize) -> bool {
+    let d = (frontier.len() / 2) as isize;
+    let k = n - m;
+    (-d..=d).contains(&k) && frontier[(k + d) as usize] == n
+}
+
+/// Where the next step onto diagonal `k` starts, before following its
+/// snake, and whether it moves down (an addition) rather than right (a
+/// removal). Moves that would leave the edit graph are never taken.
+fn step(previous: &[isize], k: isize, n: isize, m: isize) -> Option<(isize, bool)> {
+    let d = (previous.len() / 2) as isize;
+    let at = |k: isize| {
+        (-d..=d)
+            .contains(&k)
+            .then(|| previous[(k + d) as usize])
+            .filter(|&x| x != UNREACHED)
+    };
+    let down = at(k + 1).filter(|&x| x - k <= m);
+    let right = at(k - 1).map(|x| x + 1).filter(|&x| x <= n);
+    match (down, right) {
+        (Some(down), Some(right)) if down >= right => Some((down, true)),
+        (_, Some(right)) => Some((right, false)),
+        (Some(down), None) => Some((down, true)),
+        (None, None) => None,
+    }
+}
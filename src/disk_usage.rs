@@ -0,0 +1,189 @@
+//! Space taken by the workspace, per top-level entry of each root, and
+//! left on the volume it lives on.
+
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock,
+    rpc::{cancel::CancelToken, error::HandlerError},
+};
+
+/// Entries walked between cancellation checks.
+const CHECK_EVERY: u64 = 1024;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryUsage {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub bytes: u64,
+    pub files: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RootUsage {
+    pub workspace: String,
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub files: u64,
+    /// Largest first.
+    pub entries: Vec<EntryUsage>,
+}
+
+#[derive(Clone)]
+pub struct Report {
+    pub roots: Vec<RootUsage>,
+    pub computed_at: u64,
+    computed: Instant,
+}
+
+/// Free space on a filesystem, in bytes, as `df` reports it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Volume {
+    pub total: u64,
+    pub used: u64,
+    pub available: u64,
+    /// Share of `total` in use, in whole percent, rounded down.
+    pub used_percent: u64,
+    pub mount: String,
+}
+
+/// The last report, kept since walking a large workspace takes a while.
+/// Callers arriving during a walk wait for it rather than starting their
+/// own.
+#[derive(Default)]
+pub struct DiskUsage {
+    cached: Mutex<Option<Report>>,
+}
+
+impl DiskUsage {
+    /// The cached report if it is younger than `max_age`, or a new one.
+    /// Also returns whether it came from the cache.
+    pub fn report<'a>(
+        &self,
+        roots: impl Iterator<Item = (&'a str, &'a Path)>,
+        max_age: Duration,
+        cancel: &CancelToken,
+    ) -> Result<(Report, bool), HandlerError> {
+        let mut cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(report) = cached.as_ref()
+            && clock::now().duration_since(report.computed) < max_age
+        {
+            return Ok((report.clone(), true));
+        }
+        let mut walked = 0;
+        let roots = roots
+            .map(|(name, path)| measure_root(name, path, &mut walked, cancel))
+            .collect::<Result<_, _>>()?;
+        let report = Report {
+            roots,
+            computed_at: clock::unix_secs(),
+            computed: clock::now(),
+        };
+        *cached = Some(report.clone());
+        Ok((report, false))
+    }
+}
+
+fn measure_root(
+    name: &str,
+    path: &Path,
+    walked: &mut u64,
+    cancel: &CancelToken,
+) -> Result<RootUsage, HandlerError> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(path).map_err(HandlerError::IoError)? {
+        let Ok(entry) = entry else { continue };
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let kind = if metadata.is_symlink() {
+            "symlink"
+        } else if metadata.is_dir() {
+            "directory"
+        } else {
+            "file"
+        };
+        let (bytes, files) = if metadata.is_dir() {
+            measure(&entry.path(), walked, cancel)?
+        } else {
+            (metadata.len(), 1)
+        };
+        entries.push(EntryUsage {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            kind,
+            bytes,
+            files,
+        });
+    }
+    entries.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    Ok(RootUsage {
+        workspace: name.to_string(),
+        path: path.to_path_buf(),
+        bytes: entries.iter().map(|entry| entry.bytes).sum(),
+        files: entries.iter().map(|entry| entry.files).sum(),
+        entries,
+    })
+}
+
+/// Bytes and files under `dir`, not following symlinks. Unreadable
+/// entries count for nothing.
+fn measure(dir: &Path, walked: &mut u64, cancel: &CancelToken) -> Result<(u64, u64), HandlerError> {
+    let (mut bytes, mut files) = (0, 0);
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            *walked += 1;
+            if walked.is_multiple_of(CHECK_EVERY) {
+                cancel.check()?;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                bytes += metadata.len();
+                files += 1;
+            }
+        }
+    }
+    Ok((bytes, files))
+}
+
+/// The volume holding `path`, or `None` where `df` is unavailable.
+pub fn volume(path: &Path) -> Option<Volume> {
+    let output = Command::new("df")
+        .args(["-P", "-k", "--"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    // Filesystem, 1024-blocks, Used, Available, Capacity, Mounted on.
+    let fields: Vec<&str> = text.lines().nth(1)?.split_whitespace().collect();
+    let kib = |index: usize| fields.get(index)?.parse::<u64>().ok().map(|n| n * 1024);
+    let (total, used) = (kib(1)?, kib(2)?);
+    Some(Volume {
+        total,
+        used,
+        available: kib(3)?,
+        used_percent: (used * 100).checked_div(total).unwrap_or(0),
+        mount: fields.get(5..)?.join(" "),
+    })
+}
@@ -0,0 +1,301 @@
+//! Open documents: files editors hold in memory and change without saving,
+//! shared by every connection that has them open.
+//!
+//! A document is written back on `document/save` or `saveAll`, or by the
+//! auto-saver when `autoSave` is configured, and each save is announced to
+//! its holders as `documentSaved`.
+
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    charset::{self, Charset},
+    rpc::context::Notifier,
+    staged,
+    state::SharedState,
+};
+
+/// How often the auto-saver looks for documents that are due.
+const AUTO_SAVE_TICK: Duration = Duration::from_millis(250);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SaveReason {
+    Save,
+    SaveAll,
+    AutoSave,
+    /// The last holder closed it, or the server is shutting down, while
+    /// auto-save is on.
+    Flush,
+}
+
+struct Document {
+    content: String,
+    /// Bumped by every update; clients pass it back to detect races.
+    version: u64,
+    /// Version last written to disk.
+    saved_version: u64,
+    charset: Charset,
+    bom: bool,
+    edited_at: Instant,
+    saved_at: Instant,
+    /// Connections that have the document open.
+    holders: BTreeMap<u64, Notifier>,
+}
+
+impl Document {
+    fn dirty(&self) -> bool {
+        self.version != self.saved_version
+    }
+}
+
+/// What `document/open` returns.
+pub struct Opened {
+    pub content: String,
+    pub version: u64,
+    pub dirty: bool,
+    pub encoding: Charset,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Saved {
+    pub path: PathBuf,
+    pub version: u64,
+    pub bytes: usize,
+}
+
+#[derive(Default)]
+pub struct DocumentStore {
+    documents: Mutex<BTreeMap<PathBuf, Document>>,
+}
+
+impl DocumentStore {
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Document>> {
+        self.documents.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Opens `path` for a connection, reading it from disk unless another
+    /// connection already has it open.
+    pub fn open(&self, path: &Path, connection_id: u64, notifier: Notifier) -> io::Result<Opened> {
+        let key = key(path)?;
+        let mut documents = self.lock();
+        if !documents.contains_key(&key) {
+            let bytes = std::fs::read(path)?;
+            let decoded = charset::decode(&bytes, None).map_err(io::Error::other)?;
+            let now = Instant::now();
+            documents.insert(
+                key.clone(),
+                Document {
+                    content: decoded.text,
+                    version: 1,
+                    saved_version: 1,
+                    charset: decoded.charset,
+                    bom: decoded.bom,
+                    edited_at: now,
+                    saved_at: now,
+                    holders: BTreeMap::new(),
+                },
+            );
+        }
+        let document = documents.get_mut(&key).expect("document was just inserted");
+        document.holders.insert(connection_id, notifier);
+        Ok(Opened {
+            content: document.content.clone(),
+            version: document.version,
+            dirty: document.dirty(),
+            encoding: document.charset,
+        })
+    }
+
+    /// Replaces a document's content, returning its new version. `expected`
+    /// is the version the client last saw; an update based on an older one
+    /// is refused.
+    pub fn update(
+        &self,
+        path: &Path,
+        connection_id: u64,
+        content: String,
+        expected: Option<u64>,
+    ) -> Result<u64, String> {
+        let key = key(path).map_err(|e| e.to_string())?;
+        let mut documents = self.lock();
+        let Some(document) = documents
+            .get_mut(&key)
+            .filter(|document| document.holders.contains_key(&connection_id))
+        else {
+            return Err(format!("Document is not open: {}", path.display()));
+        };
+        if let Some(expected) = expected
+            && expected != document.version
+        {
+            return Err(format!(
+                "Document {} is at version {}, not {expected}",
+                path.display(),
+                document.version
+            ));
+        }
+        document.content = content;
+        document.version += 1;
+        document.edited_at = Instant::now();
+        Ok(document.version)
+    }
+
+    /// Closes a document for one connection. Once nobody holds it, it is
+    /// dropped, flushed first when `flush` is set; otherwise unsaved changes
+    /// are discarded. Returns whether any were.
+    pub fn close(&self, path: &Path, connection_id: u64, flush: bool) -> io::Result<bool> {
+        let key = key(path)?;
+        self.release(&key, connection_id, flush)
+    }
+
+    /// Closes every document a connection holds.
+    pub fn close_connection(&self, connection_id: u64, flush: bool) {
+        let held: Vec<PathBuf> = self
+            .lock()
+            .iter()
+            .filter(|(_, document)| document.holders.contains_key(&connection_id))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in held {
+            if let Err(e) = self.release(&path, connection_id, flush) {
+                warn!(path = %path.display(), error = %e, "Failed to flush closed document");
+            }
+        }
+    }
+
+    fn release(&self, key: &Path, connection_id: u64, flush: bool) -> io::Result<bool> {
+        let mut documents = self.lock();
+        let Some(document) = documents.get_mut(key) else {
+            return Ok(false);
+        };
+        document.holders.remove(&connection_id);
+        if !document.holders.is_empty() {
+            return Ok(false);
+        }
+        let discarded = document.dirty() && !flush;
+        if document.dirty() && flush {
+            save_locked(key, document, SaveReason::Flush)?;
+        }
+        documents.remove(key);
+        if discarded {
+            debug!(path = %key.display(), "Discarded unsaved document changes");
+        }
+        Ok(discarded)
+    }
+
+    /// Writes one document if it has unsaved changes.
+    pub fn save(&self, path: &Path, reason: SaveReason) -> io::Result<Option<Saved>> {
+        let key = key(path)?;
+        let mut documents = self.lock();
+        let Some(document) = documents.get_mut(&key) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Document is not open: {}", path.display()),
+            ));
+        };
+        if !document.dirty() {
+            return Ok(None);
+        }
+        save_locked(&key, document, reason).map(Some)
+    }
+
+    /// Writes every document with unsaved changes that `due` selects,
+    /// returning what was saved and what failed.
+    fn save_where(
+        &self,
+        reason: SaveReason,
+        due: impl Fn(&Document) -> bool,
+    ) -> (Vec<Saved>, Vec<(PathBuf, io::Error)>) {
+        let mut documents = self.lock();
+        let (mut saved, mut failed) = (Vec::new(), Vec::new());
+        for (path, document) in documents.iter_mut() {
+            if !document.dirty() || !due(document) {
+                continue;
+            }
+            match save_locked(path, document, reason) {
+                Ok(done) => saved.push(done),
+                Err(e) => failed.push((path.clone(), e)),
+            }
+        }
+        (saved, failed)
+    }
+
+    pub fn save_all(&self, reason: SaveReason) -> (Vec<Saved>, Vec<(PathBuf, io::Error)>) {
+        self.save_where(reason, |_| true)
+    }
+}
+
+/// Open documents are keyed by absolute path, so different spellings of
+/// one file share a document.
+fn key(path: &Path) -> io::Result<PathBuf> {
+    Ok(crate::sandbox::normalize(&std::path::absolute(path)?))
+}
+
+fn save_locked(path: &Path, document: &mut Document, reason: SaveReason) -> io::Result<Saved> {
+    let bytes = charset::encode(&document.content, document.charset, document.bom)
+        .map_err(io::Error::other)?;
+    staged::write_all([(path, bytes.as_slice())])?;
+    document.saved_version = document.version;
+    document.saved_at = Instant::now();
+    let saved = Saved {
+        path: path.to_path_buf(),
+        version: document.version,
+        bytes: bytes.len(),
+    };
+    debug!(path = %path.display(), version = saved.version, ?reason, "Saved document");
+    for notifier in document.holders.values() {
+        notifier.notify(
+            "documentSaved",
+            json!({ "path": saved.path, "version": saved.version, "reason": reason }),
+        );
+    }
+    Ok(saved)
+}
+
+/// Starts the auto-saver if `autoSave` asks for one.
+pub fn start(state: SharedState) {
+    let config = state.config.auto_save.clone();
+    if !config.enabled() {
+        return;
+    }
+    let interval = config
+        .interval_secs
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs);
+    let idle = config
+        .idle_ms
+        .filter(|&ms| ms > 0)
+        .map(Duration::from_millis);
+    info!(?interval, ?idle, "Auto-save started");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(AUTO_SAVE_TICK);
+        loop {
+            tick.tick().await;
+            let (saved, failed) = state
+                .documents
+                .save_where(SaveReason::AutoSave, |document| {
+                    interval.is_some_and(|interval| document.saved_at.elapsed() >= interval)
+                        || idle.is_some_and(|idle| document.edited_at.elapsed() >= idle)
+                });
+            for (path, e) in failed {
+                warn!(path = %path.display(), error = %e, "Auto-save failed");
+            }
+            for saved in saved {
+                state.activity.record(
+                    "file.saved",
+                    None,
+                    json!({ "path": saved.path, "bytes": saved.bytes, "autoSave": true }),
+                );
+            }
+        }
+    });
+}
@@ -0,0 +1,104 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+use tracing::{debug, warn};
+
+use crate::{clock, raw, share::random_token, state::SharedState};
+
+/// How long a download link stays valid.
+pub const DOWNLOAD_TTL_SECS: u64 = 60 * 60;
+
+#[derive(Debug, Clone)]
+pub struct Download {
+    /// Blob holding the content.
+    pub hash: String,
+    pub file_name: String,
+    pub content_type: &'static str,
+    pub expires_at: u64,
+}
+
+/// One-hour links to blobs, served by `GET /download/<token>` so browser
+/// clients can save generated artifacts without staging them over the
+/// socket.
+#[derive(Default)]
+pub struct DownloadStore {
+    downloads: Mutex<HashMap<String, Download>>,
+}
+
+impl DownloadStore {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Download>> {
+        self.downloads.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the token for a new link to `hash`.
+    pub fn create(
+        &self,
+        hash: String,
+        file_name: String,
+        content_type: &'static str,
+    ) -> Result<(String, Download), getrandom::Error> {
+        let token = random_token()?;
+        let download = Download {
+            hash,
+            file_name,
+            content_type,
+            expires_at: clock::unix_secs() + DOWNLOAD_TTL_SECS,
+        };
+        let mut downloads = self.lock();
+        let now = clock::unix_secs();
+        downloads.retain(|_, download| download.expires_at > now);
+        downloads.insert(token.clone(), download.clone());
+        Ok((token, download))
+    }
+
+    fn get(&self, token: &str) -> Option<Download> {
+        self.lock()
+            .get(token)
+            .filter(|download| download.expires_at > clock::unix_secs())
+            .cloned()
+    }
+}
+
+pub async fn download_handler(
+    State(state): State<SharedState>,
+    Path(token): Path<String>,
+) -> Response {
+    let Some(download) = state.downloads.get(&token) else {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    };
+    // Streamed from the blob: exported archives can be large.
+    let file = state.blobs.open(&download.hash).and_then(|file| {
+        let size = file.metadata()?.len();
+        Ok((tokio::fs::File::from_std(file), size))
+    });
+    match file {
+        Ok((file, size)) => {
+            debug!(file = %download.file_name, bytes = size, "Serving download");
+            let disposition = format!(
+                "attachment; filename=\"{}\"",
+                download.file_name.replace(['"', '\\'], "_")
+            );
+            (
+                [
+                    (header::CONTENT_TYPE, download.content_type.to_string()),
+                    (header::CONTENT_LENGTH, size.to_string()),
+                    (header::CONTENT_DISPOSITION, disposition),
+                    (header::CACHE_CONTROL, "no-store".to_string()),
+                ],
+                raw::file_body(Bytes::new(), file),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            warn!(error = %e, "Download blob is unavailable");
+            (StatusCode::NOT_FOUND, "Not found").into_response()
+        }
+    }
+}
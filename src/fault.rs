@@ -0,0 +1,110 @@
+//! Fault injection for exercising client retry, reconnect and conflict
+//! handling.
+//!
+//! Only builds with the `faults` feature act on the `faults` config; in
+//! any other build every request and event passes through untouched, so a
+//! stray config entry cannot make a production server misbehave.
+
+use std::{collections::BTreeMap, time::Duration};
+use tracing::{debug, warn};
+
+use crate::config::{FaultConfig, FaultRule};
+
+/// Whether the server was built to honour the `faults` config.
+pub const ENABLED: bool = cfg!(feature = "faults");
+
+/// What to do with one request or event.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Fault {
+    /// Hold it back this long first.
+    pub delay: Option<Duration>,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum Outcome {
+    #[default]
+    Pass,
+    /// Answer with an injected error instead of running the handler.
+    Fail,
+    /// Never answer or deliver.
+    Drop,
+    /// Panic in place of the handler.
+    Panic,
+}
+
+pub struct FaultInjector {
+    config: FaultConfig,
+}
+
+impl FaultInjector {
+    pub fn new(config: &FaultConfig) -> Result<Self, String> {
+        for (key, rule) in config.methods.iter().chain(&config.events) {
+            let rates = [
+                rule.delay_rate,
+                rule.fail_rate,
+                rule.drop_rate,
+                rule.panic_rate,
+            ];
+            if rates.iter().any(|rate| !(0.0..=1.0).contains(rate)) {
+                return Err(format!("fault rates for {key} must be between 0 and 1"));
+            }
+        }
+        let configured = !config.methods.is_empty() || !config.events.is_empty();
+        if configured && !ENABLED {
+            warn!("Ignoring fault config; the server was built without the faults feature");
+        }
+        Ok(Self {
+            config: config.clone(),
+        })
+    }
+
+    pub fn for_method(&self, method: &str) -> Fault {
+        let fault = decide(&self.config.methods, method);
+        if fault != Fault::default() {
+            debug!(method, fault = ?fault, "Injecting fault");
+        }
+        fault
+    }
+
+    /// Events are never failed; a fail or panic roll drops them instead.
+    pub fn for_event(&self, kind: &str) -> Fault {
+        let mut fault = decide(&self.config.events, kind);
+        if matches!(fault.outcome, Outcome::Fail | Outcome::Panic) {
+            fault.outcome = Outcome::Drop;
+        }
+        if fault != Fault::default() {
+            debug!(kind, fault = ?fault, "Injecting event fault");
+        }
+        fault
+    }
+}
+
+fn decide(rules: &BTreeMap<String, FaultRule>, key: &str) -> Fault {
+    if !ENABLED {
+        return Fault::default();
+    }
+    let Some(rule) = rules.get(key).or_else(|| rules.get("*")) else {
+        return Fault::default();
+    };
+    let delay = (chance(rule.delay_rate) && rule.max_delay_ms > 0)
+        .then(|| Duration::from_millis(random() % (rule.max_delay_ms + 1)));
+    let outcome = if chance(rule.drop_rate) {
+        Outcome::Drop
+    } else if chance(rule.fail_rate) {
+        Outcome::Fail
+    } else if chance(rule.panic_rate) {
+        Outcome::Panic
+    } else {
+        Outcome::Pass
+    };
+    Fault { delay, outcome }
+}
+
+fn chance(rate: f64) -> bool {
+    rate > 0.0 && (random() as f64 / u64::MAX as f64) < rate
+}
+
+fn random() -> u64 {
+    getrandom::u64().unwrap_or_default()
+}
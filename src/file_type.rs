@@ -0,0 +1,351 @@
+//! What a file holds, so clients can pick the text editor, an image
+//! preview or a hex view for it.
+//!
+//! The first bytes decide when they carry a known signature. Otherwise
+//! they are sniffed for text (see [`charset::sniff`]), and the extension
+//! names the kind of text, or of binary data, when it is known.
+
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::{charset, charset::Charset, syntax};
+
+/// Leading bytes read to classify a file.
+pub const SNIFF_BYTES: usize = 8192;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Category {
+    Text,
+    Image,
+    Audio,
+    Video,
+    Font,
+    Archive,
+    Document,
+    Executable,
+    Binary,
+}
+
+/// How a client should open the file.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Viewer {
+    Text,
+    Image,
+    Hex,
+}
+
+/// What settled the type.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Evidence {
+    /// The magic bytes the format starts with.
+    Signature,
+    /// The extension, for content without a signature.
+    Extension,
+    /// Sniffing alone: text or binary of no known kind.
+    Content,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct FileType {
+    pub mime: &'static str,
+    pub category: Category,
+    pub viewer: Viewer,
+    /// For text, the charset it appears to be in.
+    pub charset: Option<Charset>,
+    /// For text, the grammar its extension selects.
+    pub language: Option<&'static str>,
+    pub detected_by: Evidence,
+}
+
+/// Extensions whose type content alone does not pin down. Text types are
+/// only taken when the content is text, binary ones only when it is not.
+const EXTENSIONS: &[(&str, &str, Category)] = &[
+    ("md", "text/markdown", Category::Text),
+    ("markdown", "text/markdown", Category::Text),
+    ("txt", "text/plain", Category::Text),
+    ("html", "text/html", Category::Text),
+    ("htm", "text/html", Category::Text),
+    ("css", "text/css", Category::Text),
+    ("csv", "text/csv", Category::Text),
+    ("tsv", "text/tab-separated-values", Category::Text),
+    ("js", "text/javascript", Category::Text),
+    ("mjs", "text/javascript", Category::Text),
+    ("cjs", "text/javascript", Category::Text),
+    ("ts", "text/x-typescript", Category::Text),
+    ("tsx", "text/x-typescript", Category::Text),
+    ("jsx", "text/javascript", Category::Text),
+    ("json", "application/json", Category::Text),
+    ("map", "application/json", Category::Text),
+    ("xml", "application/xml", Category::Text),
+    ("yaml", "application/yaml", Category::Text),
+    ("yml", "application/yaml", Category::Text),
+    ("toml", "application/toml", Category::Text),
+    ("rs", "text/x-rust", Category::Text),
+    ("py", "text/x-python", Category::Text),
+    ("go", "text/x-go", Category::Text),
+    ("c", "text/x-c", Category::Text),
+    ("h", "text/x-c", Category::Text),
+    ("cpp", "text/x-c++", Category::Text),
+    ("hpp", "text/x-c++", Category::Text),
+    ("java", "text/x-java", Category::Text),
+    ("sh", "application/x-sh", Category::Text),
+    ("sql", "application/sql", Category::Text),
+    ("svg", "image/svg+xml", Category::Image),
+    ("png", "image/png", Category::Image),
+    ("jpg", "image/jpeg", Category::Image),
+    ("jpeg", "image/jpeg", Category::Image),
+    ("gif", "image/gif", Category::Image),
+    ("webp", "image/webp", Category::Image),
+    ("bmp", "image/bmp", Category::Image),
+    ("ico", "image/x-icon", Category::Image),
+    ("tif", "image/tiff", Category::Image),
+    ("tiff", "image/tiff", Category::Image),
+    ("avif", "image/avif", Category::Image),
+    ("mp3", "audio/mpeg", Category::Audio),
+    ("wav", "audio/wav", Category::Audio),
+    ("ogg", "audio/ogg", Category::Audio),
+    ("flac", "audio/flac", Category::Audio),
+    ("m4a", "audio/mp4", Category::Audio),
+    ("mp4", "video/mp4", Category::Video),
+    ("mov", "video/quicktime", Category::Video),
+    ("webm", "video/webm", Category::Video),
+    ("mkv", "video/x-matroska", Category::Video),
+    ("avi", "video/x-msvideo", Category::Video),
+    ("woff", "font/woff", Category::Font),
+    ("woff2", "font/woff2", Category::Font),
+    ("ttf", "font/ttf", Category::Font),
+    ("otf", "font/otf", Category::Font),
+    ("zip", "application/zip", Category::Archive),
+    ("jar", "application/java-archive", Category::Archive),
+    ("gz", "application/gzip", Category::Archive),
+    ("tgz", "application/gzip", Category::Archive),
+    ("tar", "application/x-tar", Category::Archive),
+    ("zst", "application/zstd", Category::Archive),
+    ("xz", "application/x-xz", Category::Archive),
+    ("bz2", "application/x-bzip2", Category::Archive),
+    ("7z", "application/x-7z-compressed", Category::Archive),
+    ("pdf", "application/pdf", Category::Document),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        Category::Document,
+    ),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        Category::Document,
+    ),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        Category::Document,
+    ),
+    (
+        "odt",
+        "application/vnd.oasis.opendocument.text",
+        Category::Document,
+    ),
+    ("epub", "application/epub+zip", Category::Document),
+    ("wasm", "application/wasm", Category::Executable),
+    (
+        "exe",
+        "application/vnd.microsoft.portable-executable",
+        Category::Executable,
+    ),
+    (
+        "dll",
+        "application/vnd.microsoft.portable-executable",
+        Category::Executable,
+    ),
+    ("so", "application/x-sharedlib", Category::Executable),
+    ("sqlite", "application/vnd.sqlite3", Category::Binary),
+    ("db", "application/vnd.sqlite3", Category::Binary),
+];
+
+/// Reads the start of `path` and classifies it.
+pub fn detect_file(path: &Path) -> io::Result<FileType> {
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    File::open(path)?
+        .take(SNIFF_BYTES as u64)
+        .read_to_end(&mut head)?;
+    let partial = head.len() == SNIFF_BYTES;
+    Ok(detect(path, &head, partial))
+}
+
+/// Classifies a file named `path` starting with `head`, which is the whole
+/// file unless `partial` is set.
+pub fn detect(path: &Path, head: &[u8], partial: bool) -> FileType {
+    let by_extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .and_then(|extension| {
+            EXTENSIONS
+                .iter()
+                .find(|(known, ..)| *known == extension)
+                .map(|&(_, mime, category)| (mime, category))
+        });
+    if let Some((mime, category)) = signature(head) {
+        // Zip containers are told apart by name.
+        let (mime, category) = match by_extension {
+            Some(named @ (_, Category::Archive | Category::Document))
+                if mime == "application/zip" =>
+            {
+                named
+            }
+            _ => (mime, category),
+        };
+        return binary(mime, category, Evidence::Signature);
+    }
+
+    if let Some(charset) = charset::sniff(head, partial) {
+        let (mime, category, detected_by) = match by_extension {
+            Some((mime, Category::Text)) => (mime, Category::Text, Evidence::Extension),
+            Some((mime @ "image/svg+xml", category)) => (mime, category, Evidence::Extension),
+            _ => ("text/plain", Category::Text, Evidence::Content),
+        };
+        return FileType {
+            mime,
+            category,
+            viewer: viewer(category),
+            charset: Some(charset),
+            language: syntax::grammar_for(path, None).map(|grammar| grammar.id),
+            detected_by,
+        };
+    }
+
+    if let Some((mime, category)) = weak_signature(head) {
+        return binary(mime, category, Evidence::Signature);
+    }
+    match by_extension {
+        Some((mime, category)) if category != Category::Text => {
+            binary(mime, category, Evidence::Extension)
+        }
+        _ => binary(
+            "application/octet-stream",
+            Category::Binary,
+            Evidence::Content,
+        ),
+    }
+}
+
+fn binary(mime: &'static str, category: Category, detected_by: Evidence) -> FileType {
+    FileType {
+        mime,
+        category,
+        viewer: viewer(category),
+        charset: None,
+        language: None,
+        detected_by,
+    }
+}
+
+fn viewer(category: Category) -> Viewer {
+    match category {
+        Category::Text => Viewer::Text,
+        Category::Image => Viewer::Image,
+        _ => Viewer::Hex,
+    }
+}
+
+/// Signatures no text starts with.
+fn signature(head: &[u8]) -> Option<(&'static str, Category)> {
+    let at = |offset: usize, magic: &[u8]| {
+        head.get(offset..)
+            .is_some_and(|rest| rest.starts_with(magic))
+    };
+    let found = if at(0, b"\x89PNG\r\n\x1a\n") {
+        ("image/png", Category::Image)
+    } else if at(0, b"\xff\xd8\xff") {
+        ("image/jpeg", Category::Image)
+    } else if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        ("image/gif", Category::Image)
+    } else if at(0, b"RIFF") && at(8, b"WEBP") {
+        ("image/webp", Category::Image)
+    } else if at(0, b"RIFF") && at(8, b"WAVE") {
+        ("audio/wav", Category::Audio)
+    } else if at(0, b"RIFF") && at(8, b"AVI ") {
+        ("video/x-msvideo", Category::Video)
+    } else if at(0, b"II*\0") || at(0, b"MM\0*") {
+        ("image/tiff", Category::Image)
+    } else if at(0, b"\0\0\x01\0") {
+        ("image/x-icon", Category::Image)
+    } else if at(4, b"ftyp") {
+        match head.get(8..12) {
+            Some(b"avif") => ("image/avif", Category::Image),
+            Some(b"heic") => ("image/heic", Category::Image),
+            Some(b"M4A ") => ("audio/mp4", Category::Audio),
+            Some(b"qt  ") => ("video/quicktime", Category::Video),
+            _ => ("video/mp4", Category::Video),
+        }
+    } else if at(0, b"\x1a\x45\xdf\xa3") {
+        ("video/webm", Category::Video)
+    } else if at(0, b"OggS") {
+        ("audio/ogg", Category::Audio)
+    } else if at(0, b"fLaC") {
+        ("audio/flac", Category::Audio)
+    } else if at(0, b"wOFF") {
+        ("font/woff", Category::Font)
+    } else if at(0, b"wOF2") {
+        ("font/woff2", Category::Font)
+    } else if at(0, b"%PDF-") {
+        ("application/pdf", Category::Document)
+    } else if at(0, b"PK\x03\x04") || at(0, b"PK\x05\x06") {
+        ("application/zip", Category::Archive)
+    } else if at(0, b"\x1f\x8b") {
+        ("application/gzip", Category::Archive)
+    } else if at(0, b"\x28\xb5\x2f\xfd") {
+        ("application/zstd", Category::Archive)
+    } else if at(0, b"\xfd7zXZ\0") {
+        ("application/x-xz", Category::Archive)
+    } else if at(0, b"7z\xbc\xaf\x27\x1c") {
+        ("application/x-7z-compressed", Category::Archive)
+    } else if at(257, b"ustar") {
+        ("application/x-tar", Category::Archive)
+    } else if at(0, b"\x7fELF") {
+        ("application/x-elf", Category::Executable)
+    } else if [
+        b"\xfe\xed\xfa\xce",
+        b"\xfe\xed\xfa\xcf",
+        b"\xce\xfa\xed\xfe",
+        b"\xcf\xfa\xed\xfe",
+    ]
+    .iter()
+    .any(|magic| at(0, *magic))
+    {
+        ("application/x-mach-binary", Category::Executable)
+    } else if at(0, b"\0asm") {
+        ("application/wasm", Category::Executable)
+    } else if at(0, b"SQLite format 3\0") {
+        ("application/vnd.sqlite3", Category::Binary)
+    } else {
+        return None;
+    };
+    Some(found)
+}
+
+/// Signatures short enough for text to start with too, only trusted for
+/// content that is not text.
+fn weak_signature(head: &[u8]) -> Option<(&'static str, Category)> {
+    let found = match head {
+        [b'I', b'D', b'3', ..] => ("audio/mpeg", Category::Audio),
+        [0xff, second, ..] if second & 0xe0 == 0xe0 => ("audio/mpeg", Category::Audio),
+        [b'B', b'M', ..] => ("image/bmp", Category::Image),
+        [b'B', b'Z', b'h', ..] => ("application/x-bzip2", Category::Archive),
+        [b'M', b'Z', ..] => (
+            "application/vnd.microsoft.portable-executable",
+            Category::Executable,
+        ),
+        [0, 1, 0, 0, ..] => ("font/ttf", Category::Font),
+        [b'O', b'T', b'T', b'O', ..] => ("font/otf", Category::Font),
+        _ => return None,
+    };
+    Some(found)
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, Weak,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+use tokio::{runtime::Handle, sync::Semaphore};
+use tracing::debug;
+
+/// Smallest window a client may negotiate, so a single terminal read or
+/// output line always fits.
+const MIN_WINDOW: usize = 4096;
+
+type StreamKey = (&'static str, u64);
+
+/// Credit-based flow control for high-volume notification streams.
+///
+/// Once a client negotiates a window in `initialize`, each stream may have
+/// at most that many bytes of `data` sent but not yet acknowledged through
+/// `stream/ack`. Producers wait for credit instead of buffering, so a slow
+/// client throttles the terminal or task feeding it.
+#[derive(Default)]
+pub struct FlowControl {
+    /// Zero until the client opts in; streams are then unthrottled.
+    window: AtomicUsize,
+    streams: Mutex<HashMap<StreamKey, Arc<Semaphore>>>,
+}
+
+impl FlowControl {
+    /// Enables flow control for streams opened from now on, returning the
+    /// window actually used.
+    pub fn enable(&self, window: usize) -> usize {
+        let window = window.max(MIN_WINDOW);
+        self.window.store(window, Ordering::Relaxed);
+        window
+    }
+
+    /// Opens a stream, or returns `None` when the client did not opt in.
+    pub fn open(self: &Arc<Self>, kind: &'static str, id: u64) -> Option<StreamCredit> {
+        let window = self.window.load(Ordering::Relaxed);
+        if window == 0 {
+            return None;
+        }
+        let semaphore = Arc::new(Semaphore::new(window));
+        self.lock().insert((kind, id), Arc::clone(&semaphore));
+        Some(StreamCredit(Arc::new(CreditInner {
+            key: (kind, id),
+            semaphore,
+            window,
+            handle: Handle::current(),
+            flow: Arc::downgrade(self),
+        })))
+    }
+
+    /// Returns `bytes` of credit to a stream. Credit never exceeds the
+    /// window, so over-acknowledging cannot disable throttling.
+    pub fn ack(&self, kind: &str, id: u64, bytes: usize) -> Result<(), String> {
+        let streams = self.lock();
+        let (_, semaphore) = streams
+            .iter()
+            .find(|((stream_kind, stream_id), _)| *stream_kind == kind && *stream_id == id)
+            .ok_or_else(|| format!("No flow-controlled {kind} stream {id}"))?;
+        let window = self.window.load(Ordering::Relaxed);
+        let outstanding = window.saturating_sub(semaphore.available_permits());
+        semaphore.add_permits(bytes.min(outstanding));
+        Ok(())
+    }
+
+    /// Releases every waiting producer; called when the connection closes.
+    pub fn close_all(&self) {
+        for (_, semaphore) in self.lock().drain() {
+            semaphore.close();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<StreamKey, Arc<Semaphore>>> {
+        self.streams.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+struct CreditInner {
+    key: StreamKey,
+    semaphore: Arc<Semaphore>,
+    window: usize,
+    handle: Handle,
+    flow: Weak<FlowControl>,
+}
+
+impl Drop for CreditInner {
+    fn drop(&mut self) {
+        if let Some(flow) = self.flow.upgrade() {
+            flow.lock().remove(&self.key);
+        }
+    }
+}
+
+/// A producer's handle on its stream's credit. Clones share the same
+/// window (a task's stdout and stderr count against one budget).
+#[derive(Clone)]
+pub struct StreamCredit(Arc<CreditInner>);
+
+impl StreamCredit {
+    /// Waits until `bytes` may be sent. Returns immediately once the
+    /// connection has closed.
+    pub async fn consume(&self, bytes: usize) {
+        let inner = &self.0;
+        let bytes = bytes.clamp(1, inner.window) as u32;
+        match inner.semaphore.acquire_many(bytes).await {
+            Ok(permit) => permit.forget(),
+            Err(_) => debug!(stream = ?inner.key, "Flow control closed"),
+        }
+    }
+
+    /// Blocking form of [`consume`](Self::consume) for producers running on
+    /// plain threads.
+    pub fn consume_blocking(&self, bytes: usize) {
+        self.0.handle.block_on(self.consume(bytes));
+    }
+}
@@ -0,0 +1,111 @@
+use axum::{
+    extract::State,
+    http::{StatusCode, Uri, header},
+    response::{IntoResponse, Response},
+};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::state::SharedState;
+
+const INDEX: &str = "index.html";
+
+/// `GET /<path>` when `staticDir` is set: serves the bundled editor UI.
+/// Paths without an extension that match no file get `index.html`, so the
+/// UI's client-side routes survive a reload; missing assets are a 404.
+pub async fn frontend_handler(State(state): State<SharedState>, uri: Uri) -> Response {
+    let Some(dir) = state.config.static_dir.clone() else {
+        return not_found();
+    };
+    let Some(relative) = relative_path(uri.path()) else {
+        warn!(
+            path = uri.path(),
+            "Rejected static path outside the directory"
+        );
+        return not_found();
+    };
+    let looks_like_asset = relative
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains('.'));
+
+    let served = tokio::task::spawn_blocking(move || {
+        let file = resolve(&dir, &relative)
+            .filter(|file| file.is_file())
+            .or_else(|| {
+                (!looks_like_asset)
+                    .then(|| resolve(&dir, Path::new(INDEX)))
+                    .flatten()
+            })?;
+        std::fs::read(&file).ok().map(|bytes| (file, bytes))
+    })
+    .await;
+    let Ok(Some((file, bytes))) = served else {
+        return not_found();
+    };
+    debug!(file = %file.display(), bytes = bytes.len(), "Serving static file");
+    // The index names the hashed assets of the current build, so it must
+    // be revalidated; the assets themselves can be cached.
+    let cache = if file.file_name().is_some_and(|name| name == INDEX) {
+        "no-cache"
+    } else {
+        "public, max-age=3600"
+    };
+    (
+        [
+            (header::CONTENT_TYPE, content_type(&file)),
+            (header::CACHE_CONTROL, cache),
+        ],
+        bytes,
+    )
+        .into_response()
+}
+
+/// The request path as a path under the static directory, or `None` if it
+/// climbs out of it.
+fn relative_path(path: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            segment if segment.contains('\\') => return None,
+            segment => relative.push(segment),
+        }
+    }
+    Some(relative)
+}
+
+/// `relative` under `dir`, unless a symlink leads it elsewhere.
+fn resolve(dir: &Path, relative: &Path) -> Option<PathBuf> {
+    let dir = dir.canonicalize().ok()?;
+    let file = dir.join(relative).canonicalize().ok()?;
+    file.starts_with(&dir).then_some(file)
+}
+
+fn content_type(file: &Path) -> &'static str {
+    let extension = file
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("json" | "map") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        Some("wasm") => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, "Not found").into_response()
+}
@@ -0,0 +1,175 @@
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+use crate::{clock, config::HistoryConfig, sandbox::normalize};
+
+/// Names the file a history directory belongs to, for anyone browsing the
+/// data directory by hand.
+const PATH_FILE: &str = "path";
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryVersion {
+    pub id: String,
+    /// Unix seconds at which the content was replaced.
+    pub saved_at: u64,
+    pub size: u64,
+}
+
+/// Previous versions of overwritten files, laid out as
+/// `<data dir>/history/<path hash>/<id>` with one file per version.
+/// Versions are copied rather than moved, since the file stays in place to
+/// be overwritten.
+pub struct FileHistory {
+    dir: PathBuf,
+    config: HistoryConfig,
+}
+
+impl FileHistory {
+    pub fn new(data_dir: &Path, config: &HistoryConfig) -> Self {
+        Self {
+            dir: data_dir.join("history"),
+            config: config.clone(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    fn file_dir(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = normalize(&std::path::absolute(path)?);
+        let hash = blake3::hash(path.as_os_str().as_encoded_bytes());
+        Ok(self.dir.join(&hash.to_hex()[..32]))
+    }
+
+    /// Copies `path`'s current content into its history, then prunes what
+    /// the retention limits no longer allow. Returns `None` when there is
+    /// nothing to keep: history is off, or `path` is missing, not a regular
+    /// file, or too large.
+    pub fn snapshot(&self, path: &Path) -> io::Result<Option<HistoryVersion>> {
+        if !self.config.enabled {
+            return Ok(None);
+        }
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if !metadata.is_file() || metadata.len() > self.config.max_file_bytes {
+            debug!(path = %path.display(), size = metadata.len(), "Not keeping history");
+            return Ok(None);
+        }
+
+        let file_dir = self.file_dir(path)?;
+        fs::create_dir_all(&file_dir)?;
+        fs::write(
+            file_dir.join(PATH_FILE),
+            normalize(&std::path::absolute(path)?)
+                .as_os_str()
+                .as_encoded_bytes(),
+        )?;
+        let saved_at = clock::unix_secs();
+        // Numbered after the newest version this second, so ids freed by
+        // pruning are never reused out of order.
+        let first = versions(&file_dir)?
+            .iter()
+            .filter(|version| version.saved_at == saved_at)
+            .filter_map(|version| sequence(&version.id))
+            .max()
+            .map_or(0, |n| n + 1);
+        let (id, version_path) = (first..)
+            .map(|n| format!("{saved_at}-{n}"))
+            .find_map(|id| {
+                let version_path = file_dir.join(&id);
+                match fs::File::create_new(&version_path) {
+                    Ok(_) => Some(Ok((id, version_path))),
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => None,
+                    Err(e) => Some(Err(e)),
+                }
+            })
+            .expect("unbounded id range")?;
+        if let Err(e) = fs::copy(path, &version_path) {
+            let _ = fs::remove_file(&version_path);
+            return Err(e);
+        }
+        debug!(path = %path.display(), id, "Kept previous version");
+        self.prune(&file_dir)?;
+        Ok(Some(HistoryVersion {
+            id,
+            saved_at,
+            size: metadata.len(),
+        }))
+    }
+
+    /// `path`'s versions, most recent first.
+    pub fn list(&self, path: &Path) -> io::Result<Vec<HistoryVersion>> {
+        versions(&self.file_dir(path)?)
+    }
+
+    /// The content `path` had before the save that kept version `id`.
+    pub fn read(&self, path: &Path, id: &str) -> io::Result<Vec<u8>> {
+        let valid = !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit() || b == b'-');
+        let version_path = self.file_dir(path)?.join(id);
+        if !valid || !version_path.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No version {id} of {}", path.display()),
+            ));
+        }
+        fs::read(version_path)
+    }
+
+    /// Removes versions beyond `maxVersions` and those older than
+    /// `maxAgeDays`.
+    fn prune(&self, file_dir: &Path) -> io::Result<()> {
+        let cutoff = (self.config.max_age_days > 0)
+            .then(|| clock::unix_secs().saturating_sub(self.config.max_age_days * SECS_PER_DAY));
+        for (index, version) in versions(file_dir)?.into_iter().enumerate() {
+            let expired = cutoff.is_some_and(|cutoff| version.saved_at < cutoff);
+            if index >= self.config.max_versions || expired {
+                debug!(id = %version.id, "Pruning history version");
+                fs::remove_file(file_dir.join(&version.id))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn versions(file_dir: &Path) -> io::Result<Vec<HistoryVersion>> {
+    let read_dir = match fs::read_dir(file_dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut versions = Vec::new();
+    for entry in read_dir {
+        let entry = entry?;
+        let id = entry.file_name().to_string_lossy().into_owned();
+        if id == PATH_FILE {
+            continue;
+        }
+        let Some((saved_at, n)) = id
+            .split_once('-')
+            .and_then(|(secs, _)| Some((secs.parse::<u64>().ok()?, sequence(&id)?)))
+        else {
+            warn!(path = %entry.path().display(), "Skipping unrecognised history file");
+            continue;
+        };
+        let size = entry.metadata()?.len();
+        versions.push((n, HistoryVersion { id, saved_at, size }));
+    }
+    versions.sort_by(|(a_n, a), (b_n, b)| b.saved_at.cmp(&a.saved_at).then(b_n.cmp(a_n)));
+    Ok(versions.into_iter().map(|(_, version)| version).collect())
+}
+
+/// The number that tells apart versions kept in the same second.
+fn sequence(id: &str) -> Option<u64> {
+    id.split_once('-')?.1.parse().ok()
+}
@@ -0,0 +1,183 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use serde_json::{Value, json};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::mpsc;
+use tracing::{Instrument, Span, debug, info_span, warn};
+
+use crate::{
+    rpc::{
+        context::{ConnectionContext, Notifier},
+        error::{
+            HandlerError, PARSE_ERROR_CODE, PAYLOAD_TOO_LARGE_CODE, create_error_response_with_data,
+        },
+        request::{JsonRpcRequest, JsonRpcResponse, next_correlation_id},
+    },
+    state::{AppState, SharedState},
+    ws::{
+        connection::{close_resources, next_connection_id, presented_key},
+        lanes::{self, Priority},
+    },
+};
+
+/// `POST /rpc`: answers one JSON-RPC request for clients that cannot hold
+/// a socket open. Each request runs on a connection of its own, through the
+/// same validation, permission checks and lanes as socket requests, so
+/// nothing it opens (terminals, documents) outlives the response and its
+/// notifications are dropped. Notifications get `204 No Content`.
+pub async fn rpc_handler(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    body: Body,
+) -> Response {
+    let Some(identity) = state.policy.authenticate(presented_key(&headers, &query)) else {
+        warn!("Rejecting HTTP request with unknown key");
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let connection_id = next_connection_id();
+    let correlation_id = next_correlation_id();
+    let limit = state.limits().max_message_bytes;
+    let body = match read_body(body, limit).await {
+        Ok(body) => body,
+        Err(Oversized(size)) => {
+            let span = info_span!("http_request", connection_id, correlation_id = %correlation_id);
+            let _enter = span.enter();
+            warn!(size, limit, "Rejecting oversized request");
+            let response = create_error_response_with_data(
+                PAYLOAD_TOO_LARGE_CODE,
+                &format!("Message exceeds the {limit} byte limit"),
+                Some(json!({ "size": size, "limit": limit })),
+                Value::Null,
+            )
+            .correlated(&correlation_id);
+            return respond(&state, StatusCode::PAYLOAD_TOO_LARGE, &response);
+        }
+    };
+    let span = info_span!(
+        "http_request",
+        connection_id,
+        correlation_id = %correlation_id,
+        request_size = body.len()
+    );
+
+    state.request_log.received(
+        &correlation_id,
+        connection_id,
+        &String::from_utf8_lossy(&body),
+    );
+    let mut request = match parse(&body) {
+        Ok(request) => request,
+        Err(response) => {
+            let response = response.correlated(&correlation_id);
+            return respond(&state, StatusCode::BAD_REQUEST, &response);
+        }
+    };
+    request.correlation_id = correlation_id;
+
+    // Notifications have nowhere to go once the response is sent.
+    let (outbound, _) = mpsc::unbounded_channel();
+    let connection = Arc::new(ConnectionContext::new(
+        connection_id,
+        Notifier::new(outbound, 0),
+        identity,
+    ));
+    let response = run(&state, &connection, request, span.clone())
+        .instrument(span)
+        .await;
+    close_resources(&state, &connection);
+    match response {
+        Some(response) => respond(&state, StatusCode::OK, &response),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+/// Runs one request on `connection` as a socket request would run.
+pub(crate) async fn run(
+    state: &SharedState,
+    connection: &Arc<ConnectionContext>,
+    request: JsonRpcRequest,
+    span: Span,
+) -> Option<JsonRpcResponse> {
+    debug!(method = %request.method, "Received HTTP JSON-RPC request");
+    connection.stats.record_method(&request.method);
+    let _permit = match state.lanes.semaphore(Priority::for_method(&request.method)) {
+        Some(lane) => Some(lane.acquire_owned().await.ok()?),
+        None => None,
+    };
+    let cancel = connection.cancellations.register(request.id.as_ref());
+    lanes::execute(
+        Arc::clone(state),
+        Arc::clone(connection),
+        request,
+        span,
+        cancel,
+    )
+    .await
+}
+
+/// A body longer than the limit, with how many bytes had arrived when
+/// reading stopped.
+pub(crate) struct Oversized(pub usize);
+
+/// Reads a request body, giving up as soon as it passes `limit`. Limits
+/// are reloadable, so they are applied here rather than by a
+/// `DefaultBodyLimit` layer sized at startup. A body cut short by the
+/// client ends where it was cut.
+pub(crate) async fn read_body(body: Body, limit: usize) -> Result<Bytes, Oversized> {
+    let mut stream = body.into_data_stream();
+    let mut read = Vec::new();
+    while let Some(Ok(chunk)) = stream.next().await {
+        read.extend_from_slice(&chunk);
+        if read.len() > limit {
+            return Err(Oversized(read.len()));
+        }
+    }
+    Ok(read.into())
+}
+
+fn respond(state: &AppState, status: StatusCode, response: &JsonRpcResponse) -> Response {
+    let text = serde_json::to_string(response).unwrap_or_default();
+    state.request_log.answered(response, &text);
+    (status, [(header::CONTENT_TYPE, "application/json")], text).into_response()
+}
+
+fn parse(body: &[u8]) -> Result<JsonRpcRequest, Box<JsonRpcResponse>> {
+    serde_json::from_slice(body).map_err(|e| {
+        let response = if serde_json::from_slice::<Value>(body).is_ok() {
+            HandlerError::InvalidRequest(
+                "request must be an object with jsonrpc, method and optional params and id"
+                    .to_string(),
+            )
+            .to_jsonrpc_error(Value::Null)
+        } else {
+            create_error_response_with_data(
+                PARSE_ERROR_CODE,
+                "Parse error",
+                Some(json!({ "line": e.line(), "column": e.column() })),
+                Value::Null,
+            )
+        };
+        Box::new(response)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stops_reading_past_the_limit() {
+        let body = read_body(Body::from("{}"), 2).await;
+        assert_eq!(body.ok().as_deref(), Some(&b"{}"[..]));
+        let Err(Oversized(size)) = read_body(Body::from(vec![b' '; 64]), 16).await else {
+            panic!("body over the limit was read");
+        };
+        assert!(size > 16);
+    }
+}
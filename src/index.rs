@@ -0,0 +1,444 @@
+//! In-memory index of the workspace's files, kept current by a background
+//! watcher so requests such as `recentFiles`, `findFiles` and
+//! `searchInFiles` answer without walking the tree themselves.
+//!
+//! The watcher polls: every [`POLL_INTERVAL`] it re-walks each root with
+//! the usual ignore rules, reading only metadata, and swaps in the result.
+//! Files that are new or whose size or modification time changed are read
+//! again for the content index (see [`crate::trigram`]) and, when they are
+//! source files of a language with a tags query, parsed again for the
+//! symbol index behind `workspaceSymbols`; the rest keep their entries.
+//! Files are keyed by canonical path; `.git` and the server's data
+//! directory are left out. Where the watcher cannot use inotify, the
+//! differences between walks are what it reports (see [`crate::watch`]).
+
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, UNIX_EPOCH},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::IndexConfig,
+    rpc::text::Range,
+    state::SharedState,
+    syntax::{self, Symbol},
+    trigram::TrigramIndex,
+    watch,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Leading bytes checked for NUL to tell binary files apart.
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexedFile {
+    pub size: u64,
+    /// Modification time in milliseconds since the Unix epoch.
+    pub modified_ms: u64,
+}
+
+/// A definition found in a source file, flattened out of the file's
+/// symbol tree.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedSymbol {
+    pub name: String,
+    /// Tag kind from the grammar, e.g. `function`, `class`, `method`.
+    pub kind: String,
+    /// Name of the definition this one is nested in.
+    pub container_name: Option<String>,
+    /// Span of the symbol's name.
+    pub range: Range,
+}
+
+#[derive(Default)]
+struct Index {
+    files: HashMap<PathBuf, IndexedFile>,
+    contents: TrigramIndex,
+    /// Text files left out of `contents`, which every search has to read.
+    unindexed: HashSet<PathBuf>,
+    symbols: HashMap<PathBuf, Vec<IndexedSymbol>>,
+    /// Whether the first walk has finished.
+    ready: bool,
+    /// Whether the source files of the first walk have all been parsed.
+    symbols_ready: bool,
+}
+
+/// What reading a file gave the content index.
+enum Content {
+    Trigrams(Vec<u32>),
+    /// Text, but too large or unreadable.
+    Unindexed,
+    /// Not UTF-8 text, so never searched.
+    Binary,
+}
+
+#[derive(Clone, Default)]
+pub struct FileIndex {
+    inner: Arc<Mutex<Index>>,
+}
+
+impl FileIndex {
+    fn lock(&self) -> MutexGuard<'_, Index> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.lock().ready
+    }
+
+    /// The `limit` most recently modified files under `under`, newest
+    /// first.
+    pub fn recent(&self, under: Option<&Path>, limit: usize) -> Vec<(PathBuf, IndexedFile)> {
+        let index = self.lock();
+        let mut files: Vec<_> = index
+            .files
+            .iter()
+            .filter(|(path, _)| under.is_none_or(|under| path.starts_with(under)))
+            .map(|(path, file)| (path.clone(), *file))
+            .collect();
+        files.sort_by(|(a_path, a), (b_path, b)| {
+            b.modified_ms
+                .cmp(&a.modified_ms)
+                .then_with(|| a_path.cmp(b_path))
+        });
+        files.truncate(limit);
+        files
+    }
+
+    /// Every file, sorted, or `None` before the first walk has finished.
+    pub fn paths(&self) -> Option<Vec<PathBuf>> {
+        let index = self.lock();
+        index.ready.then(|| {
+            let mut paths: Vec<_> = index.files.keys().cloned().collect();
+            paths.sort();
+            paths
+        })
+    }
+
+    /// The text files a search has to read, sorted: those holding every
+    /// trigram of at least one of `alternatives`, plus the files left out
+    /// of the content index. `None` for `alternatives` puts every text file
+    /// in, as does `None` from the index before its first walk.
+    pub fn candidates(&self, alternatives: Option<&[Vec<u32>]>) -> Option<Vec<PathBuf>> {
+        let index = self.lock();
+        if !index.ready {
+            return None;
+        }
+        let mut candidates: BTreeSet<&Path> =
+            index.unindexed.iter().map(PathBuf::as_path).collect();
+        match alternatives {
+            Some(alternatives) => {
+                for grams in alternatives {
+                    candidates.extend(index.contents.matching(grams));
+                }
+            }
+            None => candidates.extend(index.contents.matching(&[])),
+        }
+        Some(candidates.into_iter().map(Path::to_path_buf).collect())
+    }
+
+    /// Runs `visit` over each file's indexed symbols. Returns whether the
+    /// first walk's source files have all been parsed; until then the
+    /// symbols of some are missing.
+    pub fn visit_symbols(&self, mut visit: impl FnMut(&Path, &[IndexedSymbol])) -> bool {
+        let index = self.lock();
+        for (path, symbols) in &index.symbols {
+            visit(path, symbols);
+        }
+        index.symbols_ready
+    }
+
+    /// Files of `walked` that are new or changed since the last walk.
+    fn changed(&self, walked: &HashMap<PathBuf, IndexedFile>) -> Vec<PathBuf> {
+        let index = self.lock();
+        walked
+            .iter()
+            .filter(|(path, file)| index.files.get(*path) != Some(*file))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// What changed since the last walk, for the watcher when it polls. A
+    /// file that vanished as one of the same size and modification time
+    /// turned up is taken to have been renamed.
+    fn watch_events(
+        &self,
+        walked: &HashMap<PathBuf, IndexedFile>,
+        changed: &[PathBuf],
+    ) -> Vec<watch::Event> {
+        let index = self.lock();
+        let mut removed: Vec<(&PathBuf, &IndexedFile)> = index
+            .files
+            .iter()
+            .filter(|(path, _)| !walked.contains_key(*path))
+            .collect();
+        let mut events = Vec::new();
+        for path in changed {
+            if index.files.contains_key(path) {
+                events.push(watch::Event::Modified(path.clone()));
+                continue;
+            }
+            match removed
+                .iter()
+                .position(|(_, file)| Some(*file) == walked.get(path))
+            {
+                Some(at) => events.push(watch::Event::Renamed {
+                    from: removed.swap_remove(at).0.clone(),
+                    to: path.clone(),
+                }),
+                None => events.push(watch::Event::Created(path.clone())),
+            }
+        }
+        events.extend(
+            removed
+                .into_iter()
+                .map(|(path, _)| watch::Event::Deleted(path.clone())),
+        );
+        events
+    }
+
+    fn update(&self, walked: HashMap<PathBuf, IndexedFile>, read: Vec<(PathBuf, Content)>) {
+        let mut index = self.lock();
+        let Index {
+            files,
+            contents,
+            unindexed,
+            symbols,
+            ready,
+            ..
+        } = &mut *index;
+        for path in files.keys().filter(|path| !walked.contains_key(*path)) {
+            contents.remove(path);
+            unindexed.remove(path);
+            symbols.remove(path);
+        }
+        for (path, content) in read {
+            contents.remove(&path);
+            unindexed.remove(&path);
+            match content {
+                Content::Trigrams(grams) => contents.insert(path, grams),
+                Content::Unindexed => {
+                    unindexed.insert(path);
+                }
+                Content::Binary => {}
+            }
+        }
+        *files = walked;
+        *ready = true;
+    }
+
+    fn update_symbols(&self, parsed: Vec<(PathBuf, Vec<IndexedSymbol>)>) {
+        let mut index = self.lock();
+        for (path, symbols) in parsed {
+            if symbols.is_empty() {
+                index.symbols.remove(&path);
+            } else {
+                index.symbols.insert(path, symbols);
+            }
+        }
+        index.symbols_ready = true;
+    }
+}
+
+/// Starts the watcher, which walks the workspace straight away.
+pub fn start(state: SharedState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        let mut announced = false;
+        loop {
+            interval.tick().await;
+            let walked = Arc::clone(&state);
+            match tokio::task::spawn_blocking(move || refresh(&walked)).await {
+                Ok((files, indexed, symbols)) => {
+                    if !announced {
+                        info!(files, indexed, symbols, "Workspace index built");
+                        announced = true;
+                    }
+                }
+                Err(e) => warn!(error = %e, "Workspace index walk failed"),
+            }
+        }
+    });
+}
+
+/// Walks the workspace and reads what changed into the index, contents
+/// first so searches need not wait for parsing. Returns the number of
+/// files, of those in the content index, and of files with symbols.
+fn refresh(state: &SharedState) -> (usize, usize, usize) {
+    let walked = walk(state);
+    let changed = state.files.changed(&walked);
+    if state.watcher.is_polling() && state.files.is_ready() {
+        state
+            .watcher
+            .polled(state.files.watch_events(&walked, &changed));
+    }
+    let read: Vec<(PathBuf, Content)> = changed
+        .iter()
+        .map(|path| {
+            let content = read_content(path, &state.config.index);
+            (path.clone(), content)
+        })
+        .collect();
+    if !read.is_empty() {
+        debug!(files = read.len(), "Reading changed files into the index");
+    }
+    let files = walked.len();
+    state.files.update(walked, read);
+
+    let parsed: Vec<(PathBuf, Vec<IndexedSymbol>)> = changed
+        .into_iter()
+        .filter_map(|path| {
+            let symbols = read_symbols(state, &path)?;
+            Some((path, symbols))
+        })
+        .collect();
+    state.files.update_symbols(parsed);
+    let index = state.files.lock();
+    (files, index.contents.len(), index.symbols.len())
+}
+
+/// The definitions in `path`, or `None` if it is not a source file the
+/// symbol index covers. Files too large or failing to parse have none.
+fn read_symbols(state: &SharedState, path: &Path) -> Option<Vec<IndexedSymbol>> {
+    let config = &state.config.index;
+    if !config.symbols {
+        return None;
+    }
+    let grammar = syntax::grammar_for(path, None).filter(|grammar| grammar.has_symbols())?;
+    if fs::metadata(path).is_ok_and(|m| m.len() > config.max_file_bytes) {
+        return Some(Vec::new());
+    }
+    let Ok(source) = fs::read_to_string(path) else {
+        return Some(Vec::new());
+    };
+    let tree = match state.syntax.symbols(grammar, &source) {
+        Ok(tree) => tree,
+        Err(e) => {
+            debug!(path = %path.display(), error = %e, "Could not index symbols");
+            return Some(Vec::new());
+        }
+    };
+    Some(flatten_symbols(tree))
+}
+
+/// `tree` as the index holds it, each symbol naming its container.
+pub fn flatten_symbols(tree: Vec<Symbol>) -> Vec<IndexedSymbol> {
+    let mut symbols = Vec::new();
+    flatten(tree, None, &mut symbols);
+    symbols
+}
+
+fn flatten(tree: Vec<Symbol>, container: Option<&str>, into: &mut Vec<IndexedSymbol>) {
+    for symbol in tree {
+        into.push(IndexedSymbol {
+            name: symbol.name.clone(),
+            kind: symbol.kind,
+            container_name: container.map(str::to_string),
+            range: symbol.selection_range,
+        });
+        flatten(symbol.children, Some(&symbol.name), into);
+    }
+}
+
+fn read_content(path: &Path, config: &IndexConfig) -> Content {
+    let Ok(mut file) = fs::File::open(path) else {
+        return Content::Unindexed;
+    };
+    let mut head = Vec::with_capacity(BINARY_SNIFF_BYTES);
+    if (&mut file)
+        .take(BINARY_SNIFF_BYTES as u64)
+        .read_to_end(&mut head)
+        .is_err()
+    {
+        return Content::Unindexed;
+    }
+    if head.contains(&0) {
+        return Content::Binary;
+    }
+    if !config.contents
+        || file
+            .metadata()
+            .is_ok_and(|m| m.len() > config.max_file_bytes)
+    {
+        // Read through once so searches need not open large binaries.
+        return match is_utf8(head, file) {
+            Ok(true) => Content::Unindexed,
+            Ok(false) => Content::Binary,
+            Err(_) => Content::Unindexed,
+        };
+    }
+    let mut bytes = head;
+    if file.read_to_end(&mut bytes).is_err() {
+        return Content::Unindexed;
+    }
+    if std::str::from_utf8(&bytes).is_err() {
+        return Content::Binary;
+    }
+    Content::Trigrams(crate::trigram::trigrams(&bytes))
+}
+
+/// Whether `head` followed by the rest of `file` is UTF-8, reading it in
+/// pieces.
+fn is_utf8(mut pending: Vec<u8>, mut file: fs::File) -> std::io::Result<bool> {
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match std::str::from_utf8(&pending) {
+            Ok(_) => pending.clear(),
+            // A character cut off at the end of the piece.
+            Err(e) if e.error_len().is_none() => {
+                pending.drain(..e.valid_up_to());
+            }
+            Err(_) => return Ok(false),
+        }
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(pending.is_empty());
+        }
+        pending.extend_from_slice(&buffer[..read]);
+    }
+}
+
+/// Every file of the workspace with its metadata, as the index holds them.
+pub fn walk(state: &SharedState) -> HashMap<PathBuf, IndexedFile> {
+    let data_path = state.config.data_path();
+    let data_path = data_path.canonicalize().unwrap_or(data_path);
+    let mut files = HashMap::new();
+    for (_, root, _) in state.uris.roots() {
+        for entry in state.ignore.walker(root, true).build() {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            if !entry.file_type().is_some_and(|t| t.is_file())
+                || entry.path().starts_with(&data_path)
+                || entry.path().components().any(|c| c.as_os_str() == ".git")
+            {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let modified_ms = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_millis() as u64);
+            files.insert(
+                entry.into_path(),
+                IndexedFile {
+                    size: metadata.len(),
+                    modified_ms,
+                },
+            );
+        }
+    }
+    debug!(files = files.len(), "Walked workspace for the index");
+    files
+}
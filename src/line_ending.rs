@@ -0,0 +1,63 @@
+//! Line ending styles and conversion between them.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+/// What a write does with the line endings of its content.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum LineEndingPolicy {
+    /// Write the content exactly as sent.
+    #[default]
+    AsIs,
+    /// Convert mixed endings to whichever style the content mostly uses.
+    Normalize,
+    /// Convert to the style the file already uses, if it has one.
+    Preserve,
+    Lf,
+    Crlf,
+}
+
+impl LineEndingPolicy {
+    /// The style `content` is converted to before replacing `existing`, or
+    /// `None` to write it untouched.
+    pub fn style(self, content: &str, existing: Option<&str>) -> Option<LineEnding> {
+        match self {
+            LineEndingPolicy::AsIs => None,
+            LineEndingPolicy::Normalize => detect(content),
+            LineEndingPolicy::Preserve => existing.and_then(detect),
+            LineEndingPolicy::Lf => Some(LineEnding::Lf),
+            LineEndingPolicy::Crlf => Some(LineEnding::Crlf),
+        }
+    }
+}
+
+/// The style most of `text`'s lines end with, or `None` if it has no line
+/// breaks.
+pub fn detect(text: &str) -> Option<LineEnding> {
+    let breaks = text.matches('\n').count();
+    if breaks == 0 {
+        return None;
+    }
+    let crlf = text.matches("\r\n").count();
+    Some(if crlf * 2 > breaks {
+        LineEnding::Crlf
+    } else {
+        LineEnding::Lf
+    })
+}
+
+/// `text` with every line break in `style`.
+pub fn convert(text: &str, style: LineEnding) -> String {
+    let lf = text.replace("\r\n", "\n");
+    match style {
+        LineEnding::Lf => lf,
+        LineEnding::Crlf => lf.replace('\n', "\r\n"),
+    }
+}
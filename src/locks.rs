@@ -0,0 +1,109 @@
+//! Advisory file locks: a connection claims a file for exclusive editing
+//! and others see who holds it. Nothing stops a write to a locked file;
+//! clients are expected to check.
+
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+use tracing::debug;
+
+use crate::clock;
+
+/// Who holds a lock, as `statPath` and `document/open` report it.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FileLock {
+    pub connection_id: u64,
+    pub username: Option<String>,
+    pub note: Option<String>,
+    /// Unix seconds.
+    pub acquired_at: u64,
+}
+
+/// `lockFile` found the file locked by another connection.
+pub struct Held(pub FileLock);
+
+/// Locks by absolute path, held until released or the connection closes.
+#[derive(Default)]
+pub struct LockRegistry {
+    locks: Mutex<BTreeMap<PathBuf, FileLock>>,
+}
+
+impl LockRegistry {
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<PathBuf, FileLock>> {
+        self.locks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Locks `path` for a connection. Locking again keeps the original
+    /// acquisition time but replaces the note.
+    pub fn acquire(
+        &self,
+        path: &Path,
+        connection_id: u64,
+        username: Option<&str>,
+        note: Option<String>,
+    ) -> io::Result<Result<FileLock, Held>> {
+        let key = key(path)?;
+        let mut locks = self.lock();
+        if let Some(lock) = locks.get_mut(&key) {
+            if lock.connection_id != connection_id {
+                return Ok(Err(Held(lock.clone())));
+            }
+            lock.note = note;
+            return Ok(Ok(lock.clone()));
+        }
+        let lock = FileLock {
+            connection_id,
+            username: username.map(str::to_string),
+            note,
+            acquired_at: clock::unix_secs(),
+        };
+        locks.insert(key, lock.clone());
+        debug!(path = %path.display(), connection_id, "File locked");
+        Ok(Ok(lock))
+    }
+
+    /// Releases a connection's lock on `path`. `Ok(false)` when the file
+    /// was not locked at all.
+    pub fn release(&self, path: &Path, connection_id: u64) -> io::Result<Result<bool, Held>> {
+        let key = key(path)?;
+        let mut locks = self.lock();
+        match locks.get(&key) {
+            None => Ok(Ok(false)),
+            Some(lock) if lock.connection_id != connection_id => Ok(Err(Held(lock.clone()))),
+            Some(_) => {
+                locks.remove(&key);
+                debug!(path = %path.display(), connection_id, "File unlocked");
+                Ok(Ok(true))
+            }
+        }
+    }
+
+    pub fn holder(&self, path: &Path) -> Option<FileLock> {
+        let key = key(path).ok()?;
+        self.lock().get(&key).cloned()
+    }
+
+    /// Drops every lock a closed connection held, returning their paths.
+    pub fn close_connection(&self, connection_id: u64) -> Vec<PathBuf> {
+        let mut released = Vec::new();
+        self.lock().retain(|path, lock| {
+            let held = lock.connection_id == connection_id;
+            if held {
+                released.push(path.clone());
+            }
+            !held
+        });
+        released
+    }
+}
+
+/// Keyed like open documents, so different spellings of one file share a
+/// lock.
+fn key(path: &Path) -> io::Result<PathBuf> {
+    Ok(crate::sandbox::normalize(&std::path::absolute(path)?))
+}
@@ -0,0 +1,143 @@
+//! Log output to a file of its own, for deployments with nobody collecting
+//! stderr. The file is rotated once it would grow past a size or when the
+//! hour or day changes: `server.log` becomes `server.log.1`, the previous
+//! `.1` becomes `.2`, and so on, with the oldest beyond `keep` deleted.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::UNIX_EPOCH,
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::{
+    clock,
+    config::{LogFileConfig, LogRotation},
+};
+
+/// Shared by every event written; each write checks whether to rotate
+/// first, so a line is never split across files.
+#[derive(Clone)]
+pub struct LogFile {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    /// Hour or day, counted from the epoch, the file was started in.
+    period: u64,
+    max_bytes: u64,
+    rotation: LogRotation,
+    keep: usize,
+}
+
+impl LogFile {
+    /// Appends to the file at `path`, rotating it straight away if
+    /// it was last written in an earlier period.
+    pub fn open(path: &Path, config: &LogFileConfig) -> io::Result<Self> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let file = append(path)?;
+        let metadata = file.metadata()?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or_else(clock::unix_secs, |since| since.as_secs());
+        let mut inner = Inner {
+            path: path.to_path_buf(),
+            file,
+            size: metadata.len(),
+            period: period(config.rotate, modified),
+            max_bytes: config.max_bytes,
+            rotation: config.rotate,
+            keep: config.keep,
+        };
+        if inner.size > 0 && inner.period != period(inner.rotation, clock::unix_secs()) {
+            inner.rotate()?;
+        }
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+        })
+    }
+}
+
+impl Inner {
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        let now = period(self.rotation, clock::unix_secs());
+        let full = self.max_bytes > 0 && self.size + buf.len() as u64 > self.max_bytes;
+        if self.size > 0 && (full || now != self.period) {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            self.file.set_len(0)?;
+        } else {
+            let _ = fs::remove_file(numbered(&self.path, self.keep));
+            for n in (1..self.keep).rev() {
+                let from = numbered(&self.path, n);
+                if from.exists() {
+                    fs::rename(&from, numbered(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, numbered(&self.path, 1))?;
+            self.file = append(&self.path)?;
+        }
+        self.size = 0;
+        self.period = period(self.rotation, clock::unix_secs());
+        Ok(())
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Which period `secs` falls in; the same for all time without time-based
+/// rotation. Periods are UTC.
+fn period(rotation: LogRotation, secs: u64) -> u64 {
+    match rotation {
+        LogRotation::Never => 0,
+        LogRotation::Hourly => secs / 3600,
+        LogRotation::Daily => secs / 86_400,
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.write(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogFile {
+    type Writer = LogFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
@@ -0,0 +1,304 @@
+//! Log output. Text is the default; with `--log-format json` every event is
+//! written as one JSON object per line, carrying the fields of the spans it
+//! happened in so a request's correlation id, method and byte counts can
+//! be filtered on without parsing messages. `logFile` adds a second,
+//! rotated output in a format of its own.
+
+use serde_json::{Map, Value};
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    fmt,
+    panic::{self, PanicHookInfo},
+    sync::OnceLock,
+};
+use tracing::{
+    Event, Level, Subscriber, error,
+    field::{Field, Visit},
+    span::Record,
+    warn,
+};
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry,
+    field::RecordFields,
+    filter::Targets,
+    fmt::{
+        FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter,
+        format::{DefaultFields, Writer},
+        time::{FormatTime, SystemTime},
+    },
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    reload,
+    util::SubscriberInitExt,
+};
+
+use crate::{
+    config::{Config, LogFormat},
+    log_file::LogFile,
+    telemetry,
+};
+
+/// Swaps the filters on log output when the config is reloaded.
+static FILTERS: OnceLock<Vec<reload::Handle<EnvFilter, Registry>>> = OnceLock::new();
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Installs the subscriber for `config`, or the text format when the
+/// config failed to load. `logLevel` or `RUST_LOG` filters what is logged,
+/// to the console and `logFile` alike, not what is exported to a telemetry
+/// collector.
+pub fn init(config: Option<&Config>) {
+    let level = config.and_then(|config| config.log_level.as_deref());
+    let invalid_level = filter(level).err();
+    let format = config.map_or(LogFormat::Text, |config| config.log_format);
+    let mut outputs = vec![output(format, std::io::stdout, true)];
+    let mut unwritable = None;
+    if let Some(config) = config
+        && let Some(path) = config.log_file.path.as_deref()
+    {
+        match LogFile::open(path, &config.log_file) {
+            Ok(writer) => outputs.push(output(config.log_file.format, writer, false)),
+            Err(e) => unwritable = Some((path, e)),
+        }
+    }
+    let mut handles = Vec::new();
+    let outputs: Vec<BoxedLayer> = outputs
+        .into_iter()
+        .map(|output| {
+            let (filter, handle) = reload::Layer::new(filter_or_info(level));
+            handles.push(handle);
+            output.with_filter(filter).boxed()
+        })
+        .collect();
+    let _ = FILTERS.set(handles);
+    let (export, refused) = match config.map(|config| telemetry::layer(&config.telemetry)) {
+        Some(Ok(layer)) => (layer, None),
+        Some(Err(e)) => (None, Some(e)),
+        None => (None, None),
+    };
+    let export = export.with_filter(Targets::new().with_target("editor_server", Level::INFO));
+    tracing_subscriber::registry()
+        .with(outputs)
+        .with(export)
+        .init();
+    panic::set_hook(Box::new(log_panic));
+    if let Some((path, e)) = unwritable {
+        warn!(path = %path.display(), error = %e, "Not logging to file");
+    }
+    if let Some(e) = refused {
+        warn!(error = %e, "Telemetry export disabled");
+    }
+    if let Some(e) = invalid_level {
+        warn!(error = %e, "Ignoring logLevel");
+    }
+}
+
+fn output<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Text if ansi => layer
+            .with_target(true)
+            .with_thread_ids(true)
+            .with_line_number(true)
+            .boxed(),
+        LogFormat::Text => layer
+            .with_ansi(false)
+            .fmt_fields(PlainFields::default())
+            .with_target(true)
+            .with_thread_ids(true)
+            .with_line_number(true)
+            .boxed(),
+        LogFormat::Json => layer
+            .with_ansi(false)
+            .fmt_fields(JsonFields)
+            .event_format(JsonEvents)
+            .boxed(),
+    }
+}
+
+/// Logs a panic in place of the default hook's stderr message, so it
+/// reaches the log file and carries the spans of the request it happened
+/// in. The backtrace is included when `RUST_BACKTRACE` asks for one.
+fn log_panic(info: &PanicHookInfo<'_>) {
+    let location = info.location().map(ToString::to_string);
+    let backtrace = Backtrace::capture();
+    let backtrace =
+        (backtrace.status() == BacktraceStatus::Captured).then(|| backtrace.to_string());
+    error!(
+        location,
+        backtrace,
+        panic = panic_message(info.payload()),
+        "Panicked"
+    );
+}
+
+/// The message a panic was raised with, when it was a string.
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
+
+/// Replaces the log filters with `level`, as [`init`] would read it.
+pub fn set_level(level: Option<&str>) -> Result<(), String> {
+    filter(level)?;
+    for handle in FILTERS.get().into_iter().flatten() {
+        handle
+            .reload(filter_or_info(level))
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// An `EnvFilter` cannot be cloned, so each output builds its own.
+fn filter_or_info(level: Option<&str>) -> EnvFilter {
+    filter(level).unwrap_or_else(|_| EnvFilter::new("info"))
+}
+
+fn filter(level: Option<&str>) -> Result<EnvFilter, String> {
+    match level {
+        Some(level) => {
+            EnvFilter::try_new(level).map_err(|e| format!("invalid log level {level}: {e}"))
+        }
+        None => Ok(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))),
+    }
+}
+
+/// Collects recorded fields into a JSON object, keeping numbers and
+/// booleans as such.
+#[derive(Default)]
+pub struct JsonVisitor(pub Map<String, Value>);
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.0
+            .insert(field.name().to_string(), value.to_string().into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}").into());
+    }
+}
+
+/// Stores each span's fields as a JSON object, so [`JsonEvents`] can read
+/// them back. Fields recorded after the span was created are merged in.
+struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor::default();
+        fields.record(&mut visitor);
+        write!(writer, "{}", Value::Object(visitor.0))
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &Record<'_>,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor(serde_json::from_str(&current.fields).unwrap_or_default());
+        fields.record(&mut visitor);
+        current.fields = Value::Object(visitor.0).to_string();
+        Ok(())
+    }
+}
+
+/// Span fields are formatted once per field formatter type and shared by
+/// every layer using it, so the colourless text a file gets needs a type
+/// of its own.
+#[derive(Default)]
+struct PlainFields(DefaultFields);
+
+impl<'writer> FormatFields<'writer> for PlainFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'writer>, fields: R) -> fmt::Result {
+        self.0.format_fields(writer, fields)
+    }
+}
+
+struct JsonEvents;
+
+impl<S, N> FormatEvent<S, N> for JsonEvents
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+        let mut fields = JsonVisitor::default();
+        event.record(&mut fields);
+        let message = fields.0.remove("message").unwrap_or_default();
+        // Outermost first, as the text format prints them.
+        let spans: Vec<Value> = ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .map(|span| {
+                let mut entry: Map<String, Value> = span
+                    .extensions()
+                    .get::<FormattedFields<N>>()
+                    .and_then(|formatted| serde_json::from_str(&formatted.fields).ok())
+                    .unwrap_or_default();
+                entry.insert("name".to_string(), span.name().into());
+                Value::Object(entry)
+            })
+            .collect();
+
+        let mut line = Map::new();
+        line.insert("timestamp".to_string(), timestamp.into());
+        line.insert("level".to_string(), metadata.level().as_str().into());
+        line.insert("target".to_string(), metadata.target().into());
+        if let Some(number) = metadata.line() {
+            line.insert("line".to_string(), number.into());
+        }
+        line.insert(
+            "threadId".to_string(),
+            format!("{:?}", std::thread::current().id()).into(),
+        );
+        line.insert("message".to_string(), message);
+        if !fields.0.is_empty() {
+            line.insert("fields".to_string(), Value::Object(fields.0));
+        }
+        if !spans.is_empty() {
+            line.insert("spans".to_string(), spans.into());
+        }
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
@@ -0,0 +1,393 @@
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+    },
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{ChildStdin, Command},
+    sync::{mpsc, oneshot},
+};
+use tracing::{Instrument, debug, info, info_span, warn};
+
+use crate::{
+    config::LanguageServerDefinition,
+    rpc::{context::Notifier, error::create_error_response, request::JsonRpcResponse},
+};
+
+/// Client request ids, and the correlation ids of those requests, waiting
+/// on a response, keyed by the id we sent the language server.
+type Pending = Arc<Mutex<HashMap<i64, (Value, String)>>>;
+
+static SESSION_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+struct LspSession {
+    session_id: u64,
+    outbound: mpsc::UnboundedSender<Value>,
+    pending: Pending,
+    next_id: AtomicI64,
+    stop: Option<oneshot::Sender<()>>,
+}
+
+type Sessions = HashMap<(u64, String), LspSession>;
+
+/// Identifies the session a message is for and how to start it if needed.
+pub struct LspTarget<'a> {
+    pub connection_id: u64,
+    pub language: &'a str,
+    pub definition: &'a LanguageServerDefinition,
+    pub root: &'a Path,
+    pub notifier: &'a Notifier,
+}
+
+/// Language server processes bridged onto WebSocket connections. Each
+/// connection gets its own server per language, since LSP sessions carry
+/// per-client state (initialize, open documents).
+#[derive(Default)]
+pub struct LspBridge {
+    sessions: Arc<Mutex<Sessions>>,
+}
+
+impl LspBridge {
+    /// Forwards a request and remembers `client_id` so the eventual response
+    /// is delivered to the client as the reply to its `lsp/request`.
+    pub fn request(
+        &self,
+        target: &LspTarget<'_>,
+        method: &str,
+        params: Value,
+        client_id: Value,
+        correlation_id: &str,
+    ) -> Result<(), String> {
+        self.with_session(target, |session| {
+            let id = session.next_id.fetch_add(1, Ordering::Relaxed);
+            session
+                .pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(id, (client_id, correlation_id.to_string()));
+            session.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+        })
+    }
+
+    pub fn notify(
+        &self,
+        target: &LspTarget<'_>,
+        method: &str,
+        params: Value,
+    ) -> Result<(), String> {
+        self.with_session(target, |session| {
+            session.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+        })
+    }
+
+    /// Answers a request the language server sent to the client.
+    pub fn respond(
+        &self,
+        connection_id: u64,
+        language: &str,
+        id: Value,
+        result: Value,
+        error: Option<Value>,
+    ) -> Result<(), String> {
+        let sessions = self.lock();
+        let session = sessions
+            .get(&(connection_id, language.to_string()))
+            .ok_or_else(|| format!("No language server running for {language}"))?;
+        let message = match error {
+            Some(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+            None => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        };
+        session.send(message)
+    }
+
+    pub fn stop(&self, connection_id: u64, language: &str) -> Result<(), String> {
+        let mut session = self
+            .lock()
+            .remove(&(connection_id, language.to_string()))
+            .ok_or_else(|| format!("No language server running for {language}"))?;
+        if let Some(stop) = session.stop.take() {
+            let _ = stop.send(());
+        }
+        Ok(())
+    }
+
+    /// Stops every language server owned by a connection; called when it closes.
+    pub fn close_connection(&self, connection_id: u64) {
+        let mut sessions = self.lock();
+        let keys: Vec<_> = sessions
+            .keys()
+            .filter(|(id, _)| *id == connection_id)
+            .cloned()
+            .collect();
+        for key in keys {
+            if let Some(mut session) = sessions.remove(&key)
+                && let Some(stop) = session.stop.take()
+            {
+                debug!(connection_id, language = %key.1, "Stopping language server of closed connection");
+                let _ = stop.send(());
+            }
+        }
+    }
+
+    fn with_session(
+        &self,
+        target: &LspTarget<'_>,
+        f: impl FnOnce(&LspSession) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut sessions = self.lock();
+        let key = (target.connection_id, target.language.to_string());
+        if !sessions.contains_key(&key) {
+            let session = self.spawn(target)?;
+            sessions.insert(key.clone(), session);
+        }
+        f(&sessions[&key])
+    }
+
+    fn spawn(&self, target: &LspTarget<'_>) -> Result<LspSession, String> {
+        let LspTarget {
+            connection_id,
+            language,
+            definition,
+            root,
+            notifier,
+        } = *target;
+        let mut child = Command::new(&definition.command)
+            .args(&definition.args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start language server for {language}: {e}"))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (outbound, outbound_rx) = mpsc::unbounded_channel();
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let pending: Pending = Default::default();
+        let session_id = SESSION_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        let span = info_span!("lsp_session", connection_id, language = %language);
+        tokio::spawn(write_frames(stdin, outbound_rx).instrument(span.clone()));
+        tokio::spawn(
+            read_frames(
+                stdout,
+                language.to_string(),
+                Arc::clone(&pending),
+                notifier.clone(),
+            )
+            .instrument(span.clone()),
+        );
+
+        let sessions = Arc::clone(&self.sessions);
+        let language_key = language.to_string();
+        tokio::spawn(
+            async move {
+                tokio::select! {
+                    status = child.wait() => {
+                        info!(status = ?status.ok(), "Language server exited");
+                    }
+                    _ = stop_rx => {
+                        let _ = child.kill().await;
+                        info!("Language server stopped");
+                    }
+                }
+                // A restarted server for the same language must not be removed.
+                let mut sessions = sessions.lock().unwrap_or_else(|e| e.into_inner());
+                let key = (connection_id, language_key);
+                if sessions
+                    .get(&key)
+                    .is_some_and(|s| s.session_id == session_id)
+                {
+                    sessions.remove(&key);
+                }
+            }
+            .instrument(span),
+        );
+
+        info!(connection_id, language = %language, command = %definition.command, "Language server started");
+        Ok(LspSession {
+            session_id,
+            outbound,
+            pending,
+            next_id: AtomicI64::new(1),
+            stop: Some(stop_tx),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Sessions> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl LspSession {
+    fn send(&self, message: Value) -> Result<(), String> {
+        self.outbound
+            .send(message)
+            .map_err(|_| "Language server is no longer running".to_string())
+    }
+}
+
+/// Writes messages to the server with LSP `Content-Length` framing.
+async fn write_frames(mut stdin: ChildStdin, mut outbound: mpsc::UnboundedReceiver<Value>) {
+    while let Some(message) = outbound.recv().await {
+        let body = message.to_string();
+        let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        if let Err(e) = stdin.write_all(frame.as_bytes()).await {
+            warn!(error = %e, "Failed to write to language server");
+            return;
+        }
+    }
+}
+
+/// Reads framed messages from the server and routes them to the client:
+/// responses become replies to the pending `lsp/request`, everything else
+/// is forwarded as a notification.
+async fn read_frames(
+    stdout: impl AsyncRead + Unpin,
+    language: String,
+    pending: Pending,
+    notifier: Notifier,
+) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let message = match read_frame(&mut reader).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                warn!(error = %e, "Malformed frame from language server");
+                break;
+            }
+        };
+
+        let id = message.get("id").cloned();
+        let method = message.get("method").and_then(Value::as_str);
+        match (id, method) {
+            (Some(id), None) => {
+                let waiting = id.as_i64().and_then(|id| {
+                    pending
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .remove(&id)
+                });
+                let Some((client_id, correlation_id)) = waiting else {
+                    debug!(id = %id, "Dropping response for unknown request");
+                    continue;
+                };
+                let response =
+                    lsp_response_to_client(&message, client_id).correlated(&correlation_id);
+                match serde_json::to_string(&response) {
+                    Ok(text) => {
+                        notifier.send(text);
+                    }
+                    Err(e) => warn!(error = %e, "Failed to serialize language server response"),
+                }
+            }
+            (Some(id), Some(method)) => {
+                notifier.notify(
+                    "lsp/serverRequest",
+                    json!({
+                        "language": language,
+                        "id": id,
+                        "method": method,
+                        "params": message.get("params"),
+                    }),
+                );
+            }
+            (None, Some(method)) => {
+                notifier.notify(
+                    "lsp/notification",
+                    json!({
+                        "language": language,
+                        "method": method,
+                        "params": message.get("params"),
+                    }),
+                );
+            }
+            (None, None) => debug!("Ignoring language server message without id or method"),
+        }
+    }
+
+    // Fail whatever is still waiting so clients are not left hanging.
+    let orphaned: Vec<(Value, String)> = pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain()
+        .map(|(_, waiting)| waiting)
+        .collect();
+    for (client_id, correlation_id) in orphaned {
+        let response = create_error_response(
+            crate::rpc::error::LSP_ERROR_CODE,
+            "Language server exited before responding",
+            client_id,
+        )
+        .correlated(&correlation_id);
+        if let Ok(text) = serde_json::to_string(&response) {
+            notifier.send(text);
+        }
+    }
+}
+
+/// Reads one `Content-Length` framed message, as debug adapters also
+/// send them. `None` at the end of the stream.
+pub async fn read_frame(
+    reader: &mut (impl AsyncBufReadExt + Unpin),
+) -> std::io::Result<Option<Value>> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = content_length.ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "missing Content-Length")
+    })?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+fn lsp_response_to_client(message: &Value, client_id: Value) -> JsonRpcResponse {
+    match message.get("error") {
+        Some(error) => create_error_response(
+            error
+                .get("code")
+                .and_then(Value::as_i64)
+                .and_then(|code| i32::try_from(code).ok())
+                .unwrap_or(crate::rpc::error::LSP_ERROR_CODE),
+            error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("Language server error"),
+            client_id,
+        ),
+        None => JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            result: Some(message.get("result").cloned().unwrap_or(Value::Null)),
+            error: None,
+            id: client_id,
+            correlation_id: None,
+        },
+    }
+}
@@ -1,39 +1,181 @@
+mod activity;
+mod archive;
+mod audit;
+mod blob;
+mod charset;
+mod clock;
+mod config;
+mod cron;
+mod dap;
+mod delta;
+mod diff;
+mod disk_usage;
+mod documents;
+mod download;
+mod fault;
+mod file_type;
+mod flow;
+mod frontend;
+mod history;
+mod http_rpc;
+mod index;
+mod line_ending;
+mod locks;
+mod log_file;
+mod logging;
+mod lsp;
+mod merge;
+mod msgpack;
+mod origin;
+mod permissions;
+mod policy;
+mod presence;
+mod problems;
+mod protected;
+mod raw;
+mod reload;
+mod request_log;
 mod rpc;
+mod sandbox;
+mod scan;
+mod scheduler;
+mod share;
+mod snippet;
+mod spell;
+mod staged;
 mod state;
+mod syntax;
+mod task;
+mod telemetry;
+mod tenant;
+mod terminal;
+mod thumbnail;
+mod trash;
+mod trigram;
+mod upload;
+mod uri;
+mod viewer;
+mod walk;
+mod watch;
+mod webhook;
 mod ws;
 
-use axum::{Router, routing::get};
-use std::{net::SocketAddr, sync::Arc};
-use tokio::{net::TcpListener, sync::Mutex};
-use tracing::{error, info, info_span};
-use tracing_subscriber::EnvFilter;
+use axum::{
+    Router, middleware,
+    routing::{get, post},
+};
+use config::Config;
+use state::AppState;
+use std::{net::SocketAddr, process, sync::Arc, time::Duration};
+use tokio::net::TcpListener;
+use tracing::{error, info, info_span, warn};
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .with_target(true)
-        .with_thread_ids(true)
-        .with_line_number(true)
-        .init();
+    // Parsed first so the log format applies from the start; a bad config
+    // is then reported through the default one.
+    let config = Config::from_args();
+    logging::init(config.as_ref().ok());
 
     let server_span = info_span!("editor_server", version = "0.1.3");
     let _enter = server_span.enter();
 
-    const SERVER_ADDRESS: ([u8; 4], u16) = ([0, 0, 0, 0], 3000); //TODO: maybe should only listen container addr
+    let state = match config.and_then(AppState::new) {
+        Ok(state) => Arc::new(state),
+        Err(e) => {
+            error!(error = %e, "Failed to load configuration");
+            process::exit(1);
+        }
+    };
+    activity::watch_git(state.config.root.clone(), state.activity.clone());
+    for workspace in state.config.workspaces.values() {
+        activity::watch_git(workspace.path.clone(), state.activity.clone());
+    }
+    index::start(Arc::clone(&state));
+    documents::start(Arc::clone(&state));
+    scheduler::start(Arc::clone(&state));
+    reload::reload_on_hangup(Arc::clone(&state));
 
-    let state: Arc<Mutex<()>> = Arc::new(Mutex::new(()));
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/ws", get(ws::ws_handler))
-        .with_state(state);
+        .route("/rpc", post(http_rpc::rpc_handler))
+        .route("/download/{token}", get(download::download_handler))
+        .route("/hooks/{name}", post(webhook::webhook_handler))
+        .route("/raw/{workspace}/{*path}", get(raw::raw_handler))
+        .route("/share/{token}/", get(viewer::share_index))
+        .route("/share/{token}/{*path}", get(viewer::share_file))
+        .route("/upload/{workspace}", post(upload::upload_handler))
+        .route("/upload/{workspace}/{*dir}", post(upload::upload_handler));
+    if let Some(dir) = &state.config.static_dir {
+        info!(dir = %dir.display(), "Serving the editor UI");
+        app = app.fallback(get(frontend::frontend_handler));
+    }
+    let app = app
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            origin::check_origin,
+        ))
+        .with_state(state.clone());
 
-    let addr = SocketAddr::from(SERVER_ADDRESS);
+    let addr = SocketAddr::from(([0, 0, 0, 0], state.config.port)); //TODO: maybe should only listen container addr
     let listener = TcpListener::bind(&addr).await.unwrap();
-    info!(address = %addr, "Server starting");
+    info!(
+        address = %addr,
+        root = %state.config.root.display(),
+        read_only = state.config.read_only,
+        "Server starting"
+    );
 
     axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal(Arc::clone(&state)))
         .await
         .unwrap_or_else(|e| error!(error = %e, "Server error"));
+
+    // Upgraded connections outlive `serve`; give them a moment to send their
+    // close frames. Each holds a receiver until it has finished.
+    if tokio::time::timeout(SHUTDOWN_GRACE, state.shutdown.closed())
+        .await
+        .is_err()
+    {
+        warn!("Connections still open at shutdown");
+    }
+    if state.config.auto_save.enabled() {
+        let (saved, failed) = state.documents.save_all(documents::SaveReason::Flush);
+        for (path, e) in failed {
+            error!(path = %path.display(), error = %e, "Failed to save document at shutdown");
+        }
+        if !saved.is_empty() {
+            info!(documents = saved.len(), "Saved open documents");
+        }
+    }
+    telemetry::flush().await;
+    info!("Server stopped");
+}
+
+/// How long open connections get to close after a shutdown signal.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// Resolves on Ctrl-C or SIGTERM, telling every connection to close.
+async fn shutdown_signal(state: Arc<AppState>) {
+    let interrupt = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+    info!("Shutdown signal received");
+    state.shutdown.send_replace(true);
 }
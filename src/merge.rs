@@ -0,0 +1,110 @@
+//! Three-way line merges, in the manner of `diff3 -m`: changes made on
+//! only one side are taken, and regions both sides changed differently
+//! are kept between `<<<<<<<`, `=======` and `>>>>>>>` markers.
+
+use serde::Serialize;
+
+use crate::diff;
+
+/// Where a conflict sits in the merged text, markers included. Lines are
+/// one-based and inclusive.
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+pub struct Merged {
+    pub content: String,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Merges the changes `ours` and `theirs` each made to `base`. Conflicts
+/// show our side first, labelled `ours_label`.
+pub fn merge(base: &str, ours: &str, theirs: &str, ours_label: &str, theirs_label: &str) -> Merged {
+    let (o, a, b) = (diff::lines(base), diff::lines(ours), diff::lines(theirs));
+    let in_ours = matched(o.len(), diff::common_lines(&o, &a));
+    let in_theirs = matched(o.len(), diff::common_lines(&o, &b));
+
+    let mut merged = Output::default();
+    let mut conflicts = Vec::new();
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    while i < o.len() || j < a.len() || k < b.len() {
+        // Lines unchanged on both sides.
+        let stable = (0..)
+            .take_while(|n| {
+                i + n < o.len() && in_ours[i + n] == Some(j + n) && in_theirs[i + n] == Some(k + n)
+            })
+            .count();
+        if stable > 0 {
+            merged.extend(&o[i..i + stable]);
+            (i, j, k) = (i + stable, j + stable, k + stable);
+            continue;
+        }
+        // Up to the next base line both sides kept.
+        let (next_i, next_j, next_k) = (i..o.len())
+            .find_map(|n| Some((n, in_ours[n]?, in_theirs[n]?)))
+            .unwrap_or((o.len(), a.len(), b.len()));
+        let (base_part, ours_part, theirs_part) = (&o[i..next_i], &a[j..next_j], &b[k..next_k]);
+        if ours_part == base_part || ours_part == theirs_part {
+            merged.extend(theirs_part);
+        } else if theirs_part == base_part {
+            merged.extend(ours_part);
+        } else {
+            let start_line = merged.lines + 1;
+            merged.marker(&format!("<<<<<<< {ours_label}"));
+            merged.extend_terminated(ours_part);
+            merged.marker("=======");
+            merged.extend_terminated(theirs_part);
+            merged.marker(&format!(">>>>>>> {theirs_label}"));
+            conflicts.push(Conflict {
+                start_line,
+                end_line: merged.lines,
+            });
+        }
+        (i, j, k) = (next_i, next_j, next_k);
+    }
+    Merged {
+        content: merged.text,
+        conflicts,
+    }
+}
+
+#[derive(Default)]
+struct Output {
+    text: String,
+    lines: usize,
+}
+
+impl Output {
+    fn extend(&mut self, lines: &[&str]) {
+        for line in lines {
+            self.text.push_str(line);
+        }
+        self.lines += lines.len();
+    }
+
+    /// Adds `lines`, ending the last with a newline so a marker can follow.
+    fn extend_terminated(&mut self, lines: &[&str]) {
+        self.extend(lines);
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+    }
+
+    fn marker(&mut self, marker: &str) {
+        self.text.push_str(marker);
+        self.text.push('\n');
+        self.lines += 1;
+    }
+}
+
+/// For each base line, the index of the line it matched on the other side.
+fn matched(len: usize, pairs: Vec<(usize, usize)>) -> Vec<Option<usize>> {
+    let mut matched = vec![None; len];
+    for (base, other) in pairs {
+        matched[base] = Some(other);
+    }
+    matched
+}
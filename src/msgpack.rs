@@ -0,0 +1,259 @@
+//! MessagePack encoding of JSON values, for clients that negotiate binary
+//! framing.
+//!
+//! Every JSON value has a MessagePack form. The reverse holds for
+//! everything but `bin` values, which are decoded to base64 strings since
+//! that is how handlers take binary data; extension types and non-string
+//! map keys are refused.
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde_json::{Map, Number, Value};
+
+/// Nesting deeper than this is refused rather than risking the stack, as
+/// `serde_json` does for JSON text.
+const MAX_DEPTH: usize = 128;
+
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_value(&mut bytes, value);
+    bytes
+}
+
+fn write_value(bytes: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => bytes.push(0xc0),
+        Value::Bool(false) => bytes.push(0xc2),
+        Value::Bool(true) => bytes.push(0xc3),
+        Value::Number(number) => write_number(bytes, number),
+        Value::String(text) => {
+            write_length(bytes, text.len(), 0xa0, 32, [0xd9, 0xda, 0xdb]);
+            bytes.extend_from_slice(text.as_bytes());
+        }
+        Value::Array(items) => {
+            write_length(bytes, items.len(), 0x90, 16, [0, 0xdc, 0xdd]);
+            items.iter().for_each(|item| write_value(bytes, item));
+        }
+        Value::Object(map) => {
+            write_length(bytes, map.len(), 0x80, 16, [0, 0xde, 0xdf]);
+            for (key, value) in map {
+                write_length(bytes, key.len(), 0xa0, 32, [0xd9, 0xda, 0xdb]);
+                bytes.extend_from_slice(key.as_bytes());
+                write_value(bytes, value);
+            }
+        }
+    }
+}
+
+fn write_number(bytes: &mut Vec<u8>, number: &Number) {
+    if let Some(n) = number.as_u64() {
+        match n {
+            0..=0x7f => bytes.push(n as u8),
+            0x80..=0xff => bytes.extend([0xcc, n as u8]),
+            0x100..=0xffff => {
+                bytes.push(0xcd);
+                bytes.extend((n as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                bytes.push(0xce);
+                bytes.extend((n as u32).to_be_bytes());
+            }
+            _ => {
+                bytes.push(0xcf);
+                bytes.extend(n.to_be_bytes());
+            }
+        }
+    } else if let Some(n) = number.as_i64() {
+        // Only negative numbers get here.
+        if n >= -32 {
+            bytes.push(n as u8);
+        } else if let Ok(n) = i8::try_from(n) {
+            bytes.extend([0xd0, n as u8]);
+        } else if let Ok(n) = i16::try_from(n) {
+            bytes.push(0xd1);
+            bytes.extend(n.to_be_bytes());
+        } else if let Ok(n) = i32::try_from(n) {
+            bytes.push(0xd2);
+            bytes.extend(n.to_be_bytes());
+        } else {
+            bytes.push(0xd3);
+            bytes.extend(n.to_be_bytes());
+        }
+    } else {
+        bytes.push(0xcb);
+        bytes.extend(number.as_f64().unwrap_or_default().to_be_bytes());
+    }
+}
+
+/// Writes a string, array or map header: the fix form for lengths below
+/// `fix_limit`, otherwise the 8, 16 or 32 bit form from `markers` (a zero
+/// marker meaning the type has no 8 bit form).
+fn write_length(bytes: &mut Vec<u8>, len: usize, fix: u8, fix_limit: usize, markers: [u8; 3]) {
+    if len < fix_limit {
+        bytes.push(fix | len as u8);
+    } else if markers[0] != 0 && len <= 0xff {
+        bytes.extend([markers[0], len as u8]);
+    } else if len <= 0xffff {
+        bytes.push(markers[1]);
+        bytes.extend((len as u16).to_be_bytes());
+    } else {
+        bytes.push(markers[2]);
+        bytes.extend((len as u32).to_be_bytes());
+    }
+}
+
+/// Decodes exactly one value; trailing bytes are an error.
+pub fn decode(bytes: &[u8]) -> Result<Value, String> {
+    let mut reader = Reader { bytes, offset: 0 };
+    let value = reader.value(0)?;
+    if reader.offset != bytes.len() {
+        return Err(format!(
+            "{} trailing bytes after the value",
+            bytes.len() - reader.offset
+        ));
+    }
+    Ok(value)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| format!("Unexpected end of data at byte {}", self.offset))?;
+        let taken = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<usize, String> {
+        Ok(u16::from_be_bytes(self.array()?).into())
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        Ok(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("Nested more than {MAX_DEPTH} levels deep"));
+        }
+        let at = self.offset;
+        let marker = self.u8()?;
+        let value = match marker {
+            0x00..=0x7f => Value::from(marker),
+            0x80..=0x8f => self.map(usize::from(marker & 0x0f), depth)?,
+            0x90..=0x9f => self.items(usize::from(marker & 0x0f), depth)?,
+            0xa0..=0xbf => self.string(usize::from(marker & 0x1f))?,
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xc4 => {
+                let len = self.u8()?.into();
+                self.binary(len)?
+            }
+            0xc5 => {
+                let len = self.u16()?;
+                self.binary(len)?
+            }
+            0xc6 => {
+                let len = self.u32()?;
+                self.binary(len)?
+            }
+            0xca => float(f32::from_be_bytes(self.array()?).into(), at)?,
+            0xcb => float(f64::from_be_bytes(self.array()?), at)?,
+            0xcc => Value::from(self.u8()?),
+            0xcd => Value::from(u16::from_be_bytes(self.array()?)),
+            0xce => Value::from(u32::from_be_bytes(self.array()?)),
+            0xcf => Value::from(u64::from_be_bytes(self.array()?)),
+            0xd0 => Value::from(i8::from_be_bytes(self.array()?)),
+            0xd1 => Value::from(i16::from_be_bytes(self.array()?)),
+            0xd2 => Value::from(i32::from_be_bytes(self.array()?)),
+            0xd3 => Value::from(i64::from_be_bytes(self.array()?)),
+            0xd9 => {
+                let len = self.u8()?.into();
+                self.string(len)?
+            }
+            0xda => {
+                let len = self.u16()?;
+                self.string(len)?
+            }
+            0xdb => {
+                let len = self.u32()?;
+                self.string(len)?
+            }
+            0xdc => {
+                let len = self.u16()?;
+                self.items(len, depth)?
+            }
+            0xdd => {
+                let len = self.u32()?;
+                self.items(len, depth)?
+            }
+            0xde => {
+                let len = self.u16()?;
+                self.map(len, depth)?
+            }
+            0xdf => {
+                let len = self.u32()?;
+                self.map(len, depth)?
+            }
+            0xe0..=0xff => Value::from(marker as i8),
+            _ => return Err(format!("Unsupported type 0x{marker:02x} at byte {at}")),
+        };
+        Ok(value)
+    }
+
+    fn string(&mut self, len: usize) -> Result<Value, String> {
+        let at = self.offset;
+        let text = std::str::from_utf8(self.take(len)?)
+            .map_err(|_| format!("String at byte {at} is not valid UTF-8"))?;
+        Ok(Value::String(text.to_string()))
+    }
+
+    fn binary(&mut self, len: usize) -> Result<Value, String> {
+        Ok(Value::String(BASE64.encode(self.take(len)?)))
+    }
+
+    fn items(&mut self, len: usize, depth: usize) -> Result<Value, String> {
+        // Every item takes at least a byte, which bounds what a bogus
+        // length can make us allocate.
+        let mut items = Vec::with_capacity(len.min(self.bytes.len() - self.offset));
+        for _ in 0..len {
+            items.push(self.value(depth + 1)?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, len: usize, depth: usize) -> Result<Value, String> {
+        let mut map = Map::new();
+        for _ in 0..len {
+            let at = self.offset;
+            let Value::String(key) = self.value(depth + 1)? else {
+                return Err(format!("Map key at byte {at} is not a string"));
+            };
+            let value = self.value(depth + 1)?;
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+fn float(n: f64, at: usize) -> Result<Value, String> {
+    Number::from_f64(n)
+        .map(Value::Number)
+        .ok_or_else(|| format!("Float at byte {at} is not finite"))
+}
@@ -0,0 +1,85 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::{debug, warn};
+
+use crate::state::SharedState;
+
+/// How long browsers may cache a preflight answer.
+const PREFLIGHT_MAX_AGE_SECS: &str = "600";
+
+/// Checks the `Origin` of every request, WebSocket upgrades included,
+/// before it reaches a handler. Requests without one come from something
+/// other than a browser page and pass; browser requests must come from the
+/// server's own origin or one in `allowedOrigins`, and get CORS headers
+/// naming it. Preflight requests are answered here.
+pub async fn check_origin(
+    State(state): State<SharedState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(origin) = request.headers().get(header::ORIGIN).cloned() else {
+        return next.run(request).await;
+    };
+    if !allowed(&state.config.allowed_origins, &origin, request.headers()) {
+        warn!(
+            origin = ?origin,
+            path = request.uri().path(),
+            "Rejecting request from a disallowed origin"
+        );
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+
+    let preflight = request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    let mut response = if preflight {
+        debug!(origin = ?origin, "Answering CORS preflight");
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        let headers = response.headers_mut();
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            HeaderValue::from_static("GET, POST, OPTIONS"),
+        );
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_HEADERS,
+            HeaderValue::from_static("authorization, content-type"),
+        );
+        headers.insert(
+            header::ACCESS_CONTROL_MAX_AGE,
+            HeaderValue::from_static(PREFLIGHT_MAX_AGE_SECS),
+        );
+        response
+    } else {
+        next.run(request).await
+    };
+    let headers = response.headers_mut();
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    headers.append(header::VARY, HeaderValue::from_static("Origin"));
+    response
+}
+
+/// Whether `origin` is the server's own, going by the `Host` header, or is
+/// listed. `*` in the list allows every origin.
+fn allowed(allowed_origins: &[String], origin: &HeaderValue, headers: &HeaderMap) -> bool {
+    let Ok(origin) = origin.to_str() else {
+        return false;
+    };
+    let origin = origin.trim_end_matches('/');
+    let same_origin = origin
+        .split_once("://")
+        .zip(
+            headers
+                .get(header::HOST)
+                .and_then(|host| host.to_str().ok()),
+        )
+        .is_some_and(|((_, authority), host)| authority.eq_ignore_ascii_case(host));
+    same_origin
+        || allowed_origins.iter().any(|allowed| {
+            allowed == "*" || allowed.trim_end_matches('/').eq_ignore_ascii_case(origin)
+        })
+}
@@ -0,0 +1,112 @@
+use globset::{Glob, GlobMatcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+use tracing::debug;
+
+use crate::{
+    config::{Access, PermissionRule},
+    rpc::error::HandlerError,
+    sandbox::normalize,
+};
+
+/// One workspace root's `permissions` rules.
+#[derive(Clone)]
+struct Scope {
+    /// The root as given and canonicalized, since request paths may be
+    /// relative to either form.
+    roots: [PathBuf; 2],
+    rules: Vec<(GlobMatcher, Access)>,
+}
+
+/// The configured `permissions` rules, matched against paths relative to
+/// the workspace root they fall under. Clones share rules, so a
+/// [`Permissions::replace`] reaches every one.
+#[derive(Clone)]
+pub struct Permissions {
+    /// The primary root's scope first; its rules also cover paths outside
+    /// every root.
+    scopes: Arc<RwLock<Vec<Scope>>>,
+}
+
+impl Permissions {
+    pub fn new<'a>(
+        roots: impl IntoIterator<Item = (&'a Path, &'a [PermissionRule])>,
+    ) -> Result<Self, String> {
+        let scopes = roots
+            .into_iter()
+            .map(|(root, rules)| {
+                let rules = rules
+                    .iter()
+                    .map(|rule| {
+                        Glob::new(&rule.glob)
+                            .map(|glob| (glob.compile_matcher(), rule.access))
+                            .map_err(|e| format!("invalid permission glob {}: {e}", rule.glob))
+                    })
+                    .collect::<Result<_, _>>()?;
+                let absolute =
+                    normalize(&std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf()));
+                let canonical = root.canonicalize().unwrap_or_else(|_| absolute.clone());
+                Ok(Scope {
+                    roots: [canonical, absolute],
+                    rules,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            scopes: Arc::new(RwLock::new(scopes)),
+        })
+    }
+
+    /// Takes on the rules of `other`, as reloaded from the config.
+    pub fn replace(&self, other: &Permissions) {
+        let scopes = other.read().clone();
+        *self.scopes.write().unwrap_or_else(|e| e.into_inner()) = scopes;
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Vec<Scope>> {
+        self.scopes.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Access granted to `path`. Paths outside every root are only
+    /// matched when a primary root glob is absolute.
+    pub fn access(&self, path: &Path) -> Access {
+        let scopes = self.read();
+        if scopes.iter().all(|scope| scope.rules.is_empty()) {
+            return Access::Write;
+        }
+        let absolute = normalize(&std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+        // The innermost root wins when roots are nested.
+        let (scope, relative) = scopes
+            .iter()
+            .filter_map(|scope| {
+                let root = scope.roots.iter().find(|root| absolute.starts_with(root))?;
+                Some((scope, root))
+            })
+            .max_by_key(|(_, root)| root.components().count())
+            .map(|(scope, root)| (scope, absolute.strip_prefix(root).unwrap_or(&absolute)))
+            .unwrap_or((&scopes[0], &absolute));
+        scope
+            .rules
+            .iter()
+            .find(|(glob, _)| glob.is_match(relative))
+            .map_or(Access::Write, |(_, access)| *access)
+    }
+
+    pub fn check(&self, path: &Path, required: Access) -> Result<(), HandlerError> {
+        let granted = self.access(path);
+        if granted < required {
+            debug!(path = %path.display(), ?granted, ?required, "Permission denied");
+            return Err(HandlerError::PermissionDenied {
+                path: path.to_path_buf(),
+                required,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn is_denied(&self, path: &Path) -> bool {
+        self.access(path) == Access::Deny
+    }
+}
@@ -0,0 +1,333 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{IdentityDefinition, PolicyConfig, TierLimits, WorkspaceDefinition},
+    rpc::{archive, context::ConnectionContext, error::HandlerError},
+    state::AppState,
+    tenant::Tenant,
+    ws::lanes,
+};
+
+/// How long a measured workspace size is trusted before walking it again.
+const WORKSPACE_SIZE_TTL: Duration = Duration::from_secs(30);
+
+/// Methods that add to the workspace, checked against `maxWorkspaceBytes`.
+/// Those whose growth is not known up front are refused only once the
+/// workspace is already over the limit.
+const WRITE_METHODS: &[&str] = &[
+    "applyPatch",
+    "createFromTemplate",
+    "importZip",
+    "structuredSet",
+    "table/updateCell",
+    "uploadFile",
+    "writeFile",
+];
+
+/// Namespaces every tier may use: discovery and protocol plumbing.
+const ALWAYS_ALLOWED: &[&str] = &["$", "server"];
+
+/// Who a connection authenticated as. Anonymous connections have no name.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Identity {
+    pub name: Option<String>,
+    pub tier: Option<String>,
+    /// Where the connection is confined, if its key names a workspace.
+    #[serde(skip)]
+    pub tenant: Option<Arc<Tenant>>,
+}
+
+/// A key's identity, with its workspace resolved.
+struct Key {
+    name: String,
+    tier: String,
+    tenant: Option<Arc<Tenant>>,
+}
+
+impl Key {
+    fn identity(&self) -> Identity {
+        Identity {
+            name: Some(self.name.clone()),
+            tier: Some(self.tier.clone()),
+            tenant: self.tenant.clone(),
+        }
+    }
+}
+
+/// The `keysFile` as last read.
+#[derive(Default)]
+struct KeysFile {
+    modified: Option<SystemTime>,
+    keys: BTreeMap<String, Key>,
+}
+
+/// Enforces the usage limits of each connection's tier before requests
+/// are dispatched.
+pub struct Policy {
+    config: PolicyConfig,
+    keys: BTreeMap<String, Key>,
+    keys_file: Mutex<KeysFile>,
+    /// Roots of the `workspaces`, which keys may name.
+    workspaces: BTreeMap<String, PathBuf>,
+    workspace_sizes: Mutex<HashMap<PathBuf, (Instant, u64)>>,
+}
+
+impl Policy {
+    pub fn new(
+        config: &PolicyConfig,
+        workspaces: &BTreeMap<String, WorkspaceDefinition>,
+    ) -> Result<Self, String> {
+        if let Some(tier) = &config.default_tier
+            && !config.tiers.contains_key(tier)
+        {
+            return Err(format!("policy refers to unknown tier {tier}"));
+        }
+        let mut policy = Self {
+            config: config.clone(),
+            keys: BTreeMap::new(),
+            keys_file: Mutex::default(),
+            workspaces: workspaces
+                .iter()
+                .map(|(name, workspace)| (name.clone(), workspace.path.clone()))
+                .collect(),
+            workspace_sizes: Mutex::default(),
+        };
+        policy.keys = policy.resolve(&config.keys)?;
+        if let Some(path) = &config.keys_file {
+            let keys_file = policy.read_keys_file(path)?;
+            debug!(keys = keys_file.keys.len(), "Read keys file");
+            policy.keys_file = Mutex::new(keys_file);
+        }
+        Ok(policy)
+    }
+
+    /// Checks the tiers of `definitions` and resolves their workspaces.
+    fn resolve(
+        &self,
+        definitions: &BTreeMap<String, IdentityDefinition>,
+    ) -> Result<BTreeMap<String, Key>, String> {
+        definitions
+            .iter()
+            .map(|(key, identity)| {
+                if !self.config.tiers.contains_key(&identity.tier) {
+                    return Err(format!("policy refers to unknown tier {}", identity.tier));
+                }
+                let tenant = match &identity.workspace {
+                    Some(workspace) => {
+                        let root = self
+                            .workspaces
+                            .get(workspace)
+                            .map_or_else(|| PathBuf::from(workspace), PathBuf::clone);
+                        Some(Arc::new(Tenant::new(&root, &identity.permissions)?))
+                    }
+                    None if !identity.permissions.is_empty() => {
+                        return Err(format!(
+                            "key of {} has permissions but no workspace",
+                            identity.name
+                        ));
+                    }
+                    None => None,
+                };
+                let resolved = Key {
+                    name: identity.name.clone(),
+                    tier: identity.tier.clone(),
+                    tenant,
+                };
+                Ok((key.clone(), resolved))
+            })
+            .collect()
+    }
+
+    fn read_keys_file(&self, path: &Path) -> Result<KeysFile, String> {
+        let modified = modified(path);
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read keys file {}: {e}", path.display()))?;
+        let definitions = serde_json::from_str(&text)
+            .map_err(|e| format!("invalid keys file {}: {e}", path.display()))?;
+        Ok(KeysFile {
+            modified,
+            keys: self.resolve(&definitions)?,
+        })
+    }
+
+    /// Resolves the key presented on upgrade, or `None` if it is unknown.
+    /// The `keysFile` is read again first if it changed since last time.
+    pub fn authenticate(&self, key: Option<&str>) -> Option<Identity> {
+        let Some(key) = key else {
+            return Some(Identity {
+                name: None,
+                tier: self.config.default_tier.clone(),
+                tenant: None,
+            });
+        };
+        if let Some(known) = self.keys.get(key) {
+            return Some(known.identity());
+        }
+        let path = self.config.keys_file.as_deref()?;
+        let mut keys_file = self.keys_file.lock().unwrap_or_else(|e| e.into_inner());
+        let modified = modified(path);
+        if modified != keys_file.modified {
+            match self.read_keys_file(path) {
+                Ok(read) => {
+                    info!(keys = read.keys.len(), "Keys file changed, reloaded it");
+                    *keys_file = read;
+                }
+                Err(e) => {
+                    warn!(error = %e, "Keeping the keys last read");
+                    keys_file.modified = modified;
+                }
+            }
+        }
+        keys_file.keys.get(key).map(Key::identity)
+    }
+
+    pub fn limits(&self, identity: &Identity) -> Option<&TierLimits> {
+        self.config.tiers.get(identity.tier.as_deref()?)
+    }
+
+    /// Checks a request against the connection's tier. The returned slot,
+    /// if any, counts the request towards the search limit until dropped.
+    pub fn check<'a>(
+        &self,
+        state: &AppState,
+        connection: &'a ConnectionContext,
+        method: &str,
+        params: &Value,
+    ) -> Result<Option<SearchSlot<'a>>, HandlerError> {
+        let Some(limits) = self.limits(&connection.identity) else {
+            return Ok(None);
+        };
+        let tier = connection.identity.tier.clone().unwrap_or_default();
+
+        if let (Some(allowed), Some((namespace, _))) =
+            (&limits.allowed_namespaces, method.split_once('/'))
+            && !ALWAYS_ALLOWED.contains(&namespace)
+            && !allowed.iter().any(|allowed| allowed == namespace)
+        {
+            return Err(HandlerError::LimitExceeded {
+                message: format!("Tier {tier} does not include {namespace} methods"),
+                tier,
+                limit: "allowedNamespaces",
+                max: None,
+            });
+        }
+
+        if method == "terminal/create"
+            && let Some(max) = limits.max_terminals
+            && state.terminals.count(connection.id) >= max
+        {
+            return Err(HandlerError::LimitExceeded {
+                message: format!("Tier {tier} allows at most {max} open terminals"),
+                tier,
+                limit: "maxTerminals",
+                max: Some(max as u64),
+            });
+        }
+
+        if WRITE_METHODS.contains(&method)
+            && let Some(max) = limits.max_workspace_bytes
+        {
+            let adding = match method {
+                "uploadFile" => params
+                    .get("hash")
+                    .and_then(Value::as_str)
+                    .and_then(|hash| state.blobs.size(hash).ok())
+                    .unwrap_or(0),
+                // Declared sizes; extraction itself stops at the upload
+                // limit should they lie.
+                "importZip" => params
+                    .get("hash")
+                    .and_then(Value::as_str)
+                    .and_then(|hash| archive::expanded_size(state, hash))
+                    .unwrap_or(0),
+                _ => params
+                    .get("content")
+                    .and_then(Value::as_str)
+                    .map_or(0, str::len) as u64,
+            };
+            let root = match &connection.identity.tenant {
+                Some(tenant) => tenant.root(),
+                None => &state.config.root,
+            };
+            if self.workspace_size(root) + adding > max {
+                return Err(HandlerError::LimitExceeded {
+                    message: format!("Tier {tier} allows a workspace of at most {max} bytes"),
+                    tier,
+                    limit: "maxWorkspaceBytes",
+                    max: Some(max),
+                });
+            }
+        }
+
+        // Searches are background requests, so this bounds them along with
+        // scans, exports and blame.
+        if lanes::is_background(method)
+            && let Some(max) = limits.max_search_concurrency
+        {
+            let active = &connection.active_searches;
+            if active.fetch_add(1, Ordering::AcqRel) >= max {
+                active.fetch_sub(1, Ordering::AcqRel);
+                return Err(HandlerError::LimitExceeded {
+                    message: format!("Tier {tier} allows at most {max} concurrent searches"),
+                    tier,
+                    limit: "maxSearchConcurrency",
+                    max: Some(max as u64),
+                });
+            }
+            return Ok(Some(SearchSlot(active)));
+        }
+        Ok(None)
+    }
+
+    /// Total size of the workspace at `root`, remeasured at most every
+    /// [`WORKSPACE_SIZE_TTL`].
+    fn workspace_size(&self, root: &Path) -> u64 {
+        let mut sizes = self
+            .workspace_sizes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some((measured, size)) = sizes.get(root)
+            && measured.elapsed() < WORKSPACE_SIZE_TTL
+        {
+            return *size;
+        }
+        let size = ignore::WalkBuilder::new(root)
+            .standard_filters(false)
+            .build()
+            .filter_map(Result::ok)
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum();
+        debug!(root = %root.display(), size, "Measured workspace size");
+        sizes.insert(root.to_path_buf(), (Instant::now(), size));
+        size
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// A running request counted against `maxSearchConcurrency`.
+pub struct SearchSlot<'a>(&'a AtomicUsize);
+
+impl Drop for SearchSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
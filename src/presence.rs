@@ -0,0 +1,130 @@
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, MutexGuard},
+};
+use tokio::sync::broadcast;
+use tracing::debug;
+
+use crate::{
+    clock,
+    rpc::text::{Position, Range},
+};
+
+/// Changes buffered per connection before a slow one starts missing them.
+const LIVE_BUFFER: usize = 256;
+
+/// What one connected editor last reported about itself.
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Presence {
+    pub connection_id: u64,
+    pub username: Option<String>,
+    /// File the editor has focused.
+    pub path: Option<String>,
+    pub cursor: Option<Position>,
+    pub selection: Option<Range>,
+    /// Unix seconds of the last update.
+    pub updated_at: u64,
+}
+
+/// A client's presence changed; `presence` is `None` once it has left.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceChange {
+    pub connection_id: u64,
+    pub presence: Option<Presence>,
+}
+
+/// Fields of a `presence/update`; `None` leaves the current value alone.
+#[derive(Default)]
+pub struct PresenceUpdate {
+    pub username: Option<String>,
+    pub path: Option<String>,
+    pub cursor: Option<Position>,
+    pub selection: Option<Range>,
+}
+
+/// Who is connected and what they are looking at, shared with every
+/// connection as `presence/changed` notifications.
+pub struct PresenceRegistry {
+    entries: Mutex<BTreeMap<u64, Presence>>,
+    live: broadcast::Sender<PresenceChange>,
+}
+
+impl Default for PresenceRegistry {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::default(),
+            live: broadcast::Sender::new(LIVE_BUFFER),
+        }
+    }
+}
+
+impl PresenceRegistry {
+    /// Merges `update` into the connection's presence, creating it on the
+    /// first update, and announces the result.
+    pub fn update(
+        &self,
+        connection_id: u64,
+        default_username: Option<&str>,
+        update: PresenceUpdate,
+    ) -> Presence {
+        let presence = {
+            let mut entries = self.lock();
+            let presence = entries.entry(connection_id).or_insert_with(|| Presence {
+                connection_id,
+                username: default_username.map(str::to_string),
+                ..Presence::default()
+            });
+            if update.username.is_some() {
+                presence.username = update.username;
+            }
+            // A new file resets the position within the old one.
+            if update.path.is_some() && update.path != presence.path {
+                presence.path = update.path;
+                presence.cursor = None;
+                presence.selection = None;
+            }
+            if update.cursor.is_some() {
+                presence.cursor = update.cursor;
+            }
+            if update.selection.is_some() {
+                presence.selection = update.selection;
+            }
+            presence.updated_at = clock::unix_secs();
+            presence.clone()
+        };
+        debug!(connection_id, "Presence updated");
+        // Only fails when nobody is connected.
+        let _ = self.live.send(PresenceChange {
+            connection_id,
+            presence: Some(presence.clone()),
+        });
+        presence
+    }
+
+    /// Forgets a connection that closed, announcing its departure if it
+    /// had ever reported presence.
+    pub fn remove(&self, connection_id: u64) {
+        if self.lock().remove(&connection_id).is_some() {
+            debug!(connection_id, "Presence removed");
+            let _ = self.live.send(PresenceChange {
+                connection_id,
+                presence: None,
+            });
+        }
+    }
+
+    pub fn list(&self) -> Vec<Presence> {
+        self.lock().values().cloned().collect()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PresenceChange> {
+        self.live.subscribe()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<u64, Presence>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
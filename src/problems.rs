@@ -0,0 +1,54 @@
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::rpc::text::Range;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A diagnostic about a workspace file, produced by a background job.
+#[derive(Serialize, Debug, Clone)]
+pub struct Problem {
+    pub path: String,
+    pub range: Range,
+    pub severity: Severity,
+    pub source: String,
+    pub code: String,
+    pub message: String,
+}
+
+/// Latest problems reported by each source. A source replaces its whole
+/// set on every run so stale findings disappear once fixed.
+#[derive(Default, Clone)]
+pub struct ProblemStore {
+    by_source: Arc<Mutex<BTreeMap<String, Vec<Problem>>>>,
+}
+
+impl ProblemStore {
+    pub fn replace(&self, source: &str, problems: Vec<Problem>) {
+        self.by_source
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(source.to_string(), problems);
+    }
+
+    pub fn list(&self, source: Option<&str>, path: Option<&str>) -> Vec<Problem> {
+        self.by_source
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(name, _)| source.is_none_or(|source| source == name.as_str()))
+            .flat_map(|(_, problems)| problems)
+            .filter(|problem| path.is_none_or(|path| problem.path == path))
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,139 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::sandbox::normalize;
+
+/// Matches paths against the configured list of protected globs, relative
+/// to the workspace root each falls under.
+pub struct ProtectedPaths {
+    /// Every root as given and canonicalized, since request paths may be
+    /// relative to either form.
+    roots: Vec<PathBuf>,
+    globs: GlobSet,
+}
+
+impl ProtectedPaths {
+    pub fn new<'a>(
+        roots: impl IntoIterator<Item = &'a Path>,
+        patterns: &[String],
+    ) -> Result<Self, String> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern)
+                .map_err(|e| format!("invalid protected path pattern {pattern}: {e}"))?;
+            builder.add(glob);
+        }
+        let globs = builder
+            .build()
+            .map_err(|e| format!("failed to build protected path set: {e}"))?;
+
+        let roots = roots
+            .into_iter()
+            .flat_map(|root| {
+                let absolute =
+                    normalize(&std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf()));
+                let canonical = root.canonicalize().unwrap_or_else(|_| absolute.clone());
+                [canonical, absolute]
+            })
+            .collect();
+        Ok(Self { roots, globs })
+    }
+
+    /// Whether `path`, or the file it leads to through symlinks, matches a
+    /// protected glob.
+    pub fn is_protected(&self, path: &Path) -> bool {
+        // `absolute` keeps `..`, which would let `sub/../Cargo.lock` miss.
+        let absolute = normalize(&std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+        let canonical = canonical_target(&absolute);
+        [Some(absolute), canonical]
+            .into_iter()
+            .flatten()
+            .any(|candidate| match self.relative(&candidate) {
+                Some(relative) => self.globs.is_match(relative),
+                None => self.globs.is_match(path),
+            })
+    }
+
+    /// `path` relative to the innermost root holding it.
+    fn relative<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .and_then(|root| path.strip_prefix(root).ok())
+    }
+}
+
+/// Canonical form of `path` with symlinks resolved; the last components
+/// may be missing, as for a file about to be created.
+fn canonical_target(path: &Path) -> Option<PathBuf> {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    let mut canonical = loop {
+        match existing.canonicalize() {
+            Ok(canonical) => break canonical,
+            Err(_) => {
+                missing.push(existing.file_name()?);
+                existing = existing.parent()?;
+            }
+        }
+    };
+    canonical.extend(missing.iter().rev());
+    Some(canonical)
+}
+
+/// Emits the audit event for a forced destructive action on a protected path.
+pub fn audit_forced(operation: &str, path: &str) {
+    warn!(
+        target: "audit",
+        operation = operation,
+        path = %path,
+        "Protected path modified with force"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protected() -> ProtectedPaths {
+        ProtectedPaths::new([Path::new("/workspace")], &["Cargo.lock".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn matches_relative_to_the_root() {
+        assert!(protected().is_protected(Path::new("/workspace/Cargo.lock")));
+        assert!(!protected().is_protected(Path::new("/workspace/src/main.rs")));
+    }
+
+    #[test]
+    fn parent_segments_do_not_dodge_the_match() {
+        assert!(protected().is_protected(Path::new("/workspace/sub/../Cargo.lock")));
+        assert!(protected().is_protected(Path::new("/workspace/./a/b/../../Cargo.lock")));
+    }
+
+    #[test]
+    fn covers_every_root_and_symlinks() {
+        use std::{fs, os::unix::fs::symlink};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (primary, secondary) = (dir.path().join("primary"), dir.path().join("secondary"));
+        fs::create_dir(&primary).unwrap();
+        fs::create_dir(&secondary).unwrap();
+        fs::write(primary.join("Cargo.lock"), "").unwrap();
+        symlink(primary.join("Cargo.lock"), primary.join("lock-link")).unwrap();
+        symlink(&primary, dir.path().join("alias")).unwrap();
+        let protected = ProtectedPaths::new(
+            [dir.path().join("alias").as_path(), secondary.as_path()],
+            &["Cargo.lock".to_string()],
+        )
+        .unwrap();
+
+        assert!(protected.is_protected(&secondary.join("Cargo.lock")));
+        assert!(protected.is_protected(&primary.canonicalize().unwrap().join("Cargo.lock")));
+        assert!(protected.is_protected(&dir.path().join("alias/Cargo.lock")));
+        assert!(protected.is_protected(&primary.join("lock-link")));
+        assert!(!protected.is_protected(&primary.join("Cargo.toml")));
+    }
+}
@@ -0,0 +1,197 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use std::{collections::HashMap, time::UNIX_EPOCH};
+use tokio::io::AsyncReadExt;
+use tracing::{Instrument, debug, info_span, warn};
+
+use crate::{
+    config::Access,
+    file_type,
+    state::SharedState,
+    tenant::Tenant,
+    thumbnail,
+    ws::{connection::presented_key, pool::Saturated},
+};
+
+/// Bytes read from the file per body chunk.
+const CHUNK_BYTES: usize = 64 * 1024;
+const MIN_THUMBNAIL: usize = 16;
+const MAX_THUMBNAIL: usize = 1024;
+/// Larger images are served whole rather than decoded.
+const MAX_THUMBNAIL_SOURCE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// `GET /raw/<workspace>/<path>`: the file's bytes with its detected
+/// content type, for previews that should not travel through the RPC
+/// channel as base64. The key goes in the `Authorization` header or, for
+/// `<img src>`, the `token` query parameter; paths are checked like a
+/// `readFile`. `?thumbnail=<px>` scales PNG images down to fit; other
+/// images are served whole. Nothing served may run scripts.
+pub async fn raw_handler(
+    State(state): State<SharedState>,
+    Path((workspace, path)): Path<(String, String)>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let span = info_span!("raw_file", workspace = %workspace, path = %path);
+    async move {
+        let Some(identity) = state.policy.authenticate(presented_key(&headers, &query)) else {
+            warn!("Rejecting raw file request with unknown key");
+            return StatusCode::UNAUTHORIZED.into_response();
+        };
+        let target = match state.uris.to_path(&format!("{workspace}:{path}")) {
+            Ok(Some(target)) => target,
+            Ok(None) | Err(_) => return not_found(),
+        };
+        let confined = |tenant: &Tenant| tenant.check(&target, Access::Read);
+        if let Err(e) = state
+            .sandbox
+            .check(&target)
+            .and_then(|()| state.permissions.check(&target, Access::Read))
+            .and_then(|()| identity.tenant.as_deref().map_or(Ok(()), confined))
+        {
+            warn!(error = ?e, "Rejected raw file request");
+            return StatusCode::FORBIDDEN.into_response();
+        }
+        let Ok(metadata) = tokio::fs::metadata(&target).await else {
+            return not_found();
+        };
+        if !metadata.is_file() {
+            return not_found();
+        }
+
+        // The longest side, in pixels, to scale PNG images down to.
+        let thumbnail = query
+            .get("thumbnail")
+            .and_then(|side| side.parse::<usize>().ok())
+            .map(|side| side.clamp(MIN_THUMBNAIL, MAX_THUMBNAIL));
+        let modified_ms = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_millis());
+        let etag = match thumbnail {
+            Some(side) => format!("W/\"{:x}-{modified_ms:x}-{side}\"", metadata.len()),
+            None => format!("W/\"{:x}-{modified_ms:x}\"", metadata.len()),
+        };
+        if headers
+            .get(header::IF_NONE_MATCH)
+            .is_some_and(|presented| presented.as_bytes() == etag.as_bytes())
+        {
+            return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+        }
+
+        let Ok(mut file) = tokio::fs::File::open(&target).await else {
+            return not_found();
+        };
+        let mut head = Vec::with_capacity(file_type::SNIFF_BYTES);
+        if (&mut file)
+            .take(file_type::SNIFF_BYTES as u64)
+            .read_to_end(&mut head)
+            .await
+            .is_err()
+        {
+            return not_found();
+        }
+        let detected = file_type::detect(&target, &head, metadata.len() > head.len() as u64);
+        let content_type = match detected.charset {
+            Some(charset) => format!("{}; charset={}", detected.mime, charset.name()),
+            None => detected.mime.to_string(),
+        };
+
+        if let Some(side) = thumbnail
+            && detected.mime == "image/png"
+            && metadata.len() <= MAX_THUMBNAIL_SOURCE_BYTES
+        {
+            let source = target.clone();
+            // Decoding is bulk work: it takes a background slot like any
+            // other, and a busy pool serves the image whole.
+            let scaled = match state.lanes.background.spawn(move || {
+                std::fs::read(source)
+                    .ok()
+                    .and_then(|png| thumbnail::png_thumbnail(&png, side))
+            }) {
+                Ok(task) => task.await.flatten(),
+                Err(Saturated(queued)) => {
+                    debug!(queued, "Background pool busy; not thumbnailing");
+                    None
+                }
+            };
+            // Images already small enough, or not decodable, go out whole.
+            if let Some(png) = scaled {
+                debug!(side, bytes = png.len(), "Serving thumbnail");
+                let mut response = (
+                    [
+                        (header::CONTENT_TYPE, "image/png".to_string()),
+                        (header::ETAG, etag),
+                    ],
+                    png,
+                )
+                    .into_response();
+                secure(response.headers_mut());
+                return response;
+            }
+        }
+
+        debug!(
+            mime = detected.mime,
+            size = metadata.len(),
+            "Serving raw file"
+        );
+        let body = file_body(Bytes::from(head), file);
+        let mut response = (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CONTENT_LENGTH, metadata.len().to_string()),
+                (header::ETAG, etag),
+            ],
+            body,
+        )
+            .into_response();
+        secure(response.headers_mut());
+        response
+    }
+    .instrument(span)
+    .await
+}
+
+/// A response body of `head`, already read, followed by the rest of `file`.
+pub fn file_body(head: Bytes, file: tokio::fs::File) -> Body {
+    Body::from_stream(futures_util::stream::try_unfold(
+        (Some(head), file),
+        |(head, mut file)| async move {
+            if let Some(head) = head.filter(|head| !head.is_empty()) {
+                return Ok(Some((head, (None, file))));
+            }
+            let mut buffer = vec![0; CHUNK_BYTES];
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok::<_, std::io::Error>(None);
+            }
+            buffer.truncate(read);
+            Ok(Some((Bytes::from(buffer), (None, file))))
+        },
+    ))
+}
+
+/// Workspace files are untrusted: HTML and SVG served from here must not
+/// run scripts with the server's origin.
+fn secure(headers: &mut HeaderMap) {
+    for (name, value) in [
+        (
+            header::CONTENT_SECURITY_POLICY,
+            "default-src 'none'; img-src 'self' data:; style-src 'unsafe-inline'; sandbox",
+        ),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        (header::CACHE_CONTROL, "private, no-cache"),
+    ] {
+        headers.insert(name, HeaderValue::from_static(value));
+    }
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, "Not found").into_response()
+}
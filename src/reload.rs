@@ -0,0 +1,211 @@
+//! Applying a changed config file without a restart, on `SIGHUP` or
+//! `config/reload`. Limits, permission rules, `exclude` globs and the log
+//! level take effect at once, open connections included; other settings
+//! are read at startup only and are reported as needing a restart.
+
+use serde::Serialize;
+use serde_json::json;
+use std::{fmt::Debug, sync::Mutex};
+use tracing::{info, warn};
+
+use crate::{
+    config::Config, logging, permissions::Permissions, state::AppState, walk::IgnoreRules,
+};
+
+/// What a reload changed.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Reloaded {
+    /// Settings now in effect with new values.
+    pub applied: Vec<&'static str>,
+    /// Settings that differ from the running ones but only apply after a
+    /// restart.
+    pub restart_required: Vec<&'static str>,
+}
+
+/// The config as last loaded, to tell what a reload changes.
+pub struct Reloader {
+    current: Mutex<Config>,
+}
+
+impl Reloader {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            current: Mutex::new(config.clone()),
+        }
+    }
+
+    /// Reads the config again, from the same file and flags as at startup,
+    /// and applies what can be applied. Nothing changes if it fails to
+    /// load or holds an invalid glob or log level.
+    pub fn reload(&self, state: &AppState) -> Result<Reloaded, String> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        let result = Config::from_args().and_then(|config| apply(state, &current, config));
+        match result {
+            Ok((config, reloaded)) => {
+                *current = config;
+                info!(
+                    applied = ?reloaded.applied,
+                    restart_required = ?reloaded.restart_required,
+                    "Configuration reloaded"
+                );
+                state
+                    .activity
+                    .record("config.reloaded", None, json!(reloaded));
+                Ok(reloaded)
+            }
+            Err(e) => {
+                warn!(error = %e, "Keeping the running configuration");
+                Err(e)
+            }
+        }
+    }
+}
+
+fn apply(state: &AppState, current: &Config, config: Config) -> Result<(Config, Reloaded), String> {
+    let running = &state.config;
+    // Roots cannot move; their rules can.
+    let permissions = Permissions::new(
+        std::iter::once((running.root.as_path(), config.permissions.as_slice())).chain(
+            running.workspaces.iter().map(|(name, workspace)| {
+                let rules = config
+                    .workspaces
+                    .get(name)
+                    .map_or(&[][..], |workspace| workspace.permissions.as_slice());
+                (workspace.path.as_path(), rules)
+            }),
+        ),
+    )?;
+    let ignore = IgnoreRules::new(&running.root, &config.exclude, permissions.clone())?;
+    // Last of the checks, and the first change.
+    logging::set_level(config.log_level.as_deref())?;
+    state.set_limits(config.limits);
+    state.permissions.replace(&permissions);
+    state.ignore.replace_exclude(&ignore);
+
+    let applied = [
+        ("limits", differs(&current.limits, &config.limits)),
+        (
+            "permissions",
+            differs(&current.permissions, &config.permissions)
+                || differs(&rules_by_workspace(current), &rules_by_workspace(&config)),
+        ),
+        ("exclude", differs(&current.exclude, &config.exclude)),
+        ("logLevel", differs(&current.log_level, &config.log_level)),
+    ];
+    let restart_required = restart_required(running, &config);
+    let reloaded = Reloaded {
+        applied: changed(&applied),
+        restart_required: changed(&restart_required),
+    };
+    Ok((config, reloaded))
+}
+
+fn rules_by_workspace(config: &Config) -> Vec<(&String, String)> {
+    config
+        .workspaces
+        .iter()
+        .map(|(name, workspace)| (name, format!("{:?}", workspace.permissions)))
+        .collect()
+}
+
+/// Settings read only at startup, and whether `config` changes them.
+fn restart_required(running: &Config, config: &Config) -> Vec<(&'static str, bool)> {
+    let paths = |config: &Config| -> Vec<_> {
+        config
+            .workspaces
+            .iter()
+            .map(|(name, workspace)| (name.clone(), workspace.path.clone()))
+            .collect()
+    };
+    macro_rules! fields {
+        ($($field:ident => $name:literal),* $(,)?) => {
+            vec![$(($name, differs(&running.$field, &config.$field))),*]
+        };
+    }
+    let mut fields = fields![
+        root => "root",
+        root_name => "rootName",
+        port => "port",
+        record_dir => "recordDir",
+        log_format => "logFormat",
+        log_file => "logFile",
+        static_dir => "staticDir",
+        allowed_origins => "allowedOrigins",
+        audit_log => "auditLog",
+        data_dir => "dataDir",
+        templates_dir => "templatesDir",
+        protected_paths => "protectedPaths",
+        read_only => "readOnly",
+        follow_symlinks => "followSymlinks",
+        raw_paths => "rawPaths",
+        line_endings => "lineEndings",
+        tasks => "tasks",
+        max_concurrent_tasks => "maxConcurrentTasks",
+        jobs => "jobs",
+        webhooks => "webhooks",
+        language_servers => "languageServers",
+        debug_adapters => "debugAdapters",
+        formatters => "formatters",
+        snippets => "snippets",
+        compression_threshold => "compressionThreshold",
+        ping_interval_secs => "pingIntervalSecs",
+        idle_timeout_secs => "idleTimeoutSecs",
+        session_grace_secs => "sessionGraceSecs",
+        session_replay_limit => "sessionReplayLimit",
+        max_connections => "maxConnections",
+        max_concurrent_requests => "maxConcurrentRequests",
+        interactive_workers => "interactiveWorkers",
+        background_workers => "backgroundWorkers",
+        background_queue => "backgroundQueue",
+        rate_limit => "rateLimit",
+        timeouts => "timeouts",
+        auto_save => "autoSave",
+        history => "history",
+        policy => "policy",
+        faults => "faults",
+        telemetry => "telemetry",
+        request_log => "requestLog",
+        index => "index",
+    ];
+    fields.push(("workspaces", differs(&paths(running), &paths(config))));
+    fields
+}
+
+/// Compared by their debug output, which every config type has.
+fn differs<T: Debug>(a: &T, b: &T) -> bool {
+    format!("{a:?}") != format!("{b:?}")
+}
+
+fn changed(fields: &[(&'static str, bool)]) -> Vec<&'static str> {
+    fields
+        .iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Reloads on every `SIGHUP`, as daemons conventionally do.
+#[cfg(unix)]
+pub fn reload_on_hangup(state: crate::state::SharedState) {
+    use tokio::signal::unix::{SignalKind, signal};
+    tokio::spawn(async move {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                warn!(error = %e, "Cannot reload the configuration on SIGHUP");
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            info!("SIGHUP received, reloading the configuration");
+            let reloading = std::sync::Arc::clone(&state);
+            // Failures are logged by `reload`.
+            let _ =
+                tokio::task::spawn_blocking(move || reloading.reloader.reload(&reloading)).await;
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn reload_on_hangup(_state: crate::state::SharedState) {}
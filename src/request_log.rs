@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard},
+    time::Instant,
+};
+
+use crate::{clock, config::RequestLogConfig, rpc::request::JsonRpcResponse};
+
+/// Left out of the log, so reading it does not push out what it shows.
+const DEBUG_METHOD: &str = "debug/recentRequests";
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggedRequest {
+    pub correlation_id: String,
+    pub connection_id: u64,
+    /// Unix seconds.
+    pub time: u64,
+    /// `None` if the message was not a request at all.
+    pub method: Option<String>,
+    /// The request as received, cut to `maxBodyBytes`.
+    pub request: String,
+    pub request_bytes: usize,
+    /// The response as sent, likewise cut; `None` until it is sent, and
+    /// for notifications.
+    pub response: Option<String>,
+    pub response_bytes: Option<usize>,
+    /// Code of the error the request was answered with.
+    pub error_code: Option<i32>,
+    pub duration_ms: Option<u64>,
+    #[serde(skip)]
+    received_at: Instant,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    method: Option<String>,
+}
+
+/// The last requests and their responses, bodies truncated, for
+/// diagnosing a client in the field without raising the log level.
+pub struct RequestLog {
+    entries: Mutex<VecDeque<LoggedRequest>>,
+    config: RequestLogConfig,
+}
+
+impl RequestLog {
+    pub fn new(config: &RequestLogConfig) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(config.entries)),
+            config: config.clone(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<LoggedRequest>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn received(&self, correlation_id: &str, connection_id: u64, text: &str) {
+        if self.config.entries == 0 {
+            return;
+        }
+        let method = serde_json::from_str::<Envelope>(text)
+            .ok()
+            .and_then(|envelope| envelope.method);
+        if method.as_deref() == Some(DEBUG_METHOD) {
+            return;
+        }
+        let entry = LoggedRequest {
+            correlation_id: correlation_id.to_string(),
+            connection_id,
+            time: clock::unix_secs(),
+            method,
+            request: truncate(text, self.config.max_body_bytes),
+            request_bytes: text.len(),
+            response: None,
+            response_bytes: None,
+            error_code: None,
+            duration_ms: None,
+            received_at: clock::now(),
+        };
+        let mut entries = self.lock();
+        if entries.len() == self.config.entries {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Fills in the response to the request it answers, found by its
+    /// correlation id.
+    pub fn answered(&self, response: &JsonRpcResponse, text: &str) {
+        let Some(correlation_id) = &response.correlation_id else {
+            return;
+        };
+        let mut entries = self.lock();
+        let Some(entry) = entries
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.correlation_id == *correlation_id)
+        else {
+            return;
+        };
+        entry.response = Some(truncate(text, self.config.max_body_bytes));
+        entry.response_bytes = Some(text.len());
+        entry.error_code = response.error.as_ref().map(|error| error.code);
+        entry.duration_ms = Some(entry.received_at.elapsed().as_millis() as u64);
+    }
+
+    /// Newest first, optionally only those of one connection or method.
+    pub fn recent(
+        &self,
+        connection_id: Option<u64>,
+        method: Option<&str>,
+        limit: usize,
+    ) -> Vec<LoggedRequest> {
+        self.lock()
+            .iter()
+            .rev()
+            .filter(|entry| connection_id.is_none_or(|id| entry.connection_id == id))
+            .filter(|entry| method.is_none_or(|method| entry.method.as_deref() == Some(method)))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+fn truncate(text: &str, limit: usize) -> String {
+    if text.len() <= limit {
+        return text.to_string();
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &text[..end])
+}
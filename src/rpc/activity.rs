@@ -0,0 +1,37 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{debug, info_span};
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+struct ListActivityParams {
+    /// Only events with a larger id are returned.
+    #[serde(default)]
+    since: u64,
+    /// Kind prefix such as `git` or `task.exited`.
+    kind: Option<String>,
+    limit: Option<usize>,
+}
+
+pub fn handle_list(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("activity_list_operation");
+    let _enter = span.enter();
+
+    let params: ListActivityParams = parse_params(params)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let events = state
+        .activity
+        .list(params.since, params.kind.as_deref(), limit);
+    debug!(
+        since = params.since,
+        events = events.len(),
+        "Listing activity"
+    );
+    Ok(json!({ "events": events }))
+}
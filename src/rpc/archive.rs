@@ -0,0 +1,305 @@
+//! `exportZip` and `importZip`: whole directories in and out of the
+//! workspace as zip archives, carried as blobs.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, info_span, warn};
+
+use super::cancel::CancelToken;
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{
+    archive, config::Access, protected::audit_forced, sandbox::normalize, state::AppState,
+};
+
+/// Most entries an imported archive may hold.
+const MAX_ENTRIES: usize = 100_000;
+
+#[derive(Deserialize)]
+struct ExportZipParams {
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct ImportZipParams {
+    /// Directory to extract into, created if missing.
+    path: String,
+    /// The archive, stored with `blob/put`.
+    hash: String,
+    /// Replace files that already exist instead of skipping them.
+    #[serde(default)]
+    overwrite: bool,
+    #[serde(default)]
+    force: bool,
+    /// Report what would be extracted without writing anything.
+    #[serde(default, rename = "dryRun")]
+    dry_run: bool,
+}
+
+/// Zips the directory at `path`, honouring ignore files and leaving out
+/// `.git` and the server's data directory. Entries are named relative to
+/// the directory. The archive is stored as a blob, read in chunks with
+/// `blob/get` or streamed from the one-hour `/download/<token>` link.
+pub fn handle_export_zip(
+    state: &AppState,
+    params: Value,
+    cancel: &CancelToken,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("export_zip_operation");
+    let _enter = span.enter();
+
+    let params: ExportZipParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    if !path.exists() {
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
+    }
+    if !path.is_dir() {
+        return Err(HandlerError::InvalidParams(format!(
+            "{} is not a directory",
+            params.path
+        )));
+    }
+    let files = archive::workspace_files(path, &state.config.data_path());
+    cancel.check()?;
+    let (hash, size) = state
+        .blobs
+        .put_with(|file| archive::write_zip(file, path, &files))
+        .map_err(HandlerError::IoError)?;
+
+    let name = fs::canonicalize(path)
+        .ok()
+        .and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "workspace".to_string());
+    let (token, download) = state
+        .downloads
+        .create(hash.clone(), format!("{name}.zip"), "application/zip")
+        .map_err(|e| HandlerError::IoError(io::Error::other(e)))?;
+    info!(path = %params.path, files = files.len(), size, "Directory zipped");
+    Ok(json!({
+        "path": params.path,
+        "url": format!("/download/{token}"),
+        "fileName": download.file_name,
+        "hash": hash,
+        "size": size,
+        "files": files.len(),
+        "expiresAt": download.expires_at,
+    }))
+}
+
+/// Extracts a zip archive into the directory at `path`. An archive with
+/// an entry that would land outside it (absolute, or climbing with `..`)
+/// is refused before anything is written. Symlink entries, entries the
+/// permissions deny or that land in the server's data directory, existing
+/// files without `overwrite` and protected files without `force` are
+/// skipped and reported. Uncompressed contents count against the upload
+/// limit.
+pub fn handle_import_zip(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+    cancel: &CancelToken,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("import_zip_operation");
+    let _enter = span.enter();
+
+    let params: ImportZipParams = parse_params(params)?;
+    let target = Path::new(&params.path);
+    if target.exists() && !target.is_dir() {
+        return Err(HandlerError::InvalidParams(format!(
+            "{} is not a directory",
+            params.path
+        )));
+    }
+    let file = state
+        .blobs
+        .open(&params.hash)
+        .map_err(|e| HandlerError::BlobError(e.to_string()))?;
+    let mut zip = zip::ZipArchive::new(file)
+        .map_err(|e| HandlerError::InvalidParams(format!("Not a zip archive: {e}")))?;
+    if zip.len() > MAX_ENTRIES {
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "Archive has {} entries, more than {MAX_ENTRIES}",
+            zip.len()
+        )));
+    }
+
+    // Every name is checked, and the declared sizes added up, first.
+    let limit = state.limits().max_upload_bytes;
+    let mut entries: Vec<Option<PathBuf>> = Vec::with_capacity(zip.len());
+    let mut declared = 0u64;
+    for index in 0..zip.len() {
+        let entry = zip.by_index_raw(index).map_err(zip_error)?;
+        let Some(relative) = entry.enclosed_name() else {
+            return Err(HandlerError::InvalidParams(format!(
+                "Archive entry {} leaves the target directory",
+                entry.name()
+            )));
+        };
+        declared = declared.saturating_add(entry.size());
+        entries.push((!entry.is_symlink()).then_some(relative));
+    }
+    if declared > limit {
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "Archive expands to {declared} bytes, more than the {limit} byte upload limit"
+        )));
+    }
+
+    let data_path = state.config.data_path();
+    let data_paths: Vec<PathBuf> = [std::path::absolute(&data_path), data_path.canonicalize()]
+        .into_iter()
+        .flatten()
+        .collect();
+    let mut files = 0;
+    let mut directories = 0;
+    let mut written = 0u64;
+    let mut skipped = Vec::new();
+    for (index, relative) in entries.into_iter().enumerate() {
+        cancel.check()?;
+        let mut entry = zip.by_index(index).map_err(zip_error)?;
+        let Some(relative) = relative else {
+            skipped.push(json!({ "name": entry.name(), "reason": "symlink" }));
+            continue;
+        };
+        let dest = target.join(&relative);
+        // Directories already in the workspace may be symlinks.
+        state.sandbox.check(&dest)?;
+        let name = relative.to_string_lossy().into_owned();
+        if let Some(reason) = refusal(state, connection, &dest, &data_paths) {
+            skipped.push(json!({ "name": name, "reason": reason }));
+            continue;
+        }
+        if entry.is_dir() {
+            if !params.dry_run {
+                fs::create_dir_all(&dest).map_err(HandlerError::IoError)?;
+            }
+            directories += 1;
+            continue;
+        }
+        if dest.is_dir() {
+            skipped.push(json!({ "name": name, "reason": "directory" }));
+            continue;
+        }
+        if dest.exists() {
+            if !params.overwrite {
+                skipped.push(json!({ "name": name, "reason": "exists" }));
+                continue;
+            }
+            if state.protected.is_protected(&dest) {
+                if !params.force {
+                    skipped.push(json!({ "name": name, "reason": "protected" }));
+                    continue;
+                }
+                audit_forced("importZip", &dest.to_string_lossy());
+            }
+        }
+        if params.dry_run {
+            // Sizes were checked against the limit above.
+            written += entry.size();
+            files += 1;
+            continue;
+        }
+        if dest.exists() {
+            if let Err(e) = state.history.snapshot(&dest) {
+                warn!(path = %dest.display(), error = %e, "Failed to keep previous version");
+            }
+        } else if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(HandlerError::IoError)?;
+        }
+
+        // Declared sizes can lie; stop at the limit whatever they say.
+        let mut out = fs::File::create(&dest).map_err(HandlerError::IoError)?;
+        let copied = io::copy(&mut (&mut entry).take(limit - written + 1), &mut out)
+            .map_err(HandlerError::IoError)?;
+        written += copied;
+        if written > limit {
+            drop(out);
+            let _ = fs::remove_file(&dest);
+            return Err(HandlerError::PayloadTooLarge(format!(
+                "Archive expands to more than the {limit} byte upload limit"
+            )));
+        }
+        debug!(name = %name, bytes = copied, "Extracted archive entry");
+        files += 1;
+    }
+
+    if params.dry_run {
+        return Ok(json!({
+            "dryRun": true,
+            "path": params.path,
+            "files": files,
+            "directories": directories,
+            "bytes": written,
+            "skipped": skipped,
+        }));
+    }
+    info!(
+        path = %params.path,
+        files,
+        directories,
+        bytes = written,
+        skipped = skipped.len(),
+        "Archive imported"
+    );
+    state.activity.record(
+        "archive.imported",
+        Some(connection.id),
+        json!({ "path": params.path, "files": files, "bytes": written }),
+    );
+    Ok(json!({
+        "path": params.path,
+        "files": files,
+        "directories": directories,
+        "bytes": written,
+        "skipped": skipped,
+    }))
+}
+
+/// Why an entry may not be written to `dest`, if it may not: it lands in
+/// the server's data directory, or the permissions of the server or the
+/// connection's key deny writing there.
+fn refusal(
+    state: &AppState,
+    connection: &ConnectionContext,
+    dest: &Path,
+    data_paths: &[PathBuf],
+) -> Option<&'static str> {
+    let absolute = normalize(&std::path::absolute(dest).unwrap_or_else(|_| dest.to_path_buf()));
+    if data_paths
+        .iter()
+        .any(|data_path| absolute.starts_with(data_path))
+    {
+        return Some("serverData");
+    }
+    let denied = state.permissions.check(dest, Access::Write).is_err()
+        || connection
+            .identity
+            .tenant
+            .as_ref()
+            .is_some_and(|tenant| tenant.check(dest, Access::Write).is_err());
+    denied.then_some("denied")
+}
+
+/// Total uncompressed size the archive stored as `hash` declares, for
+/// quota checks made before extracting. `None` if it cannot be read.
+pub fn expanded_size(state: &AppState, hash: &str) -> Option<u64> {
+    let file = state.blobs.open(hash).ok()?;
+    let mut zip = zip::ZipArchive::new(file).ok()?;
+    let mut total = 0u64;
+    for index in 0..zip.len().min(MAX_ENTRIES) {
+        total = total.saturating_add(zip.by_index_raw(index).ok()?.size());
+    }
+    Some(total)
+}
+
+fn zip_error(e: zip::result::ZipError) -> HandlerError {
+    HandlerError::InvalidParams(format!("Failed to read archive: {e}"))
+}
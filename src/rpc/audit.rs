@@ -0,0 +1,45 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use tracing::{debug, info_span};
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+struct QueryAuditParams {
+    /// Only entries with a larger id are returned.
+    #[serde(default)]
+    since: u64,
+    method: Option<String>,
+    /// File or directory the entries must name.
+    path: Option<PathBuf>,
+    limit: Option<usize>,
+}
+
+pub fn handle_query(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("audit_query_operation");
+    let _enter = span.enter();
+
+    let params: QueryAuditParams = parse_params(params)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let path = params
+        .path
+        .map(|path| std::path::absolute(&path).unwrap_or(path));
+    let entries = state.audit.query(
+        params.since,
+        params.method.as_deref(),
+        path.as_deref(),
+        limit,
+    );
+    debug!(
+        since = params.since,
+        entries = entries.len(),
+        "Querying audit log"
+    );
+    Ok(json!({ "entries": entries }))
+}
@@ -0,0 +1,116 @@
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{debug, info_span};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+
+/// Largest chunk returned by a single `blob/get`.
+const MAX_CHUNK_BYTES: usize = 1024 * 1024;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PutBlobParams {
+    /// Stores a workspace file directly instead of uploaded chunks.
+    path: Option<String>,
+    upload_id: Option<u64>,
+    /// Base64-encoded chunk.
+    #[serde(default)]
+    data: String,
+    /// Finalize the upload after appending this chunk.
+    #[serde(default)]
+    done: bool,
+}
+
+#[derive(Deserialize)]
+struct GetBlobParams {
+    hash: String,
+    #[serde(default)]
+    offset: u64,
+    length: Option<usize>,
+}
+
+fn blob_error(e: impl std::fmt::Display) -> HandlerError {
+    HandlerError::BlobError(e.to_string())
+}
+
+/// Streams content into the store. The first call (without `uploadId`)
+/// opens an upload; chunks are appended until a call with `done: true`
+/// returns the content hash.
+pub fn handle_put(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("blob_put_operation");
+    let _enter = span.enter();
+
+    let params: PutBlobParams = parse_params(params)?;
+    if let Some(path) = &params.path {
+        let path = std::path::Path::new(path);
+        if !path.is_file() {
+            return Err(HandlerError::FileNotFound(path.to_path_buf()));
+        }
+        let hash = state.blobs.put_file(path).map_err(HandlerError::IoError)?;
+        let size = state.blobs.size(&hash).map_err(blob_error)?;
+        return Ok(json!({ "hash": hash, "size": size }));
+    }
+
+    let chunk = BASE64
+        .decode(params.data.as_bytes())
+        .map_err(|e| HandlerError::InvalidParams(format!("Invalid base64 data: {e}")))?;
+
+    let upload_id = match params.upload_id {
+        Some(upload_id) => upload_id,
+        None => state
+            .blobs
+            .begin_upload(connection.id)
+            .map_err(blob_error)?,
+    };
+    let size = state
+        .blobs
+        .append(connection.id, upload_id, &chunk)
+        .map_err(blob_error)?;
+    debug!(
+        upload_id,
+        chunk_size = chunk.len(),
+        total_size = size,
+        "Blob chunk stored"
+    );
+
+    if !params.done {
+        return Ok(json!({ "uploadId": upload_id, "size": size }));
+    }
+    let (hash, size) = state
+        .blobs
+        .finish(connection.id, upload_id)
+        .map_err(blob_error)?;
+    Ok(json!({ "hash": hash, "size": size }))
+}
+
+pub fn handle_get(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("blob_get_operation");
+    let _enter = span.enter();
+
+    let params: GetBlobParams = parse_params(params)?;
+    let size = state.blobs.size(&params.hash).map_err(blob_error)?;
+    let length = params
+        .length
+        .unwrap_or(MAX_CHUNK_BYTES)
+        .min(MAX_CHUNK_BYTES);
+    let bytes = state
+        .blobs
+        .read_range(&params.hash, params.offset, length)
+        .map_err(blob_error)?;
+
+    let end = params.offset + bytes.len() as u64;
+    Ok(json!({
+        "data": BASE64.encode(&bytes),
+        "offset": params.offset,
+        "size": size,
+        "eof": end >= size,
+    }))
+}
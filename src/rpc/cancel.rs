@@ -0,0 +1,144 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    io::Read,
+    process::{Child, Output},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+use tracing::{debug, info};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+
+/// How often a cancellable child process is polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Cancellation flag for one request. Long-running handlers check it
+/// between units of work and bail out with `RequestCancelled`.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicUsize>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(1, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed) != 0
+    }
+
+    pub fn check(&self) -> Result<(), HandlerError> {
+        if self.is_cancelled() {
+            Err(HandlerError::RequestCancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The raw flag, in the form tree-sitter accepts.
+    pub fn flag(&self) -> &AtomicUsize {
+        &self.0
+    }
+}
+
+/// Tokens of a connection's in-flight requests, keyed by request id.
+#[derive(Default)]
+pub struct CancelRegistry {
+    tokens: Mutex<HashMap<String, CancelToken>>,
+}
+
+impl CancelRegistry {
+    /// Creates the token for a request. Requests without an id cannot be
+    /// cancelled, so their token is not registered.
+    pub fn register(&self, id: Option<&Value>) -> CancelToken {
+        let token = CancelToken::default();
+        if let Some(id) = id {
+            self.lock().insert(id.to_string(), token.clone());
+        }
+        token
+    }
+
+    pub fn finish(&self, id: Option<&Value>) {
+        if let Some(id) = id {
+            self.lock().remove(&id.to_string());
+        }
+    }
+
+    fn cancel(&self, id: &Value) -> bool {
+        match self.lock().get(&id.to_string()) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CancelToken>> {
+        self.tokens.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[derive(Deserialize)]
+struct CancelRequestParams {
+    id: Value,
+}
+
+/// Handles `$/cancelRequest`. Runs on the connection's reader rather than a
+/// request lane so it takes effect even when the lanes are saturated.
+/// Returns whether a matching in-flight request was found.
+pub fn handle_cancel_request(
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let params: CancelRequestParams = parse_params(params)?;
+    let found = connection.cancellations.cancel(&params.id);
+    if found {
+        info!(id = %params.id, "Request cancelled");
+    } else {
+        debug!(id = %params.id, "Cancel for unknown or finished request");
+    }
+    Ok(Value::Bool(found))
+}
+
+/// Waits for a child with piped stdout/stderr like `Child::wait_with_output`,
+/// killing it if the request is cancelled first.
+pub fn wait_with_output(mut child: Child, cancel: &CancelToken) -> Result<Output, HandlerError> {
+    fn drain(stream: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut stream) = stream {
+                let _ = stream.read_to_end(&mut buffer);
+            }
+            buffer
+        })
+    }
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(HandlerError::IoError)? {
+            break status;
+        }
+        if cancel.is_cancelled() {
+            debug!(pid = child.id(), "Killing child of cancelled request");
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(HandlerError::RequestCancelled);
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
@@ -0,0 +1,95 @@
+use serde_json::{Value, json};
+use tracing::{debug, info_span};
+
+use super::compression::Encoding;
+use super::dry_run::DRY_RUN_METHODS;
+use super::error::HandlerError;
+use super::handlers::MUTATING_METHODS;
+use super::initialize::PROTOCOL_VERSION;
+use crate::{
+    charset::Charset, clock, fault, state::AppState, syntax::GRAMMARS, ws::codec::Codec,
+    ws::lanes::Priority,
+};
+
+/// Methods that stop early when `$/cancelRequest` names them.
+const CANCELLABLE_METHODS: &[&str] = &[
+    "diskUsage",
+    "exportZip",
+    "extractText",
+    "formatDocument",
+    "git/blame",
+    "highlight",
+    "importZip",
+    "searchInFiles",
+    "snippet/run",
+    "spellCheck",
+    "table/read",
+];
+
+/// Handles `server/capabilities`: every method with its parameter schema
+/// plus the optional features this build and configuration offer. Unlike
+/// `initialize` it changes no session state, so it can be called any time.
+pub fn handle_capabilities(state: &AppState) -> Result<Value, HandlerError> {
+    let span = info_span!("server_capabilities_operation");
+    let _enter = span.enter();
+
+    let methods: Vec<Value> = state
+        .methods
+        .handlers()
+        .map(|handler| {
+            let method = handler.name();
+            json!({
+                "name": method,
+                "params": handler.params_schema(),
+                "priority": match Priority::for_method(method) {
+                    Priority::Interactive => "interactive",
+                    Priority::Background => "background",
+                },
+                "cancellable": CANCELLABLE_METHODS.contains(&method),
+                "mutating": MUTATING_METHODS.contains(&method),
+                "dryRun": DRY_RUN_METHODS.contains(&method),
+            })
+        })
+        .collect();
+    debug!(methods = methods.len(), "Listing server capabilities");
+
+    Ok(json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "methods": methods,
+        "features": features(state),
+    }))
+}
+
+/// The optional features this build and configuration offer, as listed
+/// by `server/capabilities` and `server/info`.
+pub fn features(state: &AppState) -> Value {
+    let config = &state.config;
+    json!({
+        "compression": Encoding::ALL,
+        "codecs": Codec::ALL,
+        "encodings": Charset::ALL,
+        "flowControl": true,
+        "workspaceUris": true,
+        "rawFiles": true,
+        "thumbnails": ["image/png"],
+        "maxUploadBytes": state.limits().max_upload_bytes,
+        "sessionResume": config.session_grace_secs > 0,
+        "deterministic": clock::DETERMINISTIC,
+        "faultInjection": fault::ENABLED,
+        "recording": config.record_dir.is_some(),
+        "auditLog": config.audit_log.is_some(),
+        "readOnly": config.read_only,
+        "rawPaths": config.raw_paths,
+        "lineEndings": config.line_endings,
+        "autoSave": config.auto_save.enabled(),
+        "fileHistory": config.history.enabled,
+        "grammars": GRAMMARS.iter().map(|grammar| grammar.id).collect::<Vec<_>>(),
+        "formatters": config.formatters.keys().collect::<Vec<_>>(),
+        "languageServers": config.language_servers.keys().collect::<Vec<_>>(),
+        "debugAdapters": config.debug_adapters.keys().collect::<Vec<_>>(),
+        "snippets": config.snippets.interpreters.keys().collect::<Vec<_>>(),
+        "tasks": config.tasks.keys().collect::<Vec<_>>(),
+        "webhooks": config.webhooks.keys().collect::<Vec<_>>(),
+        "workspaces": state.uris.roots().map(|(name, ..)| name).collect::<Vec<_>>(),
+    })
+}
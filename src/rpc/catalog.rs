@@ -0,0 +1,193 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use tracing::{debug, info_span};
+
+use super::error::*;
+
+/// One error code the server can return. Names are stable identifiers;
+/// clients key their own (or localized) messages off them rather than the
+/// English `message` of a response.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct ErrorInfo {
+    pub code: i32,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+const fn entry(code: i32, name: &'static str, description: &'static str) -> ErrorInfo {
+    ErrorInfo {
+        code,
+        name,
+        description,
+    }
+}
+
+/// Every error code, in code order within the standard and application
+/// ranges. Add new codes here when they are added to `error.rs`.
+pub const ERROR_CATALOG: &[ErrorInfo] = &[
+    entry(
+        PARSE_ERROR_CODE,
+        "PARSE_ERROR",
+        "The message is not valid JSON.",
+    ),
+    entry(
+        INVALID_REQUEST_CODE,
+        "INVALID_REQUEST",
+        "The message is not a valid JSON-RPC request.",
+    ),
+    entry(
+        METHOD_NOT_FOUND_CODE,
+        "METHOD_NOT_FOUND",
+        "The server has no method with this name.",
+    ),
+    entry(
+        INVALID_PARAMS_CODE,
+        "INVALID_PARAMS",
+        "The request parameters are missing or malformed.",
+    ),
+    entry(
+        INTERNAL_ERROR_CODE,
+        "INTERNAL_ERROR",
+        "The server failed while handling the request.",
+    ),
+    entry(
+        FILE_NOT_FOUND_CODE,
+        "FILE_NOT_FOUND",
+        "The file does not exist.",
+    ),
+    entry(IO_ERROR_CODE, "IO", "A filesystem operation failed."),
+    entry(
+        DIRECTORY_ERROR_CODE,
+        "DIRECTORY",
+        "The directory does not exist or is not a directory.",
+    ),
+    entry(
+        PROTECTED_PATH_CODE,
+        "PROTECTED_PATH",
+        "The path is protected; retry with force: true to modify it.",
+    ),
+    entry(GIT_ERROR_CODE, "GIT_ERROR", "A git command failed."),
+    entry(
+        DIRTY_WORKSPACE_CODE,
+        "DIRTY_WORKSPACE",
+        "Local changes would be overwritten by the operation.",
+    ),
+    entry(
+        TERMINAL_ERROR_CODE,
+        "TERMINAL_ERROR",
+        "The terminal could not be created or does not exist.",
+    ),
+    entry(
+        TASK_ERROR_CODE,
+        "TASK_ERROR",
+        "The task is unknown, failed to start, or the task limit was reached.",
+    ),
+    entry(
+        LSP_ERROR_CODE,
+        "LSP_ERROR",
+        "The language server could not be started or failed.",
+    ),
+    entry(
+        FORMAT_ERROR_CODE,
+        "FORMAT_ERROR",
+        "The formatter failed or is not configured.",
+    ),
+    entry(
+        BLOB_ERROR_CODE,
+        "BLOB_ERROR",
+        "The blob or upload does not exist or its content does not match.",
+    ),
+    entry(
+        SYNTAX_ERROR_CODE,
+        "SYNTAX_ERROR",
+        "No grammar is available or the document could not be parsed.",
+    ),
+    entry(
+        RATE_LIMITED_CODE,
+        "RATE_LIMITED",
+        "Too many requests; slow down and retry.",
+    ),
+    entry(
+        PAYLOAD_TOO_LARGE_CODE,
+        "PAYLOAD_TOO_LARGE",
+        "The message or file exceeds a configured size limit.",
+    ),
+    entry(
+        TIMEOUT_CODE,
+        "TIMEOUT",
+        "The request did not finish within its time limit.",
+    ),
+    entry(
+        UNSUPPORTED_PROTOCOL_CODE,
+        "UNSUPPORTED_PROTOCOL",
+        "The client's protocol major version is not supported by this server.",
+    ),
+    entry(
+        LIMIT_EXCEEDED_CODE,
+        "LIMIT_EXCEEDED",
+        "A usage limit of the connection's tier was reached.",
+    ),
+    entry(
+        SYMLINK_REFUSED_CODE,
+        "SYMLINK_REFUSED",
+        "The path resolves through a symlink leaving the workspace, or through any symlink when followSymlinks is off.",
+    ),
+    entry(
+        READ_ONLY_CODE,
+        "READ_ONLY",
+        "The server runs read-only and the method would modify the workspace.",
+    ),
+    entry(
+        PERMISSION_DENIED_CODE,
+        "PERMISSION_DENIED",
+        "A permission rule does not allow reading or writing the path.",
+    ),
+    entry(
+        CONFIG_ERROR_CODE,
+        "CONFIG_ERROR",
+        "The config file could not be read or is invalid; the running configuration is unchanged.",
+    ),
+    entry(
+        FILE_LOCKED_CODE,
+        "FILE_LOCKED",
+        "Another connection holds the advisory lock on the file.",
+    ),
+    entry(
+        WRITE_CONFLICT_CODE,
+        "WRITE_CONFLICT",
+        "The file changed on disk since the client read it; pass merge: true to merge.",
+    ),
+    entry(
+        SNIPPET_ERROR_CODE,
+        "SNIPPET_ERROR",
+        "The interpreter for the snippet could not be started.",
+    ),
+    entry(
+        DEBUG_ADAPTER_ERROR_CODE,
+        "DEBUG_ADAPTER_ERROR",
+        "The debug adapter could not be started, failed the request or exited.",
+    ),
+    entry(
+        CONFINED_CODE,
+        "CONFINED",
+        "The connection's key confines it to a workspace, and the method reaches beyond it.",
+    ),
+    entry(
+        SERVER_BUSY_CODE,
+        "SERVER_BUSY",
+        "Too many background requests are waiting to run; retry later.",
+    ),
+    entry(
+        REQUEST_CANCELLED_CODE,
+        "REQUEST_CANCELLED",
+        "The request was cancelled by the client.",
+    ),
+];
+
+pub fn handle_error_catalog() -> Result<Value, HandlerError> {
+    let span = info_span!("error_catalog_operation");
+    let _enter = span.enter();
+
+    debug!(count = ERROR_CATALOG.len(), "Listing error catalog");
+    Ok(json!({ "errors": ERROR_CATALOG }))
+}
@@ -0,0 +1,81 @@
+//! Application-level compression of large results, negotiated at
+//! `initialize`. The WebSocket stack cannot negotiate permessage-deflate,
+//! so this is the only way to shrink multi-megabyte reads and search
+//! results on the wire.
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use flate2::{Compression, write::GzEncoder};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::io::Write;
+use tracing::{debug, warn};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    pub const ALL: &[Encoding] = &[Encoding::Zstd, Encoding::Gzip];
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "zstd" => Some(Self::Zstd),
+            "gzip" => Some(Self::Gzip),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Gzip => "gzip",
+        }
+    }
+}
+
+/// Picks the first encoding the client offered that the server supports.
+pub fn negotiate(offered: &[String]) -> Option<Encoding> {
+    offered.iter().find_map(|name| Encoding::parse(name))
+}
+
+/// Replaces results whose JSON exceeds `threshold` bytes with
+/// `{"$compressed": {"encoding", "data"}}`, where `data` is the base64 of the
+/// compressed JSON text. Small results stay plain since compressing them
+/// costs more CPU than it saves bandwidth.
+pub fn compress_result(encoding: Encoding, threshold: usize, result: Value) -> Value {
+    let text = match serde_json::to_vec(&result) {
+        Ok(text) if text.len() > threshold => text,
+        _ => return result,
+    };
+
+    let compressed = match encoding {
+        Encoding::Zstd => zstd::encode_all(text.as_slice(), 3),
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(&text).and_then(|_| encoder.finish())
+        }
+    };
+    match compressed {
+        Ok(compressed) => {
+            debug!(
+                encoding = encoding.name(),
+                original_size = text.len(),
+                compressed_size = compressed.len(),
+                "Compressed result"
+            );
+            json!({
+                "$compressed": {
+                    "encoding": encoding,
+                    "data": BASE64.encode(compressed),
+                }
+            })
+        }
+        Err(e) => {
+            warn!(encoding = encoding.name(), error = %e, "Failed to compress result");
+            result
+        }
+    }
+}
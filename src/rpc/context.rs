@@ -0,0 +1,382 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+use tokio::sync::{Notify, mpsc::UnboundedSender};
+use tracing::{debug, error, warn};
+
+use super::cancel::CancelRegistry;
+use super::compression::Encoding;
+use super::locale::Locale;
+use super::request::JsonRpcNotification;
+use super::stats::ConnectionStats;
+use crate::{flow::FlowControl, policy::Identity, uri::WorkspaceUris, ws::codec::Codec};
+
+/// Where a connection's messages go: the current socket's writer, if one
+/// is attached, and the replay buffer of a resumable session.
+struct Outbox {
+    sender: Option<UnboundedSender<String>>,
+    replay: Option<ReplayBuffer>,
+}
+
+/// Numbered copies of the latest messages, so a resumed session can be
+/// sent whatever it missed.
+struct ReplayBuffer {
+    next_seq: u64,
+    limit: usize,
+    messages: VecDeque<(u64, String)>,
+    /// Sequence number of the response to each request id seen.
+    responses: HashMap<String, Option<u64>>,
+    /// Request ids in arrival order, for evicting `responses`.
+    requests: VecDeque<String>,
+}
+
+/// What to do with a request that arrives on a resumable session.
+pub enum Replay {
+    /// Not seen before.
+    Run,
+    /// Still running; its response will be sent when it finishes.
+    Pending,
+    /// Already answered; this is the buffered response.
+    Answered(String),
+    /// Answered too long ago for the response to still be buffered.
+    Expired,
+}
+
+/// Bytes queued for a connection's writer and not yet sent, so a client
+/// that stops reading cannot make the server buffer without bound.
+pub struct Backlog {
+    bytes: AtomicUsize,
+    /// Zero means no limit.
+    limit: usize,
+    overflowed: Notify,
+}
+
+impl Backlog {
+    fn new(limit: usize) -> Self {
+        Self {
+            bytes: AtomicUsize::new(0),
+            limit,
+            overflowed: Notify::new(),
+        }
+    }
+
+    /// Counts a queued message, or returns false if it would exceed the
+    /// limit.
+    fn push(&self, len: usize) -> bool {
+        let previous = self.bytes.fetch_add(len, Ordering::Relaxed);
+        if self.limit > 0 && previous + len > self.limit {
+            self.bytes.fetch_sub(len, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Called by the writer for each message it takes off the queue.
+    pub fn sent(&self, len: usize) {
+        // Saturating, since a superseded writer may still be draining
+        // messages counted before `attach` reset the count.
+        let _ = self
+            .bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bytes| {
+                Some(bytes.saturating_sub(len))
+            });
+    }
+
+    /// Resolves once a message has been refused for exceeding the limit.
+    pub async fn overflowed(&self) {
+        self.overflowed.notified().await
+    }
+}
+
+/// Sends serialized messages to a connection's writer task. Cloneable so
+/// background workers (terminals, tasks) can push notifications after the
+/// originating request has completed.
+#[derive(Clone)]
+pub struct Notifier {
+    outbox: Arc<Mutex<Outbox>>,
+    /// Set once the client opts into `workspace://` URIs in payloads.
+    uris: Arc<Mutex<Option<Arc<WorkspaceUris>>>>,
+    backlog: Arc<Backlog>,
+}
+
+impl Notifier {
+    /// `max_backlog` bounds the bytes waiting in `outbound`; zero means no
+    /// bound.
+    pub fn new(outbound: UnboundedSender<String>, max_backlog: usize) -> Self {
+        Self {
+            outbox: Arc::new(Mutex::new(Outbox {
+                sender: Some(outbound),
+                replay: None,
+            })),
+            uris: Arc::default(),
+            backlog: Arc::new(Backlog::new(max_backlog)),
+        }
+    }
+
+    pub fn backlog(&self) -> Arc<Backlog> {
+        Arc::clone(&self.backlog)
+    }
+
+    /// Queues `text` for the writer unless that would exceed the backlog
+    /// limit, in which case the socket is dropped as if it had closed.
+    fn queue(&self, outbox: &mut Outbox, text: String) -> bool {
+        let Some(sender) = &outbox.sender else {
+            return false;
+        };
+        let len = text.len();
+        if !self.backlog.push(len) {
+            warn!(
+                queued = self.backlog.bytes.load(Ordering::Relaxed),
+                limit = self.backlog.limit,
+                "Client is not reading; dropping its socket"
+            );
+            outbox.sender = None;
+            self.backlog.overflowed.notify_one();
+            return false;
+        }
+        if sender.send(text).is_ok() {
+            true
+        } else {
+            self.backlog.sent(len);
+            false
+        }
+    }
+
+    fn outbox(&self) -> MutexGuard<'_, Outbox> {
+        self.outbox.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts numbering messages with a top-level `seq` member and keeping
+    /// the last `limit` of them for [`Notifier::attach`].
+    pub fn enable_replay(&self, limit: usize) {
+        let mut outbox = self.outbox();
+        if outbox.replay.is_none() {
+            outbox.replay = Some(ReplayBuffer {
+                next_seq: 1,
+                limit: limit.max(1),
+                messages: VecDeque::new(),
+                responses: HashMap::new(),
+                requests: VecDeque::new(),
+            });
+        }
+    }
+
+    /// Stops delivering to the current socket. Resumable sessions keep
+    /// buffering; anything else is dropped from now on.
+    pub fn detach(&self) {
+        self.outbox().sender = None;
+    }
+
+    /// Delivers to a new socket, first replaying every buffered message
+    /// after `last_seq`. Returns how many were replayed, and whether that
+    /// was all the client missed; older messages may have been evicted.
+    pub fn attach(&self, sender: UnboundedSender<String>, last_seq: u64) -> (usize, bool) {
+        let mut outbox = self.outbox();
+        let mut replayed = 0;
+        let mut complete = true;
+        if let Some(replay) = outbox.replay.as_mut() {
+            let oldest = replay
+                .messages
+                .front()
+                .map_or(replay.next_seq, |(seq, _)| *seq);
+            complete = oldest <= last_seq + 1;
+        }
+        // Whatever the old socket had queued went with it.
+        self.backlog.bytes.store(0, Ordering::Relaxed);
+        outbox.sender = Some(sender);
+        let missed: Vec<String> = outbox
+            .replay
+            .iter()
+            .flat_map(|replay| replay.messages.iter())
+            .filter(|(seq, _)| *seq > last_seq)
+            .map(|(_, text)| text.clone())
+            .collect();
+        for text in missed {
+            if self.queue(&mut outbox, text) {
+                replayed += 1;
+            }
+        }
+        (replayed, complete)
+    }
+
+    /// Sends an already numbered message again, outside the sequence.
+    pub fn resend(&self, text: String) -> bool {
+        let mut outbox = self.outbox();
+        self.queue(&mut outbox, text)
+    }
+
+    /// Drops the replay buffer and the socket, so every later send fails.
+    pub fn close(&self) {
+        let mut outbox = self.outbox();
+        outbox.sender = None;
+        outbox.replay = None;
+    }
+
+    /// Notes a request arriving on a resumable session and decides whether
+    /// it still needs to run, so requests resent after a reconnect are not
+    /// run twice. Always [`Replay::Run`] for other connections.
+    pub fn check_replay(&self, id: &Value) -> Replay {
+        let mut outbox = self.outbox();
+        let Some(replay) = outbox.replay.as_mut() else {
+            return Replay::Run;
+        };
+        let key = id.to_string();
+        match replay.responses.get(&key) {
+            None => {
+                if replay.requests.len() == replay.limit
+                    && let Some(oldest) = replay.requests.pop_front()
+                {
+                    replay.responses.remove(&oldest);
+                }
+                replay.requests.push_back(key.clone());
+                replay.responses.insert(key, None);
+                Replay::Run
+            }
+            Some(None) => Replay::Pending,
+            Some(Some(seq)) => replay
+                .messages
+                .iter()
+                .find(|(buffered, _)| buffered == seq)
+                .map_or(Replay::Expired, |(_, text)| Replay::Answered(text.clone())),
+        }
+    }
+
+    pub fn set_uris(&self, uris: Option<Arc<WorkspaceUris>>) {
+        *self.uris.lock().unwrap_or_else(|e| e.into_inner()) = uris;
+    }
+
+    /// The URI rewriter for outgoing payloads, if the client asked for one.
+    pub fn uris(&self) -> Option<Arc<WorkspaceUris>> {
+        self.uris.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Queues a raw message, returning false if the connection is gone.
+    /// A resumable session that is waiting to be resumed still accepts
+    /// messages, buffering them for replay.
+    pub fn send(&self, text: String) -> bool {
+        self.send_message(None, text)
+    }
+
+    /// Like [`Notifier::send`], for the response to request `id`.
+    pub fn send_response(&self, id: &Value, text: String) -> bool {
+        self.send_message(Some(id), text)
+    }
+
+    fn send_message(&self, id: Option<&Value>, text: String) -> bool {
+        let mut outbox = self.outbox();
+        let text = match outbox.replay.as_mut() {
+            Some(replay) => {
+                let seq = replay.next_seq;
+                replay.next_seq += 1;
+                let text = match text.strip_prefix('{') {
+                    Some(rest) => format!("{{\"seq\":{seq},{rest}"),
+                    None => text,
+                };
+                if replay.messages.len() == replay.limit {
+                    replay.messages.pop_front();
+                }
+                replay.messages.push_back((seq, text.clone()));
+                if let Some(id) = id
+                    && let Some(response) = replay.responses.get_mut(&id.to_string())
+                {
+                    *response = Some(seq);
+                }
+                text
+            }
+            None => text,
+        };
+        let resumable = outbox.replay.is_some();
+        self.queue(&mut outbox, text) || resumable
+    }
+
+    pub fn notify(&self, method: &str, params: impl Serialize) -> bool {
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params: match serde_json::to_value(params) {
+                Ok(mut params) => {
+                    if let Some(uris) = self.uris() {
+                        uris.rewrite_payload(&mut params);
+                    }
+                    params
+                }
+                Err(e) => {
+                    error!(method = %method, error = %e, "Failed to serialize notification");
+                    return false;
+                }
+            },
+        };
+        match serde_json::to_string(&notification) {
+            Ok(text) => {
+                let sent = self.send(text);
+                if !sent {
+                    debug!(method = %method, "Dropping notification for closed connection");
+                }
+                sent
+            }
+            Err(e) => {
+                error!(method = %method, error = %e, "Failed to serialize notification");
+                false
+            }
+        }
+    }
+}
+
+/// Options the client negotiated through `initialize`; defaults apply
+/// until it is called.
+#[derive(Default)]
+pub struct ClientSession {
+    /// Protocol version the client declared, once it has initialized.
+    pub protocol_version: Option<String>,
+    pub compression: Option<Encoding>,
+    /// Framing of outgoing messages, starting with the response to the
+    /// `initialize` that negotiated it.
+    pub codec: Codec,
+    /// Language for server-generated error messages.
+    pub locale: Locale,
+    /// Token a reconnecting client presents to resume this session.
+    pub resume_token: Option<String>,
+}
+
+/// Per-connection information made available to every handler.
+pub struct ConnectionContext {
+    pub id: u64,
+    pub notifier: Notifier,
+    pub flow: Arc<FlowControl>,
+    pub stats: Arc<ConnectionStats>,
+    pub cancellations: CancelRegistry,
+    /// Who the connection authenticated as; decides its usage limits.
+    pub identity: Identity,
+    /// Background requests in flight, for `maxSearchConcurrency`.
+    pub active_searches: AtomicUsize,
+    /// Woken when a reconnecting client resumes this session, so the socket
+    /// it replaces lets go.
+    pub superseded: Arc<Notify>,
+    session: Mutex<ClientSession>,
+}
+
+impl ConnectionContext {
+    pub fn new(id: u64, notifier: Notifier, identity: Identity) -> Self {
+        Self {
+            id,
+            notifier,
+            flow: Arc::default(),
+            stats: Arc::default(),
+            cancellations: CancelRegistry::default(),
+            identity,
+            active_searches: AtomicUsize::new(0),
+            superseded: Arc::default(),
+            session: Mutex::default(),
+        }
+    }
+
+    pub fn session(&self) -> MutexGuard<'_, ClientSession> {
+        self.session.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
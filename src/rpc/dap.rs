@@ -0,0 +1,142 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::info_span;
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use super::registry::{Call, Handler};
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+struct DapStartParams {
+    adapter: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DapRequestParams {
+    session_id: u64,
+    command: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DapRespondParams {
+    session_id: u64,
+    request_seq: i64,
+    #[serde(default = "default_success")]
+    success: bool,
+    #[serde(default)]
+    body: Value,
+    message: Option<String>,
+}
+
+fn default_success() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DapStopParams {
+    session_id: u64,
+}
+
+/// `dap/start`: launches the configured adapter. Its events arrive as
+/// `dap/event`, its requests to the client as `dap/reverseRequest`, and
+/// `dap/exited` says when it is gone.
+pub fn handle_start(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("dap_start_operation");
+    let _enter = span.enter();
+
+    let params: DapStartParams = parse_params(params)?;
+    let definition = state
+        .config
+        .debug_adapters
+        .get(&params.adapter)
+        .ok_or_else(|| {
+            HandlerError::InvalidParams(format!(
+                "No debug adapter configured for {}",
+                params.adapter
+            ))
+        })?;
+    let session_id = state
+        .dap
+        .start(
+            connection.id,
+            &params.adapter,
+            definition,
+            &state.config.root,
+            &connection.notifier,
+        )
+        .map_err(HandlerError::DebugAdapterError)?;
+    Ok(json!({ "sessionId": session_id, "adapter": params.adapter }))
+}
+
+/// `dap/request`: forwards a DAP request, answered with the response's
+/// `body` once the adapter sends it.
+pub struct RequestHandler;
+
+impl Handler for RequestHandler {
+    fn name(&self) -> &'static str {
+        "dap/request"
+    }
+
+    fn execute(&self, call: Call<'_>) -> Result<Option<Value>, HandlerError> {
+        let span = info_span!("dap_request_operation");
+        let _enter = span.enter();
+
+        let params: DapRequestParams = parse_params(call.params)?;
+        call.state
+            .dap
+            .request(
+                call.connection.id,
+                params.session_id,
+                &params.command,
+                params.arguments,
+                call.id.clone(),
+                call.correlation_id,
+            )
+            .map_err(HandlerError::DebugAdapterError)?;
+        Ok(None)
+    }
+}
+
+pub fn handle_respond(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let params: DapRespondParams = parse_params(params)?;
+    state
+        .dap
+        .respond(
+            connection.id,
+            params.session_id,
+            params.request_seq,
+            params.success,
+            params.body,
+            params.message,
+        )
+        .map_err(HandlerError::DebugAdapterError)?;
+    Ok(Value::Bool(true))
+}
+
+pub fn handle_stop(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let params: DapStopParams = parse_params(params)?;
+    state
+        .dap
+        .stop(connection.id, params.session_id)
+        .map_err(HandlerError::DebugAdapterError)?;
+    Ok(Value::Bool(true))
+}
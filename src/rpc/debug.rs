@@ -0,0 +1,32 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{debug, info_span};
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecentRequestsParams {
+    connection_id: Option<u64>,
+    method: Option<String>,
+    limit: Option<usize>,
+}
+
+/// The server's most recent requests with their responses, newest first,
+/// from every connection unless `connectionId` picks one.
+pub fn handle_recent_requests(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("recent_requests_operation");
+    let _enter = span.enter();
+
+    let params: RecentRequestsParams = parse_params(params)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).max(1);
+    let entries = state
+        .request_log
+        .recent(params.connection_id, params.method.as_deref(), limit);
+    debug!(entries = entries.len(), "Listing recent requests");
+    Ok(json!({ "entries": entries }))
+}
@@ -0,0 +1,154 @@
+//! `definition`: go-to-definition from the symbol index, for workspaces
+//! without a language server. It only matches names, so it is best effort:
+//! every definition of the identifier under the cursor is a candidate, the
+//! closest ones first.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, info_span};
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use super::syntax::document_content;
+use super::text::{Position, byte_offset};
+use crate::{index, state::AppState, syntax};
+
+const MAX_DEFINITIONS: usize = 200;
+
+#[derive(Deserialize)]
+struct DefinitionParams {
+    path: String,
+    position: Position,
+    /// Unsaved buffer contents; the file on disk is read when omitted.
+    content: Option<String>,
+    /// Grammar id overriding detection by extension.
+    language: Option<String>,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+/// How close a definition is to the file asking, closest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Proximity {
+    SameFile,
+    SameDirectory,
+    SameLanguage,
+    Elsewhere,
+}
+
+impl Proximity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Proximity::SameFile => "sameFile",
+            Proximity::SameDirectory => "sameDirectory",
+            Proximity::SameLanguage => "sameLanguage",
+            Proximity::Elsewhere => "elsewhere",
+        }
+    }
+}
+
+pub fn handle_definition(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("definition_operation");
+    let _enter = span.enter();
+
+    let params: DefinitionParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let content = document_content(path, params.content)?;
+    let Some(identifier) = identifier_at(&content, params.position) else {
+        debug!(path = %params.path, "No identifier at position");
+        return Ok(json!({ "identifier": null, "definitions": [], "total": 0, "indexed": true }));
+    };
+    let grammar = syntax::grammar_for(path, params.language.as_deref());
+    // The index holds canonical paths.
+    let path = std::path::absolute(path).map_err(HandlerError::IoError)?;
+    let path = path.canonicalize().unwrap_or(path);
+    let limit = params.limit.min(MAX_DEFINITIONS);
+
+    // The file asking is parsed afresh, since it may have unsaved changes.
+    let mut found: Vec<(Proximity, PathBuf, index::IndexedSymbol)> = Vec::new();
+    if let Some(grammar) = grammar.filter(|grammar| grammar.has_symbols()) {
+        let tree = state
+            .syntax
+            .symbols(grammar, &content)
+            .map_err(HandlerError::SyntaxError)?;
+        found.extend(
+            index::flatten_symbols(tree)
+                .into_iter()
+                .filter(|symbol| symbol.name == identifier)
+                .map(|symbol| (Proximity::SameFile, path.clone(), symbol)),
+        );
+    }
+    let directory = path.parent();
+    let indexed = state.files.visit_symbols(|other, symbols| {
+        if other == path {
+            return;
+        }
+        for symbol in symbols.iter().filter(|symbol| symbol.name == identifier) {
+            let proximity = if other.parent() == directory {
+                Proximity::SameDirectory
+            } else if grammar.is_some_and(|grammar| {
+                syntax::grammar_for(other, None).is_some_and(|other| other.id == grammar.id)
+            }) {
+                Proximity::SameLanguage
+            } else {
+                Proximity::Elsewhere
+            };
+            found.push((proximity, other.to_path_buf(), symbol.clone()));
+        }
+    });
+    found.sort_by(|(a_proximity, a_path, a), (b_proximity, b_path, b)| {
+        a_proximity
+            .cmp(b_proximity)
+            .then_with(|| a_path.cmp(b_path))
+            .then_with(|| a.range.start.line.cmp(&b.range.start.line))
+    });
+    let total = found.len();
+    let definitions: Vec<Value> = found
+        .into_iter()
+        .take(limit)
+        .map(|(proximity, path, symbol)| {
+            json!({
+                "path": path,
+                "range": symbol.range,
+                "kind": symbol.kind,
+                "containerName": symbol.container_name,
+                "proximity": proximity.as_str(),
+            })
+        })
+        .collect();
+    info!(
+        identifier = %identifier,
+        definitions = total,
+        "Resolved definition candidates"
+    );
+    Ok(json!({
+        "identifier": identifier,
+        "definitions": definitions,
+        "total": total,
+        "indexed": indexed,
+    }))
+}
+
+/// The identifier `position` is in or just after.
+fn identifier_at(text: &str, position: Position) -> Option<String> {
+    let is_identifier = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    let offset = byte_offset(text, position);
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_identifier(*c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_identifier(*c))
+        .map_or(text.len(), |(i, _)| offset + i);
+    let word = &text[start..end];
+    // Numbers are not names.
+    (!word.is_empty() && !word.starts_with(|c: char| c.is_ascii_digit())).then(|| word.to_string())
+}
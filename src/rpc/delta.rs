@@ -0,0 +1,70 @@
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{fs, path::Path};
+use tracing::{debug, info, info_span};
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::delta::{BlockSignature, DeltaOp, compute_delta};
+
+const MIN_BLOCK_SIZE: usize = 64;
+const MAX_BLOCK_SIZE: usize = 1024 * 1024;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadFileDeltaParams {
+    path: String,
+    block_size: usize,
+    checksums: Vec<BlockSignature>,
+}
+
+pub fn handle_read_file_delta(params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("read_file_delta_operation");
+    let _enter = span.enter();
+
+    let params: ReadFileDeltaParams = parse_params(params)?;
+    if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&params.block_size) {
+        return Err(HandlerError::InvalidParams(format!(
+            "blockSize must be between {MIN_BLOCK_SIZE} and {MAX_BLOCK_SIZE}"
+        )));
+    }
+
+    let path = Path::new(&params.path);
+    if !path.exists() {
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
+    }
+    let data = fs::read(path).map_err(HandlerError::IoError)?;
+
+    debug!(
+        path = %params.path,
+        block_size = params.block_size,
+        client_blocks = params.checksums.len(),
+        "Computing file delta"
+    );
+    let ops = compute_delta(&data, params.block_size, &params.checksums);
+
+    let mut literal_bytes = 0;
+    let ops: Vec<Value> = ops
+        .into_iter()
+        .map(|op| match op {
+            DeltaOp::Copy { start, count } => json!({ "copy": { "start": start, "count": count } }),
+            DeltaOp::Literal(bytes) => {
+                literal_bytes += bytes.len();
+                json!({ "data": BASE64.encode(bytes) })
+            }
+        })
+        .collect();
+
+    info!(
+        path = %params.path,
+        size = data.len(),
+        literal_bytes,
+        "File delta computed successfully"
+    );
+    Ok(json!({
+        "size": data.len(),
+        "hash": blake3::hash(&data).to_hex().to_string(),
+        "ops": ops,
+    }))
+}
@@ -0,0 +1,104 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{fs, path::Path};
+use tracing::{debug, info, info_span};
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{diff, state::AppState};
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum DiffFormat {
+    #[default]
+    Unified,
+    Hunks,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ComputeDiffParams {
+    /// The new side: this file, or `content` in its place.
+    path: String,
+    /// The old side when comparing two files.
+    #[serde(default)]
+    original_path: Option<String>,
+    /// Unsaved content, compared against `path` on disk.
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default = "default_context")]
+    context: usize,
+    #[serde(default)]
+    format: DiffFormat,
+}
+
+fn default_context() -> usize {
+    3
+}
+
+/// Diffs `originalPath` against `path`, or `path` on disk against
+/// `content`.
+pub fn handle_compute_diff(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("compute_diff_operation");
+    let _enter = span.enter();
+
+    let params: ComputeDiffParams = parse_params(params)?;
+    let (old_path, new) = match (&params.original_path, params.content) {
+        (Some(original), None) => (original.as_str(), read_text(state, &params.path)?),
+        (None, Some(content)) => (params.path.as_str(), content),
+        _ => {
+            return Err(HandlerError::InvalidParams(
+                "Pass exactly one of originalPath and content".to_string(),
+            ));
+        }
+    };
+    let old = read_text(state, old_path)?;
+
+    debug!(old = old_path, new = %params.path, "Computing diff");
+    let hunks = diff::hunks(&old, &new, params.context);
+    info!(hunks = hunks.len(), "Diff computed");
+    let identical = hunks.is_empty();
+    if params.format == DiffFormat::Hunks {
+        return Ok(json!({ "identical": identical, "hunks": hunks }));
+    }
+    let diff = diff::unified(
+        &format!("a/{}", label(state, Path::new(old_path))),
+        &format!("b/{}", label(state, Path::new(&params.path))),
+        &hunks,
+    );
+    Ok(json!({ "identical": identical, "diff": diff }))
+}
+
+fn read_text(state: &AppState, path: &str) -> Result<String, HandlerError> {
+    let file = Path::new(path);
+    let metadata = fs::metadata(file).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            HandlerError::FileNotFound(file.to_path_buf())
+        } else {
+            HandlerError::IoError(e)
+        }
+    })?;
+    let limit = state.limits().max_read_bytes;
+    if metadata.len() > limit {
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "File is {} bytes, larger than the {limit} byte read limit",
+            metadata.len()
+        )));
+    }
+    let bytes = fs::read(file).map_err(HandlerError::IoError)?;
+    String::from_utf8(bytes)
+        .map_err(|_| HandlerError::InvalidParams(format!("{path} is not UTF-8 text")))
+}
+
+/// `path` relative to the workspace root that holds it, for diff headers.
+pub fn label(state: &AppState, path: &Path) -> String {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let absolute = absolute.canonicalize().unwrap_or(absolute);
+    state
+        .uris
+        .roots()
+        .find_map(|(_, root, _)| absolute.strip_prefix(root).ok().map(Path::to_path_buf))
+        .unwrap_or(absolute)
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,52 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::time::Duration;
+use tracing::{debug, info_span};
+
+use super::cancel::CancelToken;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{disk_usage, state::AppState};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiskUsageParams {
+    /// Oldest cached report to accept; `0` always walks the workspace.
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: u64,
+}
+
+fn default_max_age_secs() -> u64 {
+    300
+}
+
+/// Space taken by each top-level entry of every workspace root, largest
+/// first, and what is left on the volume holding the primary root. Sizes
+/// come from a cached walk, run on the background lane; free space is read
+/// on every call.
+pub fn handle_disk_usage(
+    state: &AppState,
+    params: Value,
+    cancel: &CancelToken,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("disk_usage_operation");
+    let _enter = span.enter();
+
+    let params: DiskUsageParams = parse_params(params)?;
+    let (report, cached) = state.disk_usage.report(
+        state.uris.roots().map(|(name, path, _)| (name, path)),
+        Duration::from_secs(params.max_age_secs),
+        cancel,
+    )?;
+    let bytes: u64 = report.roots.iter().map(|root| root.bytes).sum();
+    let files: u64 = report.roots.iter().map(|root| root.files).sum();
+    debug!(bytes, files, cached, "Disk usage reported");
+    Ok(json!({
+        "roots": report.roots,
+        "bytes": bytes,
+        "files": files,
+        "volume": disk_usage::volume(&state.config.root),
+        "computedAt": report.computed_at,
+        "cached": cached,
+    }))
+}
@@ -0,0 +1,200 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{fs, io, path::Path, time::Instant};
+use tracing::{debug, field, info, info_span, warn};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{documents::SaveReason, protected::audit_forced, state::AppState};
+
+#[derive(Deserialize)]
+struct DocumentParams {
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct UpdateDocumentParams {
+    path: String,
+    /// The document's full new content.
+    content: String,
+    /// The version this content was based on; refused if the document has
+    /// moved on since.
+    #[serde(default)]
+    version: Option<u64>,
+    #[serde(default)]
+    force: bool,
+}
+
+/// Opens a document, shared with any other connection that has it open.
+/// `lockedBy` names the connection holding its `lockFile` lock, if any.
+pub fn handle_open(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("document_open_operation");
+    let _enter = span.enter();
+
+    let params: DocumentParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let metadata = fs::metadata(path).map_err(|e| not_found(path, e))?;
+    let limit = state.limits().max_read_bytes;
+    if metadata.len() > limit {
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "File is {} bytes, larger than the {limit} byte read limit",
+            metadata.len()
+        )));
+    }
+
+    let opened = state
+        .documents
+        .open(path, connection.id, connection.notifier.clone())
+        .map_err(|e| not_found(path, e))?;
+    debug!(path = %params.path, version = opened.version, "Document opened");
+    Ok(json!({
+        "content": opened.content,
+        "version": opened.version,
+        "dirty": opened.dirty,
+        "encoding": opened.encoding,
+        "lockedBy": state.locks.holder(path),
+    }))
+}
+
+/// Replaces an open document's content without writing it to disk.
+pub fn handle_update(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("document_update_operation");
+    let _enter = span.enter();
+
+    let params: UpdateDocumentParams = parse_params(params)?;
+    let limit = state.limits().max_write_bytes;
+    if params.content.len() > limit {
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "Content is {} bytes, larger than the {limit} byte write limit",
+            params.content.len()
+        )));
+    }
+    let path = Path::new(&params.path);
+    if state.protected.is_protected(path) {
+        if !params.force {
+            return Err(HandlerError::ProtectedPath(params.path));
+        }
+        audit_forced("document/update", &params.path);
+    }
+
+    let version = state
+        .documents
+        .update(path, connection.id, params.content, params.version)
+        .map_err(HandlerError::InvalidParams)?;
+    debug!(path = %params.path, version, "Document updated");
+    Ok(json!({ "version": version }))
+}
+
+/// Closes a document for this connection. Unsaved changes are kept while
+/// another connection has it open; otherwise they are saved if auto-save is
+/// on and discarded if not.
+pub fn handle_close(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("document_close_operation");
+    let _enter = span.enter();
+
+    let params: DocumentParams = parse_params(params)?;
+    let discarded = state
+        .documents
+        .close(
+            Path::new(&params.path),
+            connection.id,
+            state.config.auto_save.enabled(),
+        )
+        .map_err(HandlerError::IoError)?;
+    debug!(path = %params.path, discarded, "Document closed");
+    Ok(json!({ "discarded": discarded }))
+}
+
+/// Writes an open document to disk if it has unsaved changes.
+pub fn handle_save(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!(
+        "document_save_operation",
+        bytes = field::Empty,
+        duration_ms = field::Empty
+    );
+    let _enter = span.enter();
+
+    let params: DocumentParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let started = Instant::now();
+    let saved = state
+        .documents
+        .save(path, SaveReason::Save)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidInput => HandlerError::InvalidParams(e.to_string()),
+            _ => HandlerError::IoError(e),
+        })?;
+    let Some(saved) = saved else {
+        debug!(path = %params.path, "Document has no unsaved changes");
+        return Ok(json!({ "saved": false }));
+    };
+    span.record("bytes", saved.bytes);
+    span.record("duration_ms", started.elapsed().as_millis() as u64);
+    info!(path = %params.path, version = saved.version, "Document saved");
+    state.activity.record(
+        "file.saved",
+        Some(connection.id),
+        json!({ "path": saved.path, "bytes": saved.bytes }),
+    );
+    Ok(json!({ "saved": true, "version": saved.version }))
+}
+
+/// Writes every open document with unsaved changes, whoever opened it.
+pub fn handle_save_all(
+    state: &AppState,
+    connection: &ConnectionContext,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("save_all_operation");
+    let _enter = span.enter();
+
+    let (saved, failed) = state.documents.save_all(SaveReason::SaveAll);
+    for saved in &saved {
+        state.activity.record(
+            "file.saved",
+            Some(connection.id),
+            json!({ "path": saved.path, "bytes": saved.bytes }),
+        );
+    }
+    let failed: Vec<Value> = failed
+        .into_iter()
+        .map(|(path, e)| {
+            warn!(path = %path.display(), error = %e, "Failed to save document");
+            json!({ "path": path, "error": e.to_string() })
+        })
+        .collect();
+    info!(
+        saved = saved.len(),
+        failed = failed.len(),
+        "Saved open documents"
+    );
+    let saved: Vec<Value> = saved
+        .iter()
+        .map(|saved| json!({ "path": saved.path, "version": saved.version }))
+        .collect();
+    Ok(json!({ "saved": saved, "failed": failed }))
+}
+
+fn not_found(path: &Path, e: io::Error) -> HandlerError {
+    if e.kind() == io::ErrorKind::NotFound {
+        HandlerError::FileNotFound(path.to_path_buf())
+    } else {
+        HandlerError::IoError(e)
+    }
+}
@@ -0,0 +1,69 @@
+//! `dryRun` for the methods that write or delete files. A dry run passes
+//! the same checks as the real request, interceptors included, then
+//! reports what it would change instead of touching the disk. Other
+//! mutating methods refuse `dryRun` rather than ignore it; see
+//! [`super::interceptors::DryRun`].
+
+use serde_json::{Value, json};
+use std::{fs, path::Path};
+
+use super::diff::label;
+use crate::{diff, state::AppState};
+
+/// Mutating methods that take `dryRun`.
+pub const DRY_RUN_METHODS: &[&str] = &[
+    "applyPatch",
+    "createFromTemplate",
+    "deleteFile",
+    "history/restore",
+    "importZip",
+    "replaceInFiles",
+    "structuredSet",
+    "table/updateCell",
+    "trash/empty",
+    "trash/restore",
+    "uploadFile",
+    "writeFile",
+];
+
+/// Larger files are previewed without a diff.
+const MAX_DIFF_BYTES: usize = 1024 * 1024;
+
+pub fn requested(params: &Value) -> bool {
+    params
+        .get("dryRun")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// What writing `new` to `path` would do: `{dryRun, path, created, bytes,
+/// diff}`. `diff` is unified, and `null` unless both sides are UTF-8 text
+/// of at most [`MAX_DIFF_BYTES`].
+pub fn preview(state: &AppState, path: &Path, new: &[u8]) -> Value {
+    let old = fs::read(path).ok();
+    let name = label(state, path);
+    let old_text = match &old {
+        Some(bytes) => std::str::from_utf8(bytes).ok(),
+        None => Some(""),
+    };
+    let diff = match (old_text, std::str::from_utf8(new)) {
+        (Some(old_text), Ok(new_text))
+            if old_text.len() <= MAX_DIFF_BYTES && new_text.len() <= MAX_DIFF_BYTES =>
+        {
+            let old_name = match old {
+                Some(_) => format!("a/{name}"),
+                None => "/dev/null".to_string(),
+            };
+            let hunks = diff::hunks(old_text, new_text, 3);
+            Some(diff::unified(&old_name, &format!("b/{name}"), &hunks))
+        }
+        _ => None,
+    };
+    json!({
+        "dryRun": true,
+        "path": path,
+        "created": old.is_none(),
+        "bytes": new.len(),
+        "diff": diff,
+    })
+}
@@ -1,15 +1,34 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{path::PathBuf, time::Duration};
+use tracing::{error, info};
+
+use super::validate::ParamError;
+use crate::{config::Access, locks::FileLock};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JsonRpcError {
     pub code: i32,
     pub message: String,
+    /// Structured context for the error, so clients need not parse
+    /// `message`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
 }
 
 pub fn create_error_response(
     code: i32,
     message: &str,
     id: serde_json::Value,
+) -> super::request::JsonRpcResponse {
+    create_error_response_with_data(code, message, None, id)
+}
+
+pub fn create_error_response_with_data(
+    code: i32,
+    message: &str,
+    data: Option<Value>,
+    id: serde_json::Value,
 ) -> super::request::JsonRpcResponse {
     super::request::JsonRpcResponse {
         jsonrpc: "2.0".to_string(),
@@ -17,20 +36,416 @@ pub fn create_error_response(
         error: Some(JsonRpcError {
             code,
             message: message.to_string(),
+            data,
         }),
         id,
+        correlation_id: None,
     }
 }
 
+#[derive(Debug)]
+pub enum HandlerError {
+    InvalidRequest(String),
+    InvalidParams(String),
+    /// The params do not match the method's schema.
+    ParamsMismatch(Vec<ParamError>),
+    FileNotFound(PathBuf),
+    DirectoryError(String),
+    ProtectedPath(String),
+    GitError(String),
+    DirtyWorkspace(Vec<String>),
+    TerminalError(String),
+    TaskError(String),
+    LspError(String),
+    FormatError(String),
+    BlobError(String),
+    SyntaxError(String),
+    PayloadTooLarge(String),
+    RequestCancelled,
+    Timeout(Duration),
+    UnsupportedProtocol(String),
+    /// A usage limit of the connection's tier was reached.
+    LimitExceeded {
+        tier: String,
+        limit: &'static str,
+        max: Option<u64>,
+        message: String,
+    },
+    /// The server runs with `--read-only` and the method would modify the
+    /// workspace.
+    ReadOnly(String),
+    /// A path resolves through a symlink the sandbox does not allow:
+    /// one leaving the workspace, or any when `followSymlinks` is off.
+    SymlinkRefused {
+        path: PathBuf,
+        escapes: bool,
+    },
+    /// A `permissions` rule grants less than the operation needs.
+    PermissionDenied {
+        path: PathBuf,
+        required: Access,
+    },
+    /// The config file could not be reloaded; the running config stays.
+    ConfigError(String),
+    /// Another connection holds the advisory lock on the file.
+    FileLocked {
+        path: String,
+        lock: FileLock,
+    },
+    /// `writeFile` with `ifMatch`: the file no longer has the hash the
+    /// client last read. `actual` is `None` if it is gone.
+    WriteConflict {
+        path: String,
+        expected: String,
+        actual: Option<String>,
+    },
+    /// A debug adapter could not be started or reached.
+    DebugAdapterError(String),
+    /// A `snippet/run` interpreter could not be started.
+    SnippetError(String),
+    /// The connection is confined to a workspace and the method reaches
+    /// beyond it.
+    Confined(String),
+    /// Every background thread is busy and `queued` requests, the limit,
+    /// are already waiting for one.
+    ServerBusy {
+        queued: usize,
+    },
+    /// The handler panicked. `reference` is the request's correlation id,
+    /// logged with the panic.
+    Internal {
+        reference: String,
+    },
+    IoError(std::io::Error),
+}
+impl HandlerError {
+    pub fn to_jsonrpc_error(&self, id: Value) -> super::request::JsonRpcResponse {
+        let mut response = self.log_and_describe(id);
+        if let Some(error) = response.error.as_mut() {
+            error.data = self.data();
+        }
+        response
+    }
+
+    /// Context attached as the error's `data` member.
+    pub fn data(&self) -> Option<Value> {
+        match self {
+            HandlerError::InvalidRequest(reason) => Some(json!({ "reason": reason })),
+            HandlerError::InvalidParams(msg) => params_problem(msg),
+            // The first error's `problem` and `field` are repeated at the
+            // top, where clients of the serde-based errors look for them.
+            HandlerError::ParamsMismatch(errors) => Some(json!({
+                "problem": errors.first().map(|e| e.problem),
+                "field": errors.first().map(|e| &e.field),
+                "errors": errors,
+            })),
+            HandlerError::FileNotFound(path) => Some(json!({ "path": path })),
+            HandlerError::ProtectedPath(path) => Some(json!({ "path": path })),
+            HandlerError::DirtyWorkspace(files) => Some(json!({ "files": files })),
+            HandlerError::Timeout(limit) => Some(json!({ "limitMs": limit.as_millis() as u64 })),
+            HandlerError::UnsupportedProtocol(version) => Some(json!({
+                "requested": version,
+                "supported": super::initialize::PROTOCOL_VERSION,
+            })),
+            HandlerError::LimitExceeded {
+                tier, limit, max, ..
+            } => Some(json!({ "tier": tier, "limit": limit, "max": max })),
+            HandlerError::ReadOnly(method) | HandlerError::Confined(method) => {
+                Some(json!({ "method": method }))
+            }
+            HandlerError::PermissionDenied { path, required } => {
+                Some(json!({ "path": path, "required": required }))
+            }
+            HandlerError::SymlinkRefused { path, escapes } => Some(json!({
+                "path": path,
+                "reason": if *escapes { "escapesWorkspace" } else { "notFollowed" },
+            })),
+            HandlerError::FileLocked { path, lock } => {
+                Some(json!({ "path": path, "lockedBy": lock }))
+            }
+            HandlerError::WriteConflict {
+                path,
+                expected,
+                actual,
+            } => Some(json!({ "path": path, "expectedHash": expected, "actualHash": actual })),
+            HandlerError::ServerBusy { queued } => Some(json!({ "queued": queued })),
+            HandlerError::Internal { reference } => Some(json!({ "reference": reference })),
+            HandlerError::IoError(e) => Some(json!({
+                "kind": format!("{:?}", e.kind()),
+                "osError": e.raw_os_error(),
+            })),
+            _ => None,
+        }
+    }
+
+    fn log_and_describe(&self, id: Value) -> super::request::JsonRpcResponse {
+        match self {
+            HandlerError::InvalidRequest(reason) => {
+                error!(error_type = "invalid_request", reason = %reason, "Request failed");
+                create_error_response(
+                    INVALID_REQUEST_CODE,
+                    &format!("Invalid request: {reason}"),
+                    id,
+                )
+            }
+            HandlerError::InvalidParams(msg) => {
+                error!(error_type = "invalid_params", message = %msg, "Request failed");
+                create_error_response(INVALID_PARAMS_CODE, msg, id)
+            }
+            HandlerError::ParamsMismatch(errors) => {
+                let message = match errors.as_slice() {
+                    [only] => only.message.clone(),
+                    [first, rest @ ..] => format!("{} (and {} more)", first.message, rest.len()),
+                    [] => "Invalid params".to_string(),
+                };
+                error!(error_type = "invalid_params", message = %message, "Request failed");
+                create_error_response(INVALID_PARAMS_CODE, &message, id)
+            }
+            HandlerError::FileNotFound(path) => {
+                error!(error_type = "file_not_found", path = %path.display(), "Request failed");
+                create_error_response(FILE_NOT_FOUND_CODE, "File not found", id)
+            }
+            HandlerError::DirectoryError(msg) => {
+                error!(error_type = "directory_error", message = %msg, "Request failed");
+                create_error_response(DIRECTORY_ERROR_CODE, msg, id)
+            }
+            HandlerError::ProtectedPath(path) => {
+                error!(error_type = "protected_path", path = %path, "Request failed");
+                create_error_response(
+                    PROTECTED_PATH_CODE,
+                    &format!("Path is protected, pass force: true to modify: {path}"),
+                    id,
+                )
+            }
+            HandlerError::GitError(msg) => {
+                error!(error_type = "git_error", message = %msg, "Request failed");
+                create_error_response(GIT_ERROR_CODE, msg, id)
+            }
+            HandlerError::DirtyWorkspace(files) => {
+                error!(
+                    error_type = "dirty_workspace",
+                    files = files.len(),
+                    "Request failed"
+                );
+                create_error_response(
+                    DIRTY_WORKSPACE_CODE,
+                    &format!("Local changes would be overwritten: {}", files.join(", ")),
+                    id,
+                )
+            }
+            HandlerError::TerminalError(msg) => {
+                error!(error_type = "terminal_error", message = %msg, "Request failed");
+                create_error_response(TERMINAL_ERROR_CODE, msg, id)
+            }
+            HandlerError::TaskError(msg) => {
+                error!(error_type = "task_error", message = %msg, "Request failed");
+                create_error_response(TASK_ERROR_CODE, msg, id)
+            }
+            HandlerError::LspError(msg) => {
+                error!(error_type = "lsp_error", message = %msg, "Request failed");
+                create_error_response(LSP_ERROR_CODE, msg, id)
+            }
+            HandlerError::FormatError(msg) => {
+                error!(error_type = "format_error", message = %msg, "Request failed");
+                create_error_response(FORMAT_ERROR_CODE, msg, id)
+            }
+            HandlerError::BlobError(msg) => {
+                error!(error_type = "blob_error", message = %msg, "Request failed");
+                create_error_response(BLOB_ERROR_CODE, msg, id)
+            }
+            HandlerError::SyntaxError(msg) => {
+                error!(error_type = "syntax_error", message = %msg, "Request failed");
+                create_error_response(SYNTAX_ERROR_CODE, msg, id)
+            }
+            HandlerError::PayloadTooLarge(msg) => {
+                error!(error_type = "payload_too_large", message = %msg, "Request failed");
+                create_error_response(PAYLOAD_TOO_LARGE_CODE, msg, id)
+            }
+            HandlerError::RequestCancelled => {
+                info!(error_type = "request_cancelled", "Request cancelled");
+                create_error_response(REQUEST_CANCELLED_CODE, "Request cancelled", id)
+            }
+            HandlerError::Timeout(limit) => {
+                error!(
+                    error_type = "timeout",
+                    limit_ms = limit.as_millis() as u64,
+                    "Request failed"
+                );
+                create_error_response(
+                    TIMEOUT_CODE,
+                    &format!("Request timed out after {}ms", limit.as_millis()),
+                    id,
+                )
+            }
+            HandlerError::UnsupportedProtocol(version) => {
+                error!(error_type = "unsupported_protocol", version = %version, "Request failed");
+                create_error_response(
+                    UNSUPPORTED_PROTOCOL_CODE,
+                    &format!("Unsupported protocol version {version}"),
+                    id,
+                )
+            }
+            HandlerError::LimitExceeded {
+                tier,
+                limit,
+                message,
+                ..
+            } => {
+                error!(error_type = "limit_exceeded", tier = %tier, limit = %limit, "Request failed");
+                create_error_response(LIMIT_EXCEEDED_CODE, message, id)
+            }
+            HandlerError::ReadOnly(method) => {
+                error!(error_type = "read_only", method = %method, "Request failed");
+                create_error_response(
+                    READ_ONLY_CODE,
+                    &format!("Server is read-only; {method} is disabled"),
+                    id,
+                )
+            }
+            HandlerError::SymlinkRefused { path, escapes } => {
+                error!(
+                    error_type = "symlink_refused",
+                    path = %path.display(),
+                    escapes,
+                    "Request failed"
+                );
+                let message = if *escapes {
+                    format!("Symlink points outside the workspace: {}", path.display())
+                } else {
+                    format!("Symlinks are not followed: {}", path.display())
+                };
+                create_error_response(SYMLINK_REFUSED_CODE, &message, id)
+            }
+            HandlerError::PermissionDenied { path, required } => {
+                error!(
+                    error_type = "permission_denied",
+                    path = %path.display(),
+                    required = ?required,
+                    "Request failed"
+                );
+                let message = match required {
+                    Access::Write => format!("Writing {} is not permitted", path.display()),
+                    _ => format!("Reading {} is not permitted", path.display()),
+                };
+                create_error_response(PERMISSION_DENIED_CODE, &message, id)
+            }
+            HandlerError::ConfigError(msg) => {
+                error!(error_type = "config_error", message = %msg, "Request failed");
+                create_error_response(CONFIG_ERROR_CODE, msg, id)
+            }
+            HandlerError::FileLocked { path, lock } => {
+                error!(
+                    error_type = "file_locked",
+                    path = %path,
+                    holder = lock.connection_id,
+                    "Request failed"
+                );
+                let holder = match &lock.username {
+                    Some(name) => format!("{name} (connection {})", lock.connection_id),
+                    None => format!("connection {}", lock.connection_id),
+                };
+                create_error_response(
+                    FILE_LOCKED_CODE,
+                    &format!("File is locked by {holder}: {path}"),
+                    id,
+                )
+            }
+            HandlerError::WriteConflict { path, .. } => {
+                error!(error_type = "write_conflict", path = %path, "Request failed");
+                create_error_response(
+                    WRITE_CONFLICT_CODE,
+                    &format!("File changed since it was read: {path}"),
+                    id,
+                )
+            }
+            HandlerError::DebugAdapterError(msg) => {
+                error!(error_type = "debug_adapter_error", message = %msg, "Request failed");
+                create_error_response(DEBUG_ADAPTER_ERROR_CODE, msg, id)
+            }
+            HandlerError::SnippetError(msg) => {
+                error!(error_type = "snippet_error", message = %msg, "Request failed");
+                create_error_response(SNIPPET_ERROR_CODE, msg, id)
+            }
+            HandlerError::Confined(method) => {
+                error!(error_type = "confined", method = %method, "Request failed");
+                create_error_response(
+                    CONFINED_CODE,
+                    &format!("{method} is not available to connections confined to a workspace"),
+                    id,
+                )
+            }
+            HandlerError::ServerBusy { queued } => {
+                error!(error_type = "server_busy", queued, "Request failed");
+                create_error_response(
+                    SERVER_BUSY_CODE,
+                    &format!("Server is busy; {queued} background requests are already queued"),
+                    id,
+                )
+            }
+            HandlerError::Internal { reference } => {
+                error!(error_type = "internal", reference = %reference, "Request failed");
+                create_error_response(
+                    INTERNAL_ERROR_CODE,
+                    &format!("Internal error, reference {reference}"),
+                    id,
+                )
+            }
+            HandlerError::IoError(e) => {
+                error!(error_type = "io_error", error = %e, "Request failed");
+                create_error_response(IO_ERROR_CODE, &e.to_string(), id)
+            }
+        }
+    }
+}
+
+/// Picks the offending field out of a serde deserialization message such as
+/// ``missing field `path` ``.
+fn params_problem(message: &str) -> Option<Value> {
+    const PROBLEMS: &[(&str, &str)] = &[
+        ("missing field `", "missingField"),
+        ("unknown field `", "unknownField"),
+        ("duplicate field `", "duplicateField"),
+    ];
+    PROBLEMS.iter().find_map(|(prefix, problem)| {
+        let field = message.strip_prefix(prefix)?.split('`').next()?;
+        Some(json!({ "problem": problem, "field": field }))
+    })
+}
+
 // JSON-RPC error codes
 pub const PARSE_ERROR_CODE: i32 = -32700;
-#[allow(dead_code)]
 pub const INVALID_REQUEST_CODE: i32 = -32600;
 pub const METHOD_NOT_FOUND_CODE: i32 = -32601;
 pub const INVALID_PARAMS_CODE: i32 = -32602;
-#[allow(dead_code)]
 pub const INTERNAL_ERROR_CODE: i32 = -32603;
 // Application-specific error codes
 pub const FILE_NOT_FOUND_CODE: i32 = -32001;
 pub const IO_ERROR_CODE: i32 = -32002;
 pub const DIRECTORY_ERROR_CODE: i32 = -32003;
+pub const PROTECTED_PATH_CODE: i32 = -32004;
+pub const GIT_ERROR_CODE: i32 = -32005;
+pub const DIRTY_WORKSPACE_CODE: i32 = -32006;
+pub const TERMINAL_ERROR_CODE: i32 = -32007;
+pub const TASK_ERROR_CODE: i32 = -32008;
+pub const LSP_ERROR_CODE: i32 = -32009;
+pub const FORMAT_ERROR_CODE: i32 = -32010;
+pub const BLOB_ERROR_CODE: i32 = -32011;
+pub const SYNTAX_ERROR_CODE: i32 = -32012;
+pub const RATE_LIMITED_CODE: i32 = -32013;
+pub const PAYLOAD_TOO_LARGE_CODE: i32 = -32014;
+pub const TIMEOUT_CODE: i32 = -32015;
+pub const UNSUPPORTED_PROTOCOL_CODE: i32 = -32016;
+pub const LIMIT_EXCEEDED_CODE: i32 = -32017;
+pub const SYMLINK_REFUSED_CODE: i32 = -32018;
+pub const READ_ONLY_CODE: i32 = -32019;
+pub const PERMISSION_DENIED_CODE: i32 = -32020;
+pub const CONFIG_ERROR_CODE: i32 = -32021;
+pub const FILE_LOCKED_CODE: i32 = -32022;
+pub const WRITE_CONFLICT_CODE: i32 = -32023;
+pub const SNIPPET_ERROR_CODE: i32 = -32024;
+pub const DEBUG_ADAPTER_ERROR_CODE: i32 = -32025;
+pub const CONFINED_CODE: i32 = -32026;
+pub const SERVER_BUSY_CODE: i32 = -32027;
+/// Same value as LSP's `RequestCancelled`, so bridged clients need not
+/// special-case it.
+pub const REQUEST_CANCELLED_CODE: i32 = -32800;
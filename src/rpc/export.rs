@@ -0,0 +1,181 @@
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use tracing::{debug, info, info_span};
+
+use super::error::HandlerError;
+use super::git::run_git;
+use super::handlers::parse_params;
+use crate::{archive, clock, state::AppState};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum ExportFormat {
+    /// `git diff` of uncommitted changes against HEAD.
+    Patch,
+    /// Zip archive of the selected files.
+    Archive,
+    /// JSON bundle in the shape of the GitHub gist API's `files` object.
+    Gist,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportParams {
+    format: ExportFormat,
+    /// Files or directories to include, relative to the workspace root;
+    /// the whole workspace when omitted.
+    #[serde(default)]
+    paths: Vec<String>,
+    /// Whether a patch includes untracked files as additions.
+    #[serde(default = "default_true")]
+    include_untracked: bool,
+    /// Gist description.
+    description: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Bundles work in progress for handing off: the result is stored as a blob
+/// and offered through a one-hour `/download/<token>` link.
+pub fn handle_export(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("export_operation");
+    let _enter = span.enter();
+
+    let params: ExportParams = parse_params(params)?;
+    let root = &state.config.root;
+    let stamp = clock::unix_secs();
+    let (bytes, file_name, content_type, files) = match params.format {
+        ExportFormat::Patch => {
+            let patch = patch(state, &params)?;
+            if patch.is_empty() {
+                return Err(HandlerError::GitError(
+                    "No uncommitted changes to export".to_string(),
+                ));
+            }
+            let files = patch
+                .lines()
+                .filter(|line| line.starts_with("diff --git "))
+                .count();
+            let name = format!("changes-{stamp}.patch");
+            (patch.into_bytes(), name, "text/x-diff", files)
+        }
+        ExportFormat::Archive => {
+            let files = selected_files(state, &params.paths)?;
+            let cursor = archive::write_zip(Cursor::new(Vec::new()), root, &files)
+                .map_err(HandlerError::IoError)?;
+            let name = format!("export-{stamp}.zip");
+            (cursor.into_inner(), name, "application/zip", files.len())
+        }
+        ExportFormat::Gist => {
+            let files = selected_files(state, &params.paths)?;
+            let mut entries = Map::new();
+            for file in &files {
+                // Gists hold text only.
+                let Ok(content) = fs::read_to_string(file) else {
+                    debug!(path = %file.display(), "Leaving binary file out of gist");
+                    continue;
+                };
+                let name = file.strip_prefix(root).unwrap_or(file).to_string_lossy();
+                entries.insert(name.into_owned(), json!({ "content": content }));
+            }
+            let count = entries.len();
+            let gist = json!({
+                "description": params.description.unwrap_or_default(),
+                "public": false,
+                "files": entries,
+            });
+            let bytes = serde_json::to_vec_pretty(&gist)
+                .map_err(|e| HandlerError::IoError(std::io::Error::other(e)))?;
+            (
+                bytes,
+                format!("gist-{stamp}.json"),
+                "application/json",
+                count,
+            )
+        }
+    };
+
+    let size = bytes.len();
+    let hash = state
+        .blobs
+        .put_bytes(&bytes)
+        .map_err(HandlerError::IoError)?;
+    let (token, download) = state
+        .downloads
+        .create(hash.clone(), file_name, content_type)
+        .map_err(|e| HandlerError::IoError(std::io::Error::other(e)))?;
+    info!(file = %download.file_name, files, size, "Export created");
+    Ok(json!({
+        "url": format!("/download/{token}"),
+        "fileName": download.file_name,
+        "hash": hash,
+        "size": size,
+        "files": files,
+        "expiresAt": download.expires_at,
+    }))
+}
+
+fn patch(state: &AppState, params: &ExportParams) -> Result<String, HandlerError> {
+    let mut args = vec!["diff", "HEAD", "--binary", "--"];
+    args.extend(params.paths.iter().map(String::as_str));
+    let mut patch = run_git(state, &args)?;
+    if !params.include_untracked {
+        return Ok(patch);
+    }
+
+    let mut args = vec!["ls-files", "--others", "--exclude-standard", "-z", "--"];
+    args.extend(params.paths.iter().map(String::as_str));
+    let untracked = run_git(state, &args)?;
+    let data_dir = state.config.data_dir.to_string_lossy();
+    for file in untracked.split('\0').filter(|file| !file.is_empty()) {
+        if Path::new(file).starts_with(&*data_dir) {
+            continue;
+        }
+        patch.push_str(&addition_diff(&state.config.root, file)?);
+    }
+    Ok(patch)
+}
+
+/// Diff adding an untracked file. `git diff --no-index` exits with 1 when
+/// the inputs differ, which is always the case here.
+fn addition_diff(root: &Path, file: &str) -> Result<String, HandlerError> {
+    let output = Command::new("git")
+        .args(["diff", "--no-index", "--binary", "--", "/dev/null", file])
+        .current_dir(root)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(HandlerError::IoError)?;
+    match output.status.code() {
+        Some(0 | 1) => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        _ => Err(HandlerError::GitError(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+    }
+}
+
+fn selected_files(state: &AppState, paths: &[String]) -> Result<Vec<PathBuf>, HandlerError> {
+    let root = &state.config.root;
+    let data_path = state.config.data_path();
+    if paths.is_empty() {
+        return Ok(archive::workspace_files(root, &data_path));
+    }
+    let mut files = Vec::new();
+    for path in paths {
+        let start = root.join(path);
+        if !start.exists() {
+            return Err(HandlerError::FileNotFound(start));
+        }
+        files.extend(archive::workspace_files(&start, &data_path));
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
@@ -0,0 +1,191 @@
+use quick_xml::events::Event;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    fs,
+    io::{BufReader, Read},
+    path::Path,
+};
+use tracing::{debug, info, info_span};
+
+use super::cancel::CancelToken;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+
+/// Most bytes `word/document.xml` may decompress to, so a zip bomb cannot
+/// exhaust memory.
+const MAX_DOCX_XML_BYTES: u64 = 64 * 1024 * 1024;
+/// XML events parsed between cancellation checks.
+const CANCEL_CHECK_INTERVAL: usize = 4096;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtractTextParams {
+    path: String,
+    max_pages: Option<u32>,
+}
+
+pub fn handle_extract_text(params: Value, cancel: &CancelToken) -> Result<Value, HandlerError> {
+    let span = info_span!("extract_text_operation");
+    let _enter = span.enter();
+
+    let params: ExtractTextParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    if !path.exists() {
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    debug!(path = %params.path, extension = ?extension, "Extracting document text");
+
+    let result = match extension.as_deref() {
+        Some("pdf") => extract_pdf(path, params.max_pages, cancel)?,
+        Some("docx") => extract_docx(path, cancel)?,
+        _ => {
+            return Err(HandlerError::InvalidParams(
+                "Unsupported document type, expected .pdf or .docx".to_string(),
+            ));
+        }
+    };
+
+    info!(
+        path = %params.path,
+        text_length = result["text"].as_str().map(str::len).unwrap_or(0),
+        "Document text extracted successfully"
+    );
+    Ok(result)
+}
+
+fn document_error(e: impl std::fmt::Display) -> HandlerError {
+    HandlerError::InvalidParams(format!("Failed to parse document: {e}"))
+}
+
+fn extract_pdf(
+    path: &Path,
+    max_pages: Option<u32>,
+    cancel: &CancelToken,
+) -> Result<Value, HandlerError> {
+    let document = lopdf::Document::load(path).map_err(document_error)?;
+
+    let all_pages: Vec<u32> = document.get_pages().keys().copied().collect();
+    let total_pages = all_pages.len();
+    let pages: Vec<u32> = match max_pages {
+        Some(max) => all_pages.into_iter().take(max as usize).collect(),
+        None => all_pages,
+    };
+
+    // Pages that fail to decode (unusual fonts, broken streams) are skipped
+    // rather than failing the whole document.
+    let mut page_texts = Vec::with_capacity(pages.len());
+    for page in &pages {
+        cancel.check()?;
+        if let Ok(text) = document.extract_text(&[*page]) {
+            page_texts.push(text);
+        }
+    }
+    let text = page_texts.join("\n\u{c}");
+
+    let outline: Vec<Value> = document
+        .get_toc()
+        .map(|toc| {
+            toc.toc
+                .into_iter()
+                .map(|entry| {
+                    json!({ "title": entry.title, "level": entry.level, "page": entry.page })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(json!({
+        "format": "pdf",
+        "text": text,
+        "outline": outline,
+        "pages": total_pages,
+        "truncated": pages.len() < total_pages,
+    }))
+}
+
+fn extract_docx(path: &Path, cancel: &CancelToken) -> Result<Value, HandlerError> {
+    let file = fs::File::open(path).map_err(HandlerError::IoError)?;
+    let mut archive = zip::ZipArchive::new(file).map_err(document_error)?;
+    let entry = archive
+        .by_name("word/document.xml")
+        .map_err(document_error)?;
+    if entry.size() > MAX_DOCX_XML_BYTES {
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "Document text is {} bytes uncompressed, more than {MAX_DOCX_XML_BYTES}",
+            entry.size()
+        )));
+    }
+
+    // Declared sizes can lie; stop reading at the limit whatever they say.
+    let entry = entry.take(MAX_DOCX_XML_BYTES);
+    let mut reader = quick_xml::Reader::from_reader(BufReader::new(entry));
+    let mut buffer = Vec::new();
+    let mut text = String::new();
+    let mut outline = Vec::new();
+    let mut paragraph = String::new();
+    let mut heading_level: Option<usize> = None;
+    let mut in_text = false;
+
+    for events in 0.. {
+        if events % CANCEL_CHECK_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        match reader
+            .read_event_into(&mut buffer)
+            .map_err(document_error)?
+        {
+            Event::Start(e) if e.name().as_ref() == b"w:t" => in_text = true,
+            Event::End(e) if e.name().as_ref() == b"w:t" => in_text = false,
+            Event::Text(e) if in_text => {
+                paragraph.push_str(&e.unescape().map_err(document_error)?);
+            }
+            Event::Empty(e) | Event::Start(e) => match e.name().as_ref() {
+                b"w:tab" => paragraph.push('\t'),
+                b"w:br" => paragraph.push('\n'),
+                b"w:pStyle" => {
+                    let style = e
+                        .try_get_attribute("w:val")
+                        .map_err(document_error)?
+                        .map(|attr| String::from_utf8_lossy(&attr.value).into_owned());
+                    heading_level = style.as_deref().and_then(heading_level_of);
+                }
+                _ => {}
+            },
+            Event::End(e) if e.name().as_ref() == b"w:p" => {
+                if let Some(level) = heading_level.take()
+                    && !paragraph.trim().is_empty()
+                {
+                    outline.push(json!({ "title": paragraph.trim(), "level": level }));
+                }
+                text.push_str(&paragraph);
+                text.push('\n');
+                paragraph.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buffer.clear();
+    }
+
+    Ok(json!({
+        "format": "docx",
+        "text": text,
+        "outline": outline,
+    }))
+}
+
+/// Maps Word paragraph styles (`Title`, `Heading1`..`Heading9`) to outline levels.
+fn heading_level_of(style: &str) -> Option<usize> {
+    if style == "Title" {
+        return Some(0);
+    }
+    style
+        .strip_prefix("Heading")
+        .and_then(|level| level.parse().ok())
+}
@@ -0,0 +1,93 @@
+//! `detectFileType` and `statPath`: what a path is and, for files, what
+//! they hold (see [`crate::file_type`]).
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{fs, path::Path, time::SystemTime};
+use tracing::{debug, info_span};
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{file_type, state::AppState};
+
+#[derive(Deserialize)]
+struct PathParams {
+    path: String,
+}
+
+fn unix_ms(time: std::io::Result<SystemTime>) -> Option<u64> {
+    time.ok()
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|since| since.as_millis() as u64)
+}
+
+pub fn handle_detect_file_type(params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("detect_file_type_operation");
+    let _enter = span.enter();
+
+    let params: PathParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    if !path.exists() {
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
+    }
+    if path.is_dir() {
+        return Err(HandlerError::InvalidParams(format!(
+            "{} is a directory",
+            params.path
+        )));
+    }
+    let detected = file_type::detect_file(path).map_err(HandlerError::IoError)?;
+    debug!(path = %params.path, mime = detected.mime, "Detected file type");
+    let mut result = json!(detected);
+    result["path"] = params.path.into();
+    Ok(result)
+}
+
+/// One path's metadata, in the shape of a `listFiles` entry plus its
+/// timestamps, the `lockFile` holder if any and, for files, `fileType`.
+/// Symlinks are described, not followed.
+pub fn handle_stat_path(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("stat_path_operation");
+    let _enter = span.enter();
+
+    let params: PathParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(HandlerError::FileNotFound(path.to_path_buf()));
+        }
+        Err(e) => return Err(HandlerError::IoError(e)),
+    };
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut result = if metadata.is_symlink() {
+        json!({
+            "name": name,
+            "type": "symlink",
+            "target": fs::read_link(path).ok(),
+            "targetType": fs::metadata(path).ok().map(|target| {
+                if target.is_dir() { "directory" } else { "file" }
+            }),
+        })
+    } else if metadata.is_dir() {
+        json!({ "name": name, "type": "directory" })
+    } else {
+        json!({
+            "name": name,
+            "type": "file",
+            "size": metadata.len(),
+            "fileType": file_type::detect_file(path).map_err(HandlerError::IoError)?,
+        })
+    };
+    result["path"] = params.path.as_str().into();
+    result["modifiedMs"] = unix_ms(metadata.modified()).into();
+    result["createdMs"] = unix_ms(metadata.created()).into();
+    result["readonly"] = metadata.permissions().readonly().into();
+    result["lockedBy"] = json!(state.locks.holder(path));
+    debug!(path = %params.path, kind = %result["type"], "Stat path");
+    Ok(result)
+}
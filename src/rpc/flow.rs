@@ -0,0 +1,27 @@
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::debug;
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+
+#[derive(Deserialize)]
+struct AckParams {
+    /// Stream kind: `terminal` or `task`.
+    stream: String,
+    /// Terminal or task id.
+    id: u64,
+    /// Bytes of notification `data` the client has processed.
+    bytes: usize,
+}
+
+pub fn handle_ack(connection: &ConnectionContext, params: Value) -> Result<Value, HandlerError> {
+    let params: AckParams = parse_params(params)?;
+    debug!(stream = %params.stream, id = params.id, bytes = params.bytes, "Stream acknowledged");
+    connection
+        .flow
+        .ack(&params.stream, params.id, params.bytes)
+        .map_err(HandlerError::InvalidParams)?;
+    Ok(Value::Bool(true))
+}
@@ -0,0 +1,111 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+use tracing::{debug, info, info_span};
+
+use super::cancel::{self, CancelToken};
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+struct FormatDocumentParams {
+    path: String,
+    content: Option<String>,
+    /// Overrides the extension used to pick a formatter.
+    language: Option<String>,
+}
+
+pub fn handle_format_document(
+    state: &AppState,
+    params: Value,
+    cancel: &CancelToken,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("format_document_operation");
+    let _enter = span.enter();
+
+    let params: FormatDocumentParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+
+    let extension = params
+        .language
+        .clone()
+        .or_else(|| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_default();
+    let formatter = state.config.formatters.get(&extension).ok_or_else(|| {
+        HandlerError::InvalidParams(format!("No formatter configured for .{extension} files"))
+    })?;
+
+    let content = match params.content {
+        Some(content) => content,
+        None => {
+            if !path.exists() {
+                return Err(HandlerError::FileNotFound(path.to_path_buf()));
+            }
+            fs::read_to_string(path).map_err(HandlerError::IoError)?
+        }
+    };
+
+    let args: Vec<String> = formatter
+        .args
+        .iter()
+        .map(|arg| arg.replace("{path}", &params.path))
+        .collect();
+    debug!(command = %formatter.command, args = ?args, "Running formatter");
+
+    let mut child = Command::new(&formatter.command)
+        .args(&args)
+        .current_dir(&state.config.root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            HandlerError::FormatError(format!("Failed to run {}: {e}", formatter.command))
+        })?;
+
+    // Feed stdin from a separate thread so a formatter that writes before
+    // reading all input cannot deadlock on a full pipe.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = content.clone();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let output = cancel::wait_with_output(child, cancel)?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        debug!(status = ?output.status.code(), stderr = %stderr, "Formatter failed");
+        return Err(HandlerError::FormatError(format!(
+            "{} exited with {}: {stderr}",
+            formatter.command,
+            output
+                .status
+                .code()
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "signal".to_string())
+        )));
+    }
+
+    let formatted = String::from_utf8(output.stdout).map_err(|_| {
+        HandlerError::FormatError(format!("{} produced invalid UTF-8", formatter.command))
+    })?;
+
+    info!(
+        path = %params.path,
+        formatter = %formatter.command,
+        changed = formatted != content,
+        "Document formatted successfully"
+    );
+    Ok(json!({ "content": formatted, "changed": formatted != content }))
+}
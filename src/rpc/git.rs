@@ -0,0 +1,304 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::process::{Command, Stdio};
+use tracing::{debug, info, info_span};
+
+use super::cancel::{self, CancelToken};
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BranchesParams {
+    #[serde(default)]
+    include_remote: bool,
+}
+
+#[derive(Deserialize)]
+struct CheckoutParams {
+    branch: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateBranchParams {
+    name: String,
+    start_point: Option<String>,
+    #[serde(default)]
+    checkout: bool,
+}
+
+#[derive(Deserialize)]
+struct DeleteBranchParams {
+    name: String,
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlameParams {
+    path: String,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+}
+
+/// Commit details shared by every blame hunk that references the commit.
+#[derive(Default, Clone)]
+struct BlameCommit {
+    author: String,
+    author_mail: String,
+    author_time: i64,
+    summary: String,
+}
+
+/// Runs `git` in the workspace root, returning stdout on success and the
+/// trimmed stderr as a `GitError` otherwise.
+pub fn run_git(state: &AppState, args: &[&str]) -> Result<String, HandlerError> {
+    run_git_with_cancel(state, args, &CancelToken::default())
+}
+
+/// Like [`run_git`], killing git if the request is cancelled.
+fn run_git_with_cancel(
+    state: &AppState,
+    args: &[&str],
+    cancel: &CancelToken,
+) -> Result<String, HandlerError> {
+    debug!(args = ?args, "Running git");
+    let child = Command::new("git")
+        .args(args)
+        .current_dir(&state.config.root)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(HandlerError::IoError)?;
+    let output = cancel::wait_with_output(child, cancel)?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        debug!(args = ?args, stderr = %stderr, "git command failed");
+        Err(classify_git_error(stderr))
+    }
+}
+
+/// Turns git's "would be overwritten" failures into a structured
+/// `DirtyWorkspace` error listing the conflicting files.
+fn classify_git_error(stderr: String) -> HandlerError {
+    if !stderr.contains("would be overwritten") {
+        return HandlerError::GitError(stderr);
+    }
+
+    let files = stderr
+        .lines()
+        .filter(|line| line.starts_with('\t'))
+        .map(|line| line.trim().to_string())
+        .collect();
+    HandlerError::DirtyWorkspace(files)
+}
+
+fn validate_branch_name(name: &str) -> Result<(), HandlerError> {
+    if name.is_empty() || name.starts_with('-') {
+        return Err(HandlerError::InvalidParams(format!(
+            "Invalid branch name: {name}"
+        )));
+    }
+    Ok(())
+}
+
+pub fn handle_branches(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("git_branches_operation");
+    let _enter = span.enter();
+
+    let params: BranchesParams = parse_params(params)?;
+
+    let mut args = vec![
+        "for-each-ref",
+        "--format=%(refname)%00%(objectname:short)%00%(HEAD)%00%(upstream:short)",
+        "refs/heads",
+    ];
+    if params.include_remote {
+        args.push("refs/remotes");
+    }
+    let output = run_git(state, &args)?;
+
+    let mut current = Value::Null;
+    let mut branches = Vec::new();
+    for line in output.lines() {
+        let mut fields = line.split('\0');
+        let (Some(refname), Some(commit), Some(head), Some(upstream)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+
+        let (name, remote) = match refname.strip_prefix("refs/heads/") {
+            Some(name) => (name, false),
+            None => (refname.trim_start_matches("refs/remotes/"), true),
+        };
+        if remote && name.ends_with("/HEAD") {
+            continue;
+        }
+
+        let is_current = head == "*";
+        if is_current {
+            current = Value::String(name.to_string());
+        }
+        branches.push(json!({
+            "name": name,
+            "commit": commit,
+            "current": is_current,
+            "remote": remote,
+            "upstream": if upstream.is_empty() { Value::Null } else { Value::String(upstream.to_string()) },
+        }));
+    }
+
+    info!(
+        total_branches = branches.len(),
+        "Branches listed successfully"
+    );
+    Ok(json!({ "current": current, "branches": branches }))
+}
+
+pub fn handle_checkout(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("git_checkout_operation");
+    let _enter = span.enter();
+
+    let params: CheckoutParams = parse_params(params)?;
+    validate_branch_name(&params.branch)?;
+
+    run_git(state, &["checkout", &params.branch, "--"])?;
+
+    info!(branch = %params.branch, "Branch checked out successfully");
+    Ok(Value::Bool(true))
+}
+
+pub fn handle_create_branch(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("git_create_branch_operation");
+    let _enter = span.enter();
+
+    let params: CreateBranchParams = parse_params(params)?;
+    validate_branch_name(&params.name)?;
+
+    let mut args = if params.checkout {
+        vec!["checkout", "-b", params.name.as_str()]
+    } else {
+        vec!["branch", params.name.as_str()]
+    };
+    if let Some(start_point) = &params.start_point {
+        validate_branch_name(start_point)?;
+        args.push(start_point);
+    }
+    run_git(state, &args)?;
+
+    info!(branch = %params.name, checkout = params.checkout, "Branch created successfully");
+    Ok(Value::Bool(true))
+}
+
+pub fn handle_delete_branch(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("git_delete_branch_operation");
+    let _enter = span.enter();
+
+    let params: DeleteBranchParams = parse_params(params)?;
+    validate_branch_name(&params.name)?;
+
+    let flag = if params.force { "-D" } else { "-d" };
+    run_git(state, &["branch", flag, &params.name])?;
+
+    info!(branch = %params.name, force = params.force, "Branch deleted successfully");
+    Ok(Value::Bool(true))
+}
+
+pub fn handle_blame(
+    state: &AppState,
+    params: Value,
+    cancel: &CancelToken,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("git_blame_operation");
+    let _enter = span.enter();
+
+    let params: BlameParams = parse_params(params)?;
+
+    // Lines are zero-based with an exclusive end, git wants 1-based inclusive.
+    let line_range = match (params.start_line, params.end_line) {
+        (None, None) => None,
+        (start, end) => {
+            let start = start.unwrap_or(0) + 1;
+            let end = match end {
+                Some(end) if end < start => {
+                    return Err(HandlerError::InvalidParams(
+                        "endLine must be greater than startLine".to_string(),
+                    ));
+                }
+                Some(end) => end.to_string(),
+                None => String::new(),
+            };
+            Some(format!("{start},{end}"))
+        }
+    };
+
+    let mut args = vec!["blame", "--porcelain"];
+    if let Some(range) = &line_range {
+        args.extend(["-L", range]);
+    }
+    args.extend(["--", &params.path]);
+    let output = run_git_with_cancel(state, &args, cancel)?;
+
+    let mut commits: std::collections::HashMap<String, BlameCommit> = Default::default();
+    let mut hunks: Vec<Value> = Vec::new();
+    let mut lines = output.lines();
+
+    while let Some(header) = lines.next() {
+        let mut fields = header.split(' ');
+        let (Some(hash), Some(_), Some(final_line)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let Ok(final_line) = final_line.parse::<usize>() else {
+            continue;
+        };
+
+        let commit = commits.entry(hash.to_string()).or_default();
+        for line in lines.by_ref() {
+            if line.starts_with('\t') {
+                break;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "author" => commit.author = value.to_string(),
+                "author-mail" => {
+                    commit.author_mail = value.trim_matches(['<', '>']).to_string();
+                }
+                "author-time" => commit.author_time = value.parse().unwrap_or_default(),
+                "summary" => commit.summary = value.to_string(),
+                _ => {}
+            }
+        }
+
+        let line = final_line - 1;
+        if let Some(last) = hunks.last_mut()
+            && last["commit"] == hash
+            && last["endLine"] == line
+        {
+            last["endLine"] = Value::from(line + 1);
+            continue;
+        }
+
+        let commit = commit.clone();
+        hunks.push(json!({
+            "startLine": line,
+            "endLine": line + 1,
+            "commit": hash,
+            "uncommitted": hash.bytes().all(|b| b == b'0'),
+            "author": commit.author,
+            "authorMail": commit.author_mail,
+            "timestamp": commit.author_time,
+            "summary": commit.summary,
+        }));
+    }
+
+    info!(path = %params.path, total_hunks = hunks.len(), "Blame computed successfully");
+    Ok(Value::Array(hunks))
+}
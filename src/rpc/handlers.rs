@@ -1,61 +1,443 @@
-use crate::rpc::error::{
-    DIRECTORY_ERROR_CODE, FILE_NOT_FOUND_CODE, INVALID_PARAMS_CODE, IO_ERROR_CODE,
-    METHOD_NOT_FOUND_CODE,
-};
+use crate::charset::{self, Charset};
+use crate::line_ending::{self, LineEndingPolicy};
+use crate::merge;
+use crate::protected::audit_forced;
+use crate::rpc::error::METHOD_NOT_FOUND_CODE;
+use crate::state::{AppState, SharedState};
 
-use super::error::create_error_response;
+use super::cancel::CancelToken;
+use super::context::ConnectionContext;
+use super::error::{HandlerError, create_error_response};
+use super::registry::{Call, MethodRegistry};
 use super::request::{JsonRpcRequest, JsonRpcResponse};
-use serde::Deserialize;
+use super::{
+    activity, archive, audit, blob, cancel, capabilities, catalog, compression, dap, debug,
+    definition, delta, diff, disk_usage, document, dry_run, export, extract, file_type, flow,
+    format, git, history, info, initialize, interceptors, jobs, locks, lsp, patch, plain_text,
+    presence, problems, recent, reload, replace, scan, search, share, snippet, spell, stats,
+    structured, syntax, table, task, template, terminal, text, trash, upload, validate, watch,
+    workspace,
+};
+use serde::{Deserialize, de::DeserializeOwned};
 use serde_json::Value;
-use std::{fs, io::Write, path::Path};
-use tracing::{debug, error, info, info_span, warn};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    time::Instant,
+};
+use tracing::{debug, field, info, info_span, warn};
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct ReadFileParams {
     path: String,
+    /// Return `{content, hash}` instead of the bare content.
+    #[serde(default)]
+    include_hash: bool,
+    /// Hash from an earlier read; when the file still has it the content
+    /// is not sent and the result is `{notModified: true, hash}`.
+    if_none_match: Option<String>,
+    /// Decode as this instead of detecting the encoding.
+    encoding: Option<Charset>,
+    /// Return `{content, encoding, bom, lineEnding}` instead of the bare
+    /// content; `lineEnding` is `lf`, `crlf` or null without line breaks.
+    #[serde(default)]
+    include_encoding: bool,
+}
+
+#[derive(Deserialize)]
+struct HashFileParams {
+    path: String,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct WriteFileParams {
     path: String,
     content: String,
+    #[serde(default)]
+    force: bool,
+    /// Encoding to write; UTF-8 by default.
+    encoding: Option<Charset>,
+    /// Start with a byte order mark. By default UTF-16 gets one and UTF-8
+    /// does not.
+    bom: Option<bool>,
+    /// Overrides the `lineEndings` config for this write.
+    line_ending: Option<LineEndingPolicy>,
+    /// `hash` of the file as the client last read it; the write is refused
+    /// if the file has changed since.
+    if_match: Option<String>,
+    /// On such a conflict, merge instead of failing.
+    #[serde(default)]
+    merge: bool,
+    /// Content the client's edit started from, for `merge`. Looked up in
+    /// the file's history when missing.
+    base: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteFileParams {
+    path: String,
+    #[serde(default)]
+    force: bool,
+    /// Delete outright instead of moving to the trash.
+    #[serde(default)]
+    permanent: bool,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct ListFilesParams {
     path: String,
+    /// Entries to skip; with `limit`, switches the result to a page.
+    offset: Option<usize>,
+    limit: Option<usize>,
+    /// Only list entries whose name matches this glob, e.g. `*.rs`.
+    glob: Option<String>,
+    /// Dotfiles are listed unless this is false.
+    #[serde(default = "default_true")]
+    include_hidden: bool,
+    /// Add each entry's `modifiedMs`.
+    #[serde(default)]
+    include_mtime: bool,
+    /// Add each file's `fileType`, which reads the start of every file.
+    #[serde(default)]
+    include_type: bool,
+    /// Leave out what `.gitignore`, `.ignore` and the server's `exclude`
+    /// list exclude.
+    #[serde(default = "default_true")]
+    respect_ignore: bool,
 }
 
-#[derive(Debug)]
-enum HandlerError {
-    InvalidParams(String),
-    FileNotFound,
-    DirectoryError(String),
-    IoError(std::io::Error),
+fn default_true() -> bool {
+    true
 }
-impl HandlerError {
-    fn to_jsonrpc_error(&self, id: Value) -> JsonRpcResponse {
-        match self {
-            HandlerError::InvalidParams(msg) => {
-                error!(error_type = "invalid_params", message = %msg, "Request failed");
-                create_error_response(INVALID_PARAMS_CODE, msg, id)
-            }
-            HandlerError::FileNotFound => {
-                error!(error_type = "file_not_found", "Request failed");
-                create_error_response(FILE_NOT_FOUND_CODE, "File not found", id)
-            }
-            HandlerError::DirectoryError(msg) => {
-                error!(error_type = "directory_error", message = %msg, "Request failed");
-                create_error_response(DIRECTORY_ERROR_CODE, msg, id)
-            }
-            HandlerError::IoError(e) => {
-                error!(error_type = "io_error", error = %e, "Request failed");
-                create_error_response(IO_ERROR_CODE, &e.to_string(), id)
-            }
-        }
-    }
+
+/// Methods that modify the workspace or run commands, refused with
+/// READ_ONLY when the server is read-only.
+pub const MUTATING_METHODS: &[&str] = &[
+    "addToDictionary",
+    "applyPatch",
+    "createFromTemplate",
+    "dap/start",
+    "deleteFile",
+    "document/save",
+    "document/update",
+    "git/checkout",
+    "git/createBranch",
+    "git/deleteBranch",
+    "history/restore",
+    "importZip",
+    "jobs/run",
+    "replaceInFiles",
+    "saveAll",
+    "snippet/run",
+    "structuredSet",
+    "table/updateCell",
+    "task/run",
+    "terminal/create",
+    "trash/empty",
+    "trash/restore",
+    "uploadFile",
+    "writeFile",
+];
+
+/// Methods whose v1 shape takes host paths, translated to `workspace://`
+/// URIs while `rawPaths` is on.
+pub const RAW_PATH_METHODS: &[&str] = &["listFiles", "readFile", "writeFile"];
+
+/// The methods every server answers, registered at startup.
+pub fn builtin_methods() -> MethodRegistry {
+    let mut registry = MethodRegistry::default();
+    registry
+        .intercept(interceptors::Timing)
+        .intercept(validate::Validate)
+        .intercept(interceptors::DryRun)
+        .intercept(interceptors::ReadOnly)
+        .intercept(interceptors::Confine)
+        .intercept(interceptors::PathAccess)
+        .intercept(interceptors::Symlinks)
+        .intercept(interceptors::Policy)
+        .intercept(interceptors::Audit);
+    registry
+        // Sockets answer valid cancellations before queueing them; this
+        // serves HTTP callers, whose requests each run alone.
+        .method("$/cancelRequest", |call| {
+            cancel::handle_cancel_request(call.connection, call.params)
+        })
+        .method("activity/list", |call| {
+            activity::handle_list(call.state, call.params)
+        })
+        .method("addToDictionary", |call| {
+            spell::handle_add_to_dictionary(call.state, call.params)
+        })
+        .method("applyPatch", |call| {
+            patch::handle_apply_patch(call.state, call.connection, call.params)
+        })
+        .method("audit/query", |call| {
+            audit::handle_query(call.state, call.params)
+        })
+        .method("blob/get", |call| blob::handle_get(call.state, call.params))
+        .method("blob/put", |call| {
+            blob::handle_put(call.state, call.connection, call.params)
+        })
+        .method("computeDiff", |call| {
+            diff::handle_compute_diff(call.state, call.params)
+        })
+        .method("config/reload", |call| reload::handle_reload(call.state))
+        .method("connection/stats", |call| {
+            stats::handle_stats(call.connection)
+        })
+        .method("createFromTemplate", |call| {
+            template::handle_create(call.state, call.connection, call.params)
+        })
+        .register(dap::RequestHandler)
+        .method("dap/respond", |call| {
+            dap::handle_respond(call.state, call.connection, call.params)
+        })
+        .method("dap/start", |call| {
+            dap::handle_start(call.state, call.connection, call.params)
+        })
+        .method("dap/stop", |call| {
+            dap::handle_stop(call.state, call.connection, call.params)
+        })
+        .method("debug/recentRequests", |call| {
+            debug::handle_recent_requests(call.state, call.params)
+        })
+        .method("definition", |call| {
+            definition::handle_definition(call.state, call.params)
+        })
+        .method("deleteFile", |call| {
+            handle_delete_file(call.state, call.connection, call.params)
+        })
+        .method("detectFileType", |call| {
+            file_type::handle_detect_file_type(call.params)
+        })
+        .method("diskUsage", |call| {
+            disk_usage::handle_disk_usage(call.state, call.params, call.cancel)
+        })
+        .method("document/close", |call| {
+            document::handle_close(call.state, call.connection, call.params)
+        })
+        .method("document/open", |call| {
+            document::handle_open(call.state, call.connection, call.params)
+        })
+        .method("document/save", |call| {
+            document::handle_save(call.state, call.connection, call.params)
+        })
+        .method("document/update", |call| {
+            document::handle_update(call.state, call.connection, call.params)
+        })
+        .method("documentSymbols", |call| {
+            syntax::handle_document_symbols(call.state, call.params)
+        })
+        .method("exportZip", |call| {
+            archive::handle_export_zip(call.state, call.params, call.cancel)
+        })
+        .method("extractText", |call| {
+            extract::handle_extract_text(call.params, call.cancel)
+        })
+        .method("findFiles", |call| {
+            search::handle_find_files(call.state, call.params)
+        })
+        .method("formatDocument", |call| {
+            format::handle_format_document(call.state, call.params, call.cancel)
+        })
+        .method("git/blame", |call| {
+            git::handle_blame(call.state, call.params, call.cancel)
+        })
+        .method("git/branches", |call| {
+            git::handle_branches(call.state, call.params)
+        })
+        .method("git/checkout", |call| {
+            git::handle_checkout(call.state, call.params)
+        })
+        .method("git/createBranch", |call| {
+            git::handle_create_branch(call.state, call.params)
+        })
+        .method("git/deleteBranch", |call| {
+            git::handle_delete_branch(call.state, call.params)
+        })
+        .method("hashFile", |call| handle_hash_file(call.params))
+        .method("highlight", |call| {
+            syntax::handle_highlight(call.state, call.params, call.cancel)
+        })
+        .method("history/list", |call| {
+            history::handle_list(call.state, call.params)
+        })
+        .method("history/restore", |call| {
+            history::handle_restore(call.state, call.connection, call.params)
+        })
+        .method("importZip", |call| {
+            archive::handle_import_zip(call.state, call.connection, call.params, call.cancel)
+        })
+        .method("initialize", |call| {
+            initialize::handle_initialize(call.state, call.connection, call.params)
+        })
+        .method("jobs/history", |call| {
+            jobs::handle_history(call.state, call.params)
+        })
+        .method("jobs/list", |call| jobs::handle_list(call.state))
+        .method("jobs/run", |call| jobs::handle_run(call.state, call.params))
+        .method("listFiles", |call| {
+            handle_list_files(call.state, call.params)
+        })
+        .method("lockFile", |call| {
+            locks::handle_lock(call.state, call.connection, call.params)
+        })
+        .method("lsp/notify", |call| {
+            lsp::handle_notify(call.state, call.connection, call.params)
+        })
+        .register(lsp::RequestHandler)
+        .method("lsp/respond", |call| {
+            lsp::handle_respond(call.state, call.connection, call.params)
+        })
+        .method("lsp/stop", |call| {
+            lsp::handle_stop(call.state, call.connection, call.params)
+        })
+        .method("plainText/diff", |call| {
+            plain_text::handle_diff(call.state, call.params)
+        })
+        .method("plainText/problems", |call| {
+            plain_text::handle_problems(call.state, call.params)
+        })
+        .method("plainText/symbols", |call| {
+            plain_text::handle_symbols(call.state, call.params)
+        })
+        .method("plainText/tree", |call| {
+            plain_text::handle_tree(call.params)
+        })
+        .method("presence/list", |call| presence::handle_list(call.state))
+        .method("presence/update", |call| {
+            presence::handle_update(call.state, call.connection, call.params)
+        })
+        .method("problems/list", |call| {
+            problems::handle_list(call.state, call.params)
+        })
+        .method("readFile", |call| handle_read_file(call.state, call.params))
+        .method("readFileDelta", |call| {
+            delta::handle_read_file_delta(call.params)
+        })
+        .method("recentFiles", |call| {
+            recent::handle_recent_files(call.state, call.params)
+        })
+        .method("replaceInFiles", |call| {
+            replace::handle_replace_in_files(call.state, call.connection, call.params)
+        })
+        .method("saveAll", |call| {
+            document::handle_save_all(call.state, call.connection)
+        })
+        .method("scan/run", |call| {
+            scan::handle_run(call.state, call.connection, call.params)
+        })
+        .method("searchInFiles", |call| {
+            search::handle_search_in_files(call.state, call.params, call.cancel)
+        })
+        .method("server/capabilities", |call| {
+            capabilities::handle_capabilities(call.state)
+        })
+        .method("server/errorCatalog", |_| catalog::handle_error_catalog())
+        .method("server/info", |call| info::handle_info(call.state))
+        .method("share/create", |call| {
+            share::handle_create(call.state, call.connection, call.params)
+        })
+        .method("share/list", |call| share::handle_list(call.state))
+        .method("share/revoke", |call| {
+            share::handle_revoke(call.state, call.params)
+        })
+        .method("snippet/languages", |call| {
+            snippet::handle_languages(call.state)
+        })
+        .method("snippet/run", |call| {
+            snippet::handle_run(call.state, call.params, call.cancel)
+        })
+        .method("spellCheck", |call| {
+            spell::handle_spell_check(call.state, call.params, call.cancel)
+        })
+        .method("statPath", |call| {
+            file_type::handle_stat_path(call.state, call.params)
+        })
+        .method("stream/ack", |call| {
+            flow::handle_ack(call.connection, call.params)
+        })
+        .method("structuredGet", |call| {
+            structured::handle_structured_get(call.params)
+        })
+        .method("structuredSet", |call| {
+            structured::handle_structured_set(call.state, call.params)
+        })
+        .method("table/read", |call| {
+            table::handle_table_read(call.params, call.cancel)
+        })
+        .method("table/updateCell", |call| {
+            table::handle_table_update_cell(call.state, call.params)
+        })
+        .method("task/cancel", |call| {
+            task::handle_cancel(call.state, call.connection, call.params)
+        })
+        .method("task/list", |call| task::handle_list(call.state))
+        .method("task/run", |call| {
+            task::handle_run(call.state, call.connection, call.params)
+        })
+        .method("templates/list", |call| template::handle_list(call.state))
+        .method("terminal/create", |call| {
+            terminal::handle_create(call.state, call.connection, call.params)
+        })
+        .method("terminal/input", |call| {
+            terminal::handle_input(call.state, call.connection, call.params)
+        })
+        .method("terminal/kill", |call| {
+            terminal::handle_kill(call.state, call.connection, call.params)
+        })
+        .method("terminal/resize", |call| {
+            terminal::handle_resize(call.state, call.connection, call.params)
+        })
+        .method("transformText", |call| {
+            text::handle_transform_text(call.params)
+        })
+        .method("trash/empty", |call| {
+            trash::handle_empty(call.state, call.params)
+        })
+        .method("trash/list", |call| trash::handle_list(call.state))
+        .method("trash/restore", |call| {
+            trash::handle_restore(call.state, call.connection, call.params)
+        })
+        .method("unlockFile", |call| {
+            locks::handle_unlock(call.state, call.connection, call.params)
+        })
+        .method("uploadFile", |call| {
+            upload::handle_upload_file(call.state, call.connection, call.params)
+        })
+        .method("watch/start", |call| {
+            watch::handle_start(call.state, call.connection, call.params)
+        })
+        .method("watch/stop", |call| {
+            watch::handle_stop(call.state, call.connection, call.params)
+        })
+        .method("workspace/export", |call| {
+            export::handle_export(call.state, call.params)
+        })
+        .method("workspace/list", |call| workspace::handle_list(call.state))
+        .method("workspaceSymbols", |call| {
+            search::handle_workspace_symbols(call.state, call.params)
+        })
+        .method("writeFile", |call| {
+            handle_write_file(call.state, call.connection, call.params)
+        });
+    registry
 }
 
-pub fn process_request(request: JsonRpcRequest) -> JsonRpcResponse {
+pub fn process_request(
+    state: &SharedState,
+    connection: &ConnectionContext,
+    mut request: JsonRpcRequest,
+    cancel: &CancelToken,
+) -> Option<JsonRpcResponse> {
     let method = &request.method;
     let request_id = request
         .id
@@ -67,49 +449,123 @@ pub fn process_request(request: JsonRpcRequest) -> JsonRpcResponse {
         "rpc_request",
         method = %method,
         request_id = %request_id,
-        has_params = !request.params.is_null()
+        has_params = !request.params.is_null(),
+        duration_ms = field::Empty
     );
     let _enter = span.enter();
 
     info!("Processing JSON-RPC request");
 
+    if let Err(reason) = request.validate() {
+        let id = match request.id {
+            Some(id @ (Value::String(_) | Value::Number(_))) => id,
+            _ => Value::Null,
+        };
+        return Some(HandlerError::InvalidRequest(reason).to_jsonrpc_error(id));
+    }
+
+    // JSON-RPC notifications are never answered, even when they fail.
+    let notification = request.id.is_none();
     let id = request.id.unwrap_or(Value::Null);
 
-    let result = match request.method.as_str() {
-        "readFile" => {
-            debug!("Handling readFile request");
-            handle_read_file(request.params)
-        }
-        "writeFile" => {
-            debug!("Handling writeFile request");
-            handle_write_file(request.params)
-        }
-        "listFiles" => {
-            debug!("Handling listFiles request");
-            handle_list_files(request.params)
-        }
-        _ => {
-            warn!(method = %request.method, "Unknown method requested");
-            return create_error_response(METHOD_NOT_FOUND_CODE, "Method not Found", id);
-        }
+    if let Some(tenant) = &connection.identity.tenant {
+        tenant.rebase(&mut request.params, &state.uris);
+    }
+    if RAW_PATH_METHODS.contains(&method.as_str())
+        && let Err(e) = state
+            .uris
+            .translate_raw_paths(&mut request.params, state.config.raw_paths)
+    {
+        return (!notification).then(|| e.to_jsonrpc_error(id));
+    }
+    if let Err(e) = state.uris.resolve_params(&mut request.params) {
+        return (!notification).then(|| e.to_jsonrpc_error(id));
+    }
+
+    let Some(handler) = state.methods.get(method) else {
+        warn!(method = %request.method, "Unknown method requested");
+        return (!notification)
+            .then(|| create_error_response(METHOD_NOT_FOUND_CODE, "Method not Found", id));
+    };
+    let call = Call {
+        state,
+        connection,
+        method,
+        id: &id,
+        correlation_id: &request.correlation_id,
+        params: request.params,
+        cancel,
     };
+    let result = match state.methods.dispatch(handler, call) {
+        Ok(Some(value)) => Ok(value),
+        // The handler replies itself once it can.
+        Ok(None) => return None,
+        Err(e) => Err(e),
+    };
+
+    if notification {
+        if let Err(e) = result {
+            debug!(error = ?e, "Notification failed");
+        }
+        return None;
+    }
 
-    match result {
-        Ok(value) => {
+    let uris = connection.notifier.uris();
+    Some(match result {
+        Ok(mut value) => {
             info!("Request processed successfully");
+            if let Some(uris) = &uris {
+                uris.rewrite_payload(&mut value);
+            }
+            let encoding = connection.session().compression;
+            let value = match encoding {
+                Some(encoding) => compression::compress_result(
+                    encoding,
+                    state.config.compression_threshold,
+                    value,
+                ),
+                None => value,
+            };
             JsonRpcResponse {
                 jsonrpc: "2.0".to_string(),
                 result: Some(value),
                 error: None,
                 id,
+                correlation_id: None,
             }
         }
-        Err(e) => e.to_jsonrpc_error(id),
-    }
+        Err(e) => {
+            let mut response = e.to_jsonrpc_error(id);
+            if let Some(uris) = &uris
+                && let Some(data) = response.error.as_mut().and_then(|e| e.data.as_mut())
+            {
+                uris.rewrite_payload(data);
+            }
+            response
+        }
+    })
 }
 
-fn handle_read_file(params: Value) -> Result<Value, HandlerError> {
-    let file_span = info_span!("read_file_operation");
+/// Deserializes request params, treating `null` as an empty object so that
+/// methods whose params are all optional can be called without any.
+pub fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, HandlerError> {
+    let params = if params.is_null() {
+        Value::Object(Default::default())
+    } else {
+        params
+    };
+    serde_json::from_value(params).map_err(|e| {
+        debug!(error = %e, "Failed to deserialize parameters");
+        HandlerError::InvalidParams(e.to_string())
+    })
+}
+
+fn handle_read_file(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let file_span = info_span!(
+        "read_file_operation",
+        bytes = field::Empty,
+        duration_ms = field::Empty
+    );
     let _enter = file_span.enter();
 
     let params: ReadFileParams = serde_json::from_value(params).map_err(|e| {
@@ -122,24 +578,114 @@ fn handle_read_file(params: Value) -> Result<Value, HandlerError> {
 
     if !path.exists() {
         debug!(path = %params.path, "File does not exist");
-        return Err(HandlerError::FileNotFound);
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
     }
 
-    let content = fs::read_to_string(path).map_err(|e| {
+    let size = fs::metadata(path).map_err(HandlerError::IoError)?.len();
+    let limit = state.limits().max_read_bytes;
+    if size > limit {
+        debug!(path = %params.path, size, limit, "File exceeds read limit");
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "File is {size} bytes, larger than the {limit} byte read limit"
+        )));
+    }
+
+    let started = Instant::now();
+    let bytes = fs::read(path).map_err(|e| {
         debug!(path = %params.path, error = %e, "Failed to read file content");
         HandlerError::IoError(e)
     })?;
+    file_span.record("bytes", bytes.len());
+    file_span.record("duration_ms", started.elapsed().as_millis() as u64);
+    let decoded = charset::decode(&bytes, params.encoding).map_err(HandlerError::InvalidParams)?;
+    if decoded.charset != Charset::Utf8 {
+        debug!(path = %params.path, encoding = decoded.charset.name(), "Decoded non-UTF-8 file");
+    }
+    let content = decoded.text;
 
+    if !params.include_hash && params.if_none_match.is_none() && !params.include_encoding {
+        info!(
+            path = %params.path,
+            content_length = content.len(),
+            "File read successfully"
+        );
+        return Ok(Value::String(content));
+    }
+
+    // Of the bytes on disk, so it matches `hashFile` whatever the encoding.
+    let hash = blake3::hash(&bytes).to_hex().to_string();
+    if params.if_none_match.as_deref() == Some(hash.as_str()) {
+        debug!(path = %params.path, "File unchanged since last read");
+        return Ok(serde_json::json!({ "notModified": true, "hash": hash }));
+    }
     info!(
         path = %params.path,
         content_length = content.len(),
         "File read successfully"
     );
-    Ok(Value::String(content))
+    let style = line_ending::detect(&content);
+    let mut result = serde_json::json!({ "content": content });
+    if params.include_hash || params.if_none_match.is_some() {
+        result["hash"] = hash.into();
+    }
+    if params.include_encoding {
+        result["encoding"] = decoded.charset.name().into();
+        result["bom"] = decoded.bom.into();
+        result["lineEnding"] = serde_json::json!(style);
+    }
+    Ok(result)
+}
+
+/// BLAKE3 hash of a file's contents, matching the `hash` `readFile`
+/// reports, with its size and modification time.
+fn handle_hash_file(params: Value) -> Result<Value, HandlerError> {
+    let file_span = info_span!(
+        "hash_file_operation",
+        bytes = field::Empty,
+        duration_ms = field::Empty
+    );
+    let _enter = file_span.enter();
+
+    let params: HashFileParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    if !path.is_file() {
+        debug!(path = %params.path, "File does not exist");
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
+    }
+
+    let started = Instant::now();
+    let mut file = fs::File::open(path).map_err(HandlerError::IoError)?;
+    let metadata = file.metadata().map_err(HandlerError::IoError)?;
+    let mut hasher = blake3::Hasher::new();
+    hasher
+        .update_reader(&mut file)
+        .map_err(HandlerError::IoError)?;
+    file_span.record("bytes", metadata.len());
+    file_span.record("duration_ms", started.elapsed().as_millis() as u64);
+    let modified_ms = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|since| since.as_millis() as u64);
+
+    debug!(path = %params.path, size = metadata.len(), "File hashed");
+    Ok(serde_json::json!({
+        "hash": hasher.finalize().to_hex().to_string(),
+        "size": metadata.len(),
+        "modifiedMs": modified_ms,
+    }))
 }
 
-fn handle_write_file(params: Value) -> Result<Value, HandlerError> {
-    let file_span = info_span!("write_file_operation");
+fn handle_write_file(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let file_span = info_span!(
+        "write_file_operation",
+        bytes = field::Empty,
+        duration_ms = field::Empty
+    );
     let _enter = file_span.enter();
 
     let params: WriteFileParams = serde_json::from_value(params).map_err(|e| {
@@ -152,27 +698,218 @@ fn handle_write_file(params: Value) -> Result<Value, HandlerError> {
         content_length = params.content.len(),
         "Writing file"
     );
+    let limit = state.limits().max_write_bytes;
+    if params.content.len() > limit {
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "Content is {} bytes, larger than the {limit} byte write limit",
+            params.content.len()
+        )));
+    }
     let path = Path::new(&params.path);
 
+    if path.exists() && state.protected.is_protected(path) {
+        if !params.force {
+            debug!(path = %params.path, "Refusing to overwrite protected path");
+            return Err(HandlerError::ProtectedPath(params.path));
+        }
+        audit_forced("writeFile", &params.path);
+    }
+    if let Some(expected) = &params.if_match {
+        let current = fs::read(path).ok();
+        let actual = current
+            .as_ref()
+            .map(|bytes| blake3::hash(bytes).to_hex().to_string());
+        if actual.as_ref() != Some(expected) {
+            if !params.merge {
+                return Err(HandlerError::WriteConflict {
+                    path: params.path,
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+            return merge_conflict(state, &params, current, actual);
+        }
+    }
+
+    let policy = params.line_ending.unwrap_or(state.config.line_endings);
+    let existing = (policy == LineEndingPolicy::Preserve)
+        .then(|| fs::read(path).ok())
+        .flatten()
+        .and_then(|bytes| charset::decode(&bytes, None).ok());
+    let content = policy
+        .style(
+            &params.content,
+            existing.as_ref().map(|existing| existing.text.as_str()),
+        )
+        .map(|style| line_ending::convert(&params.content, style));
+    let content = content.as_deref().unwrap_or(&params.content);
+    let encoding = params.encoding.unwrap_or(Charset::Utf8);
+    let bom = params
+        .bom
+        .unwrap_or(matches!(encoding, Charset::Utf16Le | Charset::Utf16Be));
+    let bytes = charset::encode(content, encoding, bom).map_err(HandlerError::InvalidParams)?;
+    if params.dry_run {
+        debug!(path = %params.path, bytes = bytes.len(), "Previewing write");
+        return Ok(dry_run::preview(state, path, &bytes));
+    }
+
+    // A snapshot that fails is no reason to refuse the save.
+    if let Err(e) = state.history.snapshot(path) {
+        warn!(path = %params.path, error = %e, "Failed to keep previous version");
+    }
+    let started = Instant::now();
     let mut file = fs::File::create(path).map_err(|e| {
         debug!(path = %params.path, error = %e, "Failed to create file");
         HandlerError::IoError(e)
     })?;
 
-    file.write_all(params.content.as_bytes()).map_err(|e| {
+    file.write_all(&bytes).map_err(|e| {
         debug!(path = %params.path, error = %e, "Failed to write file content");
         HandlerError::IoError(e)
     })?;
+    file_span.record("bytes", bytes.len());
+    file_span.record("duration_ms", started.elapsed().as_millis() as u64);
 
     info!(
         path = %params.path,
         content_length = params.content.len(),
         "File written successfully"
     );
+    state.activity.record(
+        "file.saved",
+        Some(connection.id),
+        serde_json::json!({ "path": params.path, "bytes": bytes.len() }),
+    );
     Ok(Value::Bool(true))
 }
 
-fn handle_list_files(params: Value) -> Result<Value, HandlerError> {
+/// Merges the client's content with what changed on disk since `base`,
+/// for `writeFile` with `merge`. Nothing is written: the client shows the
+/// result, conflict markers and all, and writes it back with the returned
+/// `hash` as `ifMatch`.
+fn merge_conflict(
+    state: &AppState,
+    params: &WriteFileParams,
+    current: Option<Vec<u8>>,
+    actual: Option<String>,
+) -> Result<Value, HandlerError> {
+    let path = Path::new(&params.path);
+    let expected = params.if_match.as_deref().unwrap_or_default();
+    let base = match &params.base {
+        Some(base) => base.clone(),
+        None => history_version(state, path, expected)
+            .map_err(HandlerError::IoError)?
+            .ok_or_else(|| {
+                HandlerError::InvalidParams(format!(
+                    "No version of {} hashes to {expected}; pass base to merge",
+                    params.path
+                ))
+            })?,
+    };
+    let theirs = match current {
+        Some(bytes) => {
+            charset::decode(&bytes, None)
+                .map_err(HandlerError::InvalidParams)?
+                .text
+        }
+        None => String::new(),
+    };
+    let merged = merge::merge(&base, &params.content, &theirs, "yours", "disk");
+    info!(
+        path = %params.path,
+        conflicts = merged.conflicts.len(),
+        "Merged write with changes on disk"
+    );
+    Ok(serde_json::json!({
+        "written": false,
+        "merged": merged.content,
+        "conflicts": merged.conflicts,
+        "hash": actual,
+    }))
+}
+
+/// The decoded text of the history version of `path` whose bytes hash to
+/// `hash`, if one was kept.
+fn history_version(state: &AppState, path: &Path, hash: &str) -> io::Result<Option<String>> {
+    for version in state.history.list(path)? {
+        let bytes = state.history.read(path, &version.id)?;
+        if blake3::hash(&bytes).to_hex().as_str() == hash {
+            return Ok(charset::decode(&bytes, None)
+                .ok()
+                .map(|decoded| decoded.text));
+        }
+    }
+    Ok(None)
+}
+
+/// Moves a file or directory to the workspace trash, where `trash/restore`
+/// can bring it back, or removes it for good with `permanent: true`.
+fn handle_delete_file(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let file_span = info_span!("delete_file_operation");
+    let _enter = file_span.enter();
+
+    let params: DeleteFileParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        debug!(path = %params.path, "File does not exist");
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
+    };
+    let absolute = std::path::absolute(path).map_err(HandlerError::IoError)?;
+    let data_path = std::path::absolute(state.config.data_path()).map_err(HandlerError::IoError)?;
+    if absolute.starts_with(&data_path) || data_path.starts_with(&absolute) {
+        return Err(HandlerError::InvalidParams(format!(
+            "Refusing to delete server data: {}",
+            params.path
+        )));
+    }
+
+    if state.protected.is_protected(path) {
+        if !params.force {
+            debug!(path = %params.path, "Refusing to delete protected path");
+            return Err(HandlerError::ProtectedPath(params.path));
+        }
+        audit_forced("deleteFile", &params.path);
+    }
+    if params.dry_run {
+        debug!(path = %params.path, "Previewing delete");
+        return Ok(serde_json::json!({
+            "dryRun": true,
+            "path": params.path,
+            "directory": metadata.is_dir(),
+            "bytes": crate::trash::disk_size(path),
+            "permanent": params.permanent,
+        }));
+    }
+
+    let trash_id = if params.permanent {
+        let removed = if metadata.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        };
+        removed.map_err(|e| {
+            debug!(path = %params.path, error = %e, "Failed to delete file");
+            HandlerError::IoError(e)
+        })?;
+        None
+    } else {
+        Some(state.trash.put(path).map_err(HandlerError::IoError)?.id)
+    };
+
+    info!(path = %params.path, permanent = params.permanent, "File deleted successfully");
+    state.activity.record(
+        "file.deleted",
+        Some(connection.id),
+        serde_json::json!({ "path": params.path, "trashId": trash_id }),
+    );
+    Ok(serde_json::json!({ "trashId": trash_id }))
+}
+
+fn handle_list_files(state: &AppState, params: Value) -> Result<Value, HandlerError> {
     let file_span = info_span!("list_files_operation");
     let _enter = file_span.enter();
 
@@ -198,10 +935,23 @@ fn handle_list_files(params: Value) -> Result<Value, HandlerError> {
         ));
     }
 
-    let entries = fs::read_dir(path).map_err(|e| {
-        debug!(path = %params.path, error = %e, "Failed to read directory");
-        HandlerError::IoError(e)
-    })?;
+    let glob = params
+        .glob
+        .as_deref()
+        .map(|glob| {
+            globset::Glob::new(glob)
+                .map(|glob| glob.compile_matcher())
+                .map_err(|e| HandlerError::InvalidParams(format!("Invalid glob {glob}: {e}")))
+        })
+        .transpose()?;
+
+    let entries = state
+        .ignore
+        .walker(path, params.respect_ignore)
+        .max_depth(Some(1))
+        .build()
+        // The first entry is the directory itself.
+        .skip(1);
 
     let mut files = Vec::new();
     let mut directories = Vec::new();
@@ -209,28 +959,71 @@ fn handle_list_files(params: Value) -> Result<Value, HandlerError> {
     for entry in entries {
         let entry = entry.map_err(|e| {
             debug!(path = %params.path, error = %e, "Failed to read directory entry");
-            HandlerError::IoError(e)
+            HandlerError::IoError(
+                e.into_io_error()
+                    .unwrap_or_else(|| std::io::Error::other("Failed to read directory entry")),
+            )
         })?;
 
         let path = entry.path();
         let name = entry.file_name().to_string_lossy().to_string();
+        if (!params.include_hidden && name.starts_with('.'))
+            || glob.as_ref().is_some_and(|glob| !glob.is_match(&name))
+        {
+            continue;
+        }
 
-        if path.is_dir() {
-            directories.push(serde_json::json!({
+        // Symlinks are reported as such rather than as what they point to.
+        let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+        let mut item = if is_dir {
+            serde_json::json!({
                 "name": name,
                 "type": "directory"
-            }));
+            })
+        } else if entry.path_is_symlink() {
+            let target = fs::read_link(path).ok();
+            let target_type = fs::metadata(path).ok().map(|metadata| {
+                if metadata.is_dir() {
+                    "directory"
+                } else {
+                    "file"
+                }
+            });
+            serde_json::json!({
+                "name": name,
+                "type": "symlink",
+                "target": target,
+                "targetType": target_type
+            })
         } else {
-            let metadata = entry.metadata().map_err(|e| {
+            let metadata = path.symlink_metadata().map_err(|e| {
                 debug!(path = %path.display(), error = %e, "Failed to read file metadata");
                 HandlerError::IoError(e)
             })?;
 
-            files.push(serde_json::json!({
+            let mut item = serde_json::json!({
                 "name": name,
                 "type": "file",
                 "size": metadata.len()
-            }));
+            });
+            if params.include_type {
+                item["fileType"] = serde_json::json!(crate::file_type::detect_file(path).ok());
+            }
+            item
+        };
+        if params.include_mtime {
+            item["modifiedMs"] = entry
+                .metadata()
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|since| since.as_millis() as u64)
+                .into();
+        }
+        if is_dir {
+            directories.push(item);
+        } else {
+            files.push(item);
         }
     }
 
@@ -247,5 +1040,16 @@ fn handle_list_files(params: Value) -> Result<Value, HandlerError> {
         "Directory listing completed successfully"
     );
 
-    Ok(Value::Array(result))
+    if params.offset.is_none() && params.limit.is_none() {
+        return Ok(Value::Array(result));
+    }
+    let total = result.len();
+    let offset = params.offset.unwrap_or(0).min(total);
+    let limit = params.limit.unwrap_or(total);
+    let entries: Vec<Value> = result.into_iter().skip(offset).take(limit).collect();
+    Ok(serde_json::json!({
+        "entries": entries,
+        "total": total,
+        "offset": offset,
+    }))
 }
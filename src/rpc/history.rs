@@ -0,0 +1,78 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::Path;
+use tracing::{debug, info, info_span};
+
+use super::context::ConnectionContext;
+use super::dry_run;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{protected::audit_forced, staged, state::AppState};
+
+#[derive(Deserialize)]
+struct ListParams {
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct RestoreParams {
+    path: String,
+    id: String,
+    #[serde(default)]
+    force: bool,
+    #[serde(default, rename = "dryRun")]
+    dry_run: bool,
+}
+
+/// Versions kept of a file, most recent first.
+pub fn handle_list(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let params: ListParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let versions = state.history.list(path).map_err(HandlerError::IoError)?;
+    debug!(path = %params.path, versions = versions.len(), "Listing file history");
+    Ok(json!({ "enabled": state.history.enabled(), "versions": versions }))
+}
+
+/// Puts a kept version back in place. The content it replaces is kept in
+/// turn, so a restore can itself be undone.
+pub fn handle_restore(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("history_restore_operation");
+    let _enter = span.enter();
+
+    let params: RestoreParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    if path.exists() && state.protected.is_protected(path) {
+        if !params.force {
+            return Err(HandlerError::ProtectedPath(params.path));
+        }
+        audit_forced("history/restore", &params.path);
+    }
+
+    let content = state
+        .history
+        .read(path, &params.id)
+        .map_err(HandlerError::IoError)?;
+    if params.dry_run {
+        return Ok(dry_run::preview(state, path, &content));
+    }
+    let previous = state
+        .history
+        .snapshot(path)
+        .map_err(HandlerError::IoError)?;
+    staged::write_all([(path, content.as_slice())]).map_err(HandlerError::IoError)?;
+
+    info!(path = %params.path, id = %params.id, "Restored previous version");
+    state.activity.record(
+        "file.saved",
+        Some(connection.id),
+        json!({ "path": params.path, "bytes": content.len(), "historyId": params.id }),
+    );
+    Ok(json!({
+        "bytes": content.len(),
+        "previous": previous.map(|version| version.id),
+    }))
+}
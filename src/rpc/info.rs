@@ -0,0 +1,55 @@
+use serde_json::{Value, json};
+use std::fs;
+use tracing::{debug, info_span};
+
+use super::capabilities;
+use super::error::HandlerError;
+use crate::{clock, fault, state::AppState, telemetry};
+
+/// Handles `server/info`: what is running where, for an "About" panel or
+/// to paste into a bug report. Keys are never included.
+pub fn handle_info(state: &AppState) -> Result<Value, HandlerError> {
+    let span = info_span!("server_info_operation");
+    let _enter = span.enter();
+
+    let uptime = clock::now().duration_since(state.started).as_secs();
+    let build_features: Vec<&str> = [
+        ("deterministic", clock::DETERMINISTIC),
+        ("faults", fault::ENABLED),
+        ("otel", telemetry::ENABLED),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect();
+    let workspaces: Vec<Value> = state
+        .uris
+        .roots()
+        .map(|(name, path, uri)| json!({ "name": name, "path": path, "uri": uri }))
+        .collect();
+    let connections = state.open_connections();
+    debug!(uptime, connections, "Reporting server info");
+
+    Ok(json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "build": {
+            "commit": option_env!("EDITOR_SERVER_COMMIT"),
+            "rustc": option_env!("EDITOR_SERVER_RUSTC"),
+            "profile": if cfg!(debug_assertions) { "debug" } else { "release" },
+            "features": build_features,
+        },
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "pid": std::process::id(),
+        "workspaces": workspaces,
+        "dataDir": fs::canonicalize(state.config.data_path()).ok(),
+        "startedAt": clock::unix_secs().saturating_sub(uptime),
+        "uptimeSecs": uptime,
+        "connections": {
+            "open": connections,
+            "max": state.config.max_connections,
+        },
+        "backgroundPool": state.lanes.background.stats(),
+        "features": capabilities::features(state),
+    }))
+}
@@ -0,0 +1,170 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{fs, sync::Arc};
+use tracing::{debug, info, info_span};
+
+use super::compression;
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use super::locale::Locale;
+use crate::{share::random_token, state::AppState, ws::codec::Codec};
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct ClientCapabilities {
+    /// Result encodings the client can decode, in order of preference.
+    compression: Vec<String>,
+    /// Message framings the client can speak, in order of preference.
+    codecs: Vec<String>,
+    flow_control: Option<FlowControlCapability>,
+    /// Receive `workspace://` URIs instead of host paths.
+    workspace_uris: bool,
+    /// Get a token for resuming the session after a reconnect.
+    session_resume: bool,
+}
+
+#[derive(Deserialize)]
+struct FlowControlCapability {
+    /// Unacknowledged bytes allowed per notification stream.
+    window: usize,
+}
+
+/// Protocol spoken by this server. Clients on another major version are
+/// rejected; minor versions only add methods and fields.
+pub const PROTOCOL_VERSION: &str = "1.0";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeParams {
+    /// Assumed to be [`PROTOCOL_VERSION`] when omitted, for clients that
+    /// predate versioning.
+    protocol_version: Option<String>,
+    #[serde(default)]
+    capabilities: ClientCapabilities,
+    /// BCP 47 language tag for server messages, e.g. `de-AT`.
+    locale: Option<String>,
+}
+
+pub fn handle_initialize(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("initialize_operation");
+    let _enter = span.enter();
+
+    let params: InitializeParams = parse_params(params)?;
+    let protocol_version = params
+        .protocol_version
+        .unwrap_or_else(|| PROTOCOL_VERSION.to_string());
+    if major_version(&protocol_version) != major_version(PROTOCOL_VERSION) {
+        debug!(client = %protocol_version, server = PROTOCOL_VERSION, "Protocol mismatch");
+        return Err(HandlerError::UnsupportedProtocol(protocol_version));
+    }
+    let compression = compression::negotiate(&params.capabilities.compression);
+    debug!(
+        offered = ?params.capabilities.compression,
+        chosen = ?compression,
+        "Negotiated result compression"
+    );
+    connection.session().compression = compression;
+    let codec = Codec::negotiate(&params.capabilities.codecs);
+    debug!(offered = ?params.capabilities.codecs, chosen = ?codec, "Negotiated codec");
+    connection.session().codec = codec;
+
+    let flow_window = params
+        .capabilities
+        .flow_control
+        .map(|flow_control| connection.flow.enable(flow_control.window));
+    debug!(window = ?flow_window, "Negotiated notification flow control");
+
+    let workspace_uris = params.capabilities.workspace_uris;
+    connection
+        .notifier
+        .set_uris(workspace_uris.then(|| Arc::clone(&state.uris)));
+
+    let resume_token = if params.capabilities.session_resume && state.config.session_grace_secs > 0
+    {
+        let existing = connection.session().resume_token.clone();
+        let token = match existing {
+            Some(token) => token,
+            None => random_token().map_err(|e| HandlerError::IoError(std::io::Error::other(e)))?,
+        };
+        connection
+            .notifier
+            .enable_replay(state.config.session_replay_limit);
+        state.sessions.issue(&token, connection);
+        connection.session().resume_token = Some(token.clone());
+        Some(token)
+    } else {
+        None
+    };
+
+    let locale = params
+        .locale
+        .as_deref()
+        .map(Locale::negotiate)
+        .unwrap_or_default();
+    debug!(requested = ?params.locale, chosen = ?locale, "Negotiated message locale");
+    {
+        let mut session = connection.session();
+        session.locale = locale;
+        session.protocol_version = Some(protocol_version.clone());
+    }
+
+    info!(
+        connection_id = connection.id,
+        protocol_version = %protocol_version,
+        "Connection initialized"
+    );
+    let config = &state.config;
+    Ok(json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "serverInfo": {
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "workspace": {
+            "root": fs::canonicalize(&config.root).unwrap_or_else(|_| config.root.clone()),
+            "name": state.uris.name(),
+            "uri": state.uris.root_uri(),
+        },
+        "methods": state.methods.names(),
+        "limits": {
+            "maxMessageBytes": state.limits().max_message_bytes,
+            "maxWriteBytes": state.limits().max_write_bytes,
+            "maxReadBytes": state.limits().max_read_bytes,
+            "maxConcurrentRequests": config.max_concurrent_requests,
+            "rateLimit": {
+                "requestsPerSecond": config.rate_limit.requests_per_second,
+                "burst": config.rate_limit.burst,
+            },
+        },
+        "capabilities": {
+            "compression": compression.map(|encoding| json!({
+                "encoding": encoding,
+                "threshold": state.config.compression_threshold,
+            })),
+            "codec": codec,
+            "flowControl": flow_window.map(|window| json!({ "window": window })),
+            "workspaceUris": workspace_uris,
+            "sessionResume": resume_token.map(|token| json!({
+                "token": token,
+                "graceSecs": config.session_grace_secs,
+                "replayLimit": config.session_replay_limit,
+            })),
+        },
+        "locale": locale,
+        "identity": {
+            "name": connection.identity.name,
+            "tier": connection.identity.tier,
+            "workspace": connection.identity.tenant.as_ref().map(|tenant| tenant.root()),
+            "limits": state.policy.limits(&connection.identity),
+        },
+    }))
+}
+
+fn major_version(version: &str) -> &str {
+    version.split('.').next().unwrap_or(version)
+}
@@ -0,0 +1,246 @@
+//! The checks and bookkeeping every method call passes through, so no
+//! handler repeats them. [`super::handlers::builtin_methods`] installs them
+//! outermost first: timing, params validation (see [`super::validate`]),
+//! the `dryRun` check, the read-only switch, confinement to the key's workspace, path
+//! permissions, the symlink policy, the identity's tier policy, then the audit log.
+
+use serde_json::Value;
+use std::{fs, time::Instant};
+use tracing::{Span, debug};
+
+use super::dry_run::{self, DRY_RUN_METHODS};
+use super::error::HandlerError;
+use super::handlers::MUTATING_METHODS;
+use super::registry::{Call, Interceptor, Next};
+use crate::config::Access;
+use crate::uri;
+
+fn mutating(method: &str) -> bool {
+    MUTATING_METHODS.contains(&method)
+}
+
+/// Records how long the handler took as `duration_ms` on the request's
+/// span.
+pub struct Timing;
+
+impl Interceptor for Timing {
+    fn intercept(&self, call: Call<'_>, next: Next<'_>) -> Result<Option<Value>, HandlerError> {
+        debug!("Handling {} request", call.method);
+        let started = Instant::now();
+        let result = next.run(call);
+        Span::current().record("duration_ms", started.elapsed().as_millis() as u64);
+        result
+    }
+}
+
+/// Refuses `dryRun` on mutating methods without one, which would
+/// otherwise make the change the client only meant to preview.
+pub struct DryRun;
+
+impl Interceptor for DryRun {
+    fn intercept(&self, call: Call<'_>, next: Next<'_>) -> Result<Option<Value>, HandlerError> {
+        if mutating(call.method)
+            && dry_run::requested(&call.params)
+            && !DRY_RUN_METHODS.contains(&call.method)
+        {
+            return Err(HandlerError::InvalidParams(format!(
+                "{} does not support dryRun",
+                call.method
+            )));
+        }
+        next.run(call)
+    }
+}
+
+/// Refuses mutating methods while the server is read-only.
+pub struct ReadOnly;
+
+impl Interceptor for ReadOnly {
+    fn intercept(&self, call: Call<'_>, next: Next<'_>) -> Result<Option<Value>, HandlerError> {
+        if call.state.config.read_only && mutating(call.method) {
+            return Err(HandlerError::ReadOnly(call.method.to_string()));
+        }
+        next.run(call)
+    }
+}
+
+/// Keeps connections whose key names a workspace inside it; see
+/// [`crate::tenant`].
+pub struct Confine;
+
+impl Interceptor for Confine {
+    fn intercept(&self, mut call: Call<'_>, next: Next<'_>) -> Result<Option<Value>, HandlerError> {
+        if let Some(tenant) = &call.connection.identity.tenant {
+            let required = if mutating(call.method) {
+                Access::Write
+            } else {
+                Access::Read
+            };
+            tenant.confine(call.method, &mut call.params, required)?;
+        }
+        next.run(call)
+    }
+}
+
+/// Checks every path in the params against the configured permissions:
+/// write access for mutating methods, read access for the rest.
+pub struct PathAccess;
+
+impl Interceptor for PathAccess {
+    fn intercept(&self, call: Call<'_>, next: Next<'_>) -> Result<Option<Value>, HandlerError> {
+        let required = if mutating(call.method) {
+            Access::Write
+        } else {
+            Access::Read
+        };
+        uri::request_paths(&call.params)
+            .iter()
+            .try_for_each(|path| call.state.permissions.check(path, required))?;
+        next.run(call)
+    }
+}
+
+/// Methods that act on a symlink itself rather than its target, so only
+/// the directories leading to it are held to the symlink policy.
+const LINK_METHODS: &[&str] = &["deleteFile"];
+
+/// Applies the symlink policy (see [`crate::sandbox`]) to every path in
+/// the params, so no handler can read or write through a link that leaves
+/// the workspace.
+pub struct Symlinks;
+
+impl Interceptor for Symlinks {
+    fn intercept(&self, call: Call<'_>, next: Next<'_>) -> Result<Option<Value>, HandlerError> {
+        let sandbox = &call.state.sandbox;
+        uri::request_paths(&call.params)
+            .iter()
+            .try_for_each(|path| {
+                if LINK_METHODS.contains(&call.method) {
+                    sandbox.check_parent(path)
+                } else {
+                    sandbox.check(path)
+                }
+            })?;
+        next.run(call)
+    }
+}
+
+/// Applies the limits of the caller's tier. The slot it takes is held
+/// until the handler returns, so the call counts against the tier's
+/// concurrency limit while it runs.
+pub struct Policy;
+
+impl Interceptor for Policy {
+    fn intercept(&self, call: Call<'_>, next: Next<'_>) -> Result<Option<Value>, HandlerError> {
+        let _slot =
+            call.state
+                .policy
+                .check(call.state, call.connection, call.method, &call.params)?;
+        next.run(call)
+    }
+}
+
+/// Records successful mutating calls in the audit log, leaving out dry
+/// runs.
+pub struct Audit;
+
+impl Interceptor for Audit {
+    fn intercept(&self, call: Call<'_>, next: Next<'_>) -> Result<Option<Value>, HandlerError> {
+        if !mutating(call.method) || dry_run::requested(&call.params) {
+            return next.run(call);
+        }
+        let (state, connection, method) = (call.state, call.connection, call.method);
+        // Taken before the handler consumes the params.
+        let paths = uri::request_paths(&call.params);
+        let size = call
+            .params
+            .get("content")
+            .and_then(Value::as_str)
+            .map(|content| content.len() as u64);
+
+        let result = next.run(call)?;
+        let paths: Vec<_> = paths
+            .iter()
+            .map(|path| std::path::absolute(path).unwrap_or_else(|_| path.clone()))
+            .collect();
+        let size = size.or_else(|| {
+            let [path] = paths.as_slice() else {
+                return None;
+            };
+            fs::metadata(path)
+                .ok()
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+        });
+        state.audit.record(
+            connection.id,
+            connection.identity.name.as_deref(),
+            method,
+            paths,
+            size,
+        );
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::symlink, sync::Arc};
+
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    use super::super::{
+        cancel::CancelToken,
+        context::{ConnectionContext, Notifier},
+        handlers::process_request,
+        request::JsonRpcRequest,
+    };
+    use crate::{config::Config, policy::Identity, state::AppState};
+
+    /// Handlers never check the sandbox themselves, so a link out of the
+    /// workspace must be refused for every method that takes a path.
+    #[tokio::test]
+    async fn refuses_links_out_of_the_workspace_for_every_method() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(dir.path().join("secret.json"), r#"{"key": "secret"}"#).unwrap();
+        symlink(dir.path().join("secret.json"), root.join("link.json")).unwrap();
+
+        let state = Arc::new(
+            AppState::new(Config {
+                root: root.canonicalize().unwrap(),
+                ..Config::default()
+            })
+            .unwrap(),
+        );
+        let (outbound, _) = mpsc::unbounded_channel();
+        let connection = ConnectionContext::new(1, Notifier::new(outbound, 0), Identity::default());
+        let link = root.join("link.json").to_string_lossy().into_owned();
+
+        for (method, params) in [
+            ("structuredGet", json!({ "path": link, "pointer": "/key" })),
+            (
+                "structuredSet",
+                json!({ "path": link, "pointer": "/key", "value": "changed" }),
+            ),
+            ("readFile", json!({ "path": link })),
+        ] {
+            let request = JsonRpcRequest {
+                jsonrpc: "2.0".to_string(),
+                method: method.to_string(),
+                params,
+                id: Some(json!(1)),
+                correlation_id: String::new(),
+            };
+            let response =
+                process_request(&state, &connection, request, &CancelToken::default()).unwrap();
+            assert!(response.error.is_some(), "{method} followed the link");
+        }
+        assert_eq!(
+            fs::read_to_string(dir.path().join("secret.json")).unwrap(),
+            r#"{"key": "secret"}"#
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{debug, info, info_span};
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{
+    clock,
+    scheduler::{self, Trigger},
+    state::{AppState, SharedState},
+};
+
+const DEFAULT_HISTORY: usize = 20;
+
+#[derive(Deserialize)]
+struct JobNameParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct JobHistoryParams {
+    name: String,
+    limit: Option<usize>,
+}
+
+pub fn handle_list(state: &AppState) -> Result<Value, HandlerError> {
+    let span = info_span!("jobs_list_operation");
+    let _enter = span.enter();
+
+    let now = clock::unix_secs();
+    let jobs: Vec<Value> = state
+        .jobs
+        .jobs()
+        .iter()
+        .map(|(name, job)| {
+            json!({
+                "name": name,
+                "schedule": job.definition.schedule,
+                "builtin": job.definition.builtin,
+                "command": job.definition.command,
+                "nextRun": job.schedule.next_after(now),
+                "running": state.jobs.is_running(name),
+                "lastRun": state.jobs.history(name, 1).pop(),
+            })
+        })
+        .collect();
+    debug!(jobs = jobs.len(), "Listing jobs");
+    Ok(Value::Array(jobs))
+}
+
+pub fn handle_history(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let params: JobHistoryParams = parse_params(params)?;
+    if !state.jobs.jobs().contains_key(&params.name) {
+        return Err(HandlerError::TaskError(format!(
+            "Unknown job: {}",
+            params.name
+        )));
+    }
+    let runs = state
+        .jobs
+        .history(&params.name, params.limit.unwrap_or(DEFAULT_HISTORY));
+    Ok(json!({ "name": params.name, "runs": runs }))
+}
+
+/// Runs a job now, outside its schedule. Completion is reported through
+/// the activity feed as `job.finished` or `job.failed`.
+pub fn handle_run(state: &SharedState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("jobs_run_operation");
+    let _enter = span.enter();
+
+    let params: JobNameParams = parse_params(params)?;
+    scheduler::trigger(state, &params.name, Trigger::Manual).map_err(HandlerError::TaskError)?;
+    info!(job = %params.name, "Job triggered manually");
+    Ok(json!({ "started": true }))
+}
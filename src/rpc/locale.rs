@@ -0,0 +1,282 @@
+use serde::Serialize;
+
+use super::request::JsonRpcResponse;
+
+/// Languages server-generated messages are available in. English is the
+/// source language and the fallback for anything untranslated.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Fr,
+    Es,
+}
+
+impl Locale {
+    /// Picks a locale from a BCP 47 tag such as `de-AT`, matching on the
+    /// primary language only.
+    pub fn negotiate(tag: &str) -> Self {
+        let language = tag.split(['-', '_']).next().unwrap_or_default();
+        match language.to_ascii_lowercase().as_str() {
+            "de" => Locale::De,
+            "fr" => Locale::Fr,
+            "es" => Locale::Es,
+            _ => Locale::En,
+        }
+    }
+}
+
+/// English message templates with their translations (de, fr, es). `{}` in
+/// a template matches the dynamic parts of a message, which are substituted
+/// for `{0}`, `{1}`, ... in the translation.
+const MESSAGES: &[(&str, [&str; 3])] = &[
+    (
+        "Parse error",
+        ["Syntaxfehler", "Erreur d'analyse", "Error de análisis"],
+    ),
+    (
+        "Invalid request: {}",
+        [
+            "Ungültige Anfrage: {0}",
+            "Requête invalide : {0}",
+            "Solicitud no válida: {0}",
+        ],
+    ),
+    (
+        "Method not Found",
+        [
+            "Methode nicht gefunden",
+            "Méthode introuvable",
+            "Método no encontrado",
+        ],
+    ),
+    (
+        "File not found",
+        [
+            "Datei nicht gefunden",
+            "Fichier introuvable",
+            "Archivo no encontrado",
+        ],
+    ),
+    (
+        "Directory does not exist",
+        [
+            "Verzeichnis existiert nicht",
+            "Le répertoire n'existe pas",
+            "El directorio no existe",
+        ],
+    ),
+    (
+        "Path is not a directory",
+        [
+            "Pfad ist kein Verzeichnis",
+            "Le chemin n'est pas un répertoire",
+            "La ruta no es un directorio",
+        ],
+    ),
+    (
+        "Path is protected, pass force: true to modify: {}",
+        [
+            "Pfad ist geschützt, zum Ändern force: true übergeben: {0}",
+            "Chemin protégé, passez force: true pour le modifier : {0}",
+            "La ruta está protegida, use force: true para modificarla: {0}",
+        ],
+    ),
+    (
+        "Local changes would be overwritten: {}",
+        [
+            "Lokale Änderungen würden überschrieben: {0}",
+            "Des modifications locales seraient écrasées : {0}",
+            "Se sobrescribirían cambios locales: {0}",
+        ],
+    ),
+    (
+        "Request cancelled",
+        [
+            "Anfrage abgebrochen",
+            "Requête annulée",
+            "Solicitud cancelada",
+        ],
+    ),
+    (
+        "Request timed out after {}ms",
+        [
+            "Zeitüberschreitung der Anfrage nach {0} ms",
+            "Délai de la requête dépassé après {0} ms",
+            "La solicitud expiró después de {0} ms",
+        ],
+    ),
+    (
+        "Unsupported protocol version {}",
+        [
+            "Nicht unterstützte Protokollversion {0}",
+            "Version de protocole non prise en charge {0}",
+            "Versión de protocolo no compatible {0}",
+        ],
+    ),
+    (
+        "Rate limit exceeded",
+        [
+            "Anfragelimit überschritten",
+            "Limite de requêtes dépassée",
+            "Límite de solicitudes excedido",
+        ],
+    ),
+    (
+        "Message exceeds the {} byte limit",
+        [
+            "Nachricht überschreitet das Limit von {0} Bytes",
+            "Le message dépasse la limite de {0} octets",
+            "El mensaje supera el límite de {0} bytes",
+        ],
+    ),
+    (
+        "File is {} bytes, larger than the {} byte read limit",
+        [
+            "Datei ist {0} Bytes groß und überschreitet das Leselimit von {1} Bytes",
+            "Le fichier fait {0} octets, au-delà de la limite de lecture de {1} octets",
+            "El archivo tiene {0} bytes, más que el límite de lectura de {1} bytes",
+        ],
+    ),
+    (
+        "Content is {} bytes, larger than the {} byte write limit",
+        [
+            "Inhalt ist {0} Bytes groß und überschreitet das Schreiblimit von {1} Bytes",
+            "Le contenu fait {0} octets, au-delà de la limite d'écriture de {1} octets",
+            "El contenido tiene {0} bytes, más que el límite de escritura de {1} bytes",
+        ],
+    ),
+    (
+        "Tier {} allows at most {} open terminals",
+        [
+            "Tarif {0} erlaubt höchstens {1} offene Terminals",
+            "L'offre {0} autorise au plus {1} terminaux ouverts",
+            "El plan {0} permite como máximo {1} terminales abiertas",
+        ],
+    ),
+    (
+        "Tier {} allows at most {} concurrent searches",
+        [
+            "Tarif {0} erlaubt höchstens {1} gleichzeitige Suchen",
+            "L'offre {0} autorise au plus {1} recherches simultanées",
+            "El plan {0} permite como máximo {1} búsquedas simultáneas",
+        ],
+    ),
+    (
+        "Tier {} allows a workspace of at most {} bytes",
+        [
+            "Tarif {0} erlaubt einen Arbeitsbereich von höchstens {1} Bytes",
+            "L'offre {0} autorise un espace de travail d'au plus {1} octets",
+            "El plan {0} permite un espacio de trabajo de como máximo {1} bytes",
+        ],
+    ),
+    (
+        "Tier {} does not include {} methods",
+        [
+            "Tarif {0} enthält keine {1}-Methoden",
+            "L'offre {0} n'inclut pas les méthodes {1}",
+            "El plan {0} no incluye los métodos {1}",
+        ],
+    ),
+    (
+        "Symlink points outside the workspace: {}",
+        [
+            "Symlink zeigt aus dem Arbeitsbereich hinaus: {0}",
+            "Le lien symbolique sort de l'espace de travail : {0}",
+            "El enlace simbólico apunta fuera del espacio de trabajo: {0}",
+        ],
+    ),
+    (
+        "Symlinks are not followed: {}",
+        [
+            "Symlinks werden nicht verfolgt: {0}",
+            "Les liens symboliques ne sont pas suivis : {0}",
+            "Los enlaces simbólicos no se siguen: {0}",
+        ],
+    ),
+    (
+        "Server is read-only; {} is disabled",
+        [
+            "Server ist schreibgeschützt; {0} ist deaktiviert",
+            "Le serveur est en lecture seule ; {0} est désactivé",
+            "El servidor es de solo lectura; {0} está desactivado",
+        ],
+    ),
+    (
+        "Reading {} is not permitted",
+        [
+            "Lesen von {0} ist nicht erlaubt",
+            "La lecture de {0} n'est pas autorisée",
+            "No se permite leer {0}",
+        ],
+    ),
+    (
+        "Writing {} is not permitted",
+        [
+            "Schreiben von {0} ist nicht erlaubt",
+            "L'écriture de {0} n'est pas autorisée",
+            "No se permite escribir {0}",
+        ],
+    ),
+];
+
+/// Translates an English server message, or returns `None` when the locale
+/// is English or the message is not in the catalog.
+pub fn translate(locale: Locale, message: &str) -> Option<String> {
+    let column = match locale {
+        Locale::En => return None,
+        Locale::De => 0,
+        Locale::Fr => 1,
+        Locale::Es => 2,
+    };
+    MESSAGES.iter().find_map(|(template, translations)| {
+        let args = match_template(template, message)?;
+        let mut translated = translations[column].to_string();
+        for (index, arg) in args.iter().enumerate() {
+            translated = translated.replace(&format!("{{{index}}}"), arg);
+        }
+        Some(translated)
+    })
+}
+
+/// Returns the text matched by each `{}` if `message` fits `template`.
+fn match_template<'a>(template: &str, message: &'a str) -> Option<Vec<&'a str>> {
+    let mut pieces = template.split("{}");
+    let mut rest = message.strip_prefix(pieces.next()?)?;
+    let mut args = Vec::new();
+    let mut pieces = pieces.peekable();
+    while let Some(piece) = pieces.next() {
+        // The last placeholder takes everything before the final literal.
+        let end = if pieces.peek().is_none() {
+            if !rest.ends_with(piece) {
+                return None;
+            }
+            rest.len() - piece.len()
+        } else {
+            rest.find(piece)?
+        };
+        args.push(&rest[..end]);
+        rest = &rest[end + piece.len()..];
+    }
+    rest.is_empty().then_some(args)
+}
+
+/// Returns a copy of an error response with its message translated, or
+/// `None` if it needs no change.
+pub fn localize_response(locale: Locale, response: &JsonRpcResponse) -> Option<JsonRpcResponse> {
+    let error = response.error.as_ref()?;
+    let message = translate(locale, &error.message)?;
+    Some(JsonRpcResponse {
+        jsonrpc: response.jsonrpc.clone(),
+        result: response.result.clone(),
+        error: Some(super::error::JsonRpcError {
+            code: error.code,
+            message,
+            data: error.data.clone(),
+        }),
+        id: response.id.clone(),
+        correlation_id: response.correlation_id.clone(),
+    })
+}
@@ -0,0 +1,96 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::Path;
+use tracing::{debug, info_span};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{locks::Held, state::AppState};
+
+#[derive(Deserialize)]
+struct LockFileParams {
+    path: String,
+    /// Shown to other clients, e.g. what the holder is doing.
+    note: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UnlockFileParams {
+    path: String,
+}
+
+/// Claims the file at `path` for this connection until `unlockFile` or
+/// disconnect. The lock is advisory: writes still succeed, but `statPath`
+/// and `document/open` show the holder, and locking is refused to others.
+pub fn handle_lock(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("lock_file_operation");
+    let _enter = span.enter();
+
+    let params: LockFileParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    if path.is_dir() {
+        return Err(HandlerError::DirectoryError(format!(
+            "Cannot lock a directory: {}",
+            params.path
+        )));
+    }
+    if !path.exists() {
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
+    }
+    let lock = state
+        .locks
+        .acquire(
+            path,
+            connection.id,
+            connection.identity.name.as_deref(),
+            params.note,
+        )
+        .map_err(HandlerError::IoError)?
+        .map_err(|Held(lock)| HandlerError::FileLocked {
+            path: params.path.clone(),
+            lock,
+        })?;
+    state.activity.record(
+        "file.locked",
+        Some(connection.id),
+        json!({ "path": params.path, "lock": lock }),
+    );
+    Ok(json!({ "path": params.path, "lock": lock }))
+}
+
+/// Releases this connection's lock on `path`. Releasing a file nobody has
+/// locked is not an error; `released` is then `false`.
+pub fn handle_unlock(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("unlock_file_operation");
+    let _enter = span.enter();
+
+    let params: UnlockFileParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let released = state
+        .locks
+        .release(path, connection.id)
+        .map_err(HandlerError::IoError)?
+        .map_err(|Held(lock)| HandlerError::FileLocked {
+            path: params.path.clone(),
+            lock,
+        })?;
+    if released {
+        state.activity.record(
+            "file.unlocked",
+            Some(connection.id),
+            json!({ "path": params.path }),
+        );
+    } else {
+        debug!(path = %params.path, "File was not locked");
+    }
+    Ok(json!({ "path": params.path, "released": released }))
+}
@@ -0,0 +1,124 @@
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::info_span;
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use super::registry::{Call, Handler};
+use crate::lsp::LspTarget;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+struct LspMessageParams {
+    language: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct LspRespondParams {
+    language: String,
+    id: Value,
+    #[serde(default)]
+    result: Value,
+    error: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct LspStopParams {
+    language: String,
+}
+
+fn target<'a>(
+    state: &'a AppState,
+    connection: &'a ConnectionContext,
+    language: &'a str,
+) -> Result<LspTarget<'a>, HandlerError> {
+    let definition = state.config.language_servers.get(language).ok_or_else(|| {
+        HandlerError::InvalidParams(format!("No language server configured for {language}"))
+    })?;
+    Ok(LspTarget {
+        connection_id: connection.id,
+        language,
+        definition,
+        root: &state.config.root,
+        notifier: &connection.notifier,
+    })
+}
+
+/// `lsp/request`: forwards a request to the language server. The reply to
+/// the client's request is sent by the bridge once the server responds.
+pub struct RequestHandler;
+
+impl Handler for RequestHandler {
+    fn name(&self) -> &'static str {
+        "lsp/request"
+    }
+
+    fn execute(&self, call: Call<'_>) -> Result<Option<Value>, HandlerError> {
+        let span = info_span!("lsp_request_operation");
+        let _enter = span.enter();
+
+        let params: LspMessageParams = parse_params(call.params)?;
+        let target = target(call.state, call.connection, &params.language)?;
+        call.state
+            .lsp
+            .request(
+                &target,
+                &params.method,
+                params.params,
+                call.id.clone(),
+                call.correlation_id,
+            )
+            .map_err(HandlerError::LspError)?;
+        Ok(None)
+    }
+}
+
+pub fn handle_notify(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let params: LspMessageParams = parse_params(params)?;
+    let target = target(state, connection, &params.language)?;
+    state
+        .lsp
+        .notify(&target, &params.method, params.params)
+        .map_err(HandlerError::LspError)?;
+    Ok(Value::Bool(true))
+}
+
+pub fn handle_respond(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let params: LspRespondParams = parse_params(params)?;
+    state
+        .lsp
+        .respond(
+            connection.id,
+            &params.language,
+            params.id,
+            params.result,
+            params.error,
+        )
+        .map_err(HandlerError::LspError)?;
+    Ok(Value::Bool(true))
+}
+
+pub fn handle_stop(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let params: LspStopParams = parse_params(params)?;
+    state
+        .lsp
+        .stop(connection.id, &params.language)
+        .map_err(HandlerError::LspError)?;
+    Ok(Value::Bool(true))
+}
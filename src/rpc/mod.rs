@@ -1,3 +1,61 @@
+pub mod activity;
+pub mod archive;
+pub mod audit;
+pub mod blob;
+pub mod cancel;
+pub mod capabilities;
+pub mod catalog;
+pub mod compression;
+pub mod context;
+pub mod dap;
+pub mod debug;
+pub mod definition;
+pub mod delta;
+pub mod diff;
+pub mod disk_usage;
+pub mod document;
+pub mod dry_run;
 pub mod error;
+pub mod export;
+pub mod extract;
+pub mod file_type;
+pub mod flow;
+pub mod format;
+pub mod git;
 pub mod handlers;
+pub mod history;
+pub mod info;
+pub mod initialize;
+pub mod interceptors;
+pub mod jobs;
+pub mod locale;
+pub mod locks;
+pub mod lsp;
+pub mod patch;
+pub mod plain_text;
+pub mod presence;
+pub mod problems;
+pub mod recent;
+pub mod registry;
+pub mod reload;
+pub mod replace;
 pub mod request;
+pub mod scan;
+pub mod schema;
+pub mod search;
+pub mod share;
+pub mod snippet;
+pub mod spell;
+pub mod stats;
+pub mod structured;
+pub mod syntax;
+pub mod table;
+pub mod task;
+pub mod template;
+pub mod terminal;
+pub mod text;
+pub mod trash;
+pub mod upload;
+pub mod validate;
+pub mod watch;
+pub mod workspace;
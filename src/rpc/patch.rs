@@ -0,0 +1,307 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, info_span};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{
+    config::Access,
+    diff::{self, FilePatch, HunkOutcome},
+    protected::audit_forced,
+    sandbox::normalize,
+    staged,
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApplyPatchParams {
+    /// A unified diff touching one or more files.
+    patch: String,
+    /// Directory the patch's paths are relative to; the workspace root by
+    /// default.
+    #[serde(default)]
+    cwd: Option<String>,
+    /// Write the hunks that apply even if others conflict. Otherwise
+    /// nothing is written unless every hunk applies.
+    #[serde(default)]
+    partial: bool,
+    #[serde(default)]
+    force: bool,
+    /// Report what would be written without writing it; `written` then
+    /// says which files would be.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// One file of the patch, applied in memory.
+struct PatchedFile {
+    /// Where the content came from; `None` when the patch creates the file.
+    old: Option<PathBuf>,
+    /// Where it goes; `None` when the patch deletes the file.
+    new: Option<PathBuf>,
+    content: String,
+    outcomes: Vec<HunkOutcome>,
+    /// Why no hunk could be applied at all, such as a missing file.
+    problem: Option<String>,
+}
+
+impl PatchedFile {
+    fn applied(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome, HunkOutcome::Applied { .. }))
+            .count()
+    }
+
+    fn clean(&self) -> bool {
+        self.problem.is_none() && self.applied() == self.outcomes.len()
+    }
+}
+
+/// Applies a unified diff to workspace files and reports, per hunk,
+/// whether it applied and where.
+pub fn handle_apply_patch(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("apply_patch_operation");
+    let _enter = span.enter();
+
+    let params: ApplyPatchParams = parse_params(params)?;
+    let patches = diff::parse_patch(&params.patch)
+        .map_err(|e| HandlerError::InvalidParams(format!("Invalid patch: {e}")))?;
+    let base = params
+        .cwd
+        .as_deref()
+        .map_or_else(|| state.config.root.clone(), PathBuf::from);
+    debug!(files = patches.len(), cwd = %base.display(), "Applying patch");
+
+    let mut files = Vec::with_capacity(patches.len());
+    for patch in &patches {
+        files.push(apply_file(state, connection, &base, patch, params.force)?);
+    }
+
+    let clean = files.iter().all(PatchedFile::clean);
+    let writing: Vec<&PatchedFile> = files
+        .iter()
+        .filter(|file| {
+            if params.partial {
+                // Deleting a file whose hunks conflict would lose the rest.
+                file.problem.is_none() && file.applied() > 0 && (file.new.is_some() || file.clean())
+            } else {
+                clean
+            }
+        })
+        .collect();
+    let limit = state.limits().max_write_bytes;
+    if let Some(file) = writing.iter().find(|file| file.content.len() > limit) {
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "Patched content is {} bytes, larger than the {limit} byte write limit",
+            file.content.len()
+        )));
+    }
+    if params.dry_run {
+        info!(
+            files = files.len(),
+            writing = writing.len(),
+            clean,
+            "Checked patch without writing"
+        );
+        let reports = reports(&files, &patches, &writing);
+        return Ok(json!({ "dryRun": true, "applied": clean, "files": reports }));
+    }
+    staged::write_all(writing.iter().filter_map(|file| {
+        file.new
+            .as_deref()
+            .map(|path| (path, file.content.as_bytes()))
+    }))
+    .map_err(HandlerError::IoError)?;
+    for file in &writing {
+        record(state, connection, file)?;
+    }
+
+    info!(
+        files = files.len(),
+        written = writing.len(),
+        clean,
+        "Patch applied"
+    );
+    let reports = reports(&files, &patches, &writing);
+    Ok(json!({ "applied": clean, "files": reports }))
+}
+
+fn apply_file(
+    state: &AppState,
+    connection: &ConnectionContext,
+    base: &Path,
+    patch: &FilePatch,
+    force: bool,
+) -> Result<PatchedFile, HandlerError> {
+    let resolve = |name: &Option<String>| {
+        name.as_deref()
+            .map(|name| resolve(state, connection, base, name, force))
+            .transpose()
+    };
+    let (old, new) = (resolve(&patch.old_path)?, resolve(&patch.new_path)?);
+    let mut file = PatchedFile {
+        old,
+        new,
+        content: String::new(),
+        outcomes: Vec::new(),
+        problem: None,
+    };
+
+    let original = match (&file.old, &file.new) {
+        (None, Some(new)) if new.exists() => Err("File already exists".to_string()),
+        (None, _) => Ok(String::new()),
+        (Some(old), _) => fs::read(old)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => "File not found".to_string(),
+                _ => e.to_string(),
+            })
+            .and_then(|bytes| String::from_utf8(bytes).map_err(|_| "Not UTF-8 text".to_string())),
+    };
+    match original {
+        Ok(original) => {
+            (file.content, file.outcomes) = diff::apply_hunks(&original, &patch.hunks);
+            if file.new.is_none() && file.clean() && !file.content.is_empty() {
+                file.problem = Some("File is not empty after removing its lines".to_string());
+            }
+        }
+        Err(problem) => {
+            debug!(problem, "Patch does not apply to file");
+            file.outcomes = vec![HunkOutcome::Conflict; patch.hunks.len()];
+            file.problem = Some(problem);
+        }
+    }
+    Ok(file)
+}
+
+/// Host path for a path named in the patch, checked like any request path
+/// that is about to be written. The interceptors only see `cwd`, so the
+/// connection's tenant is checked here too.
+fn resolve(
+    state: &AppState,
+    connection: &ConnectionContext,
+    base: &Path,
+    name: &str,
+    force: bool,
+) -> Result<PathBuf, HandlerError> {
+    let path = match state.uris.to_path(name)? {
+        Some(path) => path,
+        None => normalize(&std::path::absolute(base.join(name)).map_err(HandlerError::IoError)?),
+    };
+    let data_path = std::path::absolute(state.config.data_path()).map_err(HandlerError::IoError)?;
+    if state.uris.to_uri(&path).is_none() || path.starts_with(&data_path) {
+        return Err(HandlerError::InvalidParams(format!(
+            "Patch path is outside the workspace: {name}"
+        )));
+    }
+    state.sandbox.check(&path)?;
+    state.permissions.check(&path, Access::Write)?;
+    if let Some(tenant) = &connection.identity.tenant {
+        tenant.check(&path, Access::Write)?;
+    }
+    if path.exists() && state.protected.is_protected(&path) {
+        if !force {
+            return Err(HandlerError::ProtectedPath(name.to_string()));
+        }
+        audit_forced("applyPatch", name);
+    }
+    Ok(path)
+}
+
+/// Finishes a written file: removes what a delete or rename left behind
+/// and records the activity.
+fn record(
+    state: &AppState,
+    connection: &ConnectionContext,
+    file: &PatchedFile,
+) -> Result<(), HandlerError> {
+    match (&file.old, &file.new) {
+        (Some(old), new) if new.as_ref() != Some(old) => {
+            let trash_id = state.trash.put(old).map_err(HandlerError::IoError)?.id;
+            state.activity.record(
+                "file.deleted",
+                Some(connection.id),
+                json!({ "path": old, "trashId": trash_id }),
+            );
+        }
+        _ => {}
+    }
+    if let Some(new) = &file.new {
+        let kind = if file.old.as_ref() == Some(new) {
+            "file.saved"
+        } else {
+            "file.created"
+        };
+        state.activity.record(
+            kind,
+            Some(connection.id),
+            json!({ "path": new, "bytes": file.content.len() }),
+        );
+    }
+    Ok(())
+}
+
+fn reports(files: &[PatchedFile], patches: &[FilePatch], writing: &[&PatchedFile]) -> Vec<Value> {
+    files
+        .iter()
+        .zip(patches)
+        .map(|(file, patch)| {
+            let written = writing.iter().any(|written| std::ptr::eq(*written, file));
+            report(file, patch, written)
+        })
+        .collect()
+}
+
+fn report(file: &PatchedFile, patch: &FilePatch, written: bool) -> Value {
+    let hunks: Vec<Value> = patch
+        .hunks
+        .iter()
+        .zip(&file.outcomes)
+        .enumerate()
+        .map(|(index, (hunk, outcome))| match outcome {
+            HunkOutcome::Applied { line, offset } => json!({
+                "index": index,
+                "oldStart": hunk.old_start,
+                "applied": true,
+                "line": line,
+                "offset": offset,
+            }),
+            HunkOutcome::Conflict => json!({
+                "index": index,
+                "oldStart": hunk.old_start,
+                "applied": false,
+            }),
+        })
+        .collect();
+    let mut report = json!({
+        "path": file.new.as_ref().or(file.old.as_ref()),
+        "status": if file.clean() {
+            "applied"
+        } else if file.applied() > 0 {
+            "partial"
+        } else {
+            "conflict"
+        },
+        "written": written,
+        "created": file.old.is_none(),
+        "deleted": file.new.is_none(),
+        "hunks": hunks,
+    });
+    if file.old.is_some() && file.new.is_some() && file.old != file.new {
+        report["originalPath"] = json!(file.old);
+    }
+    if let Some(problem) = &file.problem {
+        report["problem"] = json!(problem);
+    }
+    report
+}
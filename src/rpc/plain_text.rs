@@ -0,0 +1,320 @@
+//! Linearized plain-text renderings of structured results for
+//! screen-reader-first clients. Output never relies on colour, indentation
+//! or semantic tokens: nesting is spelled out with outline numbers, positions
+//! are one-based, and ordering is stable so repeated requests read the same.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{fmt::Write, fs, path::Path};
+use tracing::{debug, info, info_span};
+
+use super::error::HandlerError;
+use super::git;
+use super::handlers::parse_params;
+use super::syntax::document_content;
+use crate::{problems::Problem, state::AppState, syntax, syntax::Symbol};
+
+/// Entries listed by `plainText/tree` before it stops.
+const MAX_TREE_ENTRIES: usize = 1000;
+
+#[derive(Deserialize)]
+struct ProblemsParams {
+    source: Option<String>,
+    path: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SymbolsParams {
+    path: String,
+    content: Option<String>,
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TreeParams {
+    path: String,
+    #[serde(default = "default_depth")]
+    depth: usize,
+}
+
+fn default_depth() -> usize {
+    2
+}
+
+#[derive(Deserialize)]
+struct DiffParams {
+    path: Option<String>,
+    /// Describe staged changes instead of unstaged ones.
+    #[serde(default)]
+    staged: bool,
+}
+
+/// `count(2, "line", "lines")` is "2 lines".
+fn count(n: usize, singular: &str, plural: &str) -> String {
+    format!("{n} {}", if n == 1 { singular } else { plural })
+}
+
+fn text_result(text: String) -> Result<Value, HandlerError> {
+    Ok(json!({ "text": text }))
+}
+
+pub fn handle_problems(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("plain_text_problems_operation");
+    let _enter = span.enter();
+
+    let params: ProblemsParams = parse_params(params)?;
+    let mut problems = state
+        .problems
+        .list(params.source.as_deref(), params.path.as_deref());
+    problems.sort_by(|a, b| {
+        (
+            &a.path,
+            a.range.start.line,
+            a.range.start.character,
+            &a.source,
+            &a.code,
+        )
+            .cmp(&(
+                &b.path,
+                b.range.start.line,
+                b.range.start.character,
+                &b.source,
+                &b.code,
+            ))
+    });
+
+    let mut text = match problems.len() {
+        0 => "No problems.\n".to_string(),
+        n => format!("{}.\n", count(n, "problem", "problems")),
+    };
+    for (index, problem) in problems.iter().enumerate() {
+        let _ = writeln!(
+            text,
+            "Problem {} of {}: {}",
+            index + 1,
+            problems.len(),
+            describe_problem(problem)
+        );
+    }
+
+    debug!(problems = problems.len(), "Rendered problems as plain text");
+    text_result(text)
+}
+
+fn describe_problem(problem: &Problem) -> String {
+    let severity = match problem.severity {
+        crate::problems::Severity::Error => "error",
+        crate::problems::Severity::Warning => "warning",
+        crate::problems::Severity::Info => "information",
+    };
+    format!(
+        "{severity} in {}, line {}, column {}: {}. Source {}, code {}.",
+        problem.path,
+        problem.range.start.line + 1,
+        problem.range.start.character + 1,
+        problem.message.trim_end_matches('.'),
+        problem.source,
+        problem.code,
+    )
+}
+
+pub fn handle_symbols(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("plain_text_symbols_operation");
+    let _enter = span.enter();
+
+    let params: SymbolsParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let grammar = syntax::grammar_for(path, params.language.as_deref()).ok_or_else(|| {
+        HandlerError::InvalidParams(format!("No grammar available for {}", params.path))
+    })?;
+    let content = document_content(path, params.content)?;
+    let symbols = state
+        .syntax
+        .symbols(grammar, &content)
+        .map_err(HandlerError::SyntaxError)?;
+
+    let mut text = match symbols.len() {
+        0 => format!("No symbols in {}.\n", params.path),
+        n => format!(
+            "Outline of {}, {} at the top level.\n",
+            params.path,
+            count(n, "symbol", "symbols")
+        ),
+    };
+    write_symbols(&mut text, &symbols, "");
+
+    info!(path = %params.path, symbols = symbols.len(), "Rendered symbols as plain text");
+    text_result(text)
+}
+
+fn write_symbols(text: &mut String, symbols: &[Symbol], prefix: &str) {
+    for (index, symbol) in symbols.iter().enumerate() {
+        let number = format!("{prefix}{}.", index + 1);
+        let (start, end) = (symbol.range.start.line + 1, symbol.range.end.line + 1);
+        let lines = if start == end {
+            format!("line {start}")
+        } else {
+            format!("lines {start} to {end}")
+        };
+        let _ = write!(text, "{number} {} {}, {lines}", symbol.kind, symbol.name);
+        if !symbol.children.is_empty() {
+            let _ = write!(
+                text,
+                ", contains {}",
+                count(symbol.children.len(), "symbol", "symbols")
+            );
+        }
+        text.push_str(".\n");
+        write_symbols(text, &symbol.children, &number);
+    }
+}
+
+pub fn handle_tree(params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("plain_text_tree_operation");
+    let _enter = span.enter();
+
+    let params: TreeParams = parse_params(params)?;
+    let root = Path::new(&params.path);
+    if !root.is_dir() {
+        return Err(HandlerError::DirectoryError(
+            "Path is not a directory".to_string(),
+        ));
+    }
+
+    let mut lines = Vec::new();
+    let truncated = write_tree(root, params.depth.max(1), "", &mut lines)?;
+    let mut text = format!("Contents of {}.\n", params.path);
+    if lines.is_empty() {
+        text.push_str("The folder is empty.\n");
+    }
+    for line in &lines {
+        text.push_str(line);
+        text.push('\n');
+    }
+    if truncated {
+        let _ = writeln!(text, "Listing stopped after {MAX_TREE_ENTRIES} entries.");
+    }
+
+    debug!(path = %params.path, entries = lines.len(), truncated, "Rendered tree as plain text");
+    text_result(text)
+}
+
+/// Appends one line per entry, directories first then files, both sorted
+/// by name as in `listFiles`. Returns true if the entry limit was hit.
+fn write_tree(
+    dir: &Path,
+    depth: usize,
+    prefix: &str,
+    lines: &mut Vec<String>,
+) -> Result<bool, HandlerError> {
+    let mut entries: Vec<(bool, String, u64)> = Vec::new();
+    for entry in fs::read_dir(dir).map_err(HandlerError::IoError)? {
+        let entry = entry.map_err(HandlerError::IoError)?;
+        let metadata = entry.metadata().map_err(HandlerError::IoError)?;
+        entries.push((
+            metadata.is_dir(),
+            entry.file_name().to_string_lossy().into_owned(),
+            metadata.len(),
+        ));
+    }
+    entries.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    for (index, (is_dir, name, size)) in entries.iter().enumerate() {
+        if lines.len() >= MAX_TREE_ENTRIES {
+            return Ok(true);
+        }
+        let number = format!("{prefix}{}.", index + 1);
+        if *is_dir {
+            let items = fs::read_dir(dir.join(name)).map(|d| d.count()).unwrap_or(0);
+            lines.push(format!(
+                "{number} {name}, folder, {}.",
+                count(items, "item", "items")
+            ));
+            if depth > 1 && write_tree(&dir.join(name), depth - 1, &number, lines)? {
+                return Ok(true);
+            }
+        } else {
+            lines.push(format!(
+                "{number} {name}, file, {}.",
+                count(*size as usize, "byte", "bytes")
+            ));
+        }
+    }
+    Ok(false)
+}
+
+pub fn handle_diff(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("plain_text_diff_operation");
+    let _enter = span.enter();
+
+    let params: DiffParams = parse_params(params)?;
+    let mut args = vec!["diff", "--no-color", "--no-ext-diff", "-U0"];
+    if params.staged {
+        args.push("--cached");
+    }
+    if let Some(path) = &params.path {
+        args.extend(["--", path]);
+    }
+    let diff = git::run_git(state, &args)?;
+
+    let text = describe_diff(&diff);
+    info!(staged = params.staged, "Rendered diff as plain text");
+    text_result(text)
+}
+
+/// Turns `git diff -U0` output into one sentence per changed line.
+fn describe_diff(diff: &str) -> String {
+    let mut body = String::new();
+    let (mut files, mut added, mut removed) = (0, 0, 0);
+    let (mut old_line, mut new_line) = (0, 0);
+
+    for line in diff.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            files += 1;
+            let name = paths.rsplit_once(" b/").map_or(paths, |(_, name)| name);
+            let _ = writeln!(body, "File {name}:");
+        } else if let Some(hunk) = line.strip_prefix("@@ ") {
+            // @@ -old[,count] +new[,count] @@
+            let mut ranges = hunk.split(' ');
+            let start = |range: Option<&str>| {
+                range
+                    .and_then(|range| range[1..].split(',').next())
+                    .and_then(|start| start.parse::<usize>().ok())
+                    .unwrap_or(0)
+            };
+            old_line = start(ranges.next());
+            new_line = start(ranges.next());
+        } else if line.starts_with("---") || line.starts_with("+++") {
+            continue;
+        } else if let Some(content) = line.strip_prefix('-') {
+            removed += 1;
+            let _ = writeln!(body, "Removed line {old_line}: {}", spoken(content));
+            old_line += 1;
+        } else if let Some(content) = line.strip_prefix('+') {
+            added += 1;
+            let _ = writeln!(body, "Added line {new_line}: {}", spoken(content));
+            new_line += 1;
+        } else if line.starts_with("Binary files") {
+            let _ = writeln!(body, "Binary file changed.");
+        }
+    }
+
+    if files == 0 {
+        return "No changes.\n".to_string();
+    }
+    format!(
+        "{} changed, {} added, {} removed.\n{body}",
+        count(files, "file", "files"),
+        count(added, "line", "lines"),
+        count(removed, "line", "lines"),
+    )
+}
+
+/// Blank lines would otherwise be read as nothing at all.
+fn spoken(content: &str) -> &str {
+    if content.trim().is_empty() {
+        "blank"
+    } else {
+        content
+    }
+}
@@ -0,0 +1,61 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{debug, info_span};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use super::text::{Position, Range};
+use crate::{presence::PresenceUpdate, state::AppState};
+
+#[derive(Deserialize)]
+struct UpdatePresenceParams {
+    /// Defaults to the connection's identity name.
+    username: Option<String>,
+    path: Option<String>,
+    cursor: Option<Position>,
+    selection: Option<Range>,
+}
+
+/// Records what the client is doing; every other connection is sent a
+/// `presence/changed` notification.
+pub fn handle_update(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("presence_update_operation");
+    let _enter = span.enter();
+
+    let params: UpdatePresenceParams = parse_params(params)?;
+    if params
+        .username
+        .as_ref()
+        .is_some_and(|name| name.trim().is_empty())
+    {
+        return Err(HandlerError::InvalidParams(
+            "username must not be empty".to_string(),
+        ));
+    }
+    let presence = state.presence.update(
+        connection.id,
+        connection.identity.name.as_deref(),
+        PresenceUpdate {
+            username: params.username,
+            path: params.path,
+            cursor: params.cursor,
+            selection: params.selection,
+        },
+    );
+    Ok(json!(presence))
+}
+
+/// Everyone who has reported presence, this connection included.
+pub fn handle_list(state: &AppState) -> Result<Value, HandlerError> {
+    let span = info_span!("presence_list_operation");
+    let _enter = span.enter();
+
+    let clients = state.presence.list();
+    debug!(clients = clients.len(), "Listing presence");
+    Ok(json!({ "clients": clients }))
+}
@@ -0,0 +1,20 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+struct ListProblemsParams {
+    source: Option<String>,
+    path: Option<String>,
+}
+
+pub fn handle_list(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let params: ListProblemsParams = parse_params(params)?;
+    let problems = state
+        .problems
+        .list(params.source.as_deref(), params.path.as_deref());
+    serde_json::to_value(problems).map_err(|e| HandlerError::InvalidParams(e.to_string()))
+}
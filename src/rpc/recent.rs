@@ -0,0 +1,53 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::Path;
+use tracing::{debug, info_span};
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+
+const MAX_RECENT_FILES: usize = 1000;
+
+#[derive(Deserialize)]
+struct RecentFilesParams {
+    /// Only files under this directory.
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    50
+}
+
+/// The most recently modified files, newest first, from the workspace
+/// index. `indexed` is false until the index's first walk has finished.
+pub fn handle_recent_files(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("recent_files_operation");
+    let _enter = span.enter();
+
+    let params: RecentFilesParams = parse_params(params)?;
+    let under = params.path.as_deref().map(Path::new);
+    let limit = params.limit.min(MAX_RECENT_FILES);
+    // The index holds canonical paths.
+    let under = under
+        .map(|path| std::path::absolute(path).map(|path| path.canonicalize().unwrap_or(path)))
+        .transpose()
+        .map_err(HandlerError::IoError)?;
+    let files: Vec<Value> = state
+        .files
+        .recent(under.as_deref(), limit)
+        .into_iter()
+        .map(|(path, file)| {
+            json!({
+                "path": path,
+                "size": file.size,
+                "modifiedMs": file.modified_ms,
+            })
+        })
+        .collect();
+    debug!(files = files.len(), limit, "Listing recent files");
+    Ok(json!({ "files": files, "indexed": state.files.is_ready() }))
+}
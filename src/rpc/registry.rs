@@ -0,0 +1,147 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::cancel::CancelToken;
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::schema;
+use crate::state::SharedState;
+
+/// Everything a handler gets for one request.
+pub struct Call<'a> {
+    pub state: &'a SharedState,
+    pub connection: &'a ConnectionContext,
+    pub method: &'a str,
+    /// The request's id, `null` for notifications.
+    pub id: &'a Value,
+    pub correlation_id: &'a str,
+    pub params: Value,
+    /// Cancelled by `$/cancelRequest` or when the request times out;
+    /// long-running handlers should check it.
+    pub cancel: &'a CancelToken,
+}
+
+/// One JSON-RPC method. Handlers run on a blocking thread, since most do
+/// file or process I/O, after the request has passed validation and the
+/// permission, policy and read-only checks.
+pub trait Handler: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// JSON Schema of the params, listed by `server/capabilities`.
+    fn params_schema(&self) -> Option<Value> {
+        schema::params_schema(self.name())
+    }
+
+    /// The result to answer with, or `None` if the handler sends the
+    /// reply itself later.
+    fn execute(&self, call: Call<'_>) -> Result<Option<Value>, HandlerError>;
+}
+
+/// A handler answering from a plain function.
+struct FnHandler<F> {
+    name: &'static str,
+    run: F,
+}
+
+impl<F> Handler for FnHandler<F>
+where
+    F: Fn(Call<'_>) -> Result<Value, HandlerError> + Send + Sync,
+{
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn execute(&self, call: Call<'_>) -> Result<Option<Value>, HandlerError> {
+        (self.run)(call).map(Some)
+    }
+}
+
+/// Runs around every handler, for checks and bookkeeping that apply to
+/// all methods alike. An interceptor answers in place of the handler by
+/// returning without calling `next`.
+pub trait Interceptor: Send + Sync {
+    fn intercept(&self, call: Call<'_>, next: Next<'_>) -> Result<Option<Value>, HandlerError>;
+}
+
+/// The rest of the chain: the interceptors not yet run, then the handler.
+pub struct Next<'a> {
+    interceptors: &'a [Box<dyn Interceptor>],
+    handler: &'a dyn Handler,
+}
+
+impl Next<'_> {
+    /// The handler the call is for.
+    pub fn handler(&self) -> &dyn Handler {
+        self.handler
+    }
+
+    pub fn run(self, call: Call<'_>) -> Result<Option<Value>, HandlerError> {
+        match self.interceptors.split_first() {
+            Some((first, rest)) => first.intercept(
+                call,
+                Next {
+                    interceptors: rest,
+                    handler: self.handler,
+                },
+            ),
+            None => self.handler.execute(call),
+        }
+    }
+}
+
+/// The methods the server answers, keyed by name, and the interceptors
+/// every call passes through. Built once at startup.
+#[derive(Default)]
+pub struct MethodRegistry {
+    handlers: BTreeMap<&'static str, Box<dyn Handler>>,
+    interceptors: Vec<Box<dyn Interceptor>>,
+}
+
+impl MethodRegistry {
+    /// Adds `handler`, replacing any registered under the same name.
+    pub fn register(&mut self, handler: impl Handler + 'static) -> &mut Self {
+        self.handlers.insert(handler.name(), Box::new(handler));
+        self
+    }
+
+    /// Adds a method answered by `run`.
+    pub fn method<F>(&mut self, name: &'static str, run: F) -> &mut Self
+    where
+        F: Fn(Call<'_>) -> Result<Value, HandlerError> + Send + Sync + 'static,
+    {
+        self.register(FnHandler { name, run })
+    }
+
+    /// Adds `interceptor` inside those added before it, so the first added
+    /// sees each call first.
+    pub fn intercept(&mut self, interceptor: impl Interceptor + 'static) -> &mut Self {
+        self.interceptors.push(Box::new(interceptor));
+        self
+    }
+
+    /// Runs `handler` for `call` through the interceptors.
+    pub fn dispatch(
+        &self,
+        handler: &dyn Handler,
+        call: Call<'_>,
+    ) -> Result<Option<Value>, HandlerError> {
+        Next {
+            interceptors: &self.interceptors,
+            handler,
+        }
+        .run(call)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Handler> {
+        self.handlers.get(name).map(|handler| handler.as_ref())
+    }
+
+    /// Every registered method, sorted by name.
+    pub fn handlers(&self) -> impl Iterator<Item = &dyn Handler> {
+        self.handlers.values().map(|handler| handler.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.handlers.keys().copied().collect()
+    }
+}
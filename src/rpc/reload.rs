@@ -0,0 +1,18 @@
+use serde_json::{Value, json};
+use tracing::info_span;
+
+use super::error::HandlerError;
+use crate::state::AppState;
+
+/// Handles `config/reload`, the RPC twin of `SIGHUP`. A config that fails
+/// to load leaves the running one untouched and comes back as an error.
+pub fn handle_reload(state: &AppState) -> Result<Value, HandlerError> {
+    let span = info_span!("config_reload_operation");
+    let _enter = span.enter();
+
+    let reloaded = state
+        .reloader
+        .reload(state)
+        .map_err(HandlerError::ConfigError)?;
+    Ok(json!(reloaded))
+}
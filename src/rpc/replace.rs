@@ -0,0 +1,282 @@
+//! Workspace-wide find and replace.
+//!
+//! Matches never span lines. With `dryRun` the proposed edits are returned
+//! and nothing is written; otherwise the changed files are written
+//! together, see [`crate::staged`].
+
+use globset::{Glob, GlobMatcher};
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, info_span};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{config::Access, protected::audit_forced, staged, state::AppState};
+
+/// Edits listed in a dry run before the rest are only counted.
+const MAX_REPORTED_EDITS: usize = 1000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplaceInFilesParams {
+    pattern: String,
+    replacement: String,
+    /// Treat `pattern` as a regular expression, and allow `$1` style
+    /// group references in `replacement`.
+    #[serde(default)]
+    regex: bool,
+    #[serde(default = "default_true")]
+    case_sensitive: bool,
+    /// Directory or file to search; the workspace root by default.
+    #[serde(default)]
+    path: Option<String>,
+    /// Glob over paths relative to `path`, e.g. `**/*.rs`.
+    #[serde(default)]
+    include: Option<String>,
+    #[serde(default = "default_true")]
+    respect_ignore: bool,
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    force: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+struct FileChange {
+    path: PathBuf,
+    content: String,
+    replacements: usize,
+    /// `(line, before, after)` for each changed line.
+    lines: Vec<(usize, String, String)>,
+}
+
+pub fn handle_replace_in_files(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("replace_in_files_operation");
+    let _enter = span.enter();
+
+    let params: ReplaceInFilesParams = parse_params(params)?;
+    if params.pattern.is_empty() {
+        return Err(HandlerError::InvalidParams(
+            "pattern must not be empty".to_string(),
+        ));
+    }
+    let source = if params.regex {
+        params.pattern.clone()
+    } else {
+        regex::escape(&params.pattern)
+    };
+    let regex = RegexBuilder::new(&source)
+        .case_insensitive(!params.case_sensitive)
+        .build()
+        .map_err(|e| HandlerError::InvalidParams(format!("Invalid pattern: {e}")))?;
+    let include = params
+        .include
+        .as_deref()
+        .map(|glob| {
+            Glob::new(glob)
+                .map(|glob| glob.compile_matcher())
+                .map_err(|e| HandlerError::InvalidParams(format!("Invalid include glob: {e}")))
+        })
+        .transpose()?;
+    let start = params
+        .path
+        .as_deref()
+        .map_or_else(|| state.config.root.clone(), PathBuf::from);
+    if !start.exists() {
+        return Err(HandlerError::FileNotFound(start));
+    }
+
+    debug!(
+        path = %start.display(),
+        pattern = %params.pattern,
+        regex = params.regex,
+        dry_run = params.dry_run,
+        "Replacing in files"
+    );
+    let required = if params.dry_run {
+        Access::Read
+    } else {
+        Access::Write
+    };
+    let changes = find_changes(state, &start, include.as_ref(), &regex, &params, required)?;
+    let replacements: usize = changes.iter().map(|change| change.replacements).sum();
+
+    if params.dry_run {
+        let mut reported = 0;
+        let files: Vec<Value> = changes
+            .iter()
+            .map(|change| {
+                let edits: Vec<Value> = change
+                    .lines
+                    .iter()
+                    .take(MAX_REPORTED_EDITS - reported)
+                    .map(|(line, before, after)| {
+                        json!({ "line": line, "before": before, "after": after })
+                    })
+                    .collect();
+                reported += edits.len();
+                json!({
+                    "path": change.path,
+                    "replacements": change.replacements,
+                    "lines": change.lines.len(),
+                    "edits": edits,
+                })
+            })
+            .collect();
+        info!(
+            files = files.len(),
+            replacements, "Computed replacements without writing"
+        );
+        return Ok(json!({
+            "dryRun": true,
+            "files": files,
+            "filesChanged": changes.len(),
+            "replacements": replacements,
+            "truncated": reported < changes.iter().map(|change| change.lines.len()).sum(),
+        }));
+    }
+
+    staged::write_all(
+        changes
+            .iter()
+            .map(|change| (change.path.as_path(), change.content.as_bytes())),
+    )
+    .map_err(HandlerError::IoError)?;
+    for change in &changes {
+        state.activity.record(
+            "file.saved",
+            Some(connection.id),
+            json!({ "path": change.path, "bytes": change.content.len() }),
+        );
+    }
+    info!(files = changes.len(), replacements, "Replaced in files");
+    let files: Vec<Value> = changes
+        .iter()
+        .map(|change| {
+            json!({
+                "path": change.path,
+                "replacements": change.replacements,
+                "lines": change.lines.len(),
+            })
+        })
+        .collect();
+    Ok(json!({
+        "dryRun": false,
+        "files": files,
+        "filesChanged": changes.len(),
+        "replacements": replacements,
+    }))
+}
+
+/// Every file under `start` the replacement would change. Files that are
+/// not UTF-8 text or larger than the write limit are skipped, as are `.git`
+/// and the server's data directory.
+fn find_changes(
+    state: &AppState,
+    start: &Path,
+    include: Option<&GlobMatcher>,
+    regex: &Regex,
+    params: &ReplaceInFilesParams,
+    required: Access,
+) -> Result<Vec<FileChange>, HandlerError> {
+    let limit = state.limits().max_write_bytes as u64;
+    let data_path = std::path::absolute(state.config.data_path()).map_err(HandlerError::IoError)?;
+    let mut walker = state.ignore.walker(start, params.respect_ignore);
+    walker.sort_by_file_name(|a, b| a.cmp(b));
+    let mut changes = Vec::new();
+    for entry in walker.build() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let path = entry.path();
+        if !entry.file_type().is_some_and(|t| t.is_file())
+            || path.components().any(|c| c.as_os_str() == ".git")
+            || std::path::absolute(path).is_ok_and(|path| path.starts_with(&data_path))
+            || entry.metadata().is_ok_and(|m| m.len() > limit)
+        {
+            continue;
+        }
+        if let Some(include) = include {
+            let relative = path.strip_prefix(start).unwrap_or(path);
+            if !include.is_match(relative) {
+                continue;
+            }
+        }
+        let Ok(content) = fs::read_to_string(path) else {
+            continue;
+        };
+        let Some(change) = replace(path, &content, regex, params) else {
+            continue;
+        };
+
+        state.permissions.check(path, required)?;
+        if !params.dry_run && state.protected.is_protected(path) {
+            if !params.force {
+                debug!(path = %path.display(), "Refusing to rewrite protected path");
+                return Err(HandlerError::ProtectedPath(
+                    path.to_string_lossy().into_owned(),
+                ));
+            }
+            audit_forced("replaceInFiles", &path.to_string_lossy());
+        }
+        if change.content.len() as u64 > limit {
+            return Err(HandlerError::PayloadTooLarge(format!(
+                "{} would grow to {} bytes, larger than the {limit} byte write limit",
+                path.display(),
+                change.content.len()
+            )));
+        }
+        changes.push(change);
+    }
+    Ok(changes)
+}
+
+fn replace(
+    path: &Path,
+    content: &str,
+    regex: &Regex,
+    params: &ReplaceInFilesParams,
+) -> Option<FileChange> {
+    let mut replaced = String::with_capacity(content.len());
+    let mut replacements = 0;
+    let mut lines = Vec::new();
+    for (number, line) in content.split_inclusive('\n').enumerate() {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+        let count = regex.find_iter(body).count();
+        if count == 0 {
+            replaced.push_str(line);
+            continue;
+        }
+        let after = if params.regex {
+            regex.replace_all(body, params.replacement.as_str())
+        } else {
+            regex.replace_all(body, NoExpand(&params.replacement))
+        };
+        if after != body {
+            lines.push((number, body.to_string(), after.to_string()));
+            replacements += count;
+        }
+        replaced.push_str(&after);
+        replaced.push_str(ending);
+    }
+    (!lines.is_empty()).then(|| FileChange {
+        path: path.to_path_buf(),
+        content: replaced,
+        replacements,
+        lines,
+    })
+}
@@ -1,11 +1,50 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{
+    OnceLock,
+    atomic::{AtomicU64, Ordering},
+};
 
+/// Missing `jsonrpc` and `method` members deserialize as empty strings so
+/// [`validate`](Self::validate) can answer them with `INVALID_REQUEST`
+/// rather than a parse error.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JsonRpcRequest {
+    #[serde(default)]
     pub jsonrpc: String,
+    #[serde(default)]
     pub method: String,
+    #[serde(default)]
     pub params: serde_json::Value,
     pub id: Option<serde_json::Value>,
+    /// Set by the server on receipt, see [`next_correlation_id`].
+    #[serde(skip)]
+    pub correlation_id: String,
+}
+
+impl JsonRpcRequest {
+    /// Checks the envelope against JSON-RPC 2.0, returning the reason it
+    /// is malformed.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.jsonrpc != "2.0" {
+            return Err(r#"jsonrpc must be "2.0""#.to_string());
+        }
+        if self.method.is_empty() {
+            return Err("method must be a non-empty string".to_string());
+        }
+        if !matches!(
+            self.params,
+            Value::Null | Value::Object(_) | Value::Array(_)
+        ) {
+            return Err("params must be an object or an array".to_string());
+        }
+        if let Some(id) = &self.id
+            && !matches!(id, Value::Null | Value::String(_) | Value::Number(_))
+        {
+            return Err("id must be a string, a number or null".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -14,4 +53,42 @@ pub struct JsonRpcResponse {
     pub result: Option<serde_json::Value>,
     pub error: Option<super::error::JsonRpcError>,
     pub id: serde_json::Value,
+    /// Server-generated id of the message this answers, as it appears in
+    /// the server's logs. An extension to JSON-RPC that clients may ignore.
+    #[serde(
+        default,
+        rename = "correlationId",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub correlation_id: Option<String>,
+}
+
+impl JsonRpcResponse {
+    pub fn correlated(mut self, correlation_id: &str) -> Self {
+        self.correlation_id = Some(correlation_id.to_string());
+        self
+    }
+}
+
+/// A fresh id for an incoming message, logged on the spans of everything
+/// it causes and returned in its response so a client's report can be
+/// matched to the server's logs. The random prefix keeps ids from
+/// repeating across restarts.
+pub fn next_correlation_id() -> String {
+    static PREFIX: OnceLock<String> = OnceLock::new();
+    static COUNTER: AtomicU64 = AtomicU64::new(1);
+    let prefix = PREFIX.get_or_init(|| {
+        let mut bytes = [0u8; 4];
+        let _ = getrandom::fill(&mut bytes);
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    });
+    format!("{prefix}-{}", COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Server-initiated message without an id; the client sends no reply.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JsonRpcNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: serde_json::Value,
 }
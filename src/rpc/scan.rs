@@ -0,0 +1,91 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{Instrument, info, info_span, warn};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::scan::{LICENSES_SOURCE, SECRETS_SOURCE, ScanOptions, scan_workspace};
+use crate::state::AppState;
+
+#[derive(Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Check {
+    Secrets,
+    Licenses,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RunScanParams {
+    #[serde(default = "default_checks")]
+    checks: Vec<Check>,
+    #[serde(default)]
+    allowed_licenses: Vec<String>,
+    /// Skip files excluded by ignore files and the server's `exclude` list.
+    #[serde(default = "default_true")]
+    respect_ignore: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_checks() -> Vec<Check> {
+    vec![Check::Secrets, Check::Licenses]
+}
+
+/// Starts a background scan; findings land in the problems store and the
+/// caller receives `scan/completed` when it finishes.
+pub fn handle_run(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let params: RunScanParams = parse_params(params)?;
+    let options = ScanOptions {
+        secrets: params.checks.contains(&Check::Secrets),
+        licenses: params.checks.contains(&Check::Licenses),
+        allowed_licenses: params.allowed_licenses,
+        ignore: state.ignore.clone(),
+        respect_ignore: params.respect_ignore,
+    };
+
+    let root = state.config.root.clone();
+    let problems = state.problems.clone();
+    let notifier = connection.notifier.clone();
+    let span = info_span!("workspace_scan");
+    tokio::spawn(
+        async move {
+            let report =
+                match tokio::task::spawn_blocking(move || scan_workspace(&root, &options)).await {
+                    Ok(report) => report,
+                    Err(e) => {
+                        warn!(error = %e, "Workspace scan failed");
+                        return;
+                    }
+                };
+
+            let secrets = report.secrets.len();
+            let licenses = report.licenses.len();
+            problems.replace(SECRETS_SOURCE, report.secrets);
+            problems.replace(LICENSES_SOURCE, report.licenses);
+
+            info!(
+                files_scanned = report.files_scanned,
+                secrets, licenses, "Workspace scan completed"
+            );
+            notifier.notify(
+                "scan/completed",
+                json!({
+                    "filesScanned": report.files_scanned,
+                    "secrets": secrets,
+                    "licenses": licenses,
+                }),
+            );
+        }
+        .instrument(span),
+    );
+
+    Ok(json!({ "started": true }))
+}
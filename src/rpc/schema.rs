@@ -0,0 +1,508 @@
+//! JSON Schemas for method parameters, published through
+//! `server/capabilities` and checked against each request by
+//! [`super::validate`]. Keep these in step with the `*Params` structs: a
+//! field required here is refused when missing before serde sees it.
+
+use serde_json::{Map, Value, json};
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn any() -> Value {
+    json!({})
+}
+
+fn string_enum(values: &[&str]) -> Value {
+    json!({ "type": "string", "enum": values })
+}
+
+/// Names [`crate::charset::Charset`] accepts.
+fn charset() -> Value {
+    string_enum(&[
+        "utf-8",
+        "utf8",
+        "utf-16le",
+        "utf-16",
+        "utf-16be",
+        "latin1",
+        "latin-1",
+        "iso-8859-1",
+    ])
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn position() -> Value {
+    object(&[("line", integer()), ("character", integer())], &[])
+}
+
+fn range() -> Value {
+    object(&[("start", position()), ("end", position())], &[])
+}
+
+/// An object schema with `required` and `optional` properties.
+fn object(required: &[(&str, Value)], optional: &[(&str, Value)]) -> Value {
+    let properties: Map<String, Value> = required
+        .iter()
+        .chain(optional)
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+    let names: Vec<&str> = required.iter().map(|(name, _)| *name).collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": names,
+    })
+}
+
+fn empty() -> Value {
+    object(&[], &[])
+}
+
+/// Parameters of a document-based request (`highlight`, `documentSymbols`).
+fn document() -> Value {
+    object(
+        &[("path", string())],
+        &[("content", string()), ("language", string())],
+    )
+}
+
+/// Schema for `method`'s params, or `None` for unknown methods.
+pub fn params_schema(method: &str) -> Option<Value> {
+    let schema = match method {
+        "$/cancelRequest" => object(&[("id", any())], &[]),
+        "activity/list" => object(
+            &[],
+            &[
+                ("since", integer()),
+                ("kind", string()),
+                ("limit", integer()),
+            ],
+        ),
+        "addToDictionary" => object(&[("word", string())], &[("workspace", string())]),
+        "applyPatch" => object(
+            &[("patch", string())],
+            &[
+                ("cwd", string()),
+                ("partial", boolean()),
+                ("force", boolean()),
+                ("dryRun", boolean()),
+            ],
+        ),
+        "audit/query" => object(
+            &[],
+            &[
+                ("since", integer()),
+                ("method", string()),
+                ("path", string()),
+                ("limit", integer()),
+            ],
+        ),
+        "blob/get" => object(
+            &[("hash", string())],
+            &[("offset", integer()), ("length", integer())],
+        ),
+        "blob/put" => object(
+            &[],
+            &[
+                ("path", string()),
+                ("uploadId", integer()),
+                ("data", string()),
+                ("done", boolean()),
+            ],
+        ),
+        "config/reload"
+        | "connection/stats"
+        | "server/capabilities"
+        | "server/errorCatalog"
+        | "server/info"
+        | "snippet/languages"
+        | "task/list" => empty(),
+        "computeDiff" => object(
+            &[("path", string())],
+            &[
+                ("originalPath", string()),
+                ("content", string()),
+                ("context", integer()),
+                ("format", string_enum(&["unified", "hunks"])),
+            ],
+        ),
+        "createFromTemplate" => object(
+            &[("template", string()), ("path", string())],
+            &[
+                (
+                    "variables",
+                    json!({ "type": "object", "additionalProperties": { "type": "string" } }),
+                ),
+                ("dryRun", boolean()),
+            ],
+        ),
+        "dap/request" => object(
+            &[("sessionId", integer()), ("command", string())],
+            &[("arguments", any())],
+        ),
+        "dap/respond" => object(
+            &[("sessionId", integer()), ("requestSeq", integer())],
+            &[
+                ("success", boolean()),
+                ("body", any()),
+                ("message", string()),
+            ],
+        ),
+        "dap/start" => object(&[("adapter", string())], &[]),
+        "dap/stop" => object(&[("sessionId", integer())], &[]),
+        "debug/recentRequests" => object(
+            &[],
+            &[
+                ("connectionId", integer()),
+                ("method", string()),
+                ("limit", integer()),
+            ],
+        ),
+        "definition" => object(
+            &[("path", string()), ("position", position())],
+            &[
+                ("content", string()),
+                ("language", string()),
+                ("limit", integer()),
+            ],
+        ),
+        "deleteFile" => object(
+            &[("path", string())],
+            &[
+                ("force", boolean()),
+                ("permanent", boolean()),
+                ("dryRun", boolean()),
+            ],
+        ),
+        "detectFileType" | "statPath" => object(&[("path", string())], &[]),
+        "diskUsage" => object(&[], &[("maxAgeSecs", integer())]),
+        "document/close" | "document/open" | "document/save" => object(&[("path", string())], &[]),
+        "document/update" => object(
+            &[("path", string()), ("content", string())],
+            &[("version", integer()), ("force", boolean())],
+        ),
+        "documentSymbols" | "highlight" | "plainText/symbols" => document(),
+        "exportZip" => object(&[("path", string())], &[]),
+        "extractText" => object(&[("path", string())], &[("maxPages", integer())]),
+        "findFiles" => object(
+            &[("query", string())],
+            &[("path", string()), ("limit", integer())],
+        ),
+        "formatDocument" => object(
+            &[("path", string())],
+            &[("content", string()), ("language", string())],
+        ),
+        "git/blame" => object(
+            &[("path", string())],
+            &[("startLine", integer()), ("endLine", integer())],
+        ),
+        "git/branches" => object(&[], &[("includeRemote", boolean())]),
+        "git/checkout" => object(&[("branch", string())], &[]),
+        "git/createBranch" => object(
+            &[("name", string())],
+            &[("startPoint", string()), ("checkout", boolean())],
+        ),
+        "git/deleteBranch" => object(&[("name", string())], &[("force", boolean())]),
+        "hashFile" => object(&[("path", string())], &[]),
+        "history/list" => object(&[("path", string())], &[]),
+        "history/restore" => object(
+            &[("path", string()), ("id", string())],
+            &[("force", boolean()), ("dryRun", boolean())],
+        ),
+        "importZip" => object(
+            &[("path", string()), ("hash", string())],
+            &[
+                ("overwrite", boolean()),
+                ("force", boolean()),
+                ("dryRun", boolean()),
+            ],
+        ),
+        "initialize" => object(
+            &[],
+            &[
+                ("protocolVersion", string()),
+                ("locale", string()),
+                (
+                    "capabilities",
+                    object(
+                        &[],
+                        &[
+                            ("compression", array(string())),
+                            ("codecs", array(string())),
+                            ("flowControl", object(&[("window", integer())], &[])),
+                            ("workspaceUris", boolean()),
+                            ("sessionResume", boolean()),
+                        ],
+                    ),
+                ),
+            ],
+        ),
+        "jobs/history" => object(&[("name", string())], &[("limit", integer())]),
+        "jobs/list" => empty(),
+        "jobs/run" => object(&[("name", string())], &[]),
+        "listFiles" => object(
+            &[("path", string())],
+            &[
+                ("offset", integer()),
+                ("limit", integer()),
+                ("glob", string()),
+                ("includeHidden", boolean()),
+                ("includeMtime", boolean()),
+                ("includeType", boolean()),
+                ("respectIgnore", boolean()),
+            ],
+        ),
+        "lockFile" => object(&[("path", string())], &[("note", string())]),
+        "lsp/notify" | "lsp/request" => object(
+            &[("language", string()), ("method", string())],
+            &[("params", any())],
+        ),
+        "lsp/respond" => object(
+            &[("language", string()), ("id", any())],
+            &[("result", any()), ("error", any())],
+        ),
+        "lsp/stop" => object(&[("language", string())], &[]),
+        "plainText/diff" => object(&[], &[("path", string()), ("staged", boolean())]),
+        "plainText/problems" | "problems/list" => {
+            object(&[], &[("source", string()), ("path", string())])
+        }
+        "plainText/tree" => object(&[("path", string())], &[("depth", integer())]),
+        "presence/list" => empty(),
+        "presence/update" => object(
+            &[],
+            &[
+                ("username", string()),
+                ("path", string()),
+                ("cursor", position()),
+                ("selection", range()),
+            ],
+        ),
+        "readFile" => object(
+            &[("path", string())],
+            &[
+                ("includeHash", boolean()),
+                ("ifNoneMatch", string()),
+                ("encoding", charset()),
+                ("includeEncoding", boolean()),
+            ],
+        ),
+        "readFileDelta" => object(
+            &[
+                ("path", string()),
+                ("blockSize", integer()),
+                (
+                    "checksums",
+                    array(object(&[("weak", integer()), ("strong", string())], &[])),
+                ),
+            ],
+            &[],
+        ),
+        "recentFiles" => object(&[], &[("path", string()), ("limit", integer())]),
+        "replaceInFiles" => object(
+            &[("pattern", string()), ("replacement", string())],
+            &[
+                ("regex", boolean()),
+                ("caseSensitive", boolean()),
+                ("path", string()),
+                ("include", string()),
+                ("respectIgnore", boolean()),
+                ("dryRun", boolean()),
+                ("force", boolean()),
+            ],
+        ),
+        "saveAll" => empty(),
+        "scan/run" => object(
+            &[],
+            &[
+                ("checks", array(string_enum(&["secrets", "licenses"]))),
+                ("allowedLicenses", array(string())),
+                ("respectIgnore", boolean()),
+            ],
+        ),
+        "searchInFiles" => object(
+            &[("query", string())],
+            &[
+                ("regex", boolean()),
+                ("caseSensitive", boolean()),
+                ("path", string()),
+                ("include", string()),
+                ("maxResults", integer()),
+            ],
+        ),
+        "share/create" => object(
+            &[("paths", array(string()))],
+            &[("expiresInSecs", integer())],
+        ),
+        "share/list" => empty(),
+        "share/revoke" => object(&[("token", string())], &[]),
+        "snippet/run" => object(
+            &[("language", string()), ("code", string())],
+            &[("stdin", string()), ("timeoutMs", integer())],
+        ),
+        "spellCheck" => object(
+            &[],
+            &[
+                ("path", string()),
+                ("content", string()),
+                ("language", string_enum(&["markdown", "plaintext"])),
+                ("workspace", string()),
+                ("suggestions", integer()),
+            ],
+        ),
+        "stream/ack" => object(
+            &[
+                ("stream", string_enum(&["terminal", "task"])),
+                ("id", integer()),
+                ("bytes", integer()),
+            ],
+            &[],
+        ),
+        "structuredGet" => object(
+            &[("path", string())],
+            &[
+                ("pointer", string()),
+                ("format", string_enum(&["json", "yaml", "toml"])),
+            ],
+        ),
+        "structuredSet" => object(
+            &[("path", string()), ("pointer", string()), ("value", any())],
+            &[
+                ("format", string_enum(&["json", "yaml", "toml"])),
+                ("create", boolean()),
+                ("force", boolean()),
+                ("dryRun", boolean()),
+            ],
+        ),
+        "table/read" => object(
+            &[("path", string())],
+            &[
+                ("page", integer()),
+                ("pageSize", integer()),
+                ("delimiter", string()),
+                ("hasHeader", boolean()),
+            ],
+        ),
+        "table/updateCell" => object(
+            &[
+                ("path", string()),
+                ("row", integer()),
+                ("column", integer()),
+                ("value", string()),
+            ],
+            &[
+                ("delimiter", string()),
+                ("hasHeader", boolean()),
+                ("force", boolean()),
+                ("dryRun", boolean()),
+            ],
+        ),
+        "task/cancel" => object(&[("taskId", integer())], &[]),
+        "task/run" => object(&[("name", string())], &[]),
+        "terminal/create" => object(
+            &[],
+            &[
+                ("shell", string()),
+                ("args", array(string())),
+                ("cwd", string()),
+                (
+                    "env",
+                    json!({ "type": "object", "additionalProperties": string() }),
+                ),
+                ("cols", integer()),
+                ("rows", integer()),
+            ],
+        ),
+        "terminal/input" => object(&[("terminalId", integer()), ("data", string())], &[]),
+        "terminal/kill" => object(&[("terminalId", integer())], &[]),
+        "terminal/resize" => object(
+            &[
+                ("terminalId", integer()),
+                ("cols", integer()),
+                ("rows", integer()),
+            ],
+            &[],
+        ),
+        "transformText" => object(
+            &[(
+                "transform",
+                string_enum(&[
+                    "sortLines",
+                    "sortLinesDescending",
+                    "uniqueLines",
+                    "reverseLines",
+                    "upperCase",
+                    "lowerCase",
+                    "titleCase",
+                    "camelCase",
+                    "pascalCase",
+                    "snakeCase",
+                    "kebabCase",
+                    "base64Encode",
+                    "base64Decode",
+                    "jsonPretty",
+                    "jsonMinify",
+                ]),
+            )],
+            &[("text", string()), ("path", string()), ("range", range())],
+        ),
+        "trash/empty" => object(&[], &[("olderThanSecs", integer()), ("dryRun", boolean())]),
+        "templates/list" | "trash/list" | "workspace/list" => empty(),
+        "trash/restore" => object(&[("id", string())], &[("dryRun", boolean())]),
+        "unlockFile" => object(&[("path", string())], &[]),
+        "uploadFile" => object(
+            &[("path", string()), ("hash", string())],
+            &[
+                ("overwrite", boolean()),
+                ("force", boolean()),
+                ("dryRun", boolean()),
+            ],
+        ),
+        "watch/start" => object(
+            &[("path", string())],
+            &[("globs", array(string())), ("exclude", array(string()))],
+        ),
+        "watch/stop" => object(&[("subscriptionId", integer())], &[]),
+        "workspace/export" => object(
+            &[("format", string_enum(&["patch", "archive", "gist"]))],
+            &[
+                ("paths", array(string())),
+                ("includeUntracked", boolean()),
+                ("description", string()),
+            ],
+        ),
+        "workspaceSymbols" => object(
+            &[("query", string())],
+            &[("kind", string()), ("path", string()), ("limit", integer())],
+        ),
+        "writeFile" => object(
+            &[("path", string()), ("content", string())],
+            &[
+                ("force", boolean()),
+                ("encoding", charset()),
+                ("bom", boolean()),
+                (
+                    "lineEnding",
+                    string_enum(&["asIs", "preserve", "lf", "crlf"]),
+                ),
+                ("ifMatch", string()),
+                ("merge", boolean()),
+                ("base", string()),
+                ("dryRun", boolean()),
+            ],
+        ),
+        _ => return None,
+    };
+    Some(schema)
+}
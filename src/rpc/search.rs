@@ -0,0 +1,422 @@
+//! `searchInFiles`, `findFiles` and `workspaceSymbols`, answered from the
+//! workspace index (see [`crate::index`]). Until the index's first walk has
+//! finished the first two walk the tree instead, and say so with
+//! `indexed: false`; `workspaceSymbols` answers with the symbols parsed so
+//! far.
+//!
+//! A search reads only the files the content index says can match, so a
+//! file written since the last poll may be missed until the next one.
+
+use globset::{Glob, GlobMatcher};
+use regex::RegexBuilder;
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, info_span};
+
+use super::cancel::CancelToken;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{
+    index::{self, IndexedSymbol},
+    state::SharedState,
+    trigram,
+};
+
+const MAX_SEARCH_RESULTS: usize = 10_000;
+const MAX_FOUND_FILES: usize = 1000;
+const MAX_FOUND_SYMBOLS: usize = 1000;
+/// Matched lines are cut to this many bytes in results.
+const MAX_LINE_BYTES: usize = 500;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchInFilesParams {
+    query: String,
+    /// Treat `query` as a regular expression.
+    #[serde(default)]
+    regex: bool,
+    #[serde(default = "default_true")]
+    case_sensitive: bool,
+    /// Directory or file to search; the workspace root by default.
+    #[serde(default)]
+    path: Option<String>,
+    /// Glob over paths relative to `path`, e.g. `**/*.rs`.
+    #[serde(default)]
+    include: Option<String>,
+    #[serde(default = "default_max_results")]
+    max_results: usize,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_results() -> usize {
+    1000
+}
+
+#[derive(Deserialize)]
+struct FindFilesParams {
+    query: String,
+    /// Only files under this directory.
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    50
+}
+
+/// Handles `searchInFiles`: every line matching `query`, in path order.
+/// Matches never span lines; `line` and `column` count from zero, the
+/// column in characters.
+pub fn handle_search_in_files(
+    state: &SharedState,
+    params: Value,
+    cancel: &CancelToken,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("search_in_files_operation");
+    let _enter = span.enter();
+
+    let params: SearchInFilesParams = parse_params(params)?;
+    if params.query.is_empty() {
+        return Err(HandlerError::InvalidParams(
+            "query must not be empty".to_string(),
+        ));
+    }
+    let source = if params.regex {
+        params.query.clone()
+    } else {
+        regex::escape(&params.query)
+    };
+    let regex = RegexBuilder::new(&source)
+        .case_insensitive(!params.case_sensitive)
+        .build()
+        .map_err(|e| HandlerError::InvalidParams(format!("Invalid query: {e}")))?;
+    let include = params
+        .include
+        .as_deref()
+        .map(|glob| {
+            Glob::new(glob)
+                .map(|glob| glob.compile_matcher())
+                .map_err(|e| HandlerError::InvalidParams(format!("Invalid include glob: {e}")))
+        })
+        .transpose()?;
+    let start = start_path(state, params.path.as_deref())?;
+    let max_results = params.max_results.min(MAX_SEARCH_RESULTS);
+
+    let alternatives = if params.regex {
+        regex_trigrams(&source, !params.case_sensitive)
+    } else {
+        Some(vec![trigram::query_trigrams(
+            &params.query,
+            !params.case_sensitive,
+        )])
+    };
+    let indexed = state.files.is_ready();
+    let candidates = state
+        .files
+        .candidates(alternatives.as_deref())
+        .unwrap_or_else(|| walked_paths(state));
+    debug!(
+        query = %params.query,
+        regex = params.regex,
+        candidates = candidates.len(),
+        indexed,
+        "Searching in files"
+    );
+
+    let mut matches = Vec::new();
+    let mut files_searched = 0;
+    let mut truncated = false;
+    'files: for path in in_scope(&candidates, &start, include.as_ref()) {
+        cancel.check()?;
+        let Ok(content) = fs::read_to_string(path) else {
+            continue;
+        };
+        files_searched += 1;
+        for (number, line) in content.lines().enumerate() {
+            for found in regex.find_iter(line) {
+                if matches.len() == max_results {
+                    truncated = true;
+                    break 'files;
+                }
+                matches.push(json!({
+                    "path": path,
+                    "line": number,
+                    "column": line[..found.start()].chars().count(),
+                    "length": found.as_str().chars().count(),
+                    "text": cut(line, MAX_LINE_BYTES),
+                }));
+            }
+        }
+    }
+    info!(
+        matches = matches.len(),
+        files_searched, truncated, "Searched in files"
+    );
+    Ok(json!({
+        "matches": matches,
+        "filesSearched": files_searched,
+        "truncated": truncated,
+        "indexed": indexed,
+    }))
+}
+
+#[derive(Deserialize)]
+struct WorkspaceSymbolsParams {
+    query: String,
+    /// Only symbols of this kind, e.g. `function`.
+    #[serde(default)]
+    kind: Option<String>,
+    /// Only symbols in files under this directory.
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default = "default_symbol_limit")]
+    limit: usize,
+}
+
+fn default_symbol_limit() -> usize {
+    100
+}
+
+/// Handles `workspaceSymbols`: definitions whose name contains the
+/// characters of `query` in order, ignoring case, best matches first.
+/// Ranges are those of the names.
+pub fn handle_workspace_symbols(state: &SharedState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("workspace_symbols_operation");
+    let _enter = span.enter();
+
+    let params: WorkspaceSymbolsParams = parse_params(params)?;
+    let start = start_path(state, params.path.as_deref())?;
+    let limit = params.limit.min(MAX_FOUND_SYMBOLS);
+    let query: Vec<char> = params.query.to_lowercase().chars().collect();
+
+    // Only the best `limit` are cloned out of the index: once that many are
+    // kept, anything ranking below the worst kept one is just counted.
+    let mut found: Vec<(SymbolRank, PathBuf, IndexedSymbol)> = Vec::new();
+    let mut cutoff: Option<SymbolRank> = None;
+    let mut total = 0;
+    let indexed = state.files.visit_symbols(|path, symbols| {
+        if !path.starts_with(&start) {
+            return;
+        }
+        for symbol in symbols {
+            if params
+                .kind
+                .as_ref()
+                .is_some_and(|kind| *kind != symbol.kind)
+            {
+                continue;
+            }
+            let Some(score) = subsequence_score(&query, &symbol.name) else {
+                continue;
+            };
+            total += 1;
+            let rank = (Reverse(score), symbol.name.len());
+            if cutoff.is_some_and(|cutoff| rank > cutoff) {
+                continue;
+            }
+            found.push((rank, path.to_path_buf(), symbol.clone()));
+            if found.len() >= 2 * limit.max(64) {
+                sort_symbols(&mut found);
+                found.truncate(limit);
+                cutoff = found.last().map(|(rank, _, _)| *rank);
+            }
+        }
+    });
+    sort_symbols(&mut found);
+    let symbols: Vec<Value> = found
+        .into_iter()
+        .take(limit)
+        .map(|((Reverse(score), _), path, symbol)| {
+            json!({
+                "name": symbol.name,
+                "kind": symbol.kind,
+                "containerName": symbol.container_name,
+                "path": path,
+                "range": symbol.range,
+                "score": score,
+            })
+        })
+        .collect();
+    debug!(query = %params.query, total, indexed, "Found workspace symbols");
+    Ok(json!({
+        "symbols": symbols,
+        "total": total,
+        "indexed": indexed,
+    }))
+}
+
+/// Higher scores first, then shorter names.
+type SymbolRank = (Reverse<i64>, usize);
+
+fn sort_symbols(found: &mut [(SymbolRank, PathBuf, IndexedSymbol)]) {
+    found.sort_by(|(a_rank, a_path, a), (b_rank, b_path, b)| {
+        a_rank
+            .cmp(b_rank)
+            .then_with(|| a_path.cmp(b_path))
+            .then_with(|| a.range.start.line.cmp(&b.range.start.line))
+    });
+}
+
+/// Handles `findFiles`: files whose path relative to `path` contains the
+/// characters of `query` in order, ignoring case, best matches first.
+pub fn handle_find_files(state: &SharedState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("find_files_operation");
+    let _enter = span.enter();
+
+    let params: FindFilesParams = parse_params(params)?;
+    let start = start_path(state, params.path.as_deref())?;
+    let limit = params.limit.min(MAX_FOUND_FILES);
+    let query: Vec<char> = params.query.to_lowercase().chars().collect();
+
+    let indexed = state.files.is_ready();
+    let paths = state.files.paths().unwrap_or_else(|| walked_paths(state));
+    let mut scored: Vec<(i64, &Path)> = in_scope(&paths, &start, None)
+        .filter_map(|path| {
+            let relative = path.strip_prefix(&start).unwrap_or(path);
+            fuzzy_score(&query, &relative.to_string_lossy()).map(|score| (score, path))
+        })
+        .collect();
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .cmp(a_score)
+            .then_with(|| a.as_os_str().len().cmp(&b.as_os_str().len()))
+            .then_with(|| a.cmp(b))
+    });
+    let total = scored.len();
+    let files: Vec<Value> = scored
+        .into_iter()
+        .take(limit)
+        .map(|(score, path)| json!({ "path": path, "score": score }))
+        .collect();
+    debug!(query = %params.query, total, indexed, "Found files");
+    Ok(json!({
+        "files": files,
+        "total": total,
+        "indexed": indexed,
+    }))
+}
+
+/// The canonical form of the `path` param, as the index holds paths, or
+/// the workspace root.
+fn start_path(state: &SharedState, path: Option<&str>) -> Result<PathBuf, HandlerError> {
+    let start = path.map_or_else(|| state.config.root.clone(), PathBuf::from);
+    if !start.exists() {
+        return Err(HandlerError::FileNotFound(start));
+    }
+    let start = std::path::absolute(&start).map_err(HandlerError::IoError)?;
+    Ok(start.canonicalize().unwrap_or(start))
+}
+
+fn in_scope<'a>(
+    paths: &'a [PathBuf],
+    start: &'a Path,
+    include: Option<&'a GlobMatcher>,
+) -> impl Iterator<Item = &'a Path> {
+    paths
+        .iter()
+        .map(PathBuf::as_path)
+        .filter(move |path| path.starts_with(start))
+        .filter(move |path| {
+            include.is_none_or(|include| include.is_match(path.strip_prefix(start).unwrap_or(path)))
+        })
+}
+
+/// What the index would hold, for use before its first walk finishes.
+fn walked_paths(state: &SharedState) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = index::walk(state).into_keys().collect();
+    paths.sort();
+    paths
+}
+
+/// Trigrams of the literals every match of `pattern` starts or else ends
+/// with, one set per literal, or `None` when neither narrows the search:
+/// the pattern can start and end in too many ways, or with fewer than
+/// three bytes.
+fn regex_trigrams(pattern: &str, case_insensitive: bool) -> Option<Vec<Vec<u32>>> {
+    let hir = regex_syntax::ParserBuilder::new()
+        .case_insensitive(case_insensitive)
+        .build()
+        .parse(pattern)
+        .ok()?;
+    [ExtractKind::Prefix, ExtractKind::Suffix]
+        .into_iter()
+        .find_map(|kind| {
+            let found = Extractor::new().kind(kind).extract(&hir);
+            let literals = found.literals()?;
+            if literals.is_empty() || literals.iter().any(|literal| literal.as_bytes().len() < 3) {
+                return None;
+            }
+            Some(
+                literals
+                    .iter()
+                    .map(|literal| trigram::trigrams(literal.as_bytes()))
+                    .collect(),
+            )
+        })
+}
+
+/// Scores `candidate` for containing the characters of `query` in order,
+/// or `None` if it does not. Runs of consecutive characters, matches at
+/// the start of a name or word, and matches inside the file name score
+/// higher.
+fn fuzzy_score(query: &[char], candidate: &str) -> Option<i64> {
+    let score = subsequence_score(query, candidate)?;
+    let name = candidate.rsplit('/').next().unwrap_or(candidate);
+    let bonus = if subsequence_score(query, name).is_some() {
+        10
+    } else {
+        0
+    };
+    Some(score + bonus)
+}
+
+fn subsequence_score(query: &[char], candidate: &str) -> Option<i64> {
+    let mut remaining = query.iter().peekable();
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut previous_matched = false;
+    for c in candidate.chars() {
+        let matched = remaining.peek().is_some_and(|wanted| {
+            c == **wanted || (!c.is_lowercase() && c.to_lowercase().eq(std::iter::once(**wanted)))
+        });
+        if matched {
+            remaining.next();
+            score += 1;
+            if previous_matched {
+                score += 5;
+            }
+            let boundary = previous.is_none_or(|p| {
+                matches!(p, '/' | '\\' | '_' | '-' | '.' | ' ')
+                    || (p.is_lowercase() && c.is_uppercase())
+            });
+            if boundary {
+                score += 3;
+            }
+        }
+        previous_matched = matched;
+        previous = Some(c);
+    }
+    remaining.peek().is_none().then_some(score)
+}
+
+fn cut(text: &str, limit: usize) -> &str {
+    if text.len() <= limit {
+        return text;
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
@@ -0,0 +1,108 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::Path;
+use tracing::{debug, info, info_span};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{share::Share, state::AppState};
+
+const DEFAULT_TTL_SECS: u64 = 60 * 60;
+/// Longest a share link may live: one week.
+const MAX_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateShareParams {
+    /// Files or directories to share, relative to the workspace root.
+    paths: Vec<String>,
+    expires_in_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RevokeShareParams {
+    token: String,
+}
+
+fn describe(state: &AppState, share: &Share) -> Value {
+    let root = state.sandbox.root();
+    let paths: Vec<String> = share
+        .paths
+        .iter()
+        .map(|path| {
+            path.strip_prefix(root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    json!({
+        "token": share.token,
+        "url": format!("/share/{}/", share.token),
+        "paths": paths,
+        "createdAt": share.created_at,
+        "expiresAt": share.expires_at,
+        "connectionId": share.connection_id,
+    })
+}
+
+/// Mints a read-only link to `paths`, served over HTTP at the returned
+/// `url` (relative to the server's address) until it expires.
+pub fn handle_create(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("share_create_operation");
+    let _enter = span.enter();
+
+    let params: CreateShareParams = parse_params(params)?;
+    if params.paths.is_empty() {
+        return Err(HandlerError::InvalidParams(
+            "paths must name at least one file or directory".to_string(),
+        ));
+    }
+    let ttl = params.expires_in_secs.unwrap_or(DEFAULT_TTL_SECS);
+    if ttl == 0 || ttl > MAX_TTL_SECS {
+        return Err(HandlerError::InvalidParams(format!(
+            "expiresInSecs must be between 1 and {MAX_TTL_SECS}"
+        )));
+    }
+
+    let mut paths = Vec::with_capacity(params.paths.len());
+    for path in &params.paths {
+        let canonical = state.sandbox.resolve(Path::new(path))?;
+        if !canonical.exists() {
+            return Err(HandlerError::FileNotFound(canonical));
+        }
+        debug!(path = %canonical.display(), "Sharing path");
+        paths.push(canonical);
+    }
+
+    let share = state
+        .shares
+        .create(paths, ttl, connection.id)
+        .map_err(|e| HandlerError::IoError(std::io::Error::other(e)))?;
+    info!(paths = share.paths.len(), ttl, "Share link created");
+    Ok(describe(state, &share))
+}
+
+pub fn handle_list(state: &AppState) -> Result<Value, HandlerError> {
+    let shares: Vec<Value> = state
+        .shares
+        .list()
+        .iter()
+        .map(|share| describe(state, share))
+        .collect();
+    Ok(json!({ "shares": shares }))
+}
+
+pub fn handle_revoke(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let params: RevokeShareParams = parse_params(params)?;
+    let revoked = state.shares.revoke(&params.token);
+    if revoked {
+        info!("Share link revoked");
+    }
+    Ok(json!({ "revoked": revoked }))
+}
@@ -0,0 +1,77 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::time::Duration;
+use tracing::{info, info_span};
+
+use super::cancel::CancelToken;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{snippet, state::AppState};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RunSnippetParams {
+    language: String,
+    code: String,
+    /// Input for the snippet, for interpreters that take it from a file.
+    stdin: Option<String>,
+    /// Shortens, but cannot extend, the configured timeout.
+    timeout_ms: Option<u64>,
+}
+
+/// Lists the languages `snippet/run` accepts.
+pub fn handle_languages(state: &AppState) -> Result<Value, HandlerError> {
+    let snippets = &state.config.snippets;
+    let languages: Vec<Value> = snippets
+        .interpreters
+        .iter()
+        .map(|(language, interpreter)| json!({ "language": language, "command": interpreter.command }))
+        .collect();
+    Ok(json!({
+        "languages": languages,
+        "timeoutMs": snippets.timeout_ms,
+        "cpuSecs": snippets.cpu_secs,
+        "memoryBytes": snippets.memory_bytes,
+        "maxOutputBytes": snippets.max_output_bytes,
+    }))
+}
+
+/// Runs `code` with the interpreter configured for `language`, from the
+/// workspace root, and returns its output and exit status. A snippet that
+/// fails or is killed for exceeding a limit is still a successful request;
+/// only one that cannot be started is an error.
+pub fn handle_run(
+    state: &AppState,
+    params: Value,
+    cancel: &CancelToken,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("snippet_run_operation");
+    let _enter = span.enter();
+
+    let params: RunSnippetParams = parse_params(params)?;
+    let snippets = &state.config.snippets;
+    let interpreter = snippets.interpreters.get(&params.language).ok_or_else(|| {
+        HandlerError::InvalidParams(format!("No interpreter configured for {}", params.language))
+    })?;
+    let timeout_ms = params
+        .timeout_ms
+        .map_or(snippets.timeout_ms, |ms| ms.min(snippets.timeout_ms));
+
+    let run = snippet::run(
+        interpreter,
+        &params.code,
+        params.stdin.as_deref(),
+        &state.config.root,
+        snippets,
+        Duration::from_millis(timeout_ms),
+        cancel,
+    )?;
+    info!(
+        language = %params.language,
+        exit_code = ?run.exit_code,
+        timed_out = run.timed_out,
+        duration_ms = run.duration_ms,
+        "Snippet finished"
+    );
+    Ok(json!(run))
+}
@@ -0,0 +1,193 @@
+//! `spellCheck` and `addToDictionary`: spelling in markdown and plain text,
+//! against the bundled word list and the words the workspace root has
+//! added (see [`crate::spell`]).
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{collections::HashMap, path::Path};
+use tracing::{debug, info_span};
+
+use super::cancel::CancelToken;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use super::syntax::document_content;
+use super::text::LineIndex;
+use crate::{spell, state::AppState};
+
+const MAX_MISSPELLINGS: usize = 5000;
+const MAX_SUGGESTIONS: usize = 20;
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Language {
+    Markdown,
+    Plaintext,
+}
+
+impl Language {
+    fn detect(path: Option<&Path>) -> Self {
+        let extension = path
+            .and_then(|path| path.extension())
+            .and_then(|e| e.to_str());
+        match extension {
+            Some("md" | "markdown" | "mdx") => Language::Markdown,
+            _ => Language::Plaintext,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Language::Markdown => "markdown",
+            Language::Plaintext => "plaintext",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpellCheckParams {
+    path: Option<String>,
+    /// Unsaved buffer contents; the file on disk is read when omitted.
+    content: Option<String>,
+    /// Detected from the extension when omitted: markdown for `.md`, plain
+    /// text otherwise.
+    language: Option<Language>,
+    /// Root whose added words count; the one holding `path` by default.
+    workspace: Option<String>,
+    /// Suggestions per misspelling; 0 skips looking for them.
+    #[serde(default = "default_suggestions")]
+    suggestions: usize,
+}
+
+fn default_suggestions() -> usize {
+    5
+}
+
+#[derive(Deserialize)]
+struct AddToDictionaryParams {
+    word: String,
+    workspace: Option<String>,
+}
+
+/// The root named `workspace`, else the one holding `path`, else the
+/// primary root.
+fn workspace_for(
+    state: &AppState,
+    workspace: Option<String>,
+    path: Option<&Path>,
+) -> Result<String, HandlerError> {
+    if let Some(workspace) = workspace {
+        if !state.uris.roots().any(|(name, ..)| name == workspace) {
+            return Err(HandlerError::InvalidParams(format!(
+                "Unknown workspace root {workspace}"
+            )));
+        }
+        return Ok(workspace);
+    }
+    let name = path
+        .and_then(|path| state.uris.root_name(path))
+        .unwrap_or_else(|| state.uris.name());
+    Ok(name.to_string())
+}
+
+pub fn handle_spell_check(
+    state: &AppState,
+    params: Value,
+    cancel: &CancelToken,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("spell_check_operation");
+    let _enter = span.enter();
+
+    let params: SpellCheckParams = parse_params(params)?;
+    let path = params.path.as_deref().map(Path::new);
+    let content = match (path, params.content) {
+        (_, Some(content)) => content,
+        (Some(path), None) => document_content(path, None)?,
+        (None, None) => {
+            return Err(HandlerError::InvalidParams(
+                "spellCheck needs a path or content".to_string(),
+            ));
+        }
+    };
+    let language = params.language.unwrap_or_else(|| Language::detect(path));
+    let workspace = workspace_for(state, params.workspace, path)?;
+    let custom = state.dictionaries.custom(&workspace);
+    let suggestion_count = params.suggestions.min(MAX_SUGGESTIONS);
+
+    let lines = LineIndex::new(&content);
+    // Misspelt words tend to repeat, and suggesting is the slow part.
+    let mut suggested: HashMap<&str, Vec<String>> = HashMap::new();
+    let mut misspellings = Vec::new();
+    let mut checked = 0;
+    let mut truncated = false;
+    'prose: for range in spell::prose(&content, language == Language::Markdown) {
+        for (offset, word) in spell::words(&content[range.clone()]) {
+            checked += 1;
+            if spell::is_known(word, &custom) {
+                continue;
+            }
+            if misspellings.len() == MAX_MISSPELLINGS {
+                truncated = true;
+                break 'prose;
+            }
+            let suggestions = match suggested.get(word) {
+                Some(suggestions) => suggestions.clone(),
+                None if suggestion_count == 0 => Vec::new(),
+                None => {
+                    cancel.check()?;
+                    let suggestions = spell::suggestions(word, &custom, suggestion_count);
+                    suggested.insert(word, suggestions.clone());
+                    suggestions
+                }
+            };
+            let start = range.start + offset;
+            misspellings.push(json!({
+                "word": word,
+                "range": lines.range(start, start + word.len()),
+                "suggestions": suggestions,
+            }));
+        }
+    }
+    debug!(
+        language = language.as_str(),
+        workspace,
+        checked,
+        misspellings = misspellings.len(),
+        "Checked spelling"
+    );
+    Ok(json!({
+        "language": language.as_str(),
+        "workspace": workspace,
+        "misspellings": misspellings,
+        "wordsChecked": checked,
+        "truncated": truncated,
+    }))
+}
+
+/// Accepts `word` in the root's spell checks from now on, kept under the
+/// data directory.
+pub fn handle_add_to_dictionary(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("add_to_dictionary_operation");
+    let _enter = span.enter();
+
+    let params: AddToDictionaryParams = parse_params(params)?;
+    let word = params.word.trim();
+    if word.is_empty() || word.contains(char::is_whitespace) {
+        return Err(HandlerError::InvalidParams(format!(
+            "Not a single word: {:?}",
+            params.word
+        )));
+    }
+    if word.chars().count() > spell::MAX_WORD_CHARS {
+        return Err(HandlerError::InvalidParams(format!(
+            "Words are at most {} characters",
+            spell::MAX_WORD_CHARS
+        )));
+    }
+    let workspace = workspace_for(state, params.workspace, None)?;
+    let added = state
+        .dictionaries
+        .add(&workspace, word)
+        .map_err(HandlerError::IoError)?;
+    Ok(json!({ "word": word, "workspace": workspace, "added": added }))
+}
@@ -0,0 +1,116 @@
+use serde_json::{Value, json};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use crate::clock;
+
+#[derive(Default)]
+struct RoundTrips {
+    samples: u64,
+    total: Duration,
+    last: Option<Duration>,
+}
+
+/// Traffic counters for one connection, reported by `connection/stats`.
+pub struct ConnectionStats {
+    started: Instant,
+    messages_received: AtomicU64,
+    messages_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    methods: Mutex<BTreeMap<String, u64>>,
+    round_trips: Mutex<RoundTrips>,
+}
+
+impl Default for ConnectionStats {
+    fn default() -> Self {
+        Self {
+            started: clock::now(),
+            messages_received: AtomicU64::default(),
+            messages_sent: AtomicU64::default(),
+            bytes_received: AtomicU64::default(),
+            bytes_sent: AtomicU64::default(),
+            methods: Mutex::default(),
+            round_trips: Mutex::default(),
+        }
+    }
+}
+
+impl ConnectionStats {
+    pub fn record_received(&self, bytes: usize) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_sent(&self, bytes: usize) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_method(&self, method: &str) {
+        *self
+            .methods
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(method.to_string())
+            .or_default() += 1;
+    }
+
+    /// Encodes the current time as a ping payload.
+    pub fn ping_payload(&self) -> Vec<u8> {
+        (clock::now().duration_since(self.started).as_micros() as u64)
+            .to_be_bytes()
+            .to_vec()
+    }
+
+    /// Records the round trip of a pong echoing a [`ping_payload`](Self::ping_payload).
+    pub fn record_pong(&self, payload: &[u8]) {
+        let Ok(bytes) = <[u8; 8]>::try_from(payload) else {
+            return;
+        };
+        let sent = Duration::from_micros(u64::from_be_bytes(bytes));
+        let Some(round_trip) = clock::now().duration_since(self.started).checked_sub(sent) else {
+            return;
+        };
+        let mut round_trips = self.round_trips.lock().unwrap_or_else(|e| e.into_inner());
+        round_trips.samples += 1;
+        round_trips.total += round_trip;
+        round_trips.last = Some(round_trip);
+    }
+}
+
+pub fn handle_stats(connection: &ConnectionContext) -> Result<Value, HandlerError> {
+    let stats = &connection.stats;
+    let methods = stats
+        .methods
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let round_trips = stats.round_trips.lock().unwrap_or_else(|e| e.into_inner());
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+    Ok(json!({
+        "connectionId": connection.id,
+        "connectedSecs": clock::now().duration_since(stats.started).as_secs(),
+        "messagesReceived": stats.messages_received.load(Ordering::Relaxed),
+        "messagesSent": stats.messages_sent.load(Ordering::Relaxed),
+        "bytesReceived": stats.bytes_received.load(Ordering::Relaxed),
+        "bytesSent": stats.bytes_sent.load(Ordering::Relaxed),
+        "roundTrip": {
+            "samples": round_trips.samples,
+            "averageMs": (round_trips.samples > 0)
+                .then(|| millis(round_trips.total) / round_trips.samples as f64),
+            "lastMs": round_trips.last.map(millis),
+        },
+        "methods": methods,
+    }))
+}
@@ -0,0 +1,519 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{fs, path::Path};
+use toml_edit::{DocumentMut, InlineTable, Item, Table};
+use tracing::{debug, info, info_span};
+
+use super::dry_run;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::protected::audit_forced;
+use crate::state::AppState;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+#[derive(Deserialize)]
+struct StructuredGetParams {
+    path: String,
+    #[serde(default)]
+    pointer: String,
+    format: Option<Format>,
+}
+
+#[derive(Deserialize)]
+struct StructuredSetParams {
+    path: String,
+    pointer: String,
+    value: Value,
+    format: Option<Format>,
+    #[serde(default = "default_true")]
+    create: bool,
+    #[serde(default)]
+    force: bool,
+    #[serde(default, rename = "dryRun")]
+    dry_run: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn detect_format(path: &Path, explicit: Option<Format>) -> Result<Format, HandlerError> {
+    if let Some(format) = explicit {
+        return Ok(format);
+    }
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Ok(Format::Json),
+        Some("yaml" | "yml") => Ok(Format::Yaml),
+        Some("toml") => Ok(Format::Toml),
+        _ => Err(HandlerError::InvalidParams(
+            "Cannot infer format from file extension, pass format explicitly".to_string(),
+        )),
+    }
+}
+
+/// Splits a JSON pointer (`/a/b/0`) into unescaped segments.
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, HandlerError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(HandlerError::InvalidParams(
+            "Pointer must be empty or start with '/'".to_string(),
+        ));
+    };
+    Ok(rest
+        .split('/')
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn invalid_params(message: impl Into<String>) -> HandlerError {
+    HandlerError::InvalidParams(message.into())
+}
+
+fn read_source(path: &Path) -> Result<String, HandlerError> {
+    if !path.exists() {
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
+    }
+    fs::read_to_string(path).map_err(HandlerError::IoError)
+}
+
+fn parse_document(source: &str, format: Format) -> Result<Value, HandlerError> {
+    match format {
+        Format::Json => serde_json::from_str(source)
+            .map_err(|e| invalid_params(format!("Failed to parse JSON: {e}"))),
+        Format::Yaml => serde_yaml::from_str(source)
+            .map_err(|e| invalid_params(format!("Failed to parse YAML: {e}"))),
+        Format::Toml => {
+            let document: DocumentMut = source
+                .parse()
+                .map_err(|e| invalid_params(format!("Failed to parse TOML: {e}")))?;
+            Ok(toml_table_to_json(document.as_table()))
+        }
+    }
+}
+
+pub fn handle_structured_get(params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("structured_get_operation");
+    let _enter = span.enter();
+
+    let params: StructuredGetParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let format = detect_format(path, params.format)?;
+
+    debug!(path = %params.path, pointer = %params.pointer, format = ?format, "Reading structured value");
+    let document = parse_document(&read_source(path)?, format)?;
+
+    let mut current = &document;
+    for segment in parse_pointer(&params.pointer)? {
+        current = match current {
+            Value::Object(map) => map.get(&segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        }
+        .ok_or_else(|| invalid_params(format!("No value at pointer {}", params.pointer)))?;
+    }
+
+    info!(path = %params.path, pointer = %params.pointer, "Structured value read successfully");
+    Ok(current.clone())
+}
+
+pub fn handle_structured_set(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("structured_set_operation");
+    let _enter = span.enter();
+
+    let params: StructuredSetParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let format = detect_format(path, params.format)?;
+    let segments = parse_pointer(&params.pointer)?;
+
+    if state.protected.is_protected(path) {
+        if !params.force {
+            return Err(HandlerError::ProtectedPath(params.path));
+        }
+        audit_forced("structuredSet", &params.path);
+    }
+
+    debug!(path = %params.path, pointer = %params.pointer, format = ?format, "Writing structured value");
+    let source = read_source(path)?;
+
+    let output = match format {
+        Format::Toml => {
+            let mut document: DocumentMut = source
+                .parse()
+                .map_err(|e| invalid_params(format!("Failed to parse TOML: {e}")))?;
+            if segments.is_empty() {
+                return Err(invalid_params("Cannot replace the root of a TOML document"));
+            }
+            set_in_toml_table(
+                document.as_table_mut(),
+                &segments,
+                &params.value,
+                params.create,
+            )?;
+            document.to_string()
+        }
+        Format::Json | Format::Yaml => {
+            if format == Format::Yaml
+                && let Some(feature) = yaml_round_trip_loss(&source)
+            {
+                return Err(invalid_params(format!(
+                    "YAML file has {feature}, which structuredSet would strip; edit it as text instead"
+                )));
+            }
+            let mut document = parse_document(&source, format)?;
+            set_in_json(&mut document, &segments, params.value, params.create)?;
+            if format == Format::Json {
+                serialize_json_like(&source, &document)?
+            } else {
+                serde_yaml::to_string(&document)
+                    .map_err(|e| invalid_params(format!("Failed to serialize YAML: {e}")))?
+            }
+        }
+    };
+
+    if params.dry_run {
+        return Ok(dry_run::preview(state, path, output.as_bytes()));
+    }
+    fs::write(path, output).map_err(HandlerError::IoError)?;
+
+    info!(path = %params.path, pointer = %params.pointer, "Structured value written successfully");
+    Ok(Value::Bool(true))
+}
+
+fn set_in_json(
+    target: &mut Value,
+    segments: &[String],
+    value: Value,
+    create: bool,
+) -> Result<(), HandlerError> {
+    let Some((segment, rest)) = segments.split_first() else {
+        *target = value;
+        return Ok(());
+    };
+
+    let child = match target {
+        Value::Object(map) => {
+            if !map.contains_key(segment) {
+                if !create {
+                    return Err(invalid_params(format!("Missing key {segment}")));
+                }
+                map.insert(segment.clone(), Value::Object(Map::new()));
+            }
+            map.get_mut(segment).expect("key was just ensured")
+        }
+        Value::Array(items) => {
+            if segment == "-" {
+                items.push(Value::Null);
+                items.last_mut().expect("item was just pushed")
+            } else {
+                let index: usize = segment
+                    .parse()
+                    .map_err(|_| invalid_params(format!("Invalid array index {segment}")))?;
+                items
+                    .get_mut(index)
+                    .ok_or_else(|| invalid_params(format!("Array index {index} out of bounds")))?
+            }
+        }
+        _ => {
+            return Err(invalid_params(format!(
+                "Cannot index into scalar at {segment}"
+            )));
+        }
+    };
+    set_in_json(child, rest, value, create)
+}
+
+/// What rewriting `source` through `serde_yaml` would drop: comments, or
+/// anchors and aliases, which come back expanded. Block scalar contents
+/// are skipped; quoted text is not looked into.
+fn yaml_round_trip_loss(source: &str) -> Option<&'static str> {
+    // Indentation of the line that opened the block scalar being skipped.
+    let mut block: Option<usize> = None;
+    for line in source.lines() {
+        let indent = line.len() - line.trim_start().len();
+        if let Some(opener) = block {
+            if line.trim().is_empty() || indent > opener {
+                continue;
+            }
+            block = None;
+        }
+
+        let mut quote: Option<char> = None;
+        let mut previous = ' ';
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match quote {
+                Some('"') if c == '\\' => {
+                    chars.next();
+                }
+                Some(open) if c == open => quote = None,
+                Some(_) => {}
+                None if c == '#' && previous.is_whitespace() => return Some("comments"),
+                None if matches!(c, '&' | '*')
+                    && (previous.is_whitespace() || matches!(previous, '[' | '{' | ','))
+                    && chars.peek().is_some_and(|next| !next.is_whitespace()) =>
+                {
+                    return Some("anchors or aliases");
+                }
+                None if matches!(c, '\'' | '"') && !previous.is_alphanumeric() => quote = Some(c),
+                None => {}
+            }
+            previous = c;
+        }
+
+        // `key: |`, `- >-` and the like open a block scalar.
+        let header = line
+            .trim_end()
+            .trim_end_matches(|c: char| matches!(c, '-' | '+') || c.is_ascii_digit());
+        if let Some(before) = header.strip_suffix(['|', '>'])
+            && (before.is_empty() || before.ends_with(char::is_whitespace))
+        {
+            block = Some(indent);
+        }
+    }
+    None
+}
+
+/// Re-serializes JSON using the indentation detected in the original text
+/// and keeps its trailing newline.
+fn serialize_json_like(source: &str, document: &Value) -> Result<String, HandlerError> {
+    let indent: String = source
+        .lines()
+        .nth(1)
+        .map(|line| line.chars().take_while(|c| c.is_whitespace()).collect())
+        .unwrap_or_default();
+
+    let mut output = if indent.is_empty() && !source.trim().contains('\n') {
+        serde_json::to_string(document).map_err(|e| invalid_params(e.to_string()))?
+    } else {
+        let indent = if indent.is_empty() {
+            "  ".to_string()
+        } else {
+            indent
+        };
+        let mut buffer = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+        let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+        serde::Serialize::serialize(document, &mut serializer)
+            .map_err(|e| invalid_params(e.to_string()))?;
+        String::from_utf8(buffer).expect("serde_json produces UTF-8")
+    };
+    if source.ends_with('\n') {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+fn set_in_toml_table(
+    table: &mut Table,
+    segments: &[String],
+    value: &Value,
+    create: bool,
+) -> Result<(), HandlerError> {
+    let (key, rest) = segments.split_first().expect("segments are non-empty");
+
+    if rest.is_empty() {
+        let new_value = json_to_toml(value)?;
+        match table.get_mut(key).and_then(Item::as_value_mut) {
+            Some(existing) => replace_toml_value(existing, new_value),
+            None if !create => return Err(invalid_params(format!("Missing key {key}"))),
+            None => {
+                table.insert(key, Item::Value(new_value));
+            }
+        }
+        return Ok(());
+    }
+
+    if !table.contains_key(key) {
+        if !create {
+            return Err(invalid_params(format!("Missing key {key}")));
+        }
+        let mut child = Table::new();
+        child.set_implicit(true);
+        table.insert(key, Item::Table(child));
+    }
+
+    match table.get_mut(key).expect("key was just ensured") {
+        Item::Table(child) => set_in_toml_table(child, rest, value, create),
+        Item::ArrayOfTables(tables) => {
+            let (index, rest) = rest.split_first().expect("rest is non-empty");
+            let index: usize = index
+                .parse()
+                .map_err(|_| invalid_params(format!("Invalid array index {index}")))?;
+            let child = tables
+                .get_mut(index)
+                .ok_or_else(|| invalid_params(format!("Array index {index} out of bounds")))?;
+            if rest.is_empty() {
+                return Err(invalid_params(
+                    "Cannot replace a table inside an array of tables",
+                ));
+            }
+            set_in_toml_table(child, rest, value, create)
+        }
+        Item::Value(child) => set_in_toml_value(child, rest, value, create),
+        Item::None => Err(invalid_params(format!("Missing key {key}"))),
+    }
+}
+
+fn set_in_toml_value(
+    target: &mut toml_edit::Value,
+    segments: &[String],
+    value: &Value,
+    create: bool,
+) -> Result<(), HandlerError> {
+    let (segment, rest) = segments.split_first().expect("segments are non-empty");
+
+    let child = match target {
+        toml_edit::Value::InlineTable(table) => {
+            if !table.contains_key(segment) {
+                if !create {
+                    return Err(invalid_params(format!("Missing key {segment}")));
+                }
+                table.insert(segment, InlineTable::new().into());
+            }
+            table.get_mut(segment).expect("key was just ensured")
+        }
+        toml_edit::Value::Array(items) => {
+            if segment == "-" {
+                items.push(json_to_toml(value)?);
+                return if rest.is_empty() {
+                    Ok(())
+                } else {
+                    Err(invalid_params("Cannot index past an appended element"))
+                };
+            }
+            let index: usize = segment
+                .parse()
+                .map_err(|_| invalid_params(format!("Invalid array index {segment}")))?;
+            items
+                .get_mut(index)
+                .ok_or_else(|| invalid_params(format!("Array index {index} out of bounds")))?
+        }
+        _ => {
+            return Err(invalid_params(format!(
+                "Cannot index into scalar at {segment}"
+            )));
+        }
+    };
+
+    if rest.is_empty() {
+        replace_toml_value(child, json_to_toml(value)?);
+        Ok(())
+    } else {
+        set_in_toml_value(child, rest, value, create)
+    }
+}
+
+/// Replaces a TOML value while keeping the whitespace and comments around it.
+fn replace_toml_value(slot: &mut toml_edit::Value, mut value: toml_edit::Value) {
+    *value.decor_mut() = slot.decor().clone();
+    *slot = value;
+}
+
+fn json_to_toml(value: &Value) -> Result<toml_edit::Value, HandlerError> {
+    Ok(match value {
+        Value::Null => return Err(invalid_params("TOML has no null value")),
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64().unwrap_or_default().into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => {
+            let mut array = toml_edit::Array::new();
+            for item in items {
+                array.push(json_to_toml(item)?);
+            }
+            array.into()
+        }
+        Value::Object(map) => {
+            let mut table = InlineTable::new();
+            for (key, item) in map {
+                table.insert(key, json_to_toml(item)?);
+            }
+            table.into()
+        }
+    })
+}
+
+fn toml_table_to_json(table: &Table) -> Value {
+    Value::Object(
+        table
+            .iter()
+            .map(|(key, item)| (key.to_string(), toml_item_to_json(item)))
+            .collect(),
+    )
+}
+
+fn toml_item_to_json(item: &Item) -> Value {
+    match item {
+        Item::None => Value::Null,
+        Item::Value(value) => toml_value_to_json(value),
+        Item::Table(table) => toml_table_to_json(table),
+        Item::ArrayOfTables(tables) => {
+            Value::Array(tables.iter().map(toml_table_to_json).collect())
+        }
+    }
+}
+
+fn toml_value_to_json(value: &toml_edit::Value) -> Value {
+    match value {
+        toml_edit::Value::String(s) => Value::String(s.value().clone()),
+        toml_edit::Value::Integer(i) => Value::from(*i.value()),
+        toml_edit::Value::Float(f) => Value::from(*f.value()),
+        toml_edit::Value::Boolean(b) => Value::Bool(*b.value()),
+        toml_edit::Value::Datetime(d) => Value::String(d.value().to_string()),
+        toml_edit::Value::Array(items) => {
+            Value::Array(items.iter().map(toml_value_to_json).collect())
+        }
+        toml_edit::Value::InlineTable(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_string(), toml_value_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_what_a_yaml_round_trip_drops() {
+        assert_eq!(yaml_round_trip_loss("a: 1\nb: [x, y]\n"), None);
+        assert_eq!(yaml_round_trip_loss("# header\na: 1\n"), Some("comments"));
+        assert_eq!(yaml_round_trip_loss("a: 1 # note\n"), Some("comments"));
+        assert_eq!(
+            yaml_round_trip_loss("base: &base\n  x: 1\nother: *base\n"),
+            Some("anchors or aliases")
+        );
+    }
+
+    #[test]
+    fn ignores_hashes_in_strings_and_block_scalars() {
+        assert_eq!(yaml_round_trip_loss("url: http://host/#top\n"), None);
+        assert_eq!(yaml_round_trip_loss("a: \"not # a comment\"\n"), None);
+        assert_eq!(
+            yaml_round_trip_loss("script: |\n  # shell comment\n  echo hi\nb: 2\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn create_false_refuses_a_missing_last_key() {
+        let mut document = serde_json::json!({ "a": { "b": 1 } });
+        let segments = parse_pointer("/a/c").unwrap();
+        let result = set_in_json(&mut document, &segments, Value::Bool(true), false);
+        assert!(matches!(result, Err(HandlerError::InvalidParams(_))));
+        assert_eq!(document, serde_json::json!({ "a": { "b": 1 } }));
+    }
+}
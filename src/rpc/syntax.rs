@@ -0,0 +1,97 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{fs, path::Path};
+use tracing::{debug, info, info_span};
+
+use super::cancel::CancelToken;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{state::AppState, syntax};
+
+#[derive(Deserialize)]
+struct DocumentParams {
+    path: String,
+    /// Unsaved buffer contents; the file on disk is read when omitted.
+    content: Option<String>,
+    /// Grammar id overriding detection by extension.
+    language: Option<String>,
+}
+
+pub fn handle_highlight(
+    state: &AppState,
+    params: Value,
+    cancel: &CancelToken,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("highlight_operation");
+    let _enter = span.enter();
+
+    let params: DocumentParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let grammar = syntax::grammar_for(path, params.language.as_deref()).ok_or_else(|| {
+        HandlerError::InvalidParams(format!("No grammar available for {}", params.path))
+    })?;
+
+    let content = document_content(path, params.content)?;
+
+    debug!(path = %params.path, language = grammar.id, "Highlighting document");
+    let tokens = state
+        .syntax
+        .highlight(grammar, &content, cancel.flag())
+        .map_err(|e| {
+            if cancel.is_cancelled() {
+                HandlerError::RequestCancelled
+            } else {
+                HandlerError::SyntaxError(e)
+            }
+        })?;
+
+    info!(
+        path = %params.path,
+        language = grammar.id,
+        tokens = tokens.len(),
+        "Document highlighted successfully"
+    );
+    let tokens: Vec<Value> = tokens
+        .into_iter()
+        .map(|token| json!({ "range": token.range, "scope": token.scope }))
+        .collect();
+    Ok(json!({ "language": grammar.id, "tokens": tokens }))
+}
+
+pub fn handle_document_symbols(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("document_symbols_operation");
+    let _enter = span.enter();
+
+    let params: DocumentParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let grammar = syntax::grammar_for(path, params.language.as_deref()).ok_or_else(|| {
+        HandlerError::InvalidParams(format!("No grammar available for {}", params.path))
+    })?;
+    let content = document_content(path, params.content)?;
+
+    debug!(path = %params.path, language = grammar.id, "Collecting document symbols");
+    let symbols = state
+        .syntax
+        .symbols(grammar, &content)
+        .map_err(HandlerError::SyntaxError)?;
+
+    info!(
+        path = %params.path,
+        language = grammar.id,
+        symbols = symbols.len(),
+        "Document symbols collected successfully"
+    );
+    Ok(json!({ "language": grammar.id, "symbols": symbols }))
+}
+
+pub fn document_content(path: &Path, content: Option<String>) -> Result<String, HandlerError> {
+    match content {
+        Some(content) => Ok(content),
+        None => {
+            if !path.exists() {
+                return Err(HandlerError::FileNotFound(path.to_path_buf()));
+            }
+            fs::read_to_string(path).map_err(HandlerError::IoError)
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{fs, io, path::Path};
+use tracing::{debug, info, info_span};
+
+use super::cancel::CancelToken;
+use super::dry_run;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::protected::audit_forced;
+use crate::state::AppState;
+
+const DEFAULT_PAGE_SIZE: usize = 200;
+const MAX_PAGE_SIZE: usize = 5000;
+/// Records skipped between cancellation checks.
+const CANCEL_CHECK_INTERVAL: usize = 4096;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TableReadParams {
+    path: String,
+    #[serde(default)]
+    page: usize,
+    page_size: Option<usize>,
+    delimiter: Option<char>,
+    #[serde(default = "default_true")]
+    has_header: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TableUpdateCellParams {
+    path: String,
+    row: usize,
+    column: usize,
+    value: String,
+    delimiter: Option<char>,
+    #[serde(default = "default_true")]
+    has_header: bool,
+    #[serde(default)]
+    force: bool,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Uses the explicit delimiter if given, otherwise tab for `.tsv`/`.tab`
+/// files and comma for everything else.
+fn resolve_delimiter(path: &Path, explicit: Option<char>) -> Result<u8, HandlerError> {
+    let delimiter =
+        explicit.unwrap_or_else(|| match path.extension().and_then(|ext| ext.to_str()) {
+            Some("tsv" | "tab") => '\t',
+            _ => ',',
+        });
+    u8::try_from(delimiter)
+        .ok()
+        .filter(u8::is_ascii)
+        .ok_or_else(|| HandlerError::InvalidParams("Delimiter must be ASCII".to_string()))
+}
+
+fn csv_error(e: csv::Error) -> HandlerError {
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => HandlerError::IoError(e),
+        kind => HandlerError::InvalidParams(format!("Malformed table data: {kind:?}")),
+    }
+}
+
+fn open_reader(
+    path: &Path,
+    delimiter: u8,
+    has_header: bool,
+) -> Result<csv::Reader<fs::File>, HandlerError> {
+    if !path.exists() {
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
+    }
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(has_header)
+        .flexible(true)
+        .from_path(path)
+        .map_err(csv_error)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ColumnType {
+    Empty,
+    Boolean,
+    Integer,
+    Float,
+    String,
+}
+
+impl ColumnType {
+    fn of(cell: &str) -> Self {
+        let cell = cell.trim();
+        if cell.is_empty() {
+            ColumnType::Empty
+        } else if cell.eq_ignore_ascii_case("true") || cell.eq_ignore_ascii_case("false") {
+            ColumnType::Boolean
+        } else if cell.parse::<i64>().is_ok() {
+            ColumnType::Integer
+        } else if cell.parse::<f64>().is_ok() {
+            ColumnType::Float
+        } else {
+            ColumnType::String
+        }
+    }
+
+    /// Widens two observed types to one that describes both.
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Empty, t) | (t, ColumnType::Empty) => t,
+            (ColumnType::Integer, ColumnType::Float) | (ColumnType::Float, ColumnType::Integer) => {
+                ColumnType::Float
+            }
+            _ => ColumnType::String,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ColumnType::Empty => "empty",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::String => "string",
+        }
+    }
+}
+
+pub fn handle_table_read(params: Value, cancel: &CancelToken) -> Result<Value, HandlerError> {
+    let span = info_span!("table_read_operation");
+    let _enter = span.enter();
+
+    let params: TableReadParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let delimiter = resolve_delimiter(path, params.delimiter)?;
+    let page_size = params
+        .page_size
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    debug!(path = %params.path, page = params.page, page_size, "Reading table page");
+    let mut reader = open_reader(path, delimiter, params.has_header)?;
+
+    let headers: Vec<String> = if params.has_header {
+        reader
+            .headers()
+            .map_err(csv_error)?
+            .iter()
+            .map(str::to_string)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Skipping earlier pages is the slow part on large files.
+    let mut records = reader.records();
+    for (skipped, record) in records.by_ref().take(params.page * page_size).enumerate() {
+        if skipped % CANCEL_CHECK_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        record.map_err(csv_error)?;
+    }
+    let mut rows = Vec::new();
+    let mut column_types: Vec<ColumnType> = Vec::new();
+    for record in records.by_ref().take(page_size) {
+        let record = record.map_err(csv_error)?;
+        for (i, cell) in record.iter().enumerate() {
+            let cell_type = ColumnType::of(cell);
+            match column_types.get_mut(i) {
+                Some(existing) => *existing = existing.merge(cell_type),
+                None => column_types.push(cell_type),
+            }
+        }
+        rows.push(record.iter().map(str::to_string).collect::<Vec<_>>());
+    }
+    let has_more = records.next().is_some();
+
+    info!(
+        path = %params.path,
+        page = params.page,
+        rows = rows.len(),
+        "Table page read successfully"
+    );
+    Ok(json!({
+        "headers": headers,
+        "columnTypes": column_types.iter().map(|t| t.name()).collect::<Vec<_>>(),
+        "rows": rows,
+        "page": params.page,
+        "pageSize": page_size,
+        "hasMore": has_more,
+    }))
+}
+
+pub fn handle_table_update_cell(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("table_update_cell_operation");
+    let _enter = span.enter();
+
+    let params: TableUpdateCellParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    let delimiter = resolve_delimiter(path, params.delimiter)?;
+
+    if state.protected.is_protected(path) {
+        if !params.force {
+            return Err(HandlerError::ProtectedPath(params.path));
+        }
+        audit_forced("table/updateCell", &params.path);
+    }
+
+    debug!(path = %params.path, row = params.row, column = params.column, "Updating table cell");
+
+    // Headers are rewritten as a regular record so the reader must not skip them.
+    let mut reader = open_reader(path, delimiter, false)?;
+    let mut builder = csv::WriterBuilder::new();
+    builder.delimiter(delimiter).flexible(true);
+    let out_of_bounds =
+        || HandlerError::InvalidParams(format!("Row {} is out of bounds", params.row));
+
+    if params.dry_run {
+        let mut writer = builder.from_writer(Vec::new());
+        if !rewrite_cell(&mut reader, &mut writer, &params)? {
+            return Err(out_of_bounds());
+        }
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| HandlerError::IoError(e.into_error()))?;
+        return Ok(dry_run::preview(state, path, &bytes));
+    }
+
+    // A unique name in the same directory, so concurrent updates of files
+    // sharing a stem never share a temp file and the rename stays atomic.
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(dir).map_err(HandlerError::IoError)?;
+    let mut writer = builder.from_writer(temp.as_file());
+    if !rewrite_cell(&mut reader, &mut writer, &params)? {
+        return Err(out_of_bounds());
+    }
+    drop(writer);
+    // The temp file is created private; keep the table's own mode.
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(temp.path(), metadata.permissions()).map_err(HandlerError::IoError)?;
+    }
+
+    temp.persist(path)
+        .map_err(|e| HandlerError::IoError(e.error))?;
+
+    info!(path = %params.path, row = params.row, column = params.column, "Table cell updated successfully");
+    Ok(Value::Bool(true))
+}
+
+/// Copies every record to `writer`, with the cell replaced, and says
+/// whether the row was there to update.
+fn rewrite_cell<R: io::Read, W: io::Write>(
+    reader: &mut csv::Reader<R>,
+    writer: &mut csv::Writer<W>,
+    params: &TableUpdateCellParams,
+) -> Result<bool, HandlerError> {
+    let target_row = params.row + usize::from(params.has_header);
+    let mut updated = false;
+    for (i, record) in reader.records().enumerate() {
+        let record = record.map_err(csv_error)?;
+        if i == target_row {
+            let mut cells: Vec<&str> = record.iter().collect();
+            // One past the last cell appends a column; anything further
+            // would pad the record with however many cells the client asks.
+            if params.column > cells.len() {
+                return Err(HandlerError::InvalidParams(format!(
+                    "Column {} is out of bounds for a row of {} cells",
+                    params.column,
+                    cells.len()
+                )));
+            }
+            if params.column == cells.len() {
+                cells.push("");
+            }
+            cells[params.column] = &params.value;
+            writer.write_record(&cells).map_err(csv_error)?;
+            updated = true;
+        } else {
+            writer.write_record(&record).map_err(csv_error)?;
+        }
+    }
+    writer.flush().map_err(HandlerError::IoError)?;
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(column: usize) -> Result<(bool, String), HandlerError> {
+        let params: TableUpdateCellParams = parse_params(json!({
+            "path": "a.csv",
+            "row": 0,
+            "column": column,
+            "value": "x",
+        }))?;
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader("h1,h2\n1,2\n".as_bytes());
+        let mut writer = csv::WriterBuilder::new()
+            .flexible(true)
+            .from_writer(Vec::new());
+        let updated = rewrite_cell(&mut reader, &mut writer, &params)?;
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| HandlerError::IoError(e.into_error()))?;
+        Ok((updated, String::from_utf8(bytes).unwrap()))
+    }
+
+    #[test]
+    fn appends_one_column_past_the_row() {
+        let (updated, text) = update(2).unwrap();
+        assert!(updated);
+        assert_eq!(text, "h1,h2\n1,2,x\n");
+    }
+
+    #[test]
+    fn refuses_columns_far_past_the_row() {
+        let result = update(1_000_000_000_000);
+        assert!(matches!(result, Err(HandlerError::InvalidParams(_))));
+    }
+}
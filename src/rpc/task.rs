@@ -0,0 +1,71 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::info_span;
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+struct RunTaskParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CancelTaskParams {
+    task_id: u64,
+}
+
+pub fn handle_list(state: &AppState) -> Result<Value, HandlerError> {
+    let tasks: Vec<Value> = state
+        .config
+        .tasks
+        .iter()
+        .map(|(name, task)| json!({ "name": name, "command": task.command, "args": task.args }))
+        .collect();
+    Ok(Value::Array(tasks))
+}
+
+pub fn handle_run(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("task_run_operation");
+    let _enter = span.enter();
+
+    let params: RunTaskParams = parse_params(params)?;
+    let definition = state
+        .config
+        .tasks
+        .get(&params.name)
+        .ok_or_else(|| HandlerError::InvalidParams(format!("Unknown task: {}", params.name)))?;
+
+    let task_id = state
+        .tasks
+        .run(
+            connection,
+            &params.name,
+            definition,
+            &state.config.root,
+            state.config.max_concurrent_tasks,
+            &state.activity,
+        )
+        .map_err(HandlerError::TaskError)?;
+    Ok(json!({ "taskId": task_id }))
+}
+
+pub fn handle_cancel(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let params: CancelTaskParams = parse_params(params)?;
+    state
+        .tasks
+        .cancel(connection.id, params.task_id)
+        .map_err(HandlerError::TaskError)?;
+    Ok(Value::Bool(true))
+}
@@ -0,0 +1,244 @@
+//! New files from the templates in `templatesDir`.
+//!
+//! A template is any file directly in that directory, named after its
+//! file name without the extension (`rust-module.rs` is `rust-module`).
+//! `{{name}}` placeholders in it are replaced when a file is created:
+//!
+//! - `filename`: the new file's name, e.g. `user_profile.rs`
+//! - `stem`: the name without its extension, `user_profile`
+//! - `module`: the stem in snake_case, `user_profile`
+//! - `type`: the stem in PascalCase, `UserProfile`
+//! - `date` and `year`: today in UTC, `2024-05-01` and `2024`
+//!
+//! plus any `variables` passed by the client. `{{{{` writes a literal `{{`.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, info_span};
+
+use super::context::ConnectionContext;
+use super::dry_run;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{clock, cron, line_ending, state::AppState};
+
+#[derive(Deserialize)]
+struct CreateFromTemplateParams {
+    template: String,
+    path: String,
+    #[serde(default)]
+    variables: BTreeMap<String, String>,
+    #[serde(default, rename = "dryRun")]
+    dry_run: bool,
+}
+
+/// Every template, sorted by name.
+pub fn handle_list(state: &AppState) -> Result<Value, HandlerError> {
+    let span = info_span!("templates_list_operation");
+    let _enter = span.enter();
+
+    let dir = state.config.templates_path();
+    let mut templates = Vec::new();
+    match fs::read_dir(&dir) {
+        Ok(entries) => {
+            for entry in entries {
+                let path = entry.map_err(HandlerError::IoError)?.path();
+                if !path.is_file() {
+                    continue;
+                }
+                let (Some(name), extension) = (template_name(&path), path.extension()) else {
+                    continue;
+                };
+                templates.push(json!({
+                    "name": name,
+                    "extension": extension.map(|e| e.to_string_lossy()),
+                }));
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(HandlerError::IoError(e)),
+    }
+    templates.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    debug!(dir = %dir.display(), templates = templates.len(), "Listing templates");
+    Ok(json!({ "templates": templates }))
+}
+
+/// Creates `path` from a template. Existing files are never overwritten.
+pub fn handle_create(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("create_from_template_operation");
+    let _enter = span.enter();
+
+    let params: CreateFromTemplateParams = parse_params(params)?;
+    let template = find_template(&state.config.templates_path(), &params.template)?;
+    let path = Path::new(&params.path);
+    let Some(file_name) = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+    else {
+        return Err(HandlerError::InvalidParams(format!(
+            "Not a file path: {}",
+            params.path
+        )));
+    };
+
+    let source = fs::read_to_string(&template).map_err(HandlerError::IoError)?;
+    let variables = variables(&file_name, params.variables);
+    let mut content = render(&source, &variables)?;
+    if let Some(style) = state.config.line_endings.style(&content, None) {
+        content = line_ending::convert(&content, style);
+    }
+    let limit = state.limits().max_write_bytes;
+    if content.len() > limit {
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "Content is {} bytes, larger than the {limit} byte write limit",
+            content.len()
+        )));
+    }
+
+    if params.dry_run {
+        if path.exists() {
+            return Err(HandlerError::IoError(io::Error::from(
+                io::ErrorKind::AlreadyExists,
+            )));
+        }
+        return Ok(dry_run::preview(state, path, content.as_bytes()));
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(HandlerError::IoError)?;
+    }
+    let mut file = fs::File::create_new(path).map_err(|e| {
+        debug!(path = %params.path, error = %e, "Failed to create file");
+        HandlerError::IoError(e)
+    })?;
+    file.write_all(content.as_bytes())
+        .map_err(HandlerError::IoError)?;
+
+    info!(
+        path = %params.path,
+        template = %params.template,
+        bytes = content.len(),
+        "File created from template"
+    );
+    state.activity.record(
+        "file.created",
+        Some(connection.id),
+        json!({ "path": params.path, "template": params.template, "bytes": content.len() }),
+    );
+    Ok(json!({ "path": params.path, "bytes": content.len() }))
+}
+
+fn template_name(path: &Path) -> Option<String> {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+}
+
+fn find_template(dir: &Path, name: &str) -> Result<PathBuf, HandlerError> {
+    let unknown = || HandlerError::InvalidParams(format!("Unknown template {name}"));
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(unknown());
+    }
+    let entries = fs::read_dir(dir).map_err(|_| unknown())?;
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .find(|path| template_name(path).as_deref() == Some(name))
+        .ok_or_else(unknown)
+}
+
+/// The built-in variables for `file_name`, overridden by the client's.
+fn variables(file_name: &str, custom: BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let stem = Path::new(file_name)
+        .file_stem()
+        .map_or(file_name.to_string(), |stem| {
+            stem.to_string_lossy().into_owned()
+        });
+    let words = words(&stem);
+    let (year, month, day) = cron::civil_date(clock::unix_secs());
+    let mut variables = BTreeMap::from([
+        ("filename".to_string(), file_name.to_string()),
+        ("module".to_string(), words.join("_")),
+        (
+            "type".to_string(),
+            words
+                .iter()
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars.next().map_or(String::new(), |first| {
+                        first.to_uppercase().chain(chars).collect()
+                    })
+                })
+                .collect(),
+        ),
+        ("stem".to_string(), stem),
+        ("date".to_string(), format!("{year:04}-{month:02}-{day:02}")),
+        ("year".to_string(), year.to_string()),
+    ]);
+    variables.extend(custom);
+    variables
+}
+
+/// Lowercase words of an identifier in any of the usual casings:
+/// `UserProfile`, `user-profile` and `user_profile` all give
+/// `["user", "profile"]`.
+fn words(identifier: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in identifier.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            previous_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        previous_lower = c.is_lowercase() || c.is_numeric();
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn render(source: &str, variables: &BTreeMap<String, String>) -> Result<String, HandlerError> {
+    let mut output = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+        if let Some(after) = rest.strip_prefix("{{") {
+            output.push_str("{{");
+            rest = after;
+            continue;
+        }
+        let Some(end) = rest.find("}}") else {
+            return Err(HandlerError::InvalidParams(
+                "Template has an unclosed {{".to_string(),
+            ));
+        };
+        let name = rest[..end].trim();
+        let Some(value) = variables.get(name) else {
+            return Err(HandlerError::InvalidParams(format!(
+                "Template uses unknown variable {name}"
+            )));
+        };
+        output.push_str(value);
+        rest = &rest[end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
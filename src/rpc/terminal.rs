@@ -0,0 +1,123 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{collections::HashMap, path::PathBuf};
+use tracing::info_span;
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+use crate::terminal::TerminalOptions;
+
+#[derive(Deserialize)]
+struct CreateTerminalParams {
+    shell: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    cwd: Option<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default = "default_cols")]
+    cols: u16,
+    #[serde(default = "default_rows")]
+    rows: u16,
+}
+
+fn default_cols() -> u16 {
+    80
+}
+
+fn default_rows() -> u16 {
+    24
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TerminalInputParams {
+    terminal_id: u64,
+    data: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TerminalResizeParams {
+    terminal_id: u64,
+    cols: u16,
+    rows: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TerminalIdParams {
+    terminal_id: u64,
+}
+
+pub fn handle_create(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("terminal_create_operation");
+    let _enter = span.enter();
+
+    let params: CreateTerminalParams = parse_params(params)?;
+    let options = TerminalOptions {
+        shell: params.shell,
+        args: params.args,
+        cwd: params
+            .cwd
+            .map(PathBuf::from)
+            .unwrap_or_else(|| state.config.root.clone()),
+        env: params.env,
+        cols: params.cols,
+        rows: params.rows,
+    };
+
+    let terminal_id = state
+        .terminals
+        .create(connection, options)
+        .map_err(HandlerError::TerminalError)?;
+    Ok(json!({ "terminalId": terminal_id }))
+}
+
+pub fn handle_input(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let params: TerminalInputParams = parse_params(params)?;
+    state
+        .terminals
+        .input(connection.id, params.terminal_id, &params.data)
+        .map_err(HandlerError::TerminalError)?;
+    Ok(Value::Bool(true))
+}
+
+pub fn handle_resize(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let params: TerminalResizeParams = parse_params(params)?;
+    state
+        .terminals
+        .resize(connection.id, params.terminal_id, params.cols, params.rows)
+        .map_err(HandlerError::TerminalError)?;
+    Ok(Value::Bool(true))
+}
+
+pub fn handle_kill(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("terminal_kill_operation");
+    let _enter = span.enter();
+
+    let params: TerminalIdParams = parse_params(params)?;
+    state
+        .terminals
+        .kill(connection.id, params.terminal_id)
+        .map_err(HandlerError::TerminalError)?;
+    Ok(Value::Bool(true))
+}
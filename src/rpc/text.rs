@@ -0,0 +1,264 @@
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{fs, path::Path};
+use tracing::{debug, info, info_span};
+
+use super::error::HandlerError;
+use super::handlers::parse_params;
+
+/// Zero-based line and character (Unicode scalar) offset within a document.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    /// Converts the range to byte offsets into `text`, clamping positions
+    /// that fall past the end of a line or the document.
+    pub fn to_byte_range(self, text: &str) -> Result<(usize, usize), HandlerError> {
+        if self.end < self.start {
+            return Err(HandlerError::InvalidParams(
+                "Range end is before range start".to_string(),
+            ));
+        }
+        Ok((byte_offset(text, self.start), byte_offset(text, self.end)))
+    }
+}
+
+pub fn byte_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line_end = text[line_start..]
+        .find('\n')
+        .map(|i| line_start + i)
+        .unwrap_or(text.len());
+    text[line_start..line_end]
+        .char_indices()
+        .nth(position.character)
+        .map(|(i, _)| line_start + i)
+        .unwrap_or(line_end)
+}
+
+/// Maps byte offsets back to positions without rescanning the text each time.
+pub struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    pub fn position(&self, byte: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= byte) - 1;
+        let line_start = self.line_starts[line];
+        Position {
+            line,
+            character: self.text[line_start..byte].chars().count(),
+        }
+    }
+
+    pub fn range(&self, start: usize, end: usize) -> Range {
+        Range {
+            start: self.position(start),
+            end: self.position(end),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+enum Transform {
+    SortLines,
+    SortLinesDescending,
+    UniqueLines,
+    ReverseLines,
+    UpperCase,
+    LowerCase,
+    TitleCase,
+    CamelCase,
+    PascalCase,
+    SnakeCase,
+    KebabCase,
+    Base64Encode,
+    Base64Decode,
+    JsonPretty,
+    JsonMinify,
+}
+
+#[derive(Deserialize)]
+struct TransformTextParams {
+    transform: Transform,
+    text: Option<String>,
+    path: Option<String>,
+    range: Option<Range>,
+}
+
+pub fn handle_transform_text(params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("transform_text_operation");
+    let _enter = span.enter();
+
+    let params: TransformTextParams = parse_params(params)?;
+
+    let input = match (&params.text, &params.path) {
+        (Some(text), None) => text.clone(),
+        (None, Some(path)) => {
+            debug!(path = %path, "Reading transform input from file");
+            let path = Path::new(path);
+            if !path.exists() {
+                return Err(HandlerError::FileNotFound(path.to_path_buf()));
+            }
+            fs::read_to_string(path).map_err(HandlerError::IoError)?
+        }
+        _ => {
+            return Err(HandlerError::InvalidParams(
+                "Exactly one of text or path must be provided".to_string(),
+            ));
+        }
+    };
+
+    let (start, end) = match &params.range {
+        Some(range) => range.to_byte_range(&input)?,
+        None => (0, input.len()),
+    };
+
+    let output = apply_transform(params.transform, &input[start..end])?;
+
+    info!(
+        transform = ?params.transform,
+        input_length = end - start,
+        output_length = output.len(),
+        "Text transformed successfully"
+    );
+    Ok(json!({ "text": output, "range": params.range }))
+}
+
+fn apply_transform(transform: Transform, text: &str) -> Result<String, HandlerError> {
+    let output = match transform {
+        Transform::SortLines => map_lines(text, |lines| lines.sort()),
+        Transform::SortLinesDescending => map_lines(text, |lines| {
+            lines.sort();
+            lines.reverse();
+        }),
+        Transform::UniqueLines => map_lines(text, |lines| {
+            let mut seen = std::collections::HashSet::new();
+            lines.retain(|line| seen.insert(*line));
+        }),
+        Transform::ReverseLines => map_lines(text, |lines| lines.reverse()),
+        Transform::UpperCase => text.to_uppercase(),
+        Transform::LowerCase => text.to_lowercase(),
+        Transform::TitleCase => words(text)
+            .iter()
+            .map(|word| capitalize(word))
+            .collect::<Vec<_>>()
+            .join(" "),
+        Transform::CamelCase => words(text)
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                if i == 0 {
+                    word.to_lowercase()
+                } else {
+                    capitalize(word)
+                }
+            })
+            .collect(),
+        Transform::PascalCase => words(text).iter().map(|word| capitalize(word)).collect(),
+        Transform::SnakeCase => join_lower(text, "_"),
+        Transform::KebabCase => join_lower(text, "-"),
+        Transform::Base64Encode => BASE64.encode(text),
+        Transform::Base64Decode => {
+            let bytes = BASE64
+                .decode(text.trim())
+                .map_err(|e| HandlerError::InvalidParams(format!("Invalid base64 input: {e}")))?;
+            String::from_utf8(bytes).map_err(|_| {
+                HandlerError::InvalidParams("Decoded base64 is not valid UTF-8".to_string())
+            })?
+        }
+        Transform::JsonPretty | Transform::JsonMinify => {
+            let value: Value = serde_json::from_str(text)
+                .map_err(|e| HandlerError::InvalidParams(format!("Invalid JSON input: {e}")))?;
+            let result = if matches!(transform, Transform::JsonPretty) {
+                serde_json::to_string_pretty(&value)
+            } else {
+                serde_json::to_string(&value)
+            };
+            result.map_err(|e| HandlerError::InvalidParams(e.to_string()))?
+        }
+    };
+    Ok(output)
+}
+
+/// Applies `f` to the lines of `text`, keeping a trailing newline if present.
+fn map_lines(text: &str, f: impl FnOnce(&mut Vec<&str>)) -> String {
+    let trailing_newline = text.ends_with('\n');
+    let mut lines: Vec<&str> = text.lines().collect();
+    f(&mut lines);
+    let mut output = lines.join("\n");
+    if trailing_newline {
+        output.push('\n');
+    }
+    output
+}
+
+/// Splits identifiers and prose into words on non-alphanumeric characters
+/// and lower-to-upper case boundaries (`fooBar` -> `foo`, `Bar`).
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        prev_lower = c.is_lowercase() || c.is_numeric();
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+fn join_lower(text: &str, separator: &str) -> String {
+    words(text)
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
@@ -0,0 +1,74 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{info, info_span};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{clock, state::AppState};
+
+#[derive(Deserialize)]
+struct RestoreParams {
+    id: String,
+    #[serde(default, rename = "dryRun")]
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EmptyParams {
+    /// Only purge entries deleted at least this many seconds ago.
+    older_than_secs: Option<u64>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+pub fn handle_list(state: &AppState) -> Result<Value, HandlerError> {
+    let entries = state.trash.list().map_err(HandlerError::IoError)?;
+    Ok(json!({ "entries": entries }))
+}
+
+pub fn handle_restore(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("trash_restore_operation");
+    let _enter = span.enter();
+
+    let params: RestoreParams = parse_params(params)?;
+    if params.dry_run {
+        let entry = state
+            .trash
+            .restorable(&params.id)
+            .map_err(HandlerError::IoError)?;
+        return Ok(json!({ "dryRun": true, "restored": entry }));
+    }
+    let entry = state
+        .trash
+        .restore(&params.id)
+        .map_err(HandlerError::IoError)?;
+    state.activity.record(
+        "file.restored",
+        Some(connection.id),
+        json!({ "path": entry.original_path, "trashId": entry.id }),
+    );
+    Ok(json!({ "restored": entry }))
+}
+
+pub fn handle_empty(state: &AppState, params: Value) -> Result<Value, HandlerError> {
+    let span = info_span!("trash_empty_operation");
+    let _enter = span.enter();
+
+    let params: EmptyParams = parse_params(params)?;
+    let before = params
+        .older_than_secs
+        .map(|secs| clock::unix_secs().saturating_sub(secs));
+    if params.dry_run {
+        let expired = state.trash.expired(before).map_err(HandlerError::IoError)?;
+        return Ok(json!({ "dryRun": true, "removed": expired.len(), "entries": expired }));
+    }
+    let removed = state.trash.empty(before).map_err(HandlerError::IoError)?;
+    info!(removed, "Trash emptied");
+    Ok(json!({ "removed": removed }))
+}
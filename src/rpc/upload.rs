@@ -0,0 +1,107 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{fs, io, path::Path};
+use tracing::{info, info_span, warn};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::{protected::audit_forced, state::AppState};
+
+#[derive(Deserialize)]
+struct UploadFileParams {
+    path: String,
+    /// Content stored with `blob/put`.
+    hash: String,
+    /// Replace a file already at `path`.
+    #[serde(default)]
+    overwrite: bool,
+    #[serde(default)]
+    force: bool,
+    #[serde(default, rename = "dryRun")]
+    dry_run: bool,
+}
+
+/// Places uploaded content at `path`, creating missing directories. Large
+/// files are sent in chunks with `blob/put` first; `POST /upload` does both
+/// steps for browser drag-and-drop.
+pub fn handle_upload_file(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("upload_file_operation");
+    let _enter = span.enter();
+
+    let params: UploadFileParams = parse_params(params)?;
+    let size = state
+        .blobs
+        .size(&params.hash)
+        .map_err(|e| HandlerError::BlobError(e.to_string()))?;
+    let limit = state.limits().max_upload_bytes;
+    if size > limit {
+        return Err(HandlerError::PayloadTooLarge(format!(
+            "Upload is {size} bytes, larger than the {limit} byte upload limit"
+        )));
+    }
+    let path = Path::new(&params.path);
+    if path.is_dir() {
+        return Err(HandlerError::DirectoryError(format!(
+            "{} is a directory",
+            params.path
+        )));
+    }
+
+    let replaced = path.exists();
+    if replaced {
+        if !params.overwrite {
+            return Err(HandlerError::IoError(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", params.path),
+            )));
+        }
+        if state.protected.is_protected(path) {
+            if !params.force {
+                return Err(HandlerError::ProtectedPath(params.path));
+            }
+            audit_forced("uploadFile", &params.path);
+        }
+    }
+    if params.dry_run {
+        return Ok(json!({
+            "dryRun": true,
+            "path": params.path,
+            "size": size,
+            "hash": params.hash,
+            "replaced": replaced,
+        }));
+    }
+    if replaced {
+        // A snapshot that fails is no reason to refuse the upload.
+        if let Err(e) = state.history.snapshot(path) {
+            warn!(path = %params.path, error = %e, "Failed to keep previous version");
+        }
+    } else if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).map_err(HandlerError::IoError)?;
+    }
+    state
+        .blobs
+        .copy_to(&params.hash, path)
+        .map_err(HandlerError::IoError)?;
+
+    info!(path = %params.path, size, replaced, "File uploaded");
+    state.activity.record(
+        "file.uploaded",
+        Some(connection.id),
+        json!({ "path": params.path, "bytes": size }),
+    );
+    Ok(json!({
+        "path": params.path,
+        "size": size,
+        "hash": params.hash,
+        "replaced": replaced,
+    }))
+}
@@ -0,0 +1,198 @@
+//! Checks request params against the method's schema before the handler
+//! deserializes them, so a bad request is told every field that is wrong
+//! and where, rather than the first thing serde tripped over.
+//!
+//! Covers the part of JSON Schema that [`super::schema`] uses: `type`,
+//! `properties`, `required`, `additionalProperties`, `items`, `enum` and
+//! `minimum`.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::error::HandlerError;
+use super::registry::{Call, Interceptor, Next};
+
+/// One way the params differ from the schema.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ParamError {
+    /// Where in the params, as `edits[0].range`; empty for the params
+    /// themselves.
+    pub field: String,
+    /// `missingField`, `wrongType`, `notAllowed` or `tooSmall`.
+    pub problem: &'static str,
+    /// The type, allowed values or minimum the schema asks for.
+    pub expected: Value,
+    pub message: String,
+}
+
+/// Every mismatch between `params` and `schema`. `null` params count as
+/// an empty object, as in [`super::handlers::parse_params`].
+pub fn validate(schema: &Value, params: &Value) -> Vec<ParamError> {
+    let mut errors = Vec::new();
+    let empty = Value::Object(Map::new());
+    let params = if params.is_null() { &empty } else { params };
+    check(schema, params, String::new(), &mut errors);
+    errors
+}
+
+fn check(schema: &Value, value: &Value, field: String, errors: &mut Vec<ParamError>) {
+    if let Some(expected) = schema.get("type").and_then(Value::as_str)
+        && !has_type(value, expected)
+    {
+        errors.push(ParamError {
+            message: format!(
+                "{} must be {} {expected}, not {}",
+                describe(&field),
+                article(expected),
+                type_name(value)
+            ),
+            field,
+            problem: "wrongType",
+            expected: expected.into(),
+        });
+        return;
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        let names: Vec<String> = allowed.iter().map(Value::to_string).collect();
+        errors.push(ParamError {
+            message: format!("{} must be one of {}", describe(&field), names.join(", ")),
+            field,
+            problem: "notAllowed",
+            expected: allowed.clone().into(),
+        });
+        return;
+    }
+    if let Some(minimum) = schema.get("minimum")
+        && let (Some(number), Some(bound)) = (value.as_f64(), minimum.as_f64())
+        && number < bound
+    {
+        errors.push(ParamError {
+            message: format!("{} must be at least {minimum}", describe(&field)),
+            field,
+            problem: "tooSmall",
+            expected: minimum.clone(),
+        });
+        return;
+    }
+
+    match value {
+        Value::Object(members) => check_object(schema, members, &field, errors),
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(item_schema, item, format!("{field}[{index}]"), errors);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_object(
+    schema: &Value,
+    members: &Map<String, Value>,
+    field: &str,
+    errors: &mut Vec<ParamError>,
+) {
+    let member = |name: &str| {
+        if field.is_empty() {
+            name.to_string()
+        } else {
+            format!("{field}.{name}")
+        }
+    };
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let properties = schema.get("properties").and_then(Value::as_object);
+
+    for name in &required {
+        if !members.contains_key(*name) {
+            let expected = properties
+                .and_then(|properties| properties.get(*name))
+                .and_then(|schema| schema.get("type"))
+                .cloned()
+                .unwrap_or(Value::Null);
+            errors.push(ParamError {
+                message: format!("missing field `{}`", member(name)),
+                field: member(name),
+                problem: "missingField",
+                expected,
+            });
+        }
+    }
+    for (name, value) in members {
+        let schema = properties
+            .and_then(|properties| properties.get(name))
+            .or_else(|| schema.get("additionalProperties").filter(|s| s.is_object()));
+        let Some(schema) = schema else {
+            continue;
+        };
+        // Optional fields are `Option`s, which take `null` for absent.
+        if value.is_null() && !required.contains(&name.as_str()) {
+            continue;
+        }
+        check(schema, value, member(name), errors);
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(number) if number.is_f64() => "a fractional number",
+        Value::Number(_) => "an integer",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn article(type_name: &str) -> &'static str {
+    match type_name.as_bytes().first() {
+        Some(b'a' | b'e' | b'i' | b'o' | b'u') => "an",
+        _ => "a",
+    }
+}
+
+fn describe(field: &str) -> String {
+    if field.is_empty() {
+        "params".to_string()
+    } else {
+        format!("`{field}`")
+    }
+}
+
+/// Refuses calls whose params do not match the method's schema. Methods
+/// without one are passed through unchecked.
+pub struct Validate;
+
+impl Interceptor for Validate {
+    fn intercept(&self, call: Call<'_>, next: Next<'_>) -> Result<Option<Value>, HandlerError> {
+        if let Some(schema) = next.handler().params_schema() {
+            let errors = validate(&schema, &call.params);
+            if !errors.is_empty() {
+                return Err(HandlerError::ParamsMismatch(errors));
+            }
+        }
+        next.run(call)
+    }
+}
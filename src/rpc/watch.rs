@@ -0,0 +1,94 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{fs, path::Path};
+use tracing::{info, info_span};
+
+use super::context::ConnectionContext;
+use super::error::HandlerError;
+use super::handlers::parse_params;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+struct StartWatchParams {
+    path: String,
+    /// Only report paths matching one of these, relative to `path`.
+    #[serde(default)]
+    globs: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StopWatchParams {
+    subscription_id: u64,
+}
+
+/// Subscribes to changes under the directory at `path`, sent as
+/// `watch/changed` notifications of `{subscriptionId, changes, overflow}`
+/// until `watch/stop` or disconnect. Each change has a `type` of
+/// `created`, `modified`, `deleted` or `renamed`, the last with `oldPath`.
+pub fn handle_start(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("watch_start_operation");
+    let _enter = span.enter();
+
+    let params: StartWatchParams = parse_params(params)?;
+    let path = Path::new(&params.path);
+    if !path.exists() {
+        return Err(HandlerError::FileNotFound(path.to_path_buf()));
+    }
+    if !path.is_dir() {
+        return Err(HandlerError::DirectoryError(format!(
+            "Not a directory: {}",
+            params.path
+        )));
+    }
+    let include = (!params.globs.is_empty())
+        .then(|| glob_set(&params.globs))
+        .transpose()?;
+    let exclude = glob_set(&params.exclude)?;
+    let root = fs::canonicalize(path).map_err(HandlerError::IoError)?;
+    let (id, backend) = state.watcher.subscribe(
+        state,
+        connection.id,
+        connection.notifier.clone(),
+        root,
+        include,
+        exclude,
+    );
+    info!(path = %params.path, id, backend, "Watching for changes");
+    Ok(json!({ "subscriptionId": id, "path": params.path, "backend": backend }))
+}
+
+pub fn handle_stop(
+    state: &AppState,
+    connection: &ConnectionContext,
+    params: Value,
+) -> Result<Value, HandlerError> {
+    let span = info_span!("watch_stop_operation");
+    let _enter = span.enter();
+
+    let params: StopWatchParams = parse_params(params)?;
+    let stopped = state
+        .watcher
+        .unsubscribe(params.subscription_id, connection.id);
+    Ok(json!({ "stopped": stopped }))
+}
+
+fn glob_set(globs: &[String]) -> Result<GlobSet, HandlerError> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(
+            Glob::new(glob)
+                .map_err(|e| HandlerError::InvalidParams(format!("Invalid glob {glob}: {e}")))?,
+        );
+    }
+    builder
+        .build()
+        .map_err(|e| HandlerError::InvalidParams(format!("Invalid globs: {e}")))
+}
@@ -0,0 +1,27 @@
+use serde_json::{Value, json};
+use tracing::{debug, info_span};
+
+use super::error::HandlerError;
+use crate::state::AppState;
+
+/// The roots this server serves, the primary one first.
+pub fn handle_list(state: &AppState) -> Result<Value, HandlerError> {
+    let span = info_span!("workspace_list_operation");
+    let _enter = span.enter();
+
+    let workspaces: Vec<Value> = state
+        .uris
+        .roots()
+        .enumerate()
+        .map(|(i, (name, root, uri))| {
+            json!({
+                "name": name,
+                "root": root,
+                "uri": uri,
+                "primary": i == 0,
+            })
+        })
+        .collect();
+    debug!(workspaces = workspaces.len(), "Listing workspaces");
+    Ok(json!({ "workspaces": workspaces }))
+}
@@ -0,0 +1,201 @@
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::rpc::error::HandlerError;
+
+/// Symlink policy for request paths. Symlinks that stay inside the
+/// workspace are followed when `followSymlinks` is on; one that leads out
+/// of the workspace is refused either way, as is any symlink when it is off.
+pub struct Sandbox {
+    /// Canonical workspace roots, the primary one first.
+    roots: Vec<PathBuf>,
+    follow_symlinks: bool,
+}
+
+impl Sandbox {
+    pub fn new<'a>(roots: impl IntoIterator<Item = &'a Path>, follow_symlinks: bool) -> Self {
+        Self {
+            roots: roots
+                .into_iter()
+                .map(|root| root.canonicalize().unwrap_or_else(|_| root.to_path_buf()))
+                .collect(),
+            follow_symlinks,
+        }
+    }
+
+    /// The primary root.
+    pub fn root(&self) -> &Path {
+        &self.roots[0]
+    }
+
+    /// The innermost root containing `path`, which must be normalized.
+    fn root_of(&self, path: &Path) -> Option<&Path> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .map(PathBuf::as_path)
+    }
+
+    /// Canonical form of `path`, taking relative paths from the root. The
+    /// last components may be missing, so the target of a write can be
+    /// resolved. Paths outside the workspace are refused.
+    pub fn resolve(&self, path: &Path) -> Result<PathBuf, HandlerError> {
+        self.resolve_joined(&self.root().join(path), path)
+    }
+
+    /// Resolves `joined`, naming it `path` in errors.
+    fn resolve_joined(&self, joined: &Path, path: &Path) -> Result<PathBuf, HandlerError> {
+        let Some(root) = self.root_of(&normalize(joined)) else {
+            return Err(HandlerError::InvalidParams(format!(
+                "Path is outside the workspace: {}",
+                path.display()
+            )));
+        };
+        if !self.follow_symlinks {
+            // Up to the first symlink, `..` means what it says lexically.
+            let mut current = PathBuf::new();
+            for component in joined.components() {
+                match component {
+                    Component::CurDir => {}
+                    Component::ParentDir => {
+                        current.pop();
+                    }
+                    component => current.push(component),
+                }
+                if current.starts_with(root)
+                    && current != root
+                    && current.symlink_metadata().is_ok_and(|m| m.is_symlink())
+                {
+                    return Err(HandlerError::SymlinkRefused {
+                        path: path.to_path_buf(),
+                        escapes: false,
+                    });
+                }
+            }
+        }
+
+        let mut existing = joined;
+        let mut missing = Vec::new();
+        let mut canonical = loop {
+            match existing.canonicalize() {
+                Ok(canonical) => break canonical,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    let (Some(name), Some(parent)) = (existing.file_name(), existing.parent())
+                    else {
+                        return Err(HandlerError::FileNotFound(joined.to_path_buf()));
+                    };
+                    missing.push(name);
+                    existing = parent;
+                }
+                Err(e) => return Err(HandlerError::IoError(e)),
+            }
+        };
+        canonical.extend(missing.iter().rev());
+        if !canonical.starts_with(root) {
+            return Err(HandlerError::SymlinkRefused {
+                path: path.to_path_buf(),
+                escapes: true,
+            });
+        }
+        Ok(canonical)
+    }
+
+    /// Applies the symlink policy to a path that handlers open relative to
+    /// the working directory. Paths that do not point into the workspace
+    /// are left alone.
+    pub fn check(&self, path: &Path) -> Result<(), HandlerError> {
+        let absolute = std::path::absolute(path).map_err(HandlerError::IoError)?;
+        if self.root_of(&normalize(&absolute)).is_none() {
+            return Ok(());
+        }
+        self.resolve_joined(&absolute, path).map(drop)
+    }
+
+    /// Like [`Sandbox::check`], but allows the last component itself to be
+    /// a symlink, for operations such as deletion that act on the link
+    /// rather than its target.
+    pub fn check_parent(&self, path: &Path) -> Result<(), HandlerError> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => self.check(parent),
+            _ => self.check(Path::new(".")),
+        }
+    }
+}
+
+/// Removes `.` and `..` components without touching the filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, os::unix::fs::symlink};
+
+    /// A workspace with `inside.txt`, a link to it, and a link to a file
+    /// outside the workspace.
+    fn workspace() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("inside.txt"), "inside").unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        symlink(root.join("inside.txt"), root.join("inner-link")).unwrap();
+        symlink(dir.path().join("secret.txt"), root.join("escape-link")).unwrap();
+        symlink(dir.path(), root.join("escape-dir")).unwrap();
+        (dir, root)
+    }
+
+    fn escapes(result: Result<(), HandlerError>) -> bool {
+        matches!(
+            result,
+            Err(HandlerError::SymlinkRefused { escapes: true, .. })
+        )
+    }
+
+    #[test]
+    fn refuses_links_out_of_the_workspace() {
+        let (_dir, root) = workspace();
+        let sandbox = Sandbox::new([root.as_path()], true);
+        assert!(sandbox.check(&root.join("inside.txt")).is_ok());
+        assert!(sandbox.check(&root.join("inner-link")).is_ok());
+        assert!(escapes(sandbox.check(&root.join("escape-link"))));
+        assert!(escapes(sandbox.check(&root.join("escape-dir/secret.txt"))));
+        // A file yet to be written through the link is refused too.
+        assert!(escapes(sandbox.check(&root.join("escape-dir/new.txt"))));
+    }
+
+    #[test]
+    fn refuses_every_link_when_not_following() {
+        let (_dir, root) = workspace();
+        let sandbox = Sandbox::new([root.as_path()], false);
+        assert!(sandbox.check(&root.join("inside.txt")).is_ok());
+        assert!(matches!(
+            sandbox.check(&root.join("inner-link")),
+            Err(HandlerError::SymlinkRefused { escapes: false, .. })
+        ));
+    }
+
+    #[test]
+    fn deleting_a_link_checks_only_its_parent() {
+        let (_dir, root) = workspace();
+        let sandbox = Sandbox::new([root.as_path()], true);
+        assert!(sandbox.check_parent(&root.join("escape-link")).is_ok());
+        assert!(escapes(
+            sandbox.check_parent(&root.join("escape-dir/secret.txt"))
+        ));
+    }
+}
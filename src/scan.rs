@@ -0,0 +1,284 @@
+use regex::Regex;
+use std::{collections::HashMap, fs, io::Read, path::Path, sync::LazyLock};
+use tracing::debug;
+
+use crate::{
+    clock,
+    problems::{Problem, Severity},
+    rpc::text::{Position, Range},
+    walk::IgnoreRules,
+};
+
+pub const SECRETS_SOURCE: &str = "secret-scan";
+pub const LICENSES_SOURCE: &str = "license-scan";
+
+/// Files larger than this are skipped; secrets live in source and config.
+const MAX_SCAN_BYTES: u64 = 1024 * 1024;
+/// Lines at the top of a file searched for a license header.
+const LICENSE_HEADER_LINES: usize = 30;
+/// Minimum Shannon entropy (bits per char) for a generic assignment to be
+/// reported, which filters out placeholders like `password = "changeme"`.
+const MIN_SECRET_ENTROPY: f64 = 3.5;
+
+static KNOWN_TOKENS: LazyLock<Vec<(&'static str, &'static str, Regex)>> = LazyLock::new(|| {
+    [
+        (
+            "aws-access-key",
+            "AWS access key id",
+            r"\b(A3T[A-Z0-9]|AKIA|ASIA)[A-Z0-9]{16}\b",
+        ),
+        (
+            "github-token",
+            "GitHub token",
+            r"\bgh[pousr]_[A-Za-z0-9]{36,}\b",
+        ),
+        (
+            "github-pat",
+            "GitHub fine-grained token",
+            r"\bgithub_pat_[A-Za-z0-9_]{60,}\b",
+        ),
+        (
+            "slack-token",
+            "Slack token",
+            r"\bxox[abposr]-[0-9A-Za-z-]{10,}\b",
+        ),
+        (
+            "google-api-key",
+            "Google API key",
+            r"\bAIza[0-9A-Za-z_\-]{35}\b",
+        ),
+        (
+            "stripe-key",
+            "Stripe live key",
+            r"\b[sr]k_live_[0-9A-Za-z]{24,}\b",
+        ),
+        (
+            "private-key",
+            "Private key block",
+            r"-----BEGIN ([A-Z]+ )?PRIVATE KEY-----",
+        ),
+    ]
+    .into_iter()
+    .map(|(code, name, pattern)| (code, name, Regex::new(pattern).expect("valid pattern")))
+    .collect()
+});
+
+static SECRET_ASSIGNMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)(api[_-]?key|secret|token|passw(or)?d|credential|auth)[A-Za-z0-9_-]*["']?\s*[:=]\s*["']([^"'\s]{12,})["']"#,
+    )
+    .expect("valid pattern")
+});
+
+static SPDX_IDENTIFIER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"SPDX-License-Identifier:\s*([A-Za-z0-9.+\- ()]+)").expect("valid pattern")
+});
+
+const LICENSED_EXTENSIONS: &[&str] = &[
+    "rs", "c", "h", "cc", "cpp", "hpp", "go", "java", "kt", "js", "jsx", "ts", "tsx", "py", "rb",
+    "swift", "cs", "php", "scala",
+];
+
+pub struct ScanOptions {
+    pub secrets: bool,
+    pub licenses: bool,
+    /// SPDX identifiers considered acceptable; empty accepts any.
+    pub allowed_licenses: Vec<String>,
+    pub ignore: IgnoreRules,
+    /// Skip what ignore files and the `exclude` config leave out.
+    pub respect_ignore: bool,
+}
+
+#[derive(Default)]
+pub struct ScanReport {
+    pub files_scanned: usize,
+    pub secrets: Vec<Problem>,
+    pub licenses: Vec<Problem>,
+}
+
+/// Walks the workspace and collects findings.
+pub fn scan_workspace(root: &Path, options: &ScanOptions) -> ScanReport {
+    let mut report = ScanReport::default();
+
+    let mut walker = options.ignore.walker(root, options.respect_ignore);
+    if clock::DETERMINISTIC {
+        walker.sort_by_file_name(|a, b| a.cmp(b));
+    }
+    for entry in walker.build() {
+        let Ok(entry) = entry else { continue };
+        let path = entry.path();
+        if !entry.file_type().is_some_and(|t| t.is_file())
+            || path.components().any(|c| c.as_os_str() == ".git")
+        {
+            continue;
+        }
+        let Some(text) = read_text(path) else {
+            continue;
+        };
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned();
+
+        report.files_scanned += 1;
+        if options.secrets {
+            scan_secrets(&relative, &text, &mut report.secrets);
+        }
+        if options.licenses {
+            scan_license(&relative, path, &text, options, &mut report.licenses);
+        }
+    }
+
+    report
+}
+
+/// Reads small UTF-8 text files, skipping anything that looks binary.
+fn read_text(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    if metadata.len() > MAX_SCAN_BYTES {
+        debug!(path = %path.display(), "Skipping large file");
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(metadata.len() as usize);
+    fs::File::open(path).ok()?.read_to_end(&mut bytes).ok()?;
+    if bytes.iter().take(8192).any(|&b| b == 0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn line_range(line: usize, start: usize, end: usize, text: &str) -> Range {
+    let character = |byte: usize| text[..byte].chars().count();
+    Range {
+        start: Position {
+            line,
+            character: character(start),
+        },
+        end: Position {
+            line,
+            character: character(end),
+        },
+    }
+}
+
+fn scan_secrets(path: &str, text: &str, problems: &mut Vec<Problem>) {
+    for (line_number, line) in text.lines().enumerate() {
+        let mut reported = false;
+        for (code, name, pattern) in KNOWN_TOKENS.iter() {
+            for found in pattern.find_iter(line) {
+                reported = true;
+                problems.push(Problem {
+                    path: path.to_string(),
+                    range: line_range(line_number, found.start(), found.end(), line),
+                    severity: Severity::Error,
+                    source: SECRETS_SOURCE.to_string(),
+                    code: code.to_string(),
+                    message: format!("Possible {name} committed to the workspace"),
+                });
+            }
+        }
+        if reported {
+            continue;
+        }
+
+        for captures in SECRET_ASSIGNMENT.captures_iter(line) {
+            let value = captures.get(3).expect("group 3 always participates");
+            if shannon_entropy(value.as_str()) < MIN_SECRET_ENTROPY {
+                continue;
+            }
+            problems.push(Problem {
+                path: path.to_string(),
+                range: line_range(line_number, value.start(), value.end(), line),
+                severity: Severity::Warning,
+                source: SECRETS_SOURCE.to_string(),
+                code: "high-entropy-secret".to_string(),
+                message: format!(
+                    "High-entropy value assigned to {}",
+                    captures.get(1).map(|m| m.as_str()).unwrap_or("secret")
+                ),
+            });
+        }
+    }
+}
+
+fn shannon_entropy(value: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in value.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    let length = value.chars().count() as f64;
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / length;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn scan_license(
+    relative: &str,
+    path: &Path,
+    text: &str,
+    options: &ScanOptions,
+    problems: &mut Vec<Problem>,
+) {
+    let is_source = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| LICENSED_EXTENSIONS.contains(&ext));
+    if !is_source {
+        return;
+    }
+
+    let header: Vec<&str> = text.lines().take(LICENSE_HEADER_LINES).collect();
+    let spdx = header.iter().enumerate().find_map(|(line_number, line)| {
+        SPDX_IDENTIFIER
+            .captures(line)
+            .and_then(|captures| captures.get(1))
+            .map(|m| (line_number, m, *line))
+    });
+
+    let problem = |range, severity, code: &str, message: String| Problem {
+        path: relative.to_string(),
+        range,
+        severity,
+        source: LICENSES_SOURCE.to_string(),
+        code: code.to_string(),
+        message,
+    };
+
+    match spdx {
+        Some((line_number, identifier, line)) => {
+            let license = identifier.as_str().trim();
+            if !options.allowed_licenses.is_empty()
+                && !options
+                    .allowed_licenses
+                    .iter()
+                    .any(|allowed| allowed == license)
+            {
+                problems.push(problem(
+                    line_range(line_number, identifier.start(), identifier.end(), line),
+                    Severity::Warning,
+                    "disallowed-license",
+                    format!("License {license} is not in the allowed list"),
+                ));
+            }
+        }
+        None => {
+            let mentions_license = header.iter().any(|line| {
+                let lower = line.to_ascii_lowercase();
+                lower.contains("copyright") || lower.contains("license")
+            });
+            if !mentions_license {
+                problems.push(problem(
+                    line_range(0, 0, 0, ""),
+                    Severity::Info,
+                    "missing-license-header",
+                    "File has no license header".to_string(),
+                ));
+            }
+        }
+    }
+}
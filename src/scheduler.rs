@@ -0,0 +1,328 @@
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::process::Command;
+use tracing::{Instrument, debug, info, info_span, warn};
+
+use crate::{
+    archive, clock,
+    config::{BuiltinJob, Config, JobDefinition},
+    cron::{CivilTime, Schedule},
+    scan::{LICENSES_SOURCE, SECRETS_SOURCE, ScanOptions, scan_workspace},
+    state::{AppState, SharedState},
+};
+
+/// Runs remembered per job for `jobs/history`.
+const MAX_HISTORY: usize = 50;
+/// Trailing bytes of a command's output kept with its run.
+const MAX_OUTPUT_TAIL: usize = 4096;
+/// Snapshot archives kept in the data directory; older ones are deleted.
+const MAX_SNAPSHOTS: usize = 10;
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    Schedule,
+    Manual,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobRun {
+    pub trigger: Trigger,
+    /// Unix seconds.
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    /// End of the combined stdout and stderr, or a built-in's summary.
+    pub output: String,
+    pub error: Option<String>,
+}
+
+pub struct Job {
+    pub definition: JobDefinition,
+    pub schedule: Schedule,
+}
+
+/// Configured jobs with their run history. Jobs never overlap themselves:
+/// a run that comes due while the previous one is going is skipped.
+pub struct JobScheduler {
+    jobs: BTreeMap<String, Job>,
+    running: Mutex<HashSet<String>>,
+    history: Mutex<BTreeMap<String, VecDeque<JobRun>>>,
+}
+
+impl JobScheduler {
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let mut jobs = BTreeMap::new();
+        for (name, definition) in &config.jobs {
+            let schedule =
+                Schedule::parse(&definition.schedule).map_err(|e| format!("job {name}: {e}"))?;
+            if definition.builtin.is_some() == definition.command.is_some() {
+                return Err(format!(
+                    "job {name}: set exactly one of builtin and command"
+                ));
+            }
+            jobs.insert(
+                name.clone(),
+                Job {
+                    definition: definition.clone(),
+                    schedule,
+                },
+            );
+        }
+        Ok(Self {
+            jobs,
+            running: Mutex::default(),
+            history: Mutex::default(),
+        })
+    }
+
+    pub fn jobs(&self) -> &BTreeMap<String, Job> {
+        &self.jobs
+    }
+
+    pub fn is_running(&self, name: &str) -> bool {
+        self.lock_running().contains(name)
+    }
+
+    /// Most recent runs first.
+    pub fn history(&self, name: &str, limit: usize) -> Vec<JobRun> {
+        self.lock_history()
+            .get(name)
+            .map(|runs| runs.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, name: &str, run: JobRun) {
+        let mut history = self.lock_history();
+        let runs = history.entry(name.to_string()).or_default();
+        if runs.len() == MAX_HISTORY {
+            runs.pop_front();
+        }
+        runs.push_back(run);
+    }
+
+    fn lock_running(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_history(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, VecDeque<JobRun>>> {
+        self.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn unix_now() -> u64 {
+    // Real time even in deterministic builds; only reported times are frozen.
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Checks the schedules at the start of every minute while the server runs.
+pub fn start(state: SharedState) {
+    if state.jobs.jobs().is_empty() {
+        return;
+    }
+    info!(jobs = state.jobs.jobs().len(), "Job scheduler started");
+    tokio::spawn(async move {
+        let mut last_minute = unix_now() / 60;
+        loop {
+            let now = unix_now();
+            let next_minute = (now / 60 + 1) * 60;
+            tokio::time::sleep(Duration::from_secs(next_minute - now)).await;
+
+            let minute = unix_now() / 60;
+            if minute == last_minute {
+                continue; // Woke early
+            }
+            last_minute = minute;
+            let time = CivilTime::from_unix(minute * 60);
+            for (name, job) in state.jobs.jobs() {
+                if job.schedule.matches(&time) {
+                    let _ = trigger(&state, name, Trigger::Schedule);
+                }
+            }
+        }
+    });
+}
+
+/// Starts a run of `name` in the background.
+pub fn trigger(state: &SharedState, name: &str, trigger: Trigger) -> Result<(), String> {
+    let job = state
+        .jobs
+        .jobs()
+        .get(name)
+        .ok_or_else(|| format!("Unknown job: {name}"))?;
+    if !state.jobs.lock_running().insert(name.to_string()) {
+        debug!(job = %name, "Skipping run of job that is still running");
+        return Err(format!("Job {name} is already running"));
+    }
+
+    let state = Arc::clone(state);
+    let name = name.to_string();
+    let definition = job.definition.clone();
+    let span = info_span!("job", name = %name);
+    tokio::spawn(
+        async move {
+            let started_at = clock::unix_secs();
+            info!(trigger = ?trigger, "Job started");
+            let outcome = match definition.builtin {
+                Some(builtin) => run_builtin(&state, builtin).await,
+                None => run_command(&state.config.root, &definition).await,
+            };
+            state.jobs.lock_running().remove(&name);
+
+            let run = JobRun {
+                trigger,
+                started_at,
+                finished_at: clock::unix_secs(),
+                success: outcome.error.is_none(),
+                exit_code: outcome.exit_code,
+                output: outcome.output,
+                error: outcome.error,
+            };
+            let kind = if run.success {
+                info!(exit_code = ?run.exit_code, "Job finished");
+                "job.finished"
+            } else {
+                warn!(error = ?run.error, "Job failed");
+                "job.failed"
+            };
+            state.activity.record(
+                kind,
+                None,
+                json!({
+                    "name": name,
+                    "trigger": run.trigger,
+                    "exitCode": run.exit_code,
+                    "error": run.error,
+                }),
+            );
+            state.jobs.record(&name, run);
+        }
+        .instrument(span),
+    );
+    Ok(())
+}
+
+struct Outcome {
+    exit_code: Option<i32>,
+    output: String,
+    error: Option<String>,
+}
+
+async fn run_command(root: &Path, definition: &JobDefinition) -> Outcome {
+    let command = definition.command.as_deref().unwrap_or_default();
+    let cwd = match &definition.cwd {
+        Some(cwd) => root.join(cwd),
+        None => root.to_path_buf(),
+    };
+    let result = Command::new(command)
+        .args(&definition.args)
+        .envs(&definition.env)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await;
+    match result {
+        Ok(output) => {
+            let mut combined = output.stdout;
+            combined.extend_from_slice(&output.stderr);
+            let start = combined.len().saturating_sub(MAX_OUTPUT_TAIL);
+            let exit_code = output.status.code();
+            Outcome {
+                exit_code,
+                output: String::from_utf8_lossy(&combined[start..]).into_owned(),
+                error: (!output.status.success()).then(|| match exit_code {
+                    Some(code) => format!("{command} exited with {code}"),
+                    None => format!("{command} was killed by a signal"),
+                }),
+            }
+        }
+        Err(e) => Outcome {
+            exit_code: None,
+            output: String::new(),
+            error: Some(format!("Failed to run {command}: {e}")),
+        },
+    }
+}
+
+async fn run_builtin(state: &SharedState, builtin: BuiltinJob) -> Outcome {
+    let job_state = Arc::clone(state);
+    let result = tokio::task::spawn_blocking(move || match builtin {
+        BuiltinJob::Snapshot => snapshot(&job_state.config.root, &job_state.config.data_path())
+            .map(|path| format!("Wrote {}", path.display()))
+            .map_err(|e| format!("Snapshot failed: {e}")),
+        BuiltinJob::Scan => Ok(refresh_problems(&job_state)),
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("Job panicked: {e}")));
+
+    match result {
+        Ok(output) => Outcome {
+            exit_code: None,
+            output,
+            error: None,
+        },
+        Err(error) => Outcome {
+            exit_code: None,
+            output: String::new(),
+            error: Some(error),
+        },
+    }
+}
+
+/// Runs the secret and license scans over the workspace and replaces their
+/// problems, returning a one-line summary.
+pub fn refresh_problems(state: &AppState) -> String {
+    let options = ScanOptions {
+        secrets: true,
+        licenses: true,
+        allowed_licenses: Vec::new(),
+        ignore: state.ignore.clone(),
+        respect_ignore: true,
+    };
+    let report = scan_workspace(&state.config.root, &options);
+    let summary = format!(
+        "Scanned {} files: {} secrets, {} license problems",
+        report.files_scanned,
+        report.secrets.len(),
+        report.licenses.len()
+    );
+    state.problems.replace(SECRETS_SOURCE, report.secrets);
+    state.problems.replace(LICENSES_SOURCE, report.licenses);
+    summary
+}
+
+/// Zips the workspace into `<data>/snapshots/snapshot-<unix secs>.zip`,
+/// skipping `.git`, ignored files and the data directory itself.
+fn snapshot(root: &Path, data_path: &Path) -> io::Result<PathBuf> {
+    let directory = data_path.join("snapshots");
+    fs::create_dir_all(&directory)?;
+    let path = directory.join(format!("snapshot-{}.zip", unix_now()));
+    let files = archive::workspace_files(root, data_path);
+    archive::write_zip(fs::File::create(&path)?, root, &files)?;
+
+    let mut snapshots: Vec<PathBuf> = fs::read_dir(&directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "zip"))
+        .collect();
+    snapshots.sort();
+    let excess = snapshots.len().saturating_sub(MAX_SNAPSHOTS);
+    for old in &snapshots[..excess] {
+        debug!(path = %old.display(), "Removing old snapshot");
+        let _ = fs::remove_file(old);
+    }
+    Ok(path)
+}
@@ -0,0 +1,95 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+use tracing::{debug, info};
+
+use crate::clock;
+
+/// 128 random bits as hex, for URLs that act as bearer credentials.
+pub fn random_token() -> Result<String, getrandom::Error> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Share {
+    pub token: String,
+    /// Canonical files and directories the token grants read access to.
+    pub paths: Vec<PathBuf>,
+    /// Unix seconds.
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub connection_id: u64,
+}
+
+impl Share {
+    /// Whether `path` (canonical) is one of the shared paths or lies under
+    /// a shared directory.
+    pub fn allows(&self, path: &Path) -> bool {
+        self.paths.iter().any(|shared| path.starts_with(shared))
+    }
+}
+
+/// Time-limited read-only share tokens, served by the `/share` viewer.
+/// Shares outlive the connection that minted them; they end when they
+/// expire, are revoked, or the server restarts.
+#[derive(Default)]
+pub struct ShareStore {
+    shares: Mutex<HashMap<String, Share>>,
+}
+
+impl ShareStore {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Share>> {
+        self.shares.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn create(
+        &self,
+        paths: Vec<PathBuf>,
+        ttl_secs: u64,
+        connection_id: u64,
+    ) -> Result<Share, getrandom::Error> {
+        let token = random_token()?;
+        let created_at = clock::unix_secs();
+        let share = Share {
+            token: token.clone(),
+            paths,
+            created_at,
+            expires_at: created_at + ttl_secs,
+            connection_id,
+        };
+        info!(paths = share.paths.len(), ttl_secs, "Share created");
+        self.lock().insert(token, share.clone());
+        Ok(share)
+    }
+
+    /// Looks up a live share, forgetting it if it has expired.
+    pub fn get(&self, token: &str) -> Option<Share> {
+        let mut shares = self.lock();
+        let share = shares.get(token)?;
+        if share.expires_at <= clock::unix_secs() {
+            debug!("Share expired");
+            shares.remove(token);
+            return None;
+        }
+        Some(share.clone())
+    }
+
+    pub fn list(&self) -> Vec<Share> {
+        let now = clock::unix_secs();
+        let mut shares = self.lock();
+        shares.retain(|_, share| share.expires_at > now);
+        let mut shares: Vec<Share> = shares.values().cloned().collect();
+        shares.sort_by_key(|share| share.created_at);
+        shares
+    }
+
+    pub fn revoke(&self, token: &str) -> bool {
+        self.lock().remove(token).is_some()
+    }
+}
@@ -0,0 +1,277 @@
+//! Runs short snippets of code for "run selection": each in a process of
+//! its own, with a clean environment, a wall-clock deadline and, on
+//! Linux, limits on CPU time and memory. Output beyond a cap is discarded
+//! rather than buffered. This keeps a runaway snippet from hurting the
+//! server; it does not stop a snippet reading or writing what the server
+//! user can.
+
+use serde::Serialize;
+use std::{
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    config::{InterpreterDefinition, SnippetConfig},
+    rpc::{cancel::CancelToken, error::HandlerError},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Passed through to snippets; everything else in the server's
+/// environment, such as credentials, is withheld.
+const INHERITED_ENV: &[&str] = &["PATH", "HOME", "LANG", "LC_ALL", "TMPDIR", "TZ"];
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` when the snippet was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    /// Killed for running past the deadline.
+    pub timed_out: bool,
+    /// Output past `maxOutputBytes` was discarded.
+    pub truncated: bool,
+    pub duration_ms: u64,
+}
+
+/// Runs `code` with `interpreter` in `cwd`, feeding it `stdin`, and waits
+/// at most `timeout` for it.
+pub fn run(
+    interpreter: &InterpreterDefinition,
+    code: &str,
+    stdin: Option<&str>,
+    cwd: &Path,
+    config: &SnippetConfig,
+    timeout: Duration,
+    cancel: &CancelToken,
+) -> Result<Run, HandlerError> {
+    let uses_file = interpreter.args.iter().any(|arg| arg.contains("{file}"));
+    if !uses_file && stdin.is_some() {
+        return Err(HandlerError::InvalidParams(format!(
+            "{} reads the snippet from stdin, so it cannot be given input",
+            interpreter.command
+        )));
+    }
+    let file = uses_file
+        .then(|| SnippetFile::create(code, interpreter.extension.as_deref()))
+        .transpose()
+        .map_err(|e| HandlerError::SnippetError(format!("Failed to write snippet: {e}")))?;
+    let args: Vec<String> = interpreter
+        .args
+        .iter()
+        .map(|arg| match &file {
+            Some(file) => arg.replace("{file}", &file.path.to_string_lossy()),
+            None => arg.clone(),
+        })
+        .collect();
+    debug!(command = %interpreter.command, args = ?args, "Running snippet");
+
+    let mut command = Command::new(&interpreter.command);
+    command
+        .args(&args)
+        .current_dir(cwd)
+        .env_clear()
+        .envs(
+            INHERITED_ENV
+                .iter()
+                .filter_map(|name| Some((name, std::env::var_os(name)?))),
+        )
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    isolate(&mut command, config);
+    let started = Instant::now();
+    let mut child = command.spawn().map_err(|e| {
+        HandlerError::SnippetError(format!("Failed to run {}: {e}", interpreter.command))
+    })?;
+
+    let input = if uses_file {
+        stdin.unwrap_or_default()
+    } else {
+        code
+    };
+    let writer = feed(child.stdin.take(), input.to_string());
+    let stdout = drain(child.stdout.take(), config.max_output_bytes);
+    let stderr = drain(child.stderr.take(), config.max_output_bytes);
+
+    let deadline = started + timeout;
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(HandlerError::IoError)? {
+            break status;
+        }
+        if cancel.is_cancelled() {
+            debug!(pid = child.id(), "Killing snippet of cancelled request");
+            kill(&mut child);
+            return Err(HandlerError::RequestCancelled);
+        }
+        if Instant::now() >= deadline {
+            debug!(pid = child.id(), "Killing snippet that ran out of time");
+            timed_out = true;
+            kill(&mut child);
+            break child.wait().map_err(HandlerError::IoError)?;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    // Anything the snippet left running in the background would hold
+    // its output open.
+    kill(&mut child);
+    let _ = writer.join();
+    let (stdout, stdout_truncated) = stdout.join().unwrap_or_default();
+    let (stderr, stderr_truncated) = stderr.join().unwrap_or_default();
+
+    Ok(Run {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        exit_code: status.code(),
+        signal: signal(status),
+        timed_out,
+        truncated: stdout_truncated || stderr_truncated,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// The snippet written out for interpreters that take a file, removed
+/// when dropped.
+struct SnippetFile {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl SnippetFile {
+    fn create(code: &str, extension: Option<&str>) -> std::io::Result<Self> {
+        let mut suffix = [0u8; 8];
+        let _ = getrandom::fill(&mut suffix);
+        let suffix: String = suffix.iter().map(|byte| format!("{byte:02x}")).collect();
+        let dir = std::env::temp_dir().join(format!("editor-snippet-{suffix}"));
+        fs::create_dir(&dir)?;
+        let name = match extension {
+            Some(extension) => format!("snippet.{extension}"),
+            None => "snippet".to_string(),
+        };
+        let file = Self {
+            path: dir.join(name),
+            dir,
+        };
+        fs::write(&file.path, code)?;
+        Ok(file)
+    }
+}
+
+impl Drop for SnippetFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            warn!(dir = %self.dir.display(), error = %e, "Failed to remove snippet file");
+        }
+    }
+}
+
+/// Written from a thread of its own so a snippet that prints before
+/// reading all its input cannot deadlock on a full pipe.
+fn feed(stdin: Option<ChildStdin>, input: String) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(input.as_bytes());
+        }
+    })
+}
+
+/// Reads a stream to the end, keeping the first `limit` bytes, and says
+/// whether any were dropped.
+fn drain(
+    stream: Option<impl Read + Send + 'static>,
+    limit: usize,
+) -> thread::JoinHandle<(Vec<u8>, bool)> {
+    thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut truncated = false;
+        let Some(mut stream) = stream else {
+            return (kept, truncated);
+        };
+        let mut chunk = [0u8; 8192];
+        loop {
+            match stream.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => {
+                    let room = limit.saturating_sub(kept.len());
+                    kept.extend_from_slice(&chunk[..read.min(room)]);
+                    truncated |= read > room;
+                }
+            }
+        }
+        (kept, truncated)
+    })
+}
+
+/// Starts the snippet in a process group of its own, so whatever it
+/// spawns can be killed with it, under the configured resource limits.
+#[cfg(target_os = "linux")]
+fn isolate(command: &mut Command, config: &SnippetConfig) {
+    use nix::sys::resource::{Resource, setrlimit};
+    use std::os::unix::process::CommandExt;
+
+    let (cpu_secs, memory_bytes) = (config.cpu_secs, config.memory_bytes);
+    command.process_group(0);
+    // SAFETY: the closure runs between fork and exec, where only
+    // async-signal-safe calls are allowed; it makes nothing but
+    // setrlimit system calls and allocates nothing.
+    unsafe {
+        command.pre_exec(move || {
+            setrlimit(Resource::RLIMIT_CORE, 0, 0)?;
+            if cpu_secs > 0 {
+                // SIGXCPU at the limit, SIGKILL a second later.
+                setrlimit(Resource::RLIMIT_CPU, cpu_secs, cpu_secs + 1)?;
+            }
+            if memory_bytes > 0 {
+                // Not RLIMIT_AS: runtimes such as V8 reserve far more
+                // address space than they use.
+                setrlimit(Resource::RLIMIT_DATA, memory_bytes, memory_bytes)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn isolate(command: &mut Command, _config: &SnippetConfig) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+}
+
+#[cfg(not(unix))]
+fn isolate(_command: &mut Command, _config: &SnippetConfig) {}
+
+#[cfg(target_os = "linux")]
+fn kill(child: &mut Child) {
+    use nix::{
+        sys::signal::{Signal, killpg},
+        unistd::Pid,
+    };
+    let _ = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+    let _ = child.wait();
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(unix)]
+fn signal(status: ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_status: ExitStatus) -> Option<i32> {
+    None
+}
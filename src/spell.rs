@@ -0,0 +1,268 @@
+//! Spell checking for prose. Markdown and plain text are split into words,
+//! which are looked up in a bundled English word list and in the words
+//! each workspace root has accepted, kept as
+//! `<data dir>/dictionaries/<root name>.txt`, one word per line.
+
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+};
+use tracing::{debug, info};
+
+/// American English, one word per line. Possessives are left out, since
+/// any known word may take `'s`.
+static BUNDLED: LazyLock<HashSet<&'static str>> =
+    LazyLock::new(|| include_str!("spell/words.txt").lines().collect());
+
+/// Longest word `addToDictionary` accepts, in characters.
+pub const MAX_WORD_CHARS: usize = 64;
+
+/// Byte ranges of `content` holding prose. In markdown that leaves out
+/// code, HTML, front matter and autolinks; plain text is prose throughout.
+pub fn prose(content: &str, markdown: bool) -> Vec<Range<usize>> {
+    if !markdown {
+        return std::iter::once(0..content.len()).collect();
+    }
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let mut ranges = Vec::new();
+    // Depth of the elements whose text is not prose.
+    let mut skipped = 0usize;
+    for (event, range) in Parser::new_ext(content, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::MetadataBlock(_) | Tag::HtmlBlock) => {
+                skipped += 1
+            }
+            Event::Start(Tag::Link {
+                link_type: LinkType::Autolink | LinkType::Email,
+                ..
+            }) => skipped += 1,
+            Event::End(TagEnd::CodeBlock | TagEnd::MetadataBlock(_) | TagEnd::HtmlBlock) => {
+                skipped = skipped.saturating_sub(1)
+            }
+            Event::End(TagEnd::Link) if skipped > 0 => skipped -= 1,
+            // Escapes and entities are the only text that differs from its
+            // source, and neither is a word.
+            Event::Text(text) if skipped == 0 && content[range.clone()] == *text => {
+                ranges.push(range)
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// The words of `text` worth checking, with their byte offsets. URLs,
+/// email addresses, paths and file names are passed over, as are tokens
+/// holding digits or underscores, capitalized acronyms, words changing case
+/// midway, which are names or identifiers, and single letters.
+pub fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_whitespace()
+        .map(move |chunk| (chunk.as_ptr() as usize - text.as_ptr() as usize, chunk))
+        .filter(|(_, chunk)| !looks_like_address(chunk))
+        .flat_map(|(offset, chunk)| {
+            chunk
+                .split(|c: char| !(c.is_alphanumeric() || matches!(c, '\'' | '’' | '_')))
+                .map(move |token| {
+                    let start = token.as_ptr() as usize - chunk.as_ptr() as usize;
+                    let word = token.trim_start_matches(['\'', '’']);
+                    let start = start + token.len() - word.len();
+                    (offset + start, word.trim_end_matches(['\'', '’']))
+                })
+        })
+        .filter(|(_, word)| worth_checking(word))
+}
+
+fn looks_like_address(chunk: &str) -> bool {
+    let chunk = chunk.trim_matches(|c: char| !c.is_alphanumeric());
+    chunk.contains("://")
+        || chunk.contains(['@', '/', '\\'])
+        || chunk
+            .match_indices('.')
+            .any(|(i, _)| chunk[i + 1..].starts_with(|c: char| c.is_alphanumeric()))
+}
+
+fn worth_checking(word: &str) -> bool {
+    let mut chars = word.chars();
+    if chars.next().is_none() || chars.next().is_none() {
+        return false;
+    }
+    if word.contains(|c: char| c.is_numeric() || c == '_') {
+        return false;
+    }
+    // Past the first letter, any capital marks an acronym or a name.
+    !word.chars().skip(1).any(|c| c.is_uppercase())
+}
+
+/// Whether `word` is spelled right. A capitalized word may also be a
+/// lowercase one starting a sentence, and any word may take `'s`.
+pub fn is_known(word: &str, custom: &HashSet<String>) -> bool {
+    let word = word.replace('’', "'");
+    let base = word
+        .strip_suffix("'s")
+        .filter(|base| !base.is_empty())
+        .unwrap_or(&word);
+    [word.as_str(), base].into_iter().any(|word| {
+        let known = |word: &str| BUNDLED.contains(word) || custom.contains(word);
+        known(word) || (word.starts_with(char::is_uppercase) && known(&word.to_lowercase()))
+    })
+}
+
+/// Up to `limit` known words within a couple of edits of `word`, closest
+/// first, capitalized as `word` is.
+pub fn suggestions(word: &str, custom: &HashSet<String>, limit: usize) -> Vec<String> {
+    let target: Vec<char> = word.to_lowercase().chars().collect();
+    // One-letter slips only, for short words, or anything would do.
+    let max_distance = if target.len() <= 4 { 1 } else { 2 };
+    let first = target.first().copied();
+    let mut letters = target.clone();
+    letters.sort_unstable();
+    // Ties go to words with the same letters, as when two were swapped,
+    // then to lowercase words over names, then to the same first letter.
+    let mut found: Vec<(usize, bool, bool, bool, usize, &str)> = BUNDLED
+        .iter()
+        .copied()
+        .chain(custom.iter().map(String::as_str))
+        .filter_map(|candidate| {
+            let length = candidate.chars().count();
+            if length.abs_diff(target.len()) > max_distance {
+                return None;
+            }
+            let chars: Vec<char> = candidate.to_lowercase().chars().collect();
+            let distance = edit_distance(&target, &chars, max_distance)?;
+            let mut other_letters = chars.clone();
+            other_letters.sort_unstable();
+            Some((
+                distance,
+                other_letters != letters,
+                candidate.starts_with(char::is_uppercase),
+                chars.first().copied() != first,
+                length.abs_diff(target.len()),
+                candidate,
+            ))
+        })
+        .collect();
+    found.sort_unstable();
+
+    let capitalized = word.starts_with(char::is_uppercase);
+    let mut seen = HashSet::new();
+    found
+        .into_iter()
+        .map(|(.., candidate)| {
+            if capitalized {
+                capitalize(candidate)
+            } else {
+                candidate.to_string()
+            }
+        })
+        .filter(|candidate| seen.insert(candidate.clone()))
+        .take(limit)
+        .collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Edits, counting swapped neighbours as one, turning `a` into `b`, or
+/// `None` past `max`.
+fn edit_distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(before[j - 2] + 1);
+            }
+            current[j] = best;
+        }
+        if current.iter().min().is_some_and(|&least| least > max) {
+            return None;
+        }
+        before = std::mem::replace(&mut previous, current.clone());
+    }
+    Some(previous[b.len()]).filter(|&distance| distance <= max)
+}
+
+/// The words each workspace root has added, read from disk the first time
+/// the root is checked.
+pub struct Dictionaries {
+    dir: PathBuf,
+    custom: Mutex<HashMap<String, Arc<HashSet<String>>>>,
+}
+
+impl Dictionaries {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            dir: data_dir.join("dictionaries"),
+            custom: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn path(&self, workspace: &str) -> PathBuf {
+        self.dir.join(format!("{workspace}.txt"))
+    }
+
+    /// The words `workspace` has added.
+    pub fn custom(&self, workspace: &str) -> Arc<HashSet<String>> {
+        let mut custom = self.custom.lock().unwrap_or_else(|e| e.into_inner());
+        self.loaded(&mut custom, workspace).clone()
+    }
+
+    /// Adds `word` to `workspace`'s list. False if it was already there.
+    pub fn add(&self, workspace: &str, word: &str) -> io::Result<bool> {
+        let mut custom = self.custom.lock().unwrap_or_else(|e| e.into_inner());
+        let words = self.loaded(&mut custom, workspace);
+        if words.contains(word) {
+            return Ok(false);
+        }
+        fs::create_dir_all(&self.dir)?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(workspace))?;
+        writeln!(file, "{word}")?;
+        // Checks already running keep the list they started with.
+        Arc::make_mut(words).insert(word.to_string());
+        info!(workspace, word, "Added word to dictionary");
+        Ok(true)
+    }
+
+    fn loaded<'a>(
+        &self,
+        custom: &'a mut HashMap<String, Arc<HashSet<String>>>,
+        workspace: &str,
+    ) -> &'a mut Arc<HashSet<String>> {
+        custom.entry(workspace.to_string()).or_insert_with(|| {
+            let words: HashSet<String> = fs::read_to_string(self.path(workspace))
+                .map(|list| {
+                    list.lines()
+                        .map(str::trim)
+                        .filter(|word| !word.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            debug!(workspace, words = words.len(), "Loaded custom dictionary");
+            Arc::new(words)
+        })
+    }
+}
@@ -0,0 +1,64 @@
+//! Writes to several files that land together.
+//!
+//! Every file is first written to a sibling staging file, and only once all
+//! of them were written are they renamed into place. A failure while
+//! staging leaves every file untouched; renames only fail in unusual cases
+//! such as a file vanishing meanwhile.
+
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+const STAGING_SUFFIX: &str = ".editor-server-tmp";
+
+/// Replaces the content of every `(path, content)`, creating missing files
+/// and their parent directories. Existing files keep their permissions.
+pub fn write_all<'a>(files: impl IntoIterator<Item = (&'a Path, &'a [u8])>) -> io::Result<()> {
+    let files: Vec<_> = files.into_iter().collect();
+    let mut staged: Vec<PathBuf> = Vec::with_capacity(files.len());
+    for &(path, content) in &files {
+        let temp_path = staging_path(path);
+        let written = (|| {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&temp_path, content)?;
+            // Keep the original's permissions, such as an executable bit.
+            match fs::metadata(path) {
+                Ok(metadata) => fs::set_permissions(&temp_path, metadata.permissions()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e),
+            }
+        })();
+        staged.push(temp_path);
+        if let Err(e) = written {
+            warn!(path = %path.display(), error = %e, "Failed to stage write");
+            discard(&staged);
+            return Err(e);
+        }
+    }
+    for (i, (&(path, _), temp_path)) in files.iter().zip(&staged).enumerate() {
+        if let Err(e) = fs::rename(temp_path, path) {
+            // Files already renamed keep their new content.
+            warn!(path = %path.display(), error = %e, "Failed to move staged write into place");
+            discard(&staged[i..]);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+fn staging_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.file_name().unwrap_or_default());
+    name.push(STAGING_SUFFIX);
+    path.with_file_name(name)
+}
+
+fn discard(staged: &[PathBuf]) {
+    for temp_path in staged {
+        let _ = fs::remove_file(temp_path);
+    }
+}
@@ -1,10 +1,187 @@
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::{
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+use tokio::sync::{Semaphore, watch};
+
+use crate::{
+    activity::ActivityFeed,
+    audit::AuditLog,
+    blob::BlobStore,
+    clock,
+    config::{Config, PayloadLimits},
+    dap::DapBridge,
+    disk_usage::DiskUsage,
+    documents::DocumentStore,
+    download::DownloadStore,
+    fault::FaultInjector,
+    history::FileHistory,
+    index::FileIndex,
+    locks::LockRegistry,
+    lsp::LspBridge,
+    permissions::Permissions,
+    policy::Policy,
+    presence::PresenceRegistry,
+    problems::ProblemStore,
+    protected::ProtectedPaths,
+    reload::Reloader,
+    request_log::RequestLog,
+    rpc::{handlers, registry::MethodRegistry},
+    sandbox::Sandbox,
+    scheduler::JobScheduler,
+    share::ShareStore,
+    spell::Dictionaries,
+    syntax::SyntaxRegistry,
+    task::TaskRegistry,
+    terminal::TerminalRegistry,
+    trash::Trash,
+    uri::WorkspaceUris,
+    walk::IgnoreRules,
+    watch::FileWatcher,
+    ws::{lanes::RequestLanes, session::SessionRegistry},
+};
 
-#[derive(Default)]
-#[allow(unused)]
 pub struct AppState {
-    // Add shared state fields here if needed
+    pub config: Config,
+    pub methods: MethodRegistry,
+    pub activity: ActivityFeed,
+    pub audit: AuditLog,
+    pub request_log: RequestLog,
+    pub blobs: BlobStore,
+    pub downloads: DownloadStore,
+    pub trash: Trash,
+    pub history: FileHistory,
+    pub protected: ProtectedPaths,
+    pub permissions: Permissions,
+    pub sandbox: Sandbox,
+    pub uris: Arc<WorkspaceUris>,
+    pub ignore: IgnoreRules,
+    pub files: FileIndex,
+    pub watcher: FileWatcher,
+    pub documents: DocumentStore,
+    pub policy: Policy,
+    pub faults: FaultInjector,
+    pub terminals: TerminalRegistry,
+    pub tasks: TaskRegistry,
+    pub lsp: LspBridge,
+    pub dap: DapBridge,
+    pub problems: ProblemStore,
+    pub syntax: SyntaxRegistry,
+    pub lanes: RequestLanes,
+    pub jobs: JobScheduler,
+    pub shares: ShareStore,
+    pub dictionaries: Dictionaries,
+    pub disk_usage: DiskUsage,
+    pub sessions: SessionRegistry,
+    pub presence: PresenceRegistry,
+    pub locks: LockRegistry,
+    /// Set once the server begins shutting down; each connection holds a
+    /// receiver and closes itself when it flips.
+    pub shutdown: watch::Sender<bool>,
+    /// One permit per open WebSocket, up to `maxConnections`.
+    pub connections: Arc<Semaphore>,
+    connection_slots: usize,
+    /// `limits` from the config, replaced when it is reloaded.
+    limits: RwLock<PayloadLimits>,
+    pub reloader: Reloader,
+    /// When the server came up, for `server/info`.
+    pub started: Instant,
+}
+
+impl AppState {
+    pub fn new(config: Config) -> Result<Self, String> {
+        let protected = ProtectedPaths::new(
+            std::iter::once(config.root.as_path()).chain(
+                config
+                    .workspaces
+                    .values()
+                    .map(|workspace| workspace.path.as_path()),
+            ),
+            &config.protected_paths,
+        )?;
+        let connection_slots = match config.max_connections {
+            0 => Semaphore::MAX_PERMITS,
+            limit => limit,
+        };
+        let permissions = Permissions::new(
+            std::iter::once((config.root.as_path(), config.permissions.as_slice())).chain(
+                config
+                    .workspaces
+                    .values()
+                    .map(|workspace| (workspace.path.as_path(), workspace.permissions.as_slice())),
+            ),
+        )?;
+        Ok(Self {
+            blobs: BlobStore::new(&config.data_path()),
+            trash: Trash::new(&config.data_path()),
+            dictionaries: Dictionaries::new(&config.data_path()),
+            disk_usage: DiskUsage::default(),
+            limits: RwLock::new(config.limits),
+            reloader: Reloader::new(&config),
+            history: FileHistory::new(&config.data_path(), &config.history),
+            request_log: RequestLog::new(&config.request_log),
+            lanes: RequestLanes::new(
+                config.interactive_workers,
+                config.background_workers,
+                config.background_queue,
+            ),
+            jobs: JobScheduler::new(&config)?,
+            policy: Policy::new(&config.policy, &config.workspaces)?,
+            faults: FaultInjector::new(&config.faults)?,
+            audit: AuditLog::new(config.audit_log.as_deref())?,
+            ignore: IgnoreRules::new(&config.root, &config.exclude, permissions.clone())?,
+            watcher: FileWatcher::new(permissions.clone()),
+            sandbox: Sandbox::new(
+                std::iter::once(config.root.as_path()).chain(
+                    config
+                        .workspaces
+                        .values()
+                        .map(|workspace| workspace.path.as_path()),
+                ),
+                config.follow_symlinks,
+            ),
+            uris: Arc::new(WorkspaceUris::new(
+                config.root_name.as_deref(),
+                &config.root,
+                &config.workspaces,
+            )?),
+            methods: handlers::builtin_methods(),
+            config,
+            protected,
+            permissions,
+            activity: ActivityFeed::default(),
+            terminals: TerminalRegistry::default(),
+            tasks: TaskRegistry::default(),
+            lsp: LspBridge::default(),
+            dap: DapBridge::default(),
+            problems: ProblemStore::default(),
+            syntax: SyntaxRegistry::default(),
+            shares: ShareStore::default(),
+            sessions: SessionRegistry::default(),
+            presence: PresenceRegistry::default(),
+            locks: LockRegistry::default(),
+            downloads: DownloadStore::default(),
+            files: FileIndex::default(),
+            documents: DocumentStore::default(),
+            shutdown: watch::Sender::new(false),
+            connections: Arc::new(Semaphore::new(connection_slots)),
+            connection_slots,
+            started: clock::now(),
+        })
+    }
+
+    pub fn limits(&self) -> PayloadLimits {
+        *self.limits.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_limits(&self, limits: PayloadLimits) {
+        *self.limits.write().unwrap_or_else(|e| e.into_inner()) = limits;
+    }
+
+    /// WebSockets open now.
+    pub fn open_connections(&self) -> usize {
+        self.connection_slots - self.connections.available_permits()
+    }
 }
 
-pub type _SharedState = Arc<Mutex<AppState>>;
+pub type SharedState = Arc<AppState>;
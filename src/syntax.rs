@@ -0,0 +1,339 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex, atomic::AtomicUsize},
+};
+use tracing::{debug, info};
+use tree_sitter::{Language, Parser, Query, QueryCursor, StreamingIterator};
+use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter, HtmlRenderer};
+
+use crate::rpc::text::{LineIndex, Range};
+
+/// Scopes reported to clients. Grammar captures are matched against these
+/// by longest dotted prefix, so `function.method.call` reports as
+/// `function.method`.
+pub const HIGHLIGHT_SCOPES: &[&str] = &[
+    "attribute",
+    "boolean",
+    "comment",
+    "constant",
+    "constant.builtin",
+    "constructor",
+    "escape",
+    "function",
+    "function.builtin",
+    "function.macro",
+    "function.method",
+    "keyword",
+    "label",
+    "module",
+    "number",
+    "operator",
+    "property",
+    "punctuation",
+    "punctuation.bracket",
+    "punctuation.delimiter",
+    "punctuation.special",
+    "string",
+    "string.special",
+    "tag",
+    "type",
+    "type.builtin",
+    "variable",
+    "variable.builtin",
+    "variable.parameter",
+];
+
+pub struct Grammar {
+    pub id: &'static str,
+    pub extensions: &'static [&'static str],
+    language: fn() -> Language,
+    highlights: &'static str,
+    locals: &'static str,
+    /// Tags query marking definitions; `None` for data formats without symbols.
+    tags: Option<&'static str>,
+}
+
+impl Grammar {
+    /// Whether the grammar can list the definitions in a file.
+    pub fn has_symbols(&self) -> bool {
+        self.tags.is_some()
+    }
+}
+
+pub static GRAMMARS: &[Grammar] = &[
+    Grammar {
+        id: "rust",
+        extensions: &["rs"],
+        language: || tree_sitter_rust::LANGUAGE.into(),
+        highlights: tree_sitter_rust::HIGHLIGHTS_QUERY,
+        locals: "",
+        tags: Some(tree_sitter_rust::TAGS_QUERY),
+    },
+    Grammar {
+        id: "python",
+        extensions: &["py", "pyi"],
+        language: || tree_sitter_python::LANGUAGE.into(),
+        highlights: tree_sitter_python::HIGHLIGHTS_QUERY,
+        locals: "",
+        tags: Some(tree_sitter_python::TAGS_QUERY),
+    },
+    Grammar {
+        id: "javascript",
+        extensions: &["js", "jsx", "mjs", "cjs"],
+        language: || tree_sitter_javascript::LANGUAGE.into(),
+        highlights: tree_sitter_javascript::HIGHLIGHT_QUERY,
+        locals: tree_sitter_javascript::LOCALS_QUERY,
+        tags: Some(tree_sitter_javascript::TAGS_QUERY),
+    },
+    Grammar {
+        id: "json",
+        extensions: &["json"],
+        language: || tree_sitter_json::LANGUAGE.into(),
+        highlights: tree_sitter_json::HIGHLIGHTS_QUERY,
+        locals: "",
+        tags: None,
+    },
+    Grammar {
+        id: "go",
+        extensions: &["go"],
+        language: || tree_sitter_go::LANGUAGE.into(),
+        highlights: tree_sitter_go::HIGHLIGHTS_QUERY,
+        locals: "",
+        tags: Some(tree_sitter_go::TAGS_QUERY),
+    },
+];
+
+/// Picks a grammar by explicit language id, falling back to the extension.
+pub fn grammar_for(path: &Path, language: Option<&str>) -> Option<&'static Grammar> {
+    match language {
+        Some(language) => GRAMMARS.iter().find(|grammar| grammar.id == language),
+        None => {
+            let extension = path.extension()?.to_str()?;
+            GRAMMARS
+                .iter()
+                .find(|grammar| grammar.extensions.contains(&extension))
+        }
+    }
+}
+
+pub struct HighlightToken {
+    pub range: Range,
+    pub scope: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Symbol {
+    pub name: String,
+    /// Tag kind from the grammar, e.g. `function`, `class`, `method`.
+    pub kind: String,
+    /// Span of the whole definition.
+    pub range: Range,
+    /// Span of the symbol's name.
+    pub selection_range: Range,
+    pub children: Vec<Symbol>,
+}
+
+/// Compiled tree-sitter queries, built the first time a language is used.
+/// Query compilation is the expensive part, so configurations are shared
+/// across requests and connections.
+#[derive(Default)]
+pub struct SyntaxRegistry {
+    highlights: Mutex<HashMap<&'static str, Arc<HighlightConfiguration>>>,
+    tags: Mutex<HashMap<&'static str, Arc<Query>>>,
+}
+
+impl SyntaxRegistry {
+    fn highlight_config(&self, grammar: &Grammar) -> Result<Arc<HighlightConfiguration>, String> {
+        let mut configs = self.highlights.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(config) = configs.get(grammar.id) {
+            return Ok(Arc::clone(config));
+        }
+
+        debug!(language = grammar.id, "Compiling highlight queries");
+        let mut config = HighlightConfiguration::new(
+            (grammar.language)(),
+            grammar.id,
+            grammar.highlights,
+            "",
+            grammar.locals,
+        )
+        .map_err(|e| format!("Invalid highlight query for {}: {e}", grammar.id))?;
+        config.configure(HIGHLIGHT_SCOPES);
+        let config = Arc::new(config);
+        configs.insert(grammar.id, Arc::clone(&config));
+        info!(language = grammar.id, "Grammar loaded");
+        Ok(config)
+    }
+
+    /// Returns highlighted spans of `source`. Spans never cross a line
+    /// break, so clients can render them line by line.
+    /// Highlighting stops with an error once `cancellation_flag` is non-zero.
+    pub fn highlight(
+        &self,
+        grammar: &Grammar,
+        source: &str,
+        cancellation_flag: &AtomicUsize,
+    ) -> Result<Vec<HighlightToken>, String> {
+        let config = self.highlight_config(grammar)?;
+        let mut highlighter = Highlighter::new();
+        let events = highlighter
+            .highlight(
+                &config,
+                source.as_bytes(),
+                None,
+                Some(cancellation_flag),
+                |_| None,
+            )
+            .map_err(|e| format!("Highlighting failed: {e}"))?;
+
+        let index = LineIndex::new(source);
+        let mut scopes = Vec::new();
+        let mut tokens = Vec::new();
+        for event in events {
+            match event.map_err(|e| format!("Highlighting failed: {e}"))? {
+                HighlightEvent::HighlightStart(highlight) => scopes.push(highlight.0),
+                HighlightEvent::HighlightEnd => {
+                    scopes.pop();
+                }
+                HighlightEvent::Source { start, end } => {
+                    let Some(&scope) = scopes.last() else {
+                        continue;
+                    };
+                    let mut line_start = start;
+                    for line in source[start..end].split_inclusive('\n') {
+                        let line_end = line_start + line.trim_end_matches(['\r', '\n']).len();
+                        if line_end > line_start {
+                            tokens.push(HighlightToken {
+                                range: index.range(line_start, line_end),
+                                scope: HIGHLIGHT_SCOPES[scope],
+                            });
+                        }
+                        line_start += line.len();
+                    }
+                }
+            }
+        }
+        Ok(tokens)
+    }
+
+    /// Renders `source` as escaped HTML lines whose highlighted spans carry
+    /// an `hl-<scope>` class, named after the first segment of the scope.
+    pub fn highlight_html(&self, grammar: &Grammar, source: &str) -> Result<Vec<String>, String> {
+        let config = self.highlight_config(grammar)?;
+        let mut highlighter = Highlighter::new();
+        let events = highlighter
+            .highlight(&config, source.as_bytes(), None, None, |_| None)
+            .map_err(|e| format!("Highlighting failed: {e}"))?;
+
+        let mut renderer = HtmlRenderer::new();
+        renderer
+            .render(events, source.as_bytes(), &|highlight, html| {
+                let scope = HIGHLIGHT_SCOPES[highlight.0];
+                let class = scope.split('.').next().unwrap_or(scope);
+                html.extend_from_slice(format!("class=\"hl-{class}\"").as_bytes());
+            })
+            .map_err(|e| format!("Highlighting failed: {e}"))?;
+        Ok(renderer.lines().map(str::to_string).collect())
+    }
+
+    fn tags_query(&self, grammar: &Grammar) -> Result<Arc<Query>, String> {
+        let source = grammar
+            .tags
+            .ok_or_else(|| format!("Symbols are not supported for {}", grammar.id))?;
+        let mut queries = self.tags.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(query) = queries.get(grammar.id) {
+            return Ok(Arc::clone(query));
+        }
+
+        debug!(language = grammar.id, "Compiling tags query");
+        let query = Query::new(&(grammar.language)(), source)
+            .map_err(|e| format!("Invalid tags query for {}: {e}", grammar.id))?;
+        let query = Arc::new(query);
+        queries.insert(grammar.id, Arc::clone(&query));
+        Ok(query)
+    }
+
+    /// Returns the definitions in `source` as a tree, nesting each symbol
+    /// under the innermost definition that contains it.
+    pub fn symbols(&self, grammar: &Grammar, source: &str) -> Result<Vec<Symbol>, String> {
+        let query = self.tags_query(grammar)?;
+        let mut parser = Parser::new();
+        parser
+            .set_language(&(grammar.language)())
+            .map_err(|e| format!("Failed to load grammar for {}: {e}", grammar.id))?;
+        let tree = parser
+            .parse(source, None)
+            .ok_or_else(|| "Parsing was cancelled".to_string())?;
+
+        let names = query.capture_names();
+        let index = LineIndex::new(source);
+        let mut definitions: Vec<(usize, usize, Symbol)> = Vec::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&query, tree.root_node(), source.as_bytes());
+        while let Some(found) = matches.next() {
+            let mut name = None;
+            let mut definition = None;
+            for capture in found.captures() {
+                let capture_name = names[capture.index as usize];
+                if capture_name == "name" {
+                    name = Some(capture.node);
+                } else if let Some(kind) = capture_name.strip_prefix("definition.").or_else(|| {
+                    (capture_name == "reference.implementation").then_some("implementation")
+                }) {
+                    definition = Some((kind, capture.node));
+                }
+            }
+            let (Some(name), Some((kind, node))) = (name, definition) else {
+                continue;
+            };
+            // Several patterns can tag the same node (a method is also a
+            // function); the earlier, more specific pattern wins.
+            if definitions
+                .iter()
+                .any(|(start, end, _)| (*start, *end) == (node.start_byte(), node.end_byte()))
+            {
+                continue;
+            }
+            definitions.push((
+                node.start_byte(),
+                node.end_byte(),
+                Symbol {
+                    name: source[name.byte_range()].to_string(),
+                    kind: kind.to_string(),
+                    range: index.range(node.start_byte(), node.end_byte()),
+                    selection_range: index.range(name.start_byte(), name.end_byte()),
+                    children: Vec::new(),
+                },
+            ));
+        }
+
+        // Outer definitions sort before the ones they contain.
+        definitions.sort_by_key(|(start, end, _)| (*start, std::cmp::Reverse(*end)));
+        let mut roots = Vec::new();
+        let mut stack: Vec<(usize, Symbol)> = Vec::new();
+        for (_, end, symbol) in definitions {
+            while stack
+                .last()
+                .is_some_and(|(parent_end, _)| *parent_end < end)
+            {
+                pop_symbol(&mut stack, &mut roots);
+            }
+            stack.push((end, symbol));
+        }
+        while !stack.is_empty() {
+            pop_symbol(&mut stack, &mut roots);
+        }
+        Ok(roots)
+    }
+}
+
+fn pop_symbol(stack: &mut Vec<(usize, Symbol)>, roots: &mut Vec<Symbol>) {
+    let (_, symbol) = stack.pop().expect("stack is not empty");
+    match stack.last_mut() {
+        Some((_, parent)) => parent.children.push(symbol),
+        None => roots.push(symbol),
+    }
+}
@@ -0,0 +1,220 @@
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Command,
+    sync::oneshot,
+};
+use tracing::{Instrument, debug, info, info_span, warn};
+
+use crate::{
+    activity::ActivityFeed,
+    config::TaskDefinition,
+    flow::StreamCredit,
+    rpc::context::{ConnectionContext, Notifier},
+};
+
+static TASK_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+struct RunningTask {
+    connection_id: u64,
+    name: String,
+    cancel: Option<oneshot::Sender<()>>,
+}
+
+/// Workspace commands started through `task/run`, keyed by task id.
+#[derive(Default)]
+pub struct TaskRegistry {
+    running: Arc<Mutex<HashMap<u64, RunningTask>>>,
+}
+
+impl TaskRegistry {
+    pub fn run(
+        &self,
+        connection: &ConnectionContext,
+        name: &str,
+        definition: &TaskDefinition,
+        root: &std::path::Path,
+        max_concurrent: usize,
+        activity: &ActivityFeed,
+    ) -> Result<u64, String> {
+        let connection_id = connection.id;
+        let notifier = connection.notifier.clone();
+        let mut running = self.lock();
+        if running.len() >= max_concurrent {
+            return Err(format!(
+                "Too many concurrent tasks (limit {max_concurrent})"
+            ));
+        }
+
+        let cwd = match &definition.cwd {
+            Some(cwd) => root.join(cwd),
+            None => root.to_path_buf(),
+        };
+        let mut child = Command::new(&definition.command)
+            .args(&definition.args)
+            .envs(&definition.env)
+            .current_dir(cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start task {name}: {e}"))?;
+
+        let task_id = TASK_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        running.insert(
+            task_id,
+            RunningTask {
+                connection_id,
+                name: name.to_string(),
+                cancel: Some(cancel_tx),
+            },
+        );
+        drop(running);
+
+        let credit = connection.flow.open("task", task_id);
+
+        let stdout = child.stdout.take().map(|stdout| {
+            tokio::spawn(stream_output(
+                task_id,
+                "stdout",
+                stdout,
+                notifier.clone(),
+                credit.clone(),
+            ))
+        });
+        let stderr = child.stderr.take().map(|stderr| {
+            tokio::spawn(stream_output(
+                task_id,
+                "stderr",
+                stderr,
+                notifier.clone(),
+                credit,
+            ))
+        });
+
+        let registry = Arc::clone(&self.running);
+        let feed = activity.clone();
+        let task_name = name.to_string();
+        let span = info_span!("task", task_id, name = %name);
+        tokio::spawn(
+            async move {
+                let (status, cancelled) = tokio::select! {
+                    status = child.wait() => (status, false),
+                    _ = cancel_rx => {
+                        if let Err(e) = child.kill().await {
+                            warn!(error = %e, "Failed to kill task");
+                        }
+                        (child.wait().await, true)
+                    }
+                };
+
+                // Drain remaining output before reporting completion so
+                // clients see every line ahead of the exit notification.
+                for reader in [stdout, stderr].into_iter().flatten() {
+                    let _ = reader.await;
+                }
+
+                registry
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(&task_id);
+
+                let exit_code = status.ok().and_then(|status| status.code());
+                info!(exit_code = ?exit_code, cancelled, "Task finished");
+                feed.record(
+                    "task.exited",
+                    Some(connection_id),
+                    json!({
+                        "taskId": task_id,
+                        "name": task_name,
+                        "exitCode": exit_code,
+                        "cancelled": cancelled,
+                    }),
+                );
+                notifier.notify(
+                    "task/exit",
+                    json!({ "taskId": task_id, "exitCode": exit_code, "cancelled": cancelled }),
+                );
+            }
+            .instrument(span),
+        );
+
+        info!(connection_id, task_id, name = %name, "Task started");
+        activity.record(
+            "task.started",
+            Some(connection_id),
+            json!({ "taskId": task_id, "name": name }),
+        );
+        Ok(task_id)
+    }
+
+    pub fn cancel(&self, connection_id: u64, task_id: u64) -> Result<(), String> {
+        let mut running = self.lock();
+        let task = running
+            .get_mut(&task_id)
+            .filter(|task| task.connection_id == connection_id)
+            .ok_or_else(|| format!("Task {task_id} not found"))?;
+        debug!(task_id, name = %task.name, "Cancelling task");
+        if let Some(cancel) = task.cancel.take() {
+            let _ = cancel.send(());
+        }
+        Ok(())
+    }
+
+    /// Cancels every task started by a connection; called when it closes.
+    pub fn close_connection(&self, connection_id: u64) {
+        for (task_id, task) in self.lock().iter_mut() {
+            if task.connection_id == connection_id
+                && let Some(cancel) = task.cancel.take()
+            {
+                debug!(
+                    connection_id,
+                    task_id, "Cancelling task of closed connection"
+                );
+                let _ = cancel.send(());
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, RunningTask>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Forwards a child stream line by line as `task/output` notifications.
+async fn stream_output(
+    task_id: u64,
+    stream: &'static str,
+    reader: impl AsyncRead + Unpin,
+    notifier: Notifier,
+    credit: Option<StreamCredit>,
+) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                if let Some(credit) = &credit {
+                    credit.consume(line.len()).await;
+                }
+                let data = String::from_utf8_lossy(&line);
+                notifier.notify(
+                    "task/output",
+                    json!({ "taskId": task_id, "stream": stream, "data": data }),
+                );
+            }
+        }
+    }
+}
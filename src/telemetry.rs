@@ -0,0 +1,487 @@
+//! OpenTelemetry export over OTLP/HTTP with JSON bodies, for Jaeger, Tempo
+//! or any collector.
+//!
+//! Only builds with the `otel` feature export anything. Each
+//! `process_request` and `http_request` span starts a trace of its own,
+//! with the method handler's spans beneath it; `ws_connection` spans are
+//! traces covering a whole connection. `connection_id` and `method` fields
+//! become the `connection.id` and `rpc.method` attributes. Warnings and
+//! errors logged inside a span are attached to it as events.
+
+use serde_json::{Map, Value, json};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex, MutexGuard, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tracing::{
+    Event, Level, Subscriber,
+    span::{Attributes, Id, Record},
+    warn,
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::{config::TelemetryConfig, logging::JsonVisitor};
+
+/// Whether the server was built to honour the `telemetry` config.
+pub const ENABLED: bool = cfg!(feature = "otel");
+
+/// Spans that begin a new trace even inside another span.
+const TRACE_ROOTS: &[&str] = &["process_request", "http_request"];
+
+/// Finished spans held for the next export; more are dropped.
+const MAX_QUEUED_SPANS: usize = 4096;
+
+/// Upper bounds, in milliseconds, of the request duration buckets.
+const DURATION_BOUNDS_MS: &[f64] = &[1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0];
+
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static EXPORTER: OnceLock<Arc<Exporter>> = OnceLock::new();
+
+/// The layer to add to the subscriber, or `None` if export is off. Starts
+/// the export task, so it must be called inside the runtime.
+pub fn layer(config: &TelemetryConfig) -> Result<Option<OtlpLayer>, String> {
+    let Some(url) = &config.endpoint else {
+        return Ok(None);
+    };
+    if !ENABLED {
+        return Err(
+            "ignoring telemetry config; the server was built without the otel feature".to_string(),
+        );
+    }
+    if !(0.0..=1.0).contains(&config.sample_ratio) {
+        return Err("telemetry sampleRatio must be between 0 and 1".to_string());
+    }
+    let exporter = Arc::new(Exporter {
+        endpoint: Endpoint::parse(url)?,
+        resource: json!({
+            "attributes": [attribute("service.name", &Value::from(config.service_name.clone()))],
+        }),
+        started_at: unix_nanos(),
+        spans: Mutex::default(),
+        dropped: AtomicU64::new(0),
+        metrics: config.metrics.then(Mutex::default),
+        failing: AtomicBool::new(false),
+    });
+    let _ = EXPORTER.set(Arc::clone(&exporter));
+    let interval = Duration::from_secs(config.export_interval_secs.max(1));
+    let background = Arc::clone(&exporter);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await; // The first tick completes immediately
+        loop {
+            ticker.tick().await;
+            background.export().await;
+        }
+    });
+    Ok(Some(OtlpLayer {
+        exporter,
+        sample_ratio: config.sample_ratio,
+    }))
+}
+
+/// Sends whatever has not been exported yet, for use at shutdown.
+pub async fn flush() {
+    if let Some(exporter) = EXPORTER.get() {
+        exporter.export().await;
+    }
+}
+
+/// Where the collector listens: `authority` to connect to, `base` to put
+/// before the `/v1/...` paths.
+struct Endpoint {
+    authority: String,
+    base: String,
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> Result<Self, String> {
+        let Some(rest) = url.strip_prefix("http://") else {
+            return Err(format!(
+                "telemetry endpoint must be an http:// URL, got {url}"
+            ));
+        };
+        let (authority, base) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, ""),
+        };
+        if authority.is_empty() {
+            return Err(format!("telemetry endpoint has no host: {url}"));
+        }
+        let authority = if authority.contains(':') {
+            authority.to_string()
+        } else {
+            format!("{authority}:80")
+        };
+        Ok(Self {
+            authority,
+            base: base.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+/// Request counts and durations of one method since startup.
+#[derive(Default)]
+struct MethodMetrics {
+    count: u64,
+    sum_ms: f64,
+    /// One more than the bounds, for durations above the last.
+    buckets: Vec<u64>,
+}
+
+struct Exporter {
+    endpoint: Endpoint,
+    resource: Value,
+    started_at: u64,
+    spans: Mutex<Vec<Value>>,
+    dropped: AtomicU64,
+    /// `None` when metrics are not exported.
+    metrics: Option<Mutex<BTreeMap<String, MethodMetrics>>>,
+    /// Set after a failed export, so a collector that is down is reported
+    /// once rather than every interval.
+    failing: AtomicBool,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Exporter {
+    fn queue(&self, span: Value) {
+        let mut spans = lock(&self.spans);
+        if spans.len() < MAX_QUEUED_SPANS {
+            spans.push(span);
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record_request(&self, method: &str, duration_ms: f64) {
+        let Some(metrics) = &self.metrics else {
+            return;
+        };
+        let mut metrics = lock(metrics);
+        let entry = metrics.entry(method.to_string()).or_default();
+        if entry.buckets.is_empty() {
+            entry.buckets = vec![0; DURATION_BOUNDS_MS.len() + 1];
+        }
+        entry.count += 1;
+        entry.sum_ms += duration_ms;
+        let bucket = DURATION_BOUNDS_MS
+            .iter()
+            .position(|bound| duration_ms <= *bound)
+            .unwrap_or(DURATION_BOUNDS_MS.len());
+        entry.buckets[bucket] += 1;
+    }
+
+    async fn export(&self) {
+        let spans = std::mem::take(&mut *lock(&self.spans));
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(
+                dropped,
+                "Dropped spans the collector could not keep up with"
+            );
+        }
+        let mut result = Ok(());
+        if !spans.is_empty() {
+            let body = json!({
+                "resourceSpans": [{
+                    "resource": self.resource,
+                    "scopeSpans": [{ "scope": { "name": "editor-server" }, "spans": spans }],
+                }],
+            });
+            result = self.post("/v1/traces", body.to_string()).await;
+        }
+        if let Some(metrics) = self.metrics_body()
+            && result.is_ok()
+        {
+            result = self.post("/v1/metrics", metrics.to_string()).await;
+        }
+        match result {
+            Ok(()) => self.failing.store(false, Ordering::Relaxed),
+            Err(e) => {
+                if !self.failing.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        endpoint = %self.endpoint.authority,
+                        error = %e,
+                        "Failed to export telemetry"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Cumulative counts since startup, or `None` before any request.
+    fn metrics_body(&self) -> Option<Value> {
+        let metrics = lock(self.metrics.as_ref()?);
+        if metrics.is_empty() {
+            return None;
+        }
+        let start = self.started_at.to_string();
+        let now = unix_nanos().to_string();
+        let mut counts = Vec::new();
+        let mut durations = Vec::new();
+        for (method, metric) in metrics.iter() {
+            let attributes = [attribute("rpc.method", &Value::from(method.as_str()))];
+            counts.push(json!({
+                "attributes": attributes,
+                "startTimeUnixNano": start,
+                "timeUnixNano": now,
+                "asInt": metric.count.to_string(),
+            }));
+            durations.push(json!({
+                "attributes": attributes,
+                "startTimeUnixNano": start,
+                "timeUnixNano": now,
+                "count": metric.count.to_string(),
+                "sum": metric.sum_ms,
+                "bucketCounts": metric.buckets.iter().map(u64::to_string).collect::<Vec<_>>(),
+                "explicitBounds": DURATION_BOUNDS_MS,
+            }));
+        }
+        // Aggregation temporality 2 is cumulative.
+        Some(json!({
+            "resourceMetrics": [{
+                "resource": self.resource,
+                "scopeMetrics": [{
+                    "scope": { "name": "editor-server" },
+                    "metrics": [
+                        {
+                            "name": "rpc.server.requests",
+                            "unit": "{request}",
+                            "sum": {
+                                "aggregationTemporality": 2,
+                                "isMonotonic": true,
+                                "dataPoints": counts,
+                            },
+                        },
+                        {
+                            "name": "rpc.server.duration",
+                            "unit": "ms",
+                            "histogram": { "aggregationTemporality": 2, "dataPoints": durations },
+                        },
+                    ],
+                }],
+            }],
+        }))
+    }
+
+    async fn post(&self, path: &str, body: String) -> Result<(), String> {
+        let request = async {
+            let mut stream = TcpStream::connect(&self.endpoint.authority).await?;
+            let head = format!(
+                "POST {}{path} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                self.endpoint.base,
+                self.endpoint.authority,
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await?;
+            stream.write_all(body.as_bytes()).await?;
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).await?;
+            Ok::<_, std::io::Error>(response)
+        };
+        let response = tokio::time::timeout(EXPORT_TIMEOUT, request)
+            .await
+            .map_err(|_| "timed out".to_string())?
+            .map_err(|e| e.to_string())?;
+        let status = String::from_utf8_lossy(&response)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        match status.split(' ').nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(format!("collector answered {status:?}")),
+        }
+    }
+}
+
+/// What the layer keeps on each span until it closes.
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    sampled: bool,
+    started_at: u64,
+    attributes: Map<String, Value>,
+    events: Vec<Value>,
+    failed: bool,
+}
+
+pub struct OtlpLayer {
+    exporter: Arc<Exporter>,
+    sample_ratio: f64,
+}
+
+impl OtlpLayer {
+    fn sample(&self) -> bool {
+        self.sample_ratio >= 1.0
+            || (u64::from_le_bytes(random()) as f64) < self.sample_ratio * u64::MAX as f64
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = if TRACE_ROOTS.contains(&span.name()) {
+            None
+        } else {
+            span.parent().and_then(|parent| {
+                parent
+                    .extensions()
+                    .get::<SpanData>()
+                    .map(|data| (data.trace_id, data.span_id, data.sampled))
+            })
+        };
+        let (trace_id, parent_span_id, sampled) = match parent {
+            Some((trace_id, span_id, sampled)) => (trace_id, Some(span_id), sampled),
+            None => (random(), None, self.sample()),
+        };
+        let mut fields = JsonVisitor::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(SpanData {
+            trace_id,
+            span_id: random(),
+            parent_span_id,
+            sampled,
+            started_at: unix_nanos(),
+            attributes: fields.0,
+            events: Vec::new(),
+            failed: false,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+            let mut fields = JsonVisitor(std::mem::take(&mut data.attributes));
+            values.record(&mut fields);
+            data.attributes = fields.0;
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let level = *event.metadata().level();
+        if level > Level::WARN {
+            return;
+        }
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut fields = JsonVisitor::default();
+        event.record(&mut fields);
+        let name = match fields.0.remove("message") {
+            Some(Value::String(message)) => message,
+            _ => level.to_string(),
+        };
+        if let Some(data) = span.extensions_mut().get_mut::<SpanData>()
+            && data.sampled
+        {
+            data.failed |= level == Level::ERROR;
+            data.events.push(json!({
+                "timeUnixNano": unix_nanos().to_string(),
+                "name": name,
+                "attributes": attributes(&fields.0),
+            }));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+        let ended_at = unix_nanos();
+        if span.name() == "rpc_request"
+            && let Some(Value::String(method)) = data.attributes.get("method")
+        {
+            let duration_ms = ended_at.saturating_sub(data.started_at) as f64 / 1e6;
+            self.exporter.record_request(method, duration_ms);
+        }
+        if !data.sampled {
+            return;
+        }
+        // Kind 2 is SERVER, 1 INTERNAL; status code 2 is ERROR.
+        let mut exported = json!({
+            "traceId": hex(&data.trace_id),
+            "spanId": hex(&data.span_id),
+            "name": span.name(),
+            "kind": if data.parent_span_id.is_none() { 2 } else { 1 },
+            "startTimeUnixNano": data.started_at.to_string(),
+            "endTimeUnixNano": ended_at.to_string(),
+            "attributes": attributes(&data.attributes),
+            "events": data.events,
+        });
+        if let Some(parent) = data.parent_span_id {
+            exported["parentSpanId"] = hex(&parent).into();
+        }
+        if data.failed {
+            exported["status"] = json!({ "code": 2 });
+        }
+        self.exporter.queue(exported);
+    }
+}
+
+/// Span fields as OTLP attributes, under the semantic convention names
+/// where there is one.
+fn attributes(fields: &Map<String, Value>) -> Vec<Value> {
+    fields
+        .iter()
+        .map(|(key, value)| {
+            let key = match key.as_str() {
+                "connection_id" => "connection.id",
+                "method" => "rpc.method",
+                key => key,
+            };
+            attribute(key, value)
+        })
+        .collect()
+}
+
+/// 64-bit integers are strings in OTLP JSON.
+fn attribute(key: &str, value: &Value) -> Value {
+    let value = match value {
+        Value::Bool(value) => json!({ "boolValue": value }),
+        Value::Number(number) if number.is_f64() => json!({ "doubleValue": number }),
+        Value::Number(number) => json!({ "intValue": number.to_string() }),
+        Value::String(text) => json!({ "stringValue": text }),
+        other => json!({ "stringValue": other.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    let _ = getrandom::fill(&mut bytes);
+    bytes
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
@@ -0,0 +1,215 @@
+//! Confining a connection to one directory, so a single server can host
+//! several users. A key whose identity names a `workspace` authenticates
+//! connections that only reach paths under it, with the key's own
+//! `permissions` on top of the server's. Their relative paths are taken
+//! from that directory, and methods that act on the server as a whole
+//! rather than on the paths they are given are refused.
+
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+use crate::{
+    config::{Access, PermissionRule},
+    permissions::Permissions,
+    rpc::error::HandlerError,
+    sandbox::normalize,
+    uri::{self, WorkspaceUris},
+};
+
+/// Methods that run in the server's root, report on every connection or
+/// share state between them, whatever paths they are given.
+const SHARED_METHODS: &[&str] = &[
+    "activity/list",
+    "addToDictionary",
+    "audit/query",
+    "config/reload",
+    "dap/start",
+    "debug/recentRequests",
+    "definition",
+    "diskUsage",
+    "git/branches",
+    "git/checkout",
+    "git/createBranch",
+    "git/deleteBranch",
+    "jobs/history",
+    "jobs/run",
+    "lsp/notify",
+    "lsp/request",
+    "lsp/respond",
+    "lsp/stop",
+    "plainText/diff",
+    "presence/list",
+    "problems/list",
+    "saveAll",
+    "scan/run",
+    "share/list",
+    "snippet/run",
+    "task/run",
+    "terminal/create",
+    "trash/empty",
+    "trash/list",
+    "trash/restore",
+    "workspace/export",
+    "workspace/list",
+];
+
+/// Optional params that default to the server's root, given the
+/// connection's root instead.
+const DEFAULTED_PATHS: &[(&str, &str)] = &[
+    ("applyPatch", "cwd"),
+    ("findFiles", "path"),
+    ("recentFiles", "path"),
+    ("replaceInFiles", "path"),
+    ("searchInFiles", "path"),
+    ("workspaceSymbols", "path"),
+];
+
+/// The directory a connection is confined to, and the rules of its key.
+pub struct Tenant {
+    /// Canonical.
+    root: PathBuf,
+    permissions: Permissions,
+}
+
+impl Tenant {
+    pub fn new(root: &Path, rules: &[PermissionRule]) -> Result<Self, String> {
+        let root = root
+            .canonicalize()
+            .map_err(|e| format!("cannot confine keys to {}: {e}", root.display()))?;
+        if !root.is_dir() {
+            return Err(format!(
+                "cannot confine keys to {}: not a directory",
+                root.display()
+            ));
+        }
+        let permissions = Permissions::new([(root.as_path(), rules)])?;
+        Ok(Self { root, permissions })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Takes the relative paths in request params from the root. Runs
+    /// before addressed paths are resolved, which leaves the rest relative
+    /// to the server's root.
+    pub fn rebase(&self, params: &mut Value, uris: &WorkspaceUris) {
+        uri::visit_paths(params, &mut |text| {
+            let path = Path::new(text);
+            (path.is_relative() && !uris.is_addressed(text))
+                .then(|| self.root.join(path).to_string_lossy().into_owned())
+        });
+    }
+
+    /// Fits a request to the connection's root, filling in omitted
+    /// [`DEFAULTED_PATHS`], then checks every path in it against the root
+    /// and the key's rules.
+    pub fn confine(
+        &self,
+        method: &str,
+        params: &mut Value,
+        required: Access,
+    ) -> Result<(), HandlerError> {
+        if SHARED_METHODS.contains(&method) {
+            return Err(HandlerError::Confined(method.to_string()));
+        }
+        if let Some((_, key)) = DEFAULTED_PATHS.iter().find(|(name, _)| *name == method) {
+            if params.is_null() {
+                *params = Value::Object(Map::new());
+            }
+            if let Some(params) = params.as_object_mut() {
+                params
+                    .entry(*key)
+                    .or_insert_with(|| Value::String(self.root.to_string_lossy().into_owned()));
+            }
+        }
+        uri::request_paths(params)
+            .iter()
+            .try_for_each(|path| self.check(path, required))
+    }
+
+    /// Refuses paths outside the root, following symlinks, and paths the
+    /// key's rules grant less than `required`.
+    pub fn check(&self, path: &Path, required: Access) -> Result<(), HandlerError> {
+        let Some(resolved) = self.resolve(path) else {
+            debug!(path = %path.display(), root = %self.root.display(), "Path outside the tenant");
+            return Err(HandlerError::PermissionDenied {
+                path: path.to_path_buf(),
+                required,
+            });
+        };
+        self.permissions
+            .check(&resolved, required)
+            .map_err(|_| HandlerError::PermissionDenied {
+                path: path.to_path_buf(),
+                required,
+            })
+    }
+
+    /// Whether a feed payload (an activity event's details or a presence)
+    /// may be shown to the connection: it must name a path, and every path
+    /// it names must be readable here.
+    pub fn sees(&self, payload: &Value) -> bool {
+        let paths = uri::request_paths(payload);
+        !paths.is_empty()
+            && paths
+                .iter()
+                .all(|path| path.is_absolute() && self.check(path, Access::Read).is_ok())
+    }
+
+    /// Canonical form of `path` if it is under the root. The last
+    /// components may be missing, so the target of a write can be checked.
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let absolute = normalize(&self.root.join(path));
+        let mut existing = absolute.as_path();
+        let mut missing = Vec::new();
+        let mut canonical = loop {
+            match existing.canonicalize() {
+                Ok(canonical) => break canonical,
+                // A dangling symlink could still lead anywhere once written.
+                Err(_) if existing.symlink_metadata().is_ok() => return None,
+                Err(_) => {
+                    missing.push(existing.file_name()?);
+                    existing = existing.parent()?;
+                }
+            }
+        };
+        canonical.extend(missing.iter().rev());
+        canonical.starts_with(&self.root).then_some(canonical)
+    }
+}
+
+impl fmt::Debug for Tenant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tenant")
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sees_only_feed_events_in_its_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        std::fs::create_dir(&a).unwrap();
+        std::fs::create_dir(&b).unwrap();
+        let tenant = Tenant::new(&a, &[]).unwrap();
+        let a = a.canonicalize().unwrap();
+        let b = b.canonicalize().unwrap();
+
+        assert!(tenant.sees(&json!({ "path": a.join("notes.md") })));
+        assert!(!tenant.sees(&json!({ "path": b.join("notes.md") })));
+        assert!(!tenant.sees(&json!({ "path": a.join("../b/notes.md") })));
+        assert!(!tenant.sees(&json!({ "paths": [a.join("x"), b.join("y")] })));
+        assert!(!tenant.sees(&json!({ "reason": "disconnected" })));
+    }
+}
@@ -0,0 +1,254 @@
+use portable_pty::{ChildKiller, CommandBuilder, MasterPty, PtySize, native_pty_system};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    flow::StreamCredit,
+    rpc::context::{ConnectionContext, Notifier},
+};
+
+static TERMINAL_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+pub struct TerminalOptions {
+    pub shell: Option<String>,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    pub env: HashMap<String, String>,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+struct TerminalSession {
+    master: Box<dyn MasterPty + Send>,
+    /// Locked apart from the registry, since a write blocks while the
+    /// shell is not reading.
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    killer: Box<dyn ChildKiller + Send + Sync>,
+}
+
+type Sessions = HashMap<u64, HashMap<u64, TerminalSession>>;
+
+/// PTY-backed shell sessions, keyed by connection id and then terminal id.
+#[derive(Default)]
+pub struct TerminalRegistry {
+    sessions: Arc<Mutex<Sessions>>,
+}
+
+impl TerminalRegistry {
+    pub fn create(
+        &self,
+        connection: &ConnectionContext,
+        options: TerminalOptions,
+    ) -> Result<u64, String> {
+        let connection_id = connection.id;
+        let notifier = connection.notifier.clone();
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: options.rows,
+                cols: options.cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| format!("Failed to open pty: {e}"))?;
+
+        let mut command = match &options.shell {
+            Some(shell) => {
+                let mut command = CommandBuilder::new(shell);
+                command.args(&options.args);
+                command
+            }
+            None => CommandBuilder::new_default_prog(),
+        };
+        command.cwd(&options.cwd);
+        for (key, value) in &options.env {
+            command.env(key, value);
+        }
+
+        let mut child = pair
+            .slave
+            .spawn_command(command)
+            .map_err(|e| format!("Failed to spawn shell: {e}"))?;
+        // Only the child should hold the slave side, otherwise the reader
+        // never observes EOF when the shell exits.
+        drop(pair.slave);
+
+        let reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| format!("Failed to open pty reader: {e}"))?;
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|e| format!("Failed to open pty writer: {e}"))?;
+
+        let terminal_id = TERMINAL_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let session = TerminalSession {
+            master: pair.master,
+            writer: Arc::new(Mutex::new(writer)),
+            killer: child.clone_killer(),
+        };
+        self.lock()
+            .entry(connection_id)
+            .or_default()
+            .insert(terminal_id, session);
+
+        let credit = connection.flow.open("terminal", terminal_id);
+        let sessions = Arc::clone(&self.sessions);
+        thread::spawn(move || {
+            pump_output(terminal_id, reader, &notifier, credit.as_ref());
+            let exit_code = child.wait().ok().map(|status| status.exit_code());
+            if let Some(terminals) = sessions
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get_mut(&connection_id)
+            {
+                terminals.remove(&terminal_id);
+            }
+            info!(terminal_id, exit_code = ?exit_code, "Terminal exited");
+            notifier.notify(
+                "terminal/exit",
+                json!({ "terminalId": terminal_id, "exitCode": exit_code }),
+            );
+        });
+
+        info!(connection_id, terminal_id, "Terminal created");
+        Ok(terminal_id)
+    }
+
+    pub fn input(&self, connection_id: u64, terminal_id: u64, data: &str) -> Result<(), String> {
+        let writer = self.with_session(connection_id, terminal_id, |session| {
+            Ok(Arc::clone(&session.writer))
+        })?;
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+        writer
+            .write_all(data.as_bytes())
+            .and_then(|_| writer.flush())
+            .map_err(|e| format!("Failed to write to terminal: {e}"))
+    }
+
+    pub fn resize(
+        &self,
+        connection_id: u64,
+        terminal_id: u64,
+        cols: u16,
+        rows: u16,
+    ) -> Result<(), String> {
+        self.with_session(connection_id, terminal_id, |session| {
+            session
+                .master
+                .resize(PtySize {
+                    rows,
+                    cols,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                })
+                .map_err(|e| format!("Failed to resize terminal: {e}"))
+        })
+    }
+
+    pub fn kill(&self, connection_id: u64, terminal_id: u64) -> Result<(), String> {
+        let mut session = self
+            .lock()
+            .get_mut(&connection_id)
+            .and_then(|terminals| terminals.remove(&terminal_id))
+            .ok_or_else(|| format!("Terminal {terminal_id} not found"))?;
+        session
+            .killer
+            .kill()
+            .map_err(|e| format!("Failed to kill terminal: {e}"))
+    }
+
+    /// Number of terminals a connection has open.
+    pub fn count(&self, connection_id: u64) -> usize {
+        self.lock()
+            .get(&connection_id)
+            .map_or(0, |terminals| terminals.len())
+    }
+
+    /// Kills every terminal owned by a connection; called when it closes.
+    pub fn close_connection(&self, connection_id: u64) {
+        let Some(terminals) = self.lock().remove(&connection_id) else {
+            return;
+        };
+        for (terminal_id, mut session) in terminals {
+            debug!(
+                connection_id,
+                terminal_id, "Killing terminal of closed connection"
+            );
+            if let Err(e) = session.killer.kill() {
+                warn!(terminal_id, error = %e, "Failed to kill terminal");
+            }
+        }
+    }
+
+    fn with_session<T>(
+        &self,
+        connection_id: u64,
+        terminal_id: u64,
+        f: impl FnOnce(&mut TerminalSession) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut sessions = self.lock();
+        let session = sessions
+            .get_mut(&connection_id)
+            .and_then(|terminals| terminals.get_mut(&terminal_id))
+            .ok_or_else(|| format!("Terminal {terminal_id} not found"))?;
+        f(session)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Sessions> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Forwards pty output as `terminal/output` notifications until EOF,
+/// holding back incomplete UTF-8 sequences until the rest arrives. With
+/// flow control, the pty is not read further until the client has
+/// acknowledged enough output, which backpressures the shell itself.
+fn pump_output(
+    terminal_id: u64,
+    mut reader: Box<dyn Read + Send>,
+    notifier: &Notifier,
+    credit: Option<&StreamCredit>,
+) {
+    let mut buffer = [0u8; 8192];
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        pending.extend_from_slice(&buffer[..n]);
+
+        let valid_up_to = match std::str::from_utf8(&pending) {
+            Ok(_) => pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => pending.len(),
+        };
+        let data = String::from_utf8_lossy(&pending[..valid_up_to]).into_owned();
+        pending.drain(..valid_up_to);
+
+        if let Some(credit) = credit
+            && !data.is_empty()
+        {
+            credit.consume_blocking(data.len());
+        }
+        if !data.is_empty()
+            && !notifier.notify(
+                "terminal/output",
+                json!({ "terminalId": terminal_id, "data": data }),
+            )
+        {
+            break;
+        }
+    }
+}
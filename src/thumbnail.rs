@@ -0,0 +1,311 @@
+//! Downscaled copies of PNG images for previews. Decoding is done here on
+//! top of `flate2` rather than with an image library: non-interlaced PNGs
+//! of any colour type and bit depth are read, box-filtered down and written
+//! back out as 8-bit RGBA. Other formats are not thumbnailed.
+
+use flate2::{Compression, Crc, read::ZlibDecoder, write::ZlibEncoder};
+use std::io::{Read, Write};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Largest image decoded, in pixels, so a small file cannot inflate into
+/// gigabytes: a 4K screenshot fits, and the decoded image stays within
+/// 32 MiB.
+const MAX_PIXELS: u64 = 8 * 1024 * 1024;
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    fn row_bytes(&self) -> usize {
+        (self.width * self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+
+    /// Bytes per pixel as the filters count them: at least one.
+    fn filter_stride(&self) -> usize {
+        (self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+}
+
+/// An 8-bit RGBA image, rows top to bottom.
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 4]>,
+}
+
+/// `png` scaled down to fit in `max_side` pixels each way, or `None` if it
+/// already does or cannot be decoded.
+pub fn png_thumbnail(png: &[u8], max_side: usize) -> Option<Vec<u8>> {
+    let image = decode(png)?;
+    if image.width <= max_side && image.height <= max_side {
+        return None;
+    }
+    // Keep the aspect ratio; neither side drops below a pixel.
+    let scale = max_side as f64 / image.width.max(image.height) as f64;
+    let width = ((image.width as f64 * scale).round() as usize).max(1);
+    let height = ((image.height as f64 * scale).round() as usize).max(1);
+    Some(encode(&downscale(&image, width, height)))
+}
+
+fn decode(png: &[u8]) -> Option<Image> {
+    let mut rest = png.strip_prefix(SIGNATURE)?;
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut data = Vec::new();
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let kind = &rest[4..8];
+        let body = rest.get(8..8 + length)?;
+        rest = rest.get(12 + length..)?;
+        match kind {
+            b"IHDR" if body.len() >= 13 => {
+                // Interlaced images are left alone.
+                if body[12] != 0 {
+                    return None;
+                }
+                header = Some(Header {
+                    width: u32::from_be_bytes(body[..4].try_into().ok()?) as usize,
+                    height: u32::from_be_bytes(body[4..8].try_into().ok()?) as usize,
+                    bit_depth: body[8],
+                    color_type: body[9],
+                });
+            }
+            b"PLTE" => palette = body,
+            b"tRNS" => transparency = body,
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+    let header = header?;
+    let valid = match header.color_type {
+        0 => matches!(header.bit_depth, 1 | 2 | 4 | 8 | 16),
+        3 => matches!(header.bit_depth, 1 | 2 | 4 | 8) && !palette.is_empty(),
+        2 | 4 | 6 => matches!(header.bit_depth, 8 | 16),
+        _ => false,
+    };
+    if !valid
+        || header.width == 0
+        || header.height == 0
+        || (header.width as u64) * (header.height as u64) > MAX_PIXELS
+    {
+        return None;
+    }
+
+    let row_bytes = header.row_bytes();
+    let expected = (row_bytes + 1) * header.height;
+    // Grown as the data inflates rather than sized from the header, which
+    // costs nothing to forge.
+    let mut raw = Vec::new();
+    ZlibDecoder::new(data.as_slice())
+        .take(expected as u64)
+        .read_to_end(&mut raw)
+        .ok()?;
+    if raw.len() < expected {
+        return None;
+    }
+
+    let stride = header.filter_stride();
+    let mut previous = vec![0u8; row_bytes];
+    let mut pixels = Vec::with_capacity(header.width * header.height);
+    for row in raw.chunks_exact_mut(row_bytes + 1) {
+        let (filter, row) = row.split_first_mut()?;
+        unfilter(*filter, row, &previous, stride)?;
+        pixels.extend((0..header.width).map(|x| pixel(&header, row, x, palette, transparency)));
+        previous.copy_from_slice(row);
+    }
+    Some(Image {
+        width: header.width,
+        height: header.height,
+        pixels,
+    })
+}
+
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], stride: usize) -> Option<()> {
+    for i in 0..row.len() {
+        let left = if i >= stride { row[i - stride] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= stride { previous[i - stride] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return None,
+        };
+        row[i] = row[i].wrapping_add(predicted);
+    }
+    Some(())
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (a, b, c) = (
+        (estimate - left as i16).abs(),
+        (estimate - up as i16).abs(),
+        (estimate - up_left as i16).abs(),
+    );
+    if a <= b && a <= c {
+        left
+    } else if b <= c {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Sample `index` of a row, scaled to 8 bits.
+fn sample(header: &Header, row: &[u8], index: usize) -> u8 {
+    match header.bit_depth {
+        16 => row[index * 2],
+        8 => row[index],
+        depth => {
+            let bit = index * depth as usize;
+            let shift = 8 - depth as usize - bit % 8;
+            let value = (row[bit / 8] >> shift) & ((1 << depth) - 1);
+            // Palette indices keep their value; grey levels spread out.
+            if header.color_type == 3 {
+                value
+            } else {
+                value * (255 / ((1 << depth) - 1))
+            }
+        }
+    }
+}
+
+fn pixel(header: &Header, row: &[u8], x: usize, palette: &[u8], transparency: &[u8]) -> [u8; 4] {
+    let channels = header.channels();
+    let at = |channel: usize| sample(header, row, x * channels + channel);
+    match header.color_type {
+        0 => {
+            let grey = at(0);
+            // A single transparent grey level, as 16 bits.
+            let transparent = transparency.len() >= 2
+                && Some(grey) == scaled_key(header, transparency[0], transparency[1]);
+            [grey, grey, grey, if transparent { 0 } else { 255 }]
+        }
+        2 => {
+            let [r, g, b] = [at(0), at(1), at(2)];
+            let transparent = transparency.len() >= 6
+                && [0, 2, 4].map(|i| scaled_key(header, transparency[i], transparency[i + 1]))
+                    == [Some(r), Some(g), Some(b)];
+            [r, g, b, if transparent { 0 } else { 255 }]
+        }
+        3 => {
+            let index = at(0) as usize;
+            let color = palette.get(index * 3..index * 3 + 3).unwrap_or(&[0, 0, 0]);
+            let alpha = transparency.get(index).copied().unwrap_or(255);
+            [color[0], color[1], color[2], alpha]
+        }
+        4 => {
+            let grey = at(0);
+            [grey, grey, grey, at(1)]
+        }
+        _ => [at(0), at(1), at(2), at(3)],
+    }
+}
+
+/// A `tRNS` key, given as 16 bits, at the 8-bit scale samples are read at.
+fn scaled_key(header: &Header, high: u8, low: u8) -> Option<u8> {
+    let key = u16::from_be_bytes([high, low]);
+    match header.bit_depth {
+        16 => Some(high),
+        8 => u8::try_from(key).ok(),
+        depth => u8::try_from(key)
+            .ok()
+            .map(|key| key * (255 / ((1 << depth) - 1))),
+    }
+}
+
+/// Averages the source pixels behind each target pixel, weighting colour by
+/// alpha so transparent pixels do not darken the edges.
+fn downscale(image: &Image, width: usize, height: usize) -> Image {
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let (top, bottom) = span(y, height, image.height);
+        for x in 0..width {
+            let (left, right) = span(x, width, image.width);
+            let mut sums = [0u64; 4];
+            for source_y in top..bottom {
+                for &[r, g, b, a] in
+                    &image.pixels[source_y * image.width + left..source_y * image.width + right]
+                {
+                    let alpha = a as u64;
+                    sums[0] += r as u64 * alpha;
+                    sums[1] += g as u64 * alpha;
+                    sums[2] += b as u64 * alpha;
+                    sums[3] += alpha;
+                }
+            }
+            let count = ((bottom - top) * (right - left)) as u64;
+            pixels.push(match sums[3] {
+                0 => [0, 0, 0, 0],
+                alpha => [
+                    (sums[0] / alpha) as u8,
+                    (sums[1] / alpha) as u8,
+                    (sums[2] / alpha) as u8,
+                    (alpha / count) as u8,
+                ],
+            });
+        }
+    }
+    Image {
+        width,
+        height,
+        pixels,
+    }
+}
+
+/// The source rows or columns behind target row or column `index`.
+fn span(index: usize, target: usize, source: usize) -> (usize, usize) {
+    let start = index * source / target;
+    let end = ((index + 1) * source / target).max(start + 1);
+    (start, end.min(source))
+}
+
+fn encode(image: &Image) -> Vec<u8> {
+    let mut png = SIGNATURE.to_vec();
+    let mut header = Vec::with_capacity(13);
+    header.extend((image.width as u32).to_be_bytes());
+    header.extend((image.height as u32).to_be_bytes());
+    header.extend([8, 6, 0, 0, 0]);
+    chunk(&mut png, b"IHDR", &header);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in image.pixels.chunks_exact(image.width) {
+        let mut line = Vec::with_capacity(1 + row.len() * 4);
+        line.push(0);
+        line.extend(row.iter().flatten());
+        encoder.write_all(&line).expect("writing to a Vec");
+    }
+    let data = encoder.finish().expect("writing to a Vec");
+    chunk(&mut png, b"IDAT", &data);
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+    png.extend((body.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(body);
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(body);
+    png.extend(crc.sum().to_be_bytes());
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, warn};
+
+use crate::clock;
+
+const METADATA_FILE: &str = "meta.json";
+const ITEM_NAME: &str = "item";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashEntry {
+    pub id: String,
+    /// Absolute path the item was deleted from and is restored to.
+    pub original_path: PathBuf,
+    /// Unix seconds.
+    pub deleted_at: u64,
+    pub directory: bool,
+    /// Total size of the files deleted, in bytes.
+    pub size: u64,
+}
+
+/// Workspace-local recycle bin laid out as `<data dir>/trash/<id>/`, each
+/// holding the deleted `item` and its `meta.json`. Items are moved, not
+/// copied, so trashing is cheap and keeps permissions and timestamps.
+pub struct Trash {
+    dir: PathBuf,
+}
+
+impl Trash {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            dir: data_dir.join("trash"),
+        }
+    }
+
+    fn entry_dir(&self, id: &str) -> Option<PathBuf> {
+        let valid = !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit() || b == b'-');
+        valid.then(|| self.dir.join(id))
+    }
+
+    /// Moves `path` into the trash.
+    pub fn put(&self, path: &Path) -> io::Result<TrashEntry> {
+        let original_path = std::path::absolute(path)?;
+        let metadata = fs::symlink_metadata(&original_path)?;
+        let deleted_at = clock::unix_secs();
+
+        fs::create_dir_all(&self.dir)?;
+        let (id, entry_dir) = (0..)
+            .map(|n| format!("{deleted_at}-{n}"))
+            .find_map(|id| {
+                let entry_dir = self.dir.join(&id);
+                match fs::create_dir(&entry_dir) {
+                    Ok(()) => Some(Ok((id, entry_dir))),
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => None,
+                    Err(e) => Some(Err(e)),
+                }
+            })
+            .expect("unbounded id range")?;
+
+        let entry = TrashEntry {
+            id,
+            size: disk_size(&original_path),
+            directory: metadata.is_dir(),
+            original_path,
+            deleted_at,
+        };
+        let moved = fs::rename(&entry.original_path, entry_dir.join(ITEM_NAME)).and_then(|()| {
+            let metadata = serde_json::to_vec_pretty(&entry).map_err(io::Error::other)?;
+            fs::write(entry_dir.join(METADATA_FILE), metadata)
+        });
+        if let Err(e) = moved {
+            let _ = fs::remove_dir_all(&entry_dir);
+            return Err(e);
+        }
+        info!(id = %entry.id, path = %entry.original_path.display(), "Moved to trash");
+        Ok(entry)
+    }
+
+    /// Entries, most recently deleted first.
+    pub fn list(&self) -> io::Result<Vec<TrashEntry>> {
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut entries = Vec::new();
+        for dir_entry in read_dir {
+            let metadata_path = dir_entry?.path().join(METADATA_FILE);
+            match fs::read(&metadata_path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<TrashEntry>(&bytes).ok())
+            {
+                Some(entry) => entries.push(entry),
+                None => warn!(path = %metadata_path.display(), "Skipping unreadable trash entry"),
+            }
+        }
+        entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(b.id.cmp(&a.id)));
+        Ok(entries)
+    }
+
+    /// The entry `restore` would put back, failing as it would if the
+    /// entry is gone or something now occupies its path.
+    pub fn restorable(&self, id: &str) -> io::Result<TrashEntry> {
+        let entry_dir = self
+            .entry_dir(id)
+            .filter(|dir| dir.is_dir())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No such trash entry"))?;
+        let entry: TrashEntry = serde_json::from_slice(&fs::read(entry_dir.join(METADATA_FILE))?)
+            .map_err(io::Error::other)?;
+        if fs::symlink_metadata(&entry.original_path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", entry.original_path.display()),
+            ));
+        }
+        Ok(entry)
+    }
+
+    /// Moves an item back to where it was deleted from, recreating missing
+    /// parent directories. Fails if something now occupies that path.
+    pub fn restore(&self, id: &str) -> io::Result<TrashEntry> {
+        let entry = self.restorable(id)?;
+        let entry_dir = self.dir.join(id);
+        if let Some(parent) = entry.original_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(entry_dir.join(ITEM_NAME), &entry.original_path)?;
+        fs::remove_dir_all(&entry_dir)?;
+        info!(id = %entry.id, path = %entry.original_path.display(), "Restored from trash");
+        Ok(entry)
+    }
+
+    /// Permanently deletes entries trashed at or before `before` (unix
+    /// seconds), or every entry when `None`. Returns how many were removed.
+    pub fn empty(&self, before: Option<u64>) -> io::Result<usize> {
+        let mut removed = 0;
+        for entry in self.expired(before)? {
+            if let Some(entry_dir) = self.entry_dir(&entry.id) {
+                debug!(id = %entry.id, "Purging trash entry");
+                fs::remove_dir_all(entry_dir)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Entries `empty` would remove given the same `before`.
+    pub fn expired(&self, before: Option<u64>) -> io::Result<Vec<TrashEntry>> {
+        let mut entries = self.list()?;
+        entries.retain(|entry| before.is_none_or(|before| entry.deleted_at <= before));
+        Ok(entries)
+    }
+}
+
+/// Bytes used by the files under `path`, not following symlinks.
+pub fn disk_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| disk_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or_default()
+}
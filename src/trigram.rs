@@ -0,0 +1,111 @@
+//! Trigram index over file contents. A file holding some text holds every
+//! three-byte window of it, so the files containing all of a query's
+//! trigrams are the only ones worth reading. Trigrams are taken with ASCII
+//! letters lowercased, which lets case-insensitive searches use the index
+//! too.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// The distinct trigrams of `bytes`, sorted.
+pub fn trigrams(bytes: &[u8]) -> Vec<u32> {
+    let mut grams: Vec<u32> = bytes.windows(3).map(pack).collect();
+    grams.sort_unstable();
+    grams.dedup();
+    grams
+}
+
+/// Trigrams every file matching `text` must have. Case-insensitive
+/// searches only use trigrams made of ASCII, since other letters may
+/// appear in a different case in the file.
+pub fn query_trigrams(text: &str, case_insensitive: bool) -> Vec<u32> {
+    let mut grams: Vec<u32> = text
+        .as_bytes()
+        .windows(3)
+        .filter(|window| !case_insensitive || window.is_ascii())
+        .map(pack)
+        .collect();
+    grams.sort_unstable();
+    grams.dedup();
+    grams
+}
+
+fn pack(window: &[u8]) -> u32 {
+    let [a, b, c] = [window[0], window[1], window[2]].map(|byte| byte.to_ascii_lowercase());
+    u32::from_be_bytes([0, a, b, c])
+}
+
+/// Removals before dead ids are swept out of the postings, at the least.
+const COMPACT_AFTER: usize = 1024;
+
+#[derive(Default)]
+pub struct TrigramIndex {
+    ids: HashMap<PathBuf, u32>,
+    paths: HashMap<u32, PathBuf>,
+    /// Files holding each trigram, by id. Ids only grow, so pushing keeps
+    /// these sorted. Removed files' ids stay until the next compaction and
+    /// are skipped meanwhile, as they have no path.
+    postings: HashMap<u32, Vec<u32>>,
+    next_id: u32,
+    removed: usize,
+}
+
+impl TrigramIndex {
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Indexes `path` as holding `grams`, replacing what it held before.
+    pub fn insert(&mut self, path: PathBuf, grams: Vec<u32>) {
+        self.remove(&path);
+        let id = self.next_id;
+        self.next_id += 1;
+        for gram in &grams {
+            self.postings.entry(*gram).or_default().push(id);
+        }
+        self.ids.insert(path.clone(), id);
+        self.paths.insert(id, path);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        let Some(id) = self.ids.remove(path) else {
+            return;
+        };
+        self.paths.remove(&id);
+        self.removed += 1;
+        // Compacting costs a pass over every posting, so wait until the
+        // dead ids are a good share of them.
+        if self.removed > COMPACT_AFTER && self.removed > self.ids.len() / 2 {
+            let paths = &self.paths;
+            self.postings.retain(|_, files| {
+                files.retain(|id| paths.contains_key(id));
+                !files.is_empty()
+            });
+            self.removed = 0;
+        }
+    }
+
+    /// Every indexed file containing all of `grams`.
+    pub fn matching(&self, grams: &[u32]) -> Vec<&Path> {
+        if grams.is_empty() {
+            return self.ids.keys().map(PathBuf::as_path).collect();
+        }
+        let mut sets = Vec::with_capacity(grams.len());
+        for gram in grams {
+            match self.postings.get(gram) {
+                Some(files) => sets.push(files),
+                None => return Vec::new(),
+            }
+        }
+        // Walk the rarest trigram's files and look the rest up.
+        sets.sort_by_key(|files| files.len());
+        let (rarest, rest) = sets.split_first().expect("grams is not empty");
+        rarest
+            .iter()
+            .filter(|id| rest.iter().all(|files| files.binary_search(id).is_ok()))
+            .filter_map(|id| self.paths.get(id).map(PathBuf::as_path))
+            .collect()
+    }
+}
@@ -0,0 +1,328 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use serde_json::{Value, json};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::mpsc;
+use tracing::{Instrument, debug, info, info_span, warn};
+
+use crate::{
+    http_rpc,
+    rpc::{
+        context::{ConnectionContext, Notifier},
+        request::{JsonRpcRequest, next_correlation_id},
+    },
+    state::SharedState,
+    ws::connection::{close_resources, next_connection_id, presented_key},
+};
+
+/// Largest header block a part may have.
+const MAX_PART_HEADER_BYTES: usize = 16 * 1024;
+
+/// `POST /upload/<workspace>[/<dir>]`: writes the files of a
+/// `multipart/form-data` body into `dir`, for dropping files from the
+/// desktop into the editor. Parts are streamed into the blob store and
+/// each file is then placed by an `uploadFile` call, so it is checked and
+/// logged like one; a file's name may hold directories, which are
+/// created. Existing files are kept unless `?overwrite=true`. The key is
+/// presented as for `/rpc`.
+pub async fn upload_handler(
+    State(state): State<SharedState>,
+    Path(target): Path<HashMap<String, String>>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    body: Body,
+) -> Response {
+    let workspace = target.get("workspace").cloned().unwrap_or_default();
+    let dir = target.get("dir").cloned().unwrap_or_default();
+    let connection_id = next_connection_id();
+    let span = info_span!("http_upload", connection_id, workspace = %workspace, dir = %dir);
+    async move {
+        let Some(identity) = state.policy.authenticate(presented_key(&headers, &query)) else {
+            warn!("Rejecting upload with unknown key");
+            return StatusCode::UNAUTHORIZED.into_response();
+        };
+        match state.uris.to_path(&format!("{workspace}:{dir}")) {
+            Ok(Some(_)) => {}
+            Ok(None) => return (StatusCode::NOT_FOUND, "Unknown workspace").into_response(),
+            Err(_) => return (StatusCode::BAD_REQUEST, "Invalid directory").into_response(),
+        }
+        let Some(boundary) = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(boundary)
+        else {
+            return (StatusCode::BAD_REQUEST, "Expected multipart/form-data").into_response();
+        };
+
+        let (outbound, _) = mpsc::unbounded_channel();
+        let connection = Arc::new(ConnectionContext::new(
+            connection_id,
+            Notifier::new(outbound, 0),
+            identity,
+        ));
+        let response = async {
+            let parts = match receive(&state, &connection, body, &boundary).await {
+                Ok(parts) => parts,
+                Err((status, message)) => {
+                    warn!(status = %status, message, "Rejected upload body");
+                    return (status, message).into_response();
+                }
+            };
+            let overwrite = query.get("overwrite").is_some_and(|value| value == "true");
+            let mut files = Vec::with_capacity(parts.len());
+            for part in parts {
+                let path = format!("{workspace}:{}", join(&dir, &part.relative));
+                let request = JsonRpcRequest {
+                    jsonrpc: "2.0".to_string(),
+                    method: "uploadFile".to_string(),
+                    params: json!({ "path": path, "hash": part.hash, "overwrite": overwrite }),
+                    id: Some(json!(files.len())),
+                    correlation_id: next_correlation_id(),
+                };
+                let answer =
+                    http_rpc::run(&state, &connection, request, tracing::Span::current()).await;
+                files.push(match answer {
+                    Some(answer) if answer.error.is_none() => {
+                        let placed = answer
+                            .result
+                            .map_or(Value::Null, |mut result| result["path"].take());
+                        json!({
+                            "name": part.name,
+                            "path": placed,
+                            "size": part.size,
+                            "hash": part.hash,
+                        })
+                    }
+                    Some(answer) => json!({ "name": part.name, "error": answer.error }),
+                    None => json!({ "name": part.name, "error": "Server is shutting down" }),
+                });
+            }
+            let uploaded = files
+                .iter()
+                .filter(|file| file.get("error").is_none())
+                .count();
+            info!(files = files.len(), uploaded, "Upload handled");
+            axum::Json(json!({ "files": files, "uploaded": uploaded })).into_response()
+        }
+        .await;
+        close_resources(&state, &connection);
+        response
+    }
+    .instrument(span)
+    .await
+}
+
+/// A file part, stored in the blob store.
+struct Part {
+    name: String,
+    relative: String,
+    hash: String,
+    size: u64,
+}
+
+enum Stage {
+    /// Before the first delimiter.
+    Preamble,
+    /// Just past a delimiter: another part follows, or the body ends.
+    Delimiter,
+    Headers,
+    /// Inside a part; file contents go to the open upload.
+    Body(Option<(String, String, u64)>),
+    Done,
+}
+
+/// Streams the body's file parts into the blob store. Fields without a
+/// filename are ignored.
+async fn receive(
+    state: &SharedState,
+    connection: &ConnectionContext,
+    body: Body,
+    boundary: &str,
+) -> Result<Vec<Part>, (StatusCode, String)> {
+    let malformed = |message: &str| (StatusCode::BAD_REQUEST, message.to_string());
+    let limit = state.limits().max_upload_bytes;
+    let delimiter = format!("\r\n--{boundary}").into_bytes();
+    // The first delimiter has no line break before it.
+    let mut buffer = b"\r\n".to_vec();
+    let mut stream = body.into_data_stream();
+    let mut stage = Stage::Preamble;
+    let mut parts = Vec::new();
+    let mut total = 0u64;
+    let mut ended = false;
+    loop {
+        // Work through what has arrived before reading more.
+        let progressed = match &mut stage {
+            Stage::Preamble => match find(&buffer, &delimiter) {
+                Some(at) => {
+                    buffer.drain(..at + delimiter.len());
+                    stage = Stage::Delimiter;
+                    true
+                }
+                None => {
+                    let keep = buffer.len().min(delimiter.len() - 1);
+                    buffer.drain(..buffer.len() - keep);
+                    false
+                }
+            },
+            Stage::Delimiter if buffer.len() >= 2 => {
+                if buffer.starts_with(b"--") {
+                    stage = Stage::Done;
+                } else if buffer.starts_with(b"\r\n") {
+                    buffer.drain(..2);
+                    stage = Stage::Headers;
+                } else {
+                    return Err(malformed("Malformed multipart delimiter"));
+                }
+                true
+            }
+            Stage::Headers => {
+                let end = if buffer.starts_with(b"\r\n") {
+                    Some((0, 2))
+                } else {
+                    find(&buffer, b"\r\n\r\n").map(|at| (at, at + 4))
+                };
+                match end {
+                    Some((at, skip)) => {
+                        let headers = String::from_utf8_lossy(&buffer[..at]).into_owned();
+                        buffer.drain(..skip);
+                        stage = Stage::Body(match filename(&headers) {
+                            Some(name) => {
+                                let Some(relative) = sanitize(&name) else {
+                                    return Err(malformed(&format!("Invalid file name {name}")));
+                                };
+                                let upload =
+                                    state.blobs.begin_upload(connection.id).map_err(|e| {
+                                        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                                    })?;
+                                Some((name, relative, upload))
+                            }
+                            None => None,
+                        });
+                        true
+                    }
+                    None if buffer.len() > MAX_PART_HEADER_BYTES => {
+                        return Err(malformed("Part headers too long"));
+                    }
+                    None => false,
+                }
+            }
+            Stage::Body(file) => {
+                let found = find(&buffer, &delimiter);
+                let contents = found.unwrap_or(buffer.len().saturating_sub(delimiter.len() - 1));
+                if let Some((_, _, upload)) = file {
+                    total += contents as u64;
+                    if total > limit {
+                        return Err((
+                            StatusCode::PAYLOAD_TOO_LARGE,
+                            format!("Upload exceeds the {limit} byte limit"),
+                        ));
+                    }
+                    tokio::task::block_in_place(|| {
+                        state
+                            .blobs
+                            .append(connection.id, *upload, &buffer[..contents])
+                    })
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+                }
+                buffer.drain(..contents);
+                match found {
+                    Some(_) => {
+                        buffer.drain(..delimiter.len());
+                        if let Some((name, relative, upload)) = file.take() {
+                            let (hash, size) = tokio::task::block_in_place(|| {
+                                state.blobs.finish(connection.id, upload)
+                            })
+                            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+                            debug!(name = %name, size, "Received upload part");
+                            parts.push(Part {
+                                name,
+                                relative,
+                                hash,
+                                size,
+                            });
+                        }
+                        stage = Stage::Delimiter;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Stage::Done => return Ok(parts),
+            Stage::Delimiter => false,
+        };
+        if progressed {
+            continue;
+        }
+        if ended {
+            return Err(malformed("Multipart body ended early"));
+        }
+        match stream.next().await {
+            Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+            Some(Err(e)) => return Err(malformed(&format!("Failed to read body: {e}"))),
+            None => ended = true,
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// The `boundary` parameter of a `multipart/form-data` content type.
+fn boundary(content_type: &str) -> Option<String> {
+    let (kind, parameters) = content_type.split_once(';')?;
+    if !kind.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    parameters.split(';').find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        let value = value.trim().trim_matches('"');
+        (name.trim().eq_ignore_ascii_case("boundary") && !value.is_empty())
+            .then(|| value.to_string())
+    })
+}
+
+/// The `filename` of a part's `Content-Disposition`, if it is a file.
+fn filename(headers: &str) -> Option<String> {
+    let disposition = headers.split("\r\n").find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("content-disposition")
+            .then_some(value)
+    })?;
+    let start = disposition.find("filename=\"")? + "filename=\"".len();
+    let mut name = String::new();
+    let mut chars = disposition[start..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(name),
+            '\\' => name.extend(chars.next()),
+            c => name.push(c),
+        }
+    }
+    None
+}
+
+/// `name` as a path relative to the upload directory. Browsers send
+/// folders as `folder/file`; anything that could climb out is refused.
+fn sanitize(name: &str) -> Option<String> {
+    let components: Vec<&str> = name.split(['/', '\\']).collect();
+    components
+        .iter()
+        .all(|component| !component.is_empty() && *component != "." && *component != "..")
+        .then(|| components.join("/"))
+}
+
+fn join(dir: &str, relative: &str) -> String {
+    match dir.trim_matches('/') {
+        "" => relative.to_string(),
+        dir => format!("{dir}/{relative}"),
+    }
+}
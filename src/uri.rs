@@ -0,0 +1,401 @@
+//! `workspace://<root-name>/<relative/path>` URIs, which name files the
+//! same way whatever directory the server runs in, and the shorter
+//! `<root-name>:<relative/path>` form.
+//!
+//! Requests may use them anywhere a path is accepted. Clients that pass
+//! `workspaceUris: true` to `initialize` also receive them in place of
+//! host paths in results, error data and notifications. Host paths are
+//! still accepted from v1 clients while `rawPaths` is on.
+
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+use crate::{config::WorkspaceDefinition, rpc::error::HandlerError, sandbox::normalize};
+
+pub const SCHEME: &str = "workspace://";
+
+/// Payload fields holding a path, or an array of them, that are converted
+/// in both directions. Content fields are never touched.
+const PATH_KEYS: &[&str] = &["path", "paths", "originalPath", "cwd"];
+
+struct Root {
+    name: String,
+    /// `name` percent-encoded for URIs.
+    encoded: String,
+    /// Canonical root directory.
+    path: PathBuf,
+}
+
+pub struct WorkspaceUris {
+    /// The primary root first, then any further `workspaces`.
+    roots: Vec<Root>,
+    /// Directory relative request paths are opened from.
+    cwd: PathBuf,
+}
+
+impl WorkspaceUris {
+    /// `name` defaults to the root directory's name.
+    pub fn new(
+        name: Option<&str>,
+        root: &Path,
+        workspaces: &BTreeMap<String, WorkspaceDefinition>,
+    ) -> Result<Self, String> {
+        let primary = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => primary
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "workspace".to_string()),
+        };
+        let mut roots: Vec<Root> = Vec::new();
+        let named =
+            std::iter::once((name, primary)).chain(workspaces.iter().map(|(name, workspace)| {
+                let path = &workspace.path;
+                (
+                    name.clone(),
+                    path.canonicalize().unwrap_or_else(|_| path.clone()),
+                )
+            }));
+        for (name, path) in named {
+            if name.is_empty() || name.contains(['/', '\\', ':']) {
+                return Err(format!("invalid workspace root name {name:?}"));
+            }
+            if roots.iter().any(|root| root.name == name) {
+                return Err(format!("workspace root name {name:?} is used twice"));
+            }
+            if !path.is_dir() {
+                return Err(format!(
+                    "workspace {name} is not a directory: {}",
+                    path.display()
+                ));
+            }
+            roots.push(Root {
+                encoded: encode(&name),
+                name,
+                path,
+            });
+        }
+        let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+        Ok(Self { roots, cwd })
+    }
+
+    /// Name of the primary root.
+    pub fn name(&self) -> &str {
+        &self.roots[0].name
+    }
+
+    /// URI of the primary root itself.
+    pub fn root_uri(&self) -> String {
+        uri_of(&self.roots[0])
+    }
+
+    /// Whether further roots are configured, so paths must name theirs.
+    pub fn is_multi_root(&self) -> bool {
+        self.roots.len() > 1
+    }
+
+    /// Name, canonical path and URI of every root, the primary first.
+    pub fn roots(&self) -> impl Iterator<Item = (&str, &Path, String)> {
+        self.roots
+            .iter()
+            .map(|root| (root.name.as_str(), root.path.as_path(), uri_of(root)))
+    }
+
+    /// Whether a host path, absolute or relative to the working directory,
+    /// lies in a root, either as written or once the part of it that
+    /// exists is canonicalized, as when a root is reached through a
+    /// symlink. Symlinks inside a root are left to [`crate::sandbox`].
+    fn contains(&self, path: &Path) -> bool {
+        let absolute = normalize(&self.cwd.join(path));
+        if self.root_of(&absolute).is_some() {
+            return true;
+        }
+        let mut existing = absolute.as_path();
+        let mut missing = Vec::new();
+        let mut canonical = loop {
+            match existing.canonicalize() {
+                Ok(canonical) => break canonical,
+                Err(_) => match (existing.file_name(), existing.parent()) {
+                    (Some(name), Some(parent)) => {
+                        missing.push(name);
+                        existing = parent;
+                    }
+                    _ => return false,
+                },
+            }
+        };
+        canonical.extend(missing.iter().rev());
+        self.root_of(&canonical).is_some()
+    }
+
+    /// URI for a host path, absolute or relative to the working directory,
+    /// or `None` if it lies outside every root.
+    pub fn to_uri(&self, path: &Path) -> Option<String> {
+        let absolute = normalize(&self.cwd.join(path));
+        let (root, relative) = self.root_of(&absolute)?;
+        Some(format!(
+            "{}{}",
+            uri_of(root),
+            encode(&relative.to_string_lossy())
+        ))
+    }
+
+    /// Name of the root holding `path`, absolute or relative to the working
+    /// directory.
+    pub fn root_name(&self, path: &Path) -> Option<&str> {
+        let absolute = normalize(&self.cwd.join(path));
+        self.root_of(&absolute).map(|(root, _)| root.name.as_str())
+    }
+
+    /// The root holding the normalized absolute `path`, and the path within
+    /// it. The innermost root wins when roots are nested.
+    fn root_of<'a>(&self, absolute: &'a Path) -> Option<(&Root, &'a Path)> {
+        self.roots
+            .iter()
+            .filter_map(|root| Some((root, absolute.strip_prefix(&root.path).ok()?)))
+            .max_by_key(|(root, _)| root.path.components().count())
+    }
+
+    /// Whether `text` is a URI or `name:relative/path` rather than a host
+    /// path.
+    pub fn is_addressed(&self, text: &str) -> bool {
+        text.starts_with(SCHEME)
+            || text
+                .split_once(':')
+                .is_some_and(|(name, _)| self.roots.iter().any(|root| root.name == name))
+    }
+
+    /// Host path named by `text`, a workspace URI or `name:relative/path`,
+    /// or `None` if it is neither.
+    pub fn to_path(&self, text: &str) -> Result<Option<PathBuf>, HandlerError> {
+        let (root, relative) = match text.strip_prefix(SCHEME) {
+            Some(rest) => {
+                let (name, relative) = rest.split_once('/').unwrap_or((rest, ""));
+                let Some(root) = self.roots.iter().find(|root| root.encoded == name) else {
+                    return Err(HandlerError::InvalidParams(format!(
+                        "Unknown workspace root {name} in {text}"
+                    )));
+                };
+                let relative = decode(relative)
+                    .ok_or_else(|| HandlerError::InvalidParams(format!("Malformed URI {text}")))?;
+                (root, relative)
+            }
+            None => {
+                let Some((name, relative)) = text.split_once(':') else {
+                    return Ok(None);
+                };
+                let Some(root) = self.roots.iter().find(|root| root.name == name) else {
+                    return Ok(None);
+                };
+                (root, relative.to_string())
+            }
+        };
+        let path = normalize(&root.path.join(relative.trim_start_matches('/')));
+        if !path.starts_with(&root.path) {
+            return Err(HandlerError::InvalidParams(format!(
+                "Path leaves workspace {}: {text}",
+                root.name
+            )));
+        }
+        Ok(Some(path))
+    }
+
+    /// Rewrites host paths in v1 request params as URIs, so they go through
+    /// the same resolution as new clients' requests. Paths outside the
+    /// workspace are passed on for [`WorkspaceUris::resolve_params`] to
+    /// refuse. With `allowed` off any host path is refused instead.
+    pub fn translate_raw_paths(
+        &self,
+        params: &mut Value,
+        allowed: bool,
+    ) -> Result<(), HandlerError> {
+        let mut result = Ok(());
+        visit_paths(params, &mut |text| {
+            if self.is_addressed(text) {
+                return None;
+            }
+            if !allowed {
+                result = Err(HandlerError::InvalidParams(format!(
+                    "Raw paths are not accepted; use a workspace URI instead of {text}"
+                )));
+                return None;
+            }
+            let uri = self.to_uri(Path::new(text));
+            debug!(path = text, uri = ?uri, "Translated v1 path");
+            uri
+        });
+        result
+    }
+
+    /// Replaces addressed paths in request params with host paths. With
+    /// several roots every path has to be addressed; with one, host paths
+    /// outside it are refused.
+    pub fn resolve_params(&self, params: &mut Value) -> Result<(), HandlerError> {
+        let mut result = Ok(());
+        visit_paths(params, &mut |text| match self.to_path(text) {
+            Ok(Some(path)) => Some(path.to_string_lossy().into_owned()),
+            Ok(None) if self.is_multi_root() => {
+                result = Err(HandlerError::InvalidParams(format!(
+                    "Path must name its workspace, as workspace:relative/path: {text}"
+                )));
+                None
+            }
+            Ok(None) if !self.contains(Path::new(text)) => {
+                result = Err(HandlerError::InvalidParams(format!(
+                    "Path is outside the workspace: {text}"
+                )));
+                None
+            }
+            Ok(None) => None,
+            Err(e) => {
+                result = Err(e);
+                None
+            }
+        });
+        result
+    }
+
+    /// Replaces host paths inside the workspace with URIs in an outgoing
+    /// payload.
+    pub fn rewrite_payload(&self, payload: &mut Value) {
+        visit_paths(payload, &mut |text| {
+            if text.starts_with(SCHEME) {
+                return None;
+            }
+            self.to_uri(Path::new(text))
+        });
+    }
+}
+
+/// Every path named in request params, after URIs have been resolved.
+pub fn request_paths(params: &Value) -> Vec<PathBuf> {
+    fn collect(value: &Value, in_path: bool, paths: &mut Vec<PathBuf>) {
+        match value {
+            Value::String(text) if in_path => paths.push(PathBuf::from(text)),
+            Value::Object(object) => {
+                for (key, value) in object {
+                    collect(value, PATH_KEYS.contains(&key.as_str()), paths);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    collect(item, in_path, paths);
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut paths = Vec::new();
+    collect(params, false, &mut paths);
+    paths
+}
+
+fn uri_of(root: &Root) -> String {
+    format!("{SCHEME}{}/", root.encoded)
+}
+
+/// Calls `convert` on every string under a [`PATH_KEYS`] field, replacing
+/// it with the returned value, if any.
+pub fn visit_paths(value: &mut Value, convert: &mut dyn FnMut(&str) -> Option<String>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if PATH_KEYS.contains(&key.as_str()) {
+                    convert_strings(value, convert);
+                } else {
+                    visit_paths(value, convert);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                visit_paths(item, convert);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn convert_strings(value: &mut Value, convert: &mut dyn FnMut(&str) -> Option<String>) {
+    match value {
+        Value::String(text) => {
+            if let Some(converted) = convert(text) {
+                *text = converted;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                convert_strings(item, convert);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Percent-encodes everything but unreserved characters and `/`.
+pub fn encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut input = text.bytes();
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let high = (input.next()? as char).to_digit(16)?;
+            let low = (input.next()? as char).to_digit(16)?;
+            bytes.push((high * 16 + low) as u8);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn refuses_host_paths_outside_the_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        let uris = WorkspaceUris::new(None, &root, &BTreeMap::new()).unwrap();
+
+        let inside = root.join("src/main.rs").to_string_lossy().into_owned();
+        let mut params = json!({ "path": inside });
+        uris.resolve_params(&mut params).unwrap();
+
+        for outside in [
+            dir.path().join("secret.txt"),
+            root.join("../secret.txt"),
+            PathBuf::from("/etc/passwd"),
+        ] {
+            let mut params = json!({ "path": outside });
+            assert!(
+                matches!(
+                    uris.resolve_params(&mut params),
+                    Err(HandlerError::InvalidParams(_))
+                ),
+                "{} was accepted",
+                outside.display()
+            );
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use axum::{
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{Html, IntoResponse, Response},
+};
+use pulldown_cmark::{Event, Options, Parser};
+use std::{fs, path::Path as FsPath};
+use tracing::{Instrument, debug, info_span, warn};
+
+use crate::{config::Access, share::Share, state::SharedState, syntax, uri};
+
+const STYLE: &str = "\
+body{margin:0;font:15px/1.5 system-ui,sans-serif;color:#1f2328;background:#fff}\
+header{padding:10px 20px;border-bottom:1px solid #d0d7de;background:#f6f8fa;font-family:monospace}\
+header a{color:#0969da;text-decoration:none}\
+main{padding:20px;max-width:960px}\
+ul.listing{list-style:none;padding:0;font-family:monospace}\
+pre.code{counter-reset:line;font:13px/1.45 monospace;overflow-x:auto}\
+pre.code .line{display:block;white-space:pre}\
+pre.code .line::before{counter-increment:line;content:counter(line);display:inline-block;\
+width:3em;margin-right:1em;text-align:right;color:#8c959f}\
+.hl-comment{color:#6e7781}.hl-keyword{color:#cf222e}.hl-string{color:#0a3069}\
+.hl-number,.hl-constant,.hl-boolean{color:#0550ae}.hl-function{color:#8250df}\
+.hl-type,.hl-constructor{color:#953800}.hl-attribute,.hl-tag,.hl-module{color:#116329}\
+.hl-property,.hl-label{color:#0550ae}.hl-escape{color:#cf222e}";
+
+/// `GET /share/<token>/`: lists the shared files and directories.
+pub async fn share_index(State(state): State<SharedState>, Path(token): Path<String>) -> Response {
+    let span = info_span!("share_view");
+    async move {
+        let Some(share) = state.shares.get(&token) else {
+            return not_found();
+        };
+        let root = state.sandbox.root();
+        let entries: Vec<(String, bool)> = share
+            .paths
+            .iter()
+            .map(|path| (relative(root, path), path.is_dir()))
+            .collect();
+        page(&share, "Shared files", &listing(&share.token, &entries))
+    }
+    .instrument(span)
+    .await
+}
+
+/// `GET /share/<token>/<path>`: renders a shared file, or lists a shared
+/// directory. Anything outside the share is reported as missing.
+pub async fn share_file(
+    State(state): State<SharedState>,
+    Path((token, path)): Path<(String, String)>,
+) -> Response {
+    let span = info_span!("share_view", path = %path);
+    async move {
+        let Some(share) = state.shares.get(&token) else {
+            return not_found();
+        };
+        let root = state.sandbox.root();
+        let Ok(target) = state.sandbox.resolve(FsPath::new(&path)) else {
+            return not_found();
+        };
+        if !target.exists() || !share.allows(&target) {
+            warn!("Rejected request for a path outside the share");
+            return not_found();
+        }
+        if state.permissions.check(&target, Access::Read).is_err() {
+            return not_found();
+        }
+
+        if target.is_dir() {
+            let Ok(read_dir) = fs::read_dir(&target) else {
+                return not_found();
+            };
+            let mut entries: Vec<(String, bool)> = read_dir
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.file_name().is_some_and(|name| name != ".git"))
+                .filter(|path| !state.permissions.is_denied(path))
+                .map(|path| (relative(root, &path), path.is_dir()))
+                .collect();
+            entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            return page(&share, &path, &listing(&share.token, &entries));
+        }
+
+        let size = fs::metadata(&target).map(|m| m.len()).unwrap_or_default();
+        if size > state.limits().max_read_bytes {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "File is too large to preview",
+            )
+                .into_response();
+        }
+        let Ok(content) = fs::read(&target) else {
+            return not_found();
+        };
+        let Ok(content) = String::from_utf8(content) else {
+            return (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Binary files cannot be previewed",
+            )
+                .into_response();
+        };
+        debug!(bytes = content.len(), "Rendering shared file");
+        let body = tokio::task::block_in_place(|| render(&state, &target, &content));
+        page(&share, &path, &body)
+    }
+    .instrument(span)
+    .await
+}
+
+/// Markdown is rendered as HTML (with embedded HTML shown as text), code
+/// with a grammar is syntax highlighted, and anything else is shown as is.
+fn render(state: &SharedState, path: &FsPath, content: &str) -> String {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if matches!(extension, "md" | "markdown") {
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_FOOTNOTES;
+        let events = Parser::new_ext(content, options).map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            event => event,
+        });
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, events);
+        return html;
+    }
+
+    let lines = match syntax::grammar_for(path, None) {
+        Some(grammar) => state
+            .syntax
+            .highlight_html(grammar, content)
+            .unwrap_or_else(|e| {
+                warn!(error = %e, "Falling back to plain text");
+                content.lines().map(escape).collect()
+            }),
+        None => content.lines().map(escape).collect(),
+    };
+    let mut html = String::from("<pre class=\"code\"><code>");
+    for line in lines {
+        html.push_str("<span class=\"line\">");
+        html.push_str(line.trim_end_matches('\n'));
+        html.push_str("</span>");
+    }
+    html.push_str("</code></pre>");
+    html
+}
+
+fn listing(token: &str, entries: &[(String, bool)]) -> String {
+    let mut html = String::from("<ul class=\"listing\">");
+    for (path, is_dir) in entries {
+        let suffix = if *is_dir { "/" } else { "" };
+        html.push_str(&format!(
+            "<li><a href=\"/share/{token}/{}\">{}{suffix}</a></li>",
+            uri::encode(path),
+            escape(path)
+        ));
+    }
+    html.push_str("</ul>");
+    html
+}
+
+fn page(share: &Share, title: &str, body: &str) -> Response {
+    let html = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\">\
+         <meta name=\"referrer\" content=\"no-referrer\"><title>{title}</title>\
+         <style>{STYLE}</style></head><body><header><a href=\"/share/{token}/\">shared</a> / {title}</header>\
+         <main>{body}</main></body></html>",
+        title = escape(title),
+        token = share.token,
+    );
+    (
+        [
+            // Shared documents are untrusted; nothing in them may run.
+            (
+                header::CONTENT_SECURITY_POLICY,
+                "default-src 'none'; style-src 'unsafe-inline'",
+            ),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            (header::REFERRER_POLICY, "no-referrer"),
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        Html(html),
+    )
+        .into_response()
+}
+
+/// Unknown tokens, expired shares and paths outside a share all look the
+/// same, so a link reveals nothing beyond what it grants.
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, "Not found").into_response()
+}
+
+fn relative(root: &FsPath, path: &FsPath) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,67 @@
+use ignore::{
+    WalkBuilder,
+    overrides::{Override, OverrideBuilder},
+};
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use crate::permissions::Permissions;
+
+/// What listings and searches leave out: `.gitignore` and `.ignore` files
+/// plus the server's `exclude` globs, and always whatever the permission
+/// rules deny. Cheap to clone into background work.
+#[derive(Clone)]
+pub struct IgnoreRules {
+    exclude: Arc<RwLock<Override>>,
+    permissions: Permissions,
+}
+
+impl IgnoreRules {
+    pub fn new(root: &Path, exclude: &[String], permissions: Permissions) -> Result<Self, String> {
+        let mut builder = OverrideBuilder::new(root);
+        for glob in exclude {
+            // Override globs whitelist by default; `!` makes them excludes.
+            builder
+                .add(&format!("!{glob}"))
+                .map_err(|e| format!("Invalid exclude glob {glob}: {e}"))?;
+        }
+        let exclude = builder
+            .build()
+            .map_err(|e| format!("Invalid exclude globs: {e}"))?;
+        Ok(Self {
+            exclude: Arc::new(RwLock::new(exclude)),
+            permissions,
+        })
+    }
+
+    /// Takes on the `exclude` globs of `other`, as reloaded from the
+    /// config. Permission rules are replaced through [`Permissions`].
+    pub fn replace_exclude(&self, other: &IgnoreRules) {
+        let exclude = other
+            .exclude
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        *self.exclude.write().unwrap_or_else(|e| e.into_inner()) = exclude;
+    }
+
+    /// A walker over `start` that visits hidden files and, if
+    /// `respect_ignore` is set, skips ignored ones. Denied paths are
+    /// always skipped. Ignore files apply
+    /// whether or not the workspace is a git repository.
+    pub fn walker(&self, start: &Path, respect_ignore: bool) -> WalkBuilder {
+        let mut walker = WalkBuilder::new(start);
+        walker.hidden(false);
+        if respect_ignore {
+            let exclude = self.exclude.read().unwrap_or_else(|e| e.into_inner());
+            walker.require_git(false).overrides(exclude.clone());
+        } else {
+            walker.standard_filters(false);
+        }
+        let permissions = self.permissions.clone();
+        walker.filter_entry(move |entry| !permissions.is_denied(entry.path()));
+        walker
+    }
+}
@@ -0,0 +1,590 @@
+//! Change subscriptions: a client runs `watch/start` on a directory and is
+//! sent what changes under it as `watch/changed` notifications.
+//!
+//! On Linux the changes come from inotify, with a watch on every directory
+//! the ignore rules let through, so a `.gitignore`d `node_modules` or
+//! `target` costs no watches and raises no events however busy `npm
+//! install` or `cargo build` keep it. Elsewhere, or where inotify cannot
+//! start, the index's walk stands in (see [`crate::index`]) and changes
+//! arrive every few seconds. Nothing is watched until the first
+//! subscription.
+//!
+//! Either way changes are held until the tree has been quiet for
+//! [`DEBOUNCE`], or for at most [`MAX_LATENCY`], and coalesced per path: a
+//! file created and deleted within a batch is never reported, and a rename
+//! is one change naming both paths. When events were lost, because the
+//! kernel queue overflowed or a batch grew past [`MAX_PENDING`] changes,
+//! subscribers get `overflow: true` instead and should rescan.
+
+use globset::GlobSet;
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, warn};
+
+use crate::{permissions::Permissions, rpc::context::Notifier, state::AppState};
+
+/// Quiet time that ends a batch.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+/// Longest a change waits while the tree keeps changing.
+const MAX_LATENCY: Duration = Duration::from_secs(1);
+/// Changes a batch may hold before it is reported as an overflow.
+const MAX_PENDING: usize = 10_000;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    #[serde(rename = "type")]
+    pub kind: ChangeKind,
+    pub path: PathBuf,
+    /// Where a renamed file or directory was before.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<PathBuf>,
+}
+
+impl Change {
+    fn new(kind: ChangeKind, path: PathBuf) -> Self {
+        Self {
+            kind,
+            path,
+            old_path: None,
+        }
+    }
+}
+
+/// What a backend saw, before coalescing.
+pub enum Event {
+    Created(PathBuf),
+    Modified(PathBuf),
+    Deleted(PathBuf),
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Events were dropped before they could be read.
+    Overflow,
+}
+
+/// Changes waiting to be delivered, by the path they now apply to.
+#[derive(Default)]
+struct Batch {
+    changes: BTreeMap<PathBuf, Change>,
+    overflow: bool,
+}
+
+impl Batch {
+    fn add(&mut self, event: Event) {
+        use ChangeKind::*;
+        if self.overflow {
+            return;
+        }
+        match event {
+            Event::Created(path) | Event::Modified(path)
+                if self.changes.get(&path).is_some_and(|c| c.kind == Deleted) =>
+            {
+                self.changes
+                    .insert(path.clone(), Change::new(Modified, path));
+            }
+            Event::Created(path) => {
+                self.changes
+                    .entry(path.clone())
+                    .or_insert_with(|| Change::new(Created, path));
+            }
+            Event::Modified(path) => {
+                self.changes
+                    .entry(path.clone())
+                    .or_insert_with(|| Change::new(Modified, path));
+            }
+            Event::Deleted(path) => match self.changes.remove(&path) {
+                Some(Change { kind: Created, .. }) => {}
+                Some(Change {
+                    old_path: Some(old_path),
+                    ..
+                }) => {
+                    self.changes
+                        .insert(old_path.clone(), Change::new(Deleted, old_path));
+                }
+                _ => {
+                    self.changes
+                        .insert(path.clone(), Change::new(Deleted, path));
+                }
+            },
+            Event::Renamed { from, to } => {
+                let change = match self.changes.remove(&from) {
+                    Some(Change { kind: Created, .. }) => Change::new(Created, to.clone()),
+                    Some(Change {
+                        old_path: Some(old_path),
+                        ..
+                    }) if old_path == to => Change::new(Modified, to.clone()),
+                    previous => Change {
+                        kind: Renamed,
+                        path: to.clone(),
+                        old_path: Some(previous.and_then(|c| c.old_path).unwrap_or(from)),
+                    },
+                };
+                self.changes.insert(to, change);
+            }
+            Event::Overflow => self.overflow = true,
+        }
+        if self.changes.len() > MAX_PENDING {
+            self.overflow = true;
+        }
+        if self.overflow {
+            self.changes.clear();
+        }
+    }
+}
+
+struct Subscription {
+    connection_id: u64,
+    root: PathBuf,
+    /// Relative to `root`; everything when `None`.
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    notifier: Notifier,
+}
+
+impl Subscription {
+    fn wants(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        !self.exclude.is_match(relative)
+            && self
+                .include
+                .as_ref()
+                .is_none_or(|include| include.is_match(relative))
+    }
+
+    /// `change` as this subscription sees it: a rename across its filters
+    /// is a creation or a deletion.
+    fn view(&self, change: &Change) -> Option<Change> {
+        let wanted = self.wants(&change.path);
+        let Some(old_path) = &change.old_path else {
+            return wanted.then(|| change.clone());
+        };
+        match (self.wants(old_path), wanted) {
+            (true, true) => Some(change.clone()),
+            (true, false) => Some(Change::new(ChangeKind::Deleted, old_path.clone())),
+            (false, true) => Some(Change::new(ChangeKind::Created, change.path.clone())),
+            (false, false) => None,
+        }
+    }
+}
+
+struct Backend {
+    name: &'static str,
+    events: Sender<Event>,
+}
+
+type Subscriptions = Arc<Mutex<BTreeMap<u64, Subscription>>>;
+
+pub struct FileWatcher {
+    subscriptions: Subscriptions,
+    next_id: AtomicU64,
+    permissions: Permissions,
+    /// Started by the first subscription.
+    backend: Mutex<Option<Backend>>,
+}
+
+impl FileWatcher {
+    pub fn new(permissions: Permissions) -> Self {
+        Self {
+            subscriptions: Arc::default(),
+            next_id: AtomicU64::new(1),
+            permissions,
+            backend: Mutex::default(),
+        }
+    }
+
+    fn subscriptions(&self) -> MutexGuard<'_, BTreeMap<u64, Subscription>> {
+        lock(&self.subscriptions)
+    }
+
+    /// Subscribes a connection to changes under `root`, which must be
+    /// canonical. Returns the subscription id and the backend's name,
+    /// `inotify` or `poll`.
+    pub fn subscribe(
+        &self,
+        state: &AppState,
+        connection_id: u64,
+        notifier: Notifier,
+        root: PathBuf,
+        include: Option<GlobSet>,
+        exclude: GlobSet,
+    ) -> (u64, &'static str) {
+        let backend = {
+            let mut backend = self.backend.lock().unwrap_or_else(|e| e.into_inner());
+            backend.get_or_insert_with(|| self.start(state)).name
+        };
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        debug!(id, connection_id, root = %root.display(), "Watch subscribed");
+        self.subscriptions().insert(
+            id,
+            Subscription {
+                connection_id,
+                root,
+                include,
+                exclude,
+                notifier,
+            },
+        );
+        (id, backend)
+    }
+
+    /// Ends a connection's subscription; `false` if it has none by `id`.
+    pub fn unsubscribe(&self, id: u64, connection_id: u64) -> bool {
+        let mut subscriptions = self.subscriptions();
+        if subscriptions
+            .get(&id)
+            .is_some_and(|subscription| subscription.connection_id == connection_id)
+        {
+            subscriptions.remove(&id);
+            debug!(id, connection_id, "Watch unsubscribed");
+            return true;
+        }
+        false
+    }
+
+    pub fn close_connection(&self, connection_id: u64) {
+        self.subscriptions()
+            .retain(|_, subscription| subscription.connection_id != connection_id);
+    }
+
+    /// Whether changes should come from the index's walks.
+    pub fn is_polling(&self) -> bool {
+        let backend = self.backend.lock().unwrap_or_else(|e| e.into_inner());
+        backend
+            .as_ref()
+            .is_some_and(|backend| backend.name == "poll")
+    }
+
+    /// Changes the index found between two walks, when polling.
+    pub fn polled(&self, events: Vec<Event>) {
+        let backend = self.backend.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(backend) = backend.as_ref() {
+            for event in events {
+                let _ = backend.events.send(event);
+            }
+        }
+    }
+
+    fn start(&self, state: &AppState) -> Backend {
+        let (events, received) = mpsc::channel();
+        let subscriptions = Arc::clone(&self.subscriptions);
+        let permissions = self.permissions.clone();
+        thread::spawn(move || deliver_batches(received, subscriptions, permissions));
+
+        #[cfg(target_os = "linux")]
+        {
+            let roots = state.uris.roots().map(|(_, root, _)| root.to_path_buf());
+            let data_path = state.config.data_path();
+            let data_path = data_path.canonicalize().unwrap_or(data_path);
+            match inotify::start(
+                roots.collect(),
+                state.ignore.clone(),
+                self.permissions.clone(),
+                data_path,
+                events.clone(),
+            ) {
+                Ok(()) => {
+                    info!("Watching the workspace with inotify");
+                    return Backend {
+                        name: "inotify",
+                        events,
+                    };
+                }
+                Err(e) => warn!(error = %e, "inotify unavailable, watching by polling"),
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = state;
+        Backend {
+            name: "poll",
+            events,
+        }
+    }
+}
+
+fn lock(subscriptions: &Subscriptions) -> MutexGuard<'_, BTreeMap<u64, Subscription>> {
+    subscriptions.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Collects events into batches and sends each subscriber its part.
+fn deliver_batches(
+    events: Receiver<Event>,
+    subscriptions: Subscriptions,
+    permissions: Permissions,
+) {
+    while let Ok(first) = events.recv() {
+        let mut batch = Batch::default();
+        batch.add(first);
+        let started = Instant::now();
+        while let Some(left) = MAX_LATENCY.checked_sub(started.elapsed()) {
+            match events.recv_timeout(left.min(DEBOUNCE)) {
+                Ok(event) => batch.add(event),
+                Err(_) => break,
+            }
+        }
+
+        let changes: Vec<Change> = batch
+            .changes
+            .into_values()
+            .filter(|change| !permissions.is_denied(&change.path))
+            .collect();
+        debug!(
+            changes = changes.len(),
+            overflow = batch.overflow,
+            "Watch batch"
+        );
+        for (id, subscription) in lock(&subscriptions).iter() {
+            let changes: Vec<Change> = changes
+                .iter()
+                .filter_map(|change| subscription.view(change))
+                .collect();
+            if changes.is_empty() && !batch.overflow {
+                continue;
+            }
+            subscription.notifier.notify(
+                "watch/changed",
+                json!({ "subscriptionId": id, "changes": changes, "overflow": batch.overflow }),
+            );
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use ignore::WalkBuilder;
+    use nix::{
+        errno::Errno,
+        sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor},
+    };
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+        sync::mpsc::Sender,
+        thread,
+    };
+    use tracing::{debug, warn};
+
+    use super::Event;
+    use crate::{permissions::Permissions, walk::IgnoreRules};
+
+    fn mask() -> AddWatchFlags {
+        AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MODIFY
+            | AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_ONLYDIR
+            | AddWatchFlags::IN_DONT_FOLLOW
+    }
+
+    /// Watches `roots` from a thread of its own, which ends if the events
+    /// cannot be read or nobody is left to send them to.
+    pub fn start(
+        roots: Vec<PathBuf>,
+        ignore: IgnoreRules,
+        permissions: Permissions,
+        data_path: PathBuf,
+        events: Sender<Event>,
+    ) -> nix::Result<()> {
+        let inotify = Inotify::init(InitFlags::IN_CLOEXEC)?;
+        thread::spawn(move || {
+            let mut watches = Watches {
+                inotify,
+                dirs: HashMap::new(),
+                ignore,
+                permissions,
+                data_path,
+                full: false,
+            };
+            let mut initial = Vec::new();
+            for root in &roots {
+                watches.add_tree(root, &mut initial, false);
+            }
+            debug!(
+                directories = watches.dirs.len(),
+                "Watching workspace directories"
+            );
+            let mut found = initial;
+            loop {
+                if found.drain(..).any(|event| events.send(event).is_err()) {
+                    return;
+                }
+                match watches.inotify.read_events() {
+                    Ok(batch) => watches.handle(batch, &mut found),
+                    Err(Errno::EINTR) => {}
+                    Err(e) => {
+                        warn!(error = %e, "Stopped reading inotify events");
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    struct Watches {
+        inotify: Inotify,
+        dirs: HashMap<WatchDescriptor, PathBuf>,
+        ignore: IgnoreRules,
+        permissions: Permissions,
+        data_path: PathBuf,
+        /// Set once the kernel refused a watch for want of room.
+        full: bool,
+    }
+
+    impl Watches {
+        /// The ignore rules' walker, also leaving out `.git` and the
+        /// server's data directory.
+        fn walker(&self, start: &Path) -> WalkBuilder {
+            let mut walker = self.ignore.walker(start, true);
+            let permissions = self.permissions.clone();
+            let data_path = self.data_path.clone();
+            walker.filter_entry(move |entry| {
+                entry.file_name() != ".git"
+                    && !entry.path().starts_with(&data_path)
+                    && !permissions.is_denied(entry.path())
+            });
+            walker
+        }
+
+        /// Whether the ignore rules let the new directory `dir` through.
+        fn wanted(&self, dir: &Path) -> bool {
+            let Some(parent) = dir.parent() else {
+                return false;
+            };
+            self.walker(parent)
+                .max_depth(Some(1))
+                .build()
+                .filter_map(Result::ok)
+                .any(|entry| entry.path() == dir)
+        }
+
+        /// Watches `dir` and the directories under it. With `report`, the
+        /// files already inside are reported as created, since they may
+        /// have been written before the watch was in place.
+        fn add_tree(&mut self, dir: &Path, found: &mut Vec<Event>, report: bool) {
+            for entry in self.walker(dir).build().filter_map(Result::ok) {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                if report && entry.path() != dir {
+                    found.push(Event::Created(entry.path().to_path_buf()));
+                }
+                if !is_dir {
+                    continue;
+                }
+                match self.inotify.add_watch(entry.path(), mask()) {
+                    Ok(wd) => {
+                        self.dirs.insert(wd, entry.into_path());
+                    }
+                    Err(Errno::ENOSPC) if !self.full => {
+                        warn!(
+                            "Out of inotify watches; raise fs.inotify.max_user_watches to watch \
+                             the whole workspace"
+                        );
+                        self.full = true;
+                        found.push(Event::Overflow);
+                    }
+                    Err(e) => debug!(path = %entry.path().display(), error = %e, "Not watched"),
+                }
+            }
+        }
+
+        fn handle(&mut self, batch: Vec<InotifyEvent>, found: &mut Vec<Event>) {
+            // Renames arrive as a pair sharing a cookie.
+            let mut moved_from: HashMap<u32, (PathBuf, bool)> = HashMap::new();
+            for event in batch {
+                if event.mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+                    found.push(Event::Overflow);
+                    continue;
+                }
+                if event.mask.contains(AddWatchFlags::IN_IGNORED) {
+                    self.dirs.remove(&event.wd);
+                    continue;
+                }
+                let (Some(dir), Some(name)) = (self.dirs.get(&event.wd), event.name) else {
+                    continue;
+                };
+                let path = dir.join(name);
+                let is_dir = event.mask.contains(AddWatchFlags::IN_ISDIR);
+                if event.mask.contains(AddWatchFlags::IN_MOVED_FROM) {
+                    moved_from.insert(event.cookie, (path, is_dir));
+                } else if event.mask.contains(AddWatchFlags::IN_MOVED_TO) {
+                    match moved_from.remove(&event.cookie) {
+                        Some((from, _)) => {
+                            if is_dir {
+                                self.move_dirs(&from, &path);
+                            }
+                            found.push(Event::Renamed { from, to: path });
+                        }
+                        None => self.created(path, is_dir, found),
+                    }
+                } else if event.mask.contains(AddWatchFlags::IN_CREATE) {
+                    self.created(path, is_dir, found);
+                } else if event.mask.contains(AddWatchFlags::IN_DELETE) {
+                    found.push(Event::Deleted(path));
+                } else if !is_dir {
+                    found.push(Event::Modified(path));
+                }
+            }
+            // Moved out of the workspace.
+            for (from, is_dir) in moved_from.into_values() {
+                if is_dir {
+                    self.unwatch(&from);
+                }
+                found.push(Event::Deleted(from));
+            }
+        }
+
+        fn created(&mut self, path: PathBuf, is_dir: bool, found: &mut Vec<Event>) {
+            found.push(Event::Created(path.clone()));
+            if is_dir && self.wanted(&path) {
+                self.add_tree(&path, found, true);
+            }
+        }
+
+        /// Renames the watched directories at and under `from`.
+        fn move_dirs(&mut self, from: &Path, to: &Path) {
+            for dir in self.dirs.values_mut() {
+                if let Ok(rest) = dir.strip_prefix(from) {
+                    *dir = to.join(rest);
+                }
+            }
+        }
+
+        fn unwatch(&mut self, under: &Path) {
+            let gone: Vec<WatchDescriptor> = self
+                .dirs
+                .iter()
+                .filter(|(_, dir)| dir.starts_with(under))
+                .map(|(wd, _)| *wd)
+                .collect();
+            for wd in gone {
+                let _ = self.inotify.rm_watch(wd);
+                self.dirs.remove(&wd);
+            }
+        }
+    }
+}
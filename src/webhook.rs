@@ -0,0 +1,158 @@
+use axum::{
+    Json,
+    body::{Body, Bytes},
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tracing::{Instrument, info, info_span, warn};
+
+use crate::{
+    config::WebhookAction,
+    http_rpc::{Oversized, read_body},
+    rpc::{error::HandlerError, git},
+    scheduler,
+    state::{AppState, SharedState},
+};
+
+/// `POST /hooks/<name>`: runs the webhook's configured action once the
+/// caller proves it holds the hook's token. The optional JSON body carries
+/// per-call arguments (currently only a broadcast `message`).
+pub async fn webhook_handler(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let span = info_span!("webhook", hook = %name);
+    let limit = state.limits().max_message_bytes;
+    match read_body(body, limit).await {
+        Ok(body) => handle(state, name, headers, body).instrument(span).await,
+        Err(Oversized(size)) => {
+            warn!(parent: &span, size, limit, "Rejecting oversized webhook body");
+            let message = format!("Body exceeds the {limit} byte limit");
+            error_response(StatusCode::PAYLOAD_TOO_LARGE, &message)
+        }
+    }
+}
+
+async fn handle(state: SharedState, name: String, headers: HeaderMap, body: Bytes) -> Response {
+    let Some(definition) = state.config.webhooks.get(&name) else {
+        warn!("Unknown webhook");
+        return error_response(StatusCode::NOT_FOUND, "Unknown webhook");
+    };
+    if !authorized(&headers, &definition.token) {
+        warn!("Rejected webhook call with a missing or wrong token");
+        return error_response(StatusCode::UNAUTHORIZED, "Invalid webhook token");
+    }
+    let body: Value = if body.is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(body) => body,
+            Err(e) => {
+                return error_response(StatusCode::BAD_REQUEST, &format!("Invalid body: {e}"));
+            }
+        }
+    };
+
+    let action = definition.action.clone();
+    let action_state = Arc::clone(&state);
+    let hook = name.clone();
+    let result =
+        tokio::task::spawn_blocking(move || run_action(&action_state, &hook, &action, &body))
+            .await
+            .unwrap_or_else(|e| Err(HandlerError::TaskError(format!("Webhook panicked: {e}"))));
+
+    let result = result.map_err(|e| {
+        let status = match e {
+            HandlerError::InvalidParams(_) => StatusCode::BAD_REQUEST,
+            HandlerError::DirtyWorkspace(_) => StatusCode::CONFLICT,
+            HandlerError::ReadOnly(_) => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, describe(&e))
+    });
+    let mut details = json!({ "hook": name, "action": definition.action });
+    match &result {
+        Ok(output) => details["output"] = json!(output),
+        Err((_, error)) => details["error"] = error["message"].clone(),
+    }
+    state.activity.record("webhook.triggered", None, details);
+
+    match result {
+        Ok(output) => {
+            info!("Webhook action completed");
+            (
+                StatusCode::OK,
+                Json(json!({ "hook": name, "action": definition.action, "output": output })),
+            )
+                .into_response()
+        }
+        Err((status, error)) => (status, Json(json!({ "error": error }))).into_response(),
+    }
+}
+
+/// Compares digests rather than the tokens themselves so the check takes
+/// the same time however much of the token matches.
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    let Some(presented) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    !token.is_empty() && blake3::hash(presented.as_bytes()) == blake3::hash(token.as_bytes())
+}
+
+fn run_action(
+    state: &AppState,
+    hook: &str,
+    action: &WebhookAction,
+    body: &Value,
+) -> Result<String, HandlerError> {
+    match action {
+        WebhookAction::RefreshIndex => Ok(scheduler::refresh_problems(state)),
+        WebhookAction::PullLatest { .. } if state.config.read_only => {
+            Err(HandlerError::ReadOnly("pullLatest".to_string()))
+        }
+        WebhookAction::PullLatest { remote, branch } => {
+            let mut args = vec!["pull", "--ff-only"];
+            if remote.is_some() || branch.is_some() {
+                args.push(remote.as_deref().unwrap_or("origin"));
+            }
+            args.extend(branch.as_deref());
+            let output = git::run_git(state, &args)?;
+            Ok(output.trim().to_string())
+        }
+        WebhookAction::Broadcast { message } => {
+            let message = body
+                .get("message")
+                .and_then(Value::as_str)
+                .or(message.as_deref())
+                .ok_or_else(|| {
+                    HandlerError::InvalidParams("No message to broadcast".to_string())
+                })?;
+            state.activity.record(
+                "server.message",
+                None,
+                json!({ "message": message, "hook": hook }),
+            );
+            Ok(format!("Broadcast: {message}"))
+        }
+    }
+}
+
+/// The JSON-RPC rendering of an error, so webhook callers see the same
+/// codes and data as editors.
+fn describe(error: &HandlerError) -> Value {
+    let response = error.to_jsonrpc_error(Value::Null);
+    serde_json::to_value(response.error).unwrap_or_default()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": { "message": message } }))).into_response()
+}
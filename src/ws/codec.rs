@@ -0,0 +1,65 @@
+use axum::extract::ws::Message;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::msgpack;
+
+/// How messages are framed on the socket, negotiated at `initialize`.
+/// Everything behind the socket speaks JSON text; the codec only converts
+/// at the edge, so handlers, recordings and replay buffers never see it.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    /// JSON in text frames.
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    /// MessagePack in binary frames.
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl Codec {
+    pub const ALL: &[Codec] = &[Codec::Json, Codec::MessagePack];
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "msgpack" => Some(Self::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// Picks the first codec the client offered that the server supports,
+    /// or JSON if none is.
+    pub fn negotiate(offered: &[String]) -> Self {
+        offered
+            .iter()
+            .find_map(|name| Self::parse(name))
+            .unwrap_or_default()
+    }
+
+    /// Frames an outgoing JSON message.
+    pub fn frame(self, text: String) -> Message {
+        match self {
+            Self::Json => Message::Text(text.into()),
+            Self::MessagePack => match serde_json::from_str(&text) {
+                Ok(value) => Message::Binary(msgpack::encode(&value).into()),
+                Err(e) => {
+                    warn!(error = %e, "Sending unencodable message as text");
+                    Message::Text(text.into())
+                }
+            },
+        }
+    }
+
+    /// The JSON text of an incoming binary frame. Without MessagePack a
+    /// binary frame is taken to hold JSON, as some clients send it.
+    pub fn read_binary(self, bytes: &[u8]) -> Result<String, String> {
+        match self {
+            Self::Json => String::from_utf8(bytes.to_vec()).map_err(|_| {
+                "binary frame is not UTF-8 JSON and msgpack was not negotiated".to_string()
+            }),
+            Self::MessagePack => msgpack::decode(bytes).map(|value| value.to_string()),
+        }
+    }
+}
@@ -1,100 +1,725 @@
 use axum::{
     extract::{
-        State,
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade, close_code},
     },
-    response::IntoResponse,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
 };
 use futures_util::{SinkExt, StreamExt};
-use std::sync::{
-    Arc,
-    atomic::{AtomicU64, Ordering},
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{broadcast, mpsc, oneshot},
+    task::JoinHandle,
+    time::Instant,
 };
-use tokio::sync::Mutex;
-use tracing::{Instrument, debug, error, info, info_span, warn};
+use tokio_tungstenite::tungstenite;
+use tracing::{Instrument, Span, debug, error, info, info_span, warn};
 
-use crate::rpc::{error::PARSE_ERROR_CODE, handlers::process_request};
+use super::lanes::ConnectionLanes;
+use super::rate_limit::TokenBucket;
+use super::record::SessionRecorder;
+use super::session::{Parked, ResumeError};
+use crate::{
+    fault::Outcome,
+    policy::Identity,
+    presence::PresenceChange,
+    rpc::{
+        cancel,
+        context::{ConnectionContext, Notifier, Replay},
+        error::{
+            HandlerError, PARSE_ERROR_CODE, PAYLOAD_TOO_LARGE_CODE, RATE_LIMITED_CODE,
+            create_error_response, create_error_response_with_data,
+        },
+        locale,
+        request::JsonRpcResponse,
+        request::{JsonRpcRequest, next_correlation_id},
+    },
+    state::SharedState,
+    tenant::Tenant,
+};
 
 static CONNECTION_COUNTER: AtomicU64 = AtomicU64::new(0);
 
+/// How long the writer gets to deliver a close frame before it is stopped.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Close code (from the 4000-4999 application range) for connections
+/// reaped after `idleTimeoutSecs` without traffic.
+const IDLE_TIMEOUT_CLOSE_CODE: u16 = 4000;
+/// Close code for a socket whose session a reconnecting client resumed.
+const SUPERSEDED_CLOSE_CODE: u16 = 4001;
+/// Close code for a connection whose client fell `maxOutboundBytes`
+/// behind in reading.
+const BACKLOG_CLOSE_CODE: u16 = 4002;
+
+fn close_frame(code: u16, reason: &str) -> CloseFrame {
+    CloseFrame {
+        code,
+        reason: reason.into(),
+    }
+}
+
+/// Picks the close frame for a failed read, so clients can tell an
+/// oversized message from a broken stream.
+fn read_error_frame(error: axum::Error) -> CloseFrame {
+    match error
+        .into_inner()
+        .downcast::<tungstenite::Error>()
+        .map(|e| *e)
+    {
+        Ok(tungstenite::Error::Capacity(_)) => close_frame(close_code::SIZE, "Message too large"),
+        Ok(tungstenite::Error::Utf8) => close_frame(close_code::INVALID, "Invalid UTF-8"),
+        // Such as a continuation frame with nothing to continue.
+        Ok(tungstenite::Error::Protocol(e)) => close_frame(close_code::PROTOCOL, &e.to_string()),
+        _ => close_frame(close_code::PROTOCOL, "Protocol error"),
+    }
+}
+
+/// The `id` of a request answered without being handled, so the client
+/// can match the error to it; null when none can be found. Only the `id`
+/// is kept, so this is cheap even on an oversized message.
+fn request_id(text: &str) -> serde_json::Value {
+    #[derive(Deserialize)]
+    struct Id {
+        id: Option<serde_json::Value>,
+    }
+    serde_json::from_str::<Id>(text)
+        .ok()
+        .and_then(|request| request.id)
+        .unwrap_or(serde_json::Value::Null)
+}
+
+/// The API key sent as `Authorization: Bearer <key>`, or as `?token=`
+/// since browsers cannot set headers on a WebSocket handshake.
+pub fn presented_key<'a>(
+    headers: &'a HeaderMap,
+    query: &'a HashMap<String, String>,
+) -> Option<&'a str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or(query.get("token").map(String::as_str))
+}
+
+/// Ids are shared by sockets and HTTP requests, so activity and logs never
+/// confuse the two.
+pub fn next_connection_id() -> u64 {
+    CONNECTION_COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
-    State(state): State<Arc<Mutex<()>>>,
-) -> impl IntoResponse {
-    let connection_id = CONNECTION_COUNTER.fetch_add(1, Ordering::Relaxed);
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let Some(identity) = state.policy.authenticate(presented_key(&headers, &query)) else {
+        warn!("Rejecting connection with unknown key");
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    // Taken before resuming, so a refused client keeps its parked session.
+    let Ok(slot) = Arc::clone(&state.connections).try_acquire_owned() else {
+        warn!(
+            limit = state.config.max_connections,
+            "Rejecting connection over the limit"
+        );
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+    let resumed = match query.get("resume") {
+        Some(token) => match state.sessions.resume(token, identity.name.as_deref()).await {
+            Ok(parked) => {
+                let last_seq = query
+                    .get("lastSeq")
+                    .and_then(|seq| seq.parse().ok())
+                    .unwrap_or(0);
+                Some((parked, last_seq))
+            }
+            Err(e) => {
+                warn!(error = ?e, "Refusing to resume session");
+                return match e {
+                    ResumeError::Unknown => StatusCode::GONE,
+                    ResumeError::Forbidden => StatusCode::FORBIDDEN,
+                    ResumeError::Busy => StatusCode::CONFLICT,
+                }
+                .into_response();
+            }
+        },
+        None => None,
+    };
+    let connection_id = match &resumed {
+        Some((parked, _)) => parked.connection.id,
+        None => next_connection_id(),
+    };
     info!(
         connection_id = connection_id,
+        resumed = resumed.is_some(),
         "WebSocket connection request received"
     );
-    ws.on_upgrade(move |socket| {
-        let connection_span = info_span!("ws_connection", connection_id = connection_id);
-        handle_socket(socket, state, connection_id).instrument(connection_span)
-    })
+    debug!(
+        connection_id,
+        identity = ?identity.name,
+        tier = ?identity.tier,
+        "Connection authenticated"
+    );
+    // Messages between the configured limit and twice it get a
+    // PAYLOAD_TOO_LARGE reply; anything larger is refused while reading so
+    // it is never buffered in full.
+    let hard_limit = state.limits().max_message_bytes.saturating_mul(2);
+    ws.max_message_size(hard_limit)
+        .max_frame_size(hard_limit)
+        .on_upgrade(move |socket| {
+            let connection_span = info_span!("ws_connection", connection_id = connection_id);
+            async move {
+                handle_socket(socket, state, connection_id, identity, resumed).await;
+                drop(slot);
+            }
+            .instrument(connection_span)
+        })
+        .into_response()
 }
 
-async fn handle_socket(socket: WebSocket, _state: Arc<Mutex<()>>, connection_id: u64) {
+async fn handle_socket(
+    socket: WebSocket,
+    state: SharedState,
+    connection_id: u64,
+    identity: Identity,
+    resumed: Option<(Parked, u64)>,
+) {
     info!(
         connection_id = connection_id,
         "WebSocket connection established"
     );
     let (mut sender, mut receiver) = socket.split();
 
-    while let Some(msg_result) = receiver.next().await {
+    // Responses and notifications share one queue drained by a writer task,
+    // so background work can push messages while requests are being read.
+    let (outbound, mut outbound_rx) = mpsc::unbounded_channel::<String>();
+    let (close_tx, mut close_rx) = oneshot::channel::<CloseFrame>();
+    let (connection, lanes, forwarder) = match resumed {
+        Some((parked, last_seq)) => {
+            let connection = parked.connection;
+            let (replayed, complete) = connection.notifier.attach(outbound, last_seq);
+            info!(last_seq, replayed, complete, "Session resumed");
+            connection.notifier.notify(
+                "session/resumed",
+                json!({ "replayed": replayed, "complete": complete }),
+            );
+            state
+                .activity
+                .record("connection.resumed", Some(connection_id), json!({}));
+            (connection, parked.lanes, parked.forwarder)
+        }
+        None => {
+            let connection = Arc::new(ConnectionContext::new(
+                connection_id,
+                Notifier::new(outbound, state.limits().max_outbound_bytes),
+                identity,
+            ));
+            let lanes = ConnectionLanes::spawn(&state, &connection);
+            let forwarder = forward_events(&state, &connection);
+            state
+                .activity
+                .record("connection.opened", Some(connection_id), json!({}));
+            (connection, lanes, forwarder)
+        }
+    };
+    let recorder = state.config.record_dir.as_ref().and_then(|dir| {
+        SessionRecorder::create(dir, connection_id, &state.config.root)
+            .map(Arc::new)
+            .map_err(|e| warn!(error = %e, "Failed to start session recording"))
+            .ok()
+    });
+    let writer_recorder = recorder.clone();
+    let writer_connection = Arc::clone(&connection);
+    let backlog = connection.notifier.backlog();
+    let stats = Arc::clone(&connection.stats);
+    let ping_interval = state.config.ping_interval_secs;
+    let mut writer = tokio::spawn(
+        async move {
+            let mut ping = tokio::time::interval(Duration::from_secs(ping_interval.max(1)));
+            ping.tick().await; // The first tick completes immediately
+            loop {
+                let message = tokio::select! {
+                    text = outbound_rx.recv() => match text {
+                        Some(text) => {
+                            backlog.sent(text.len());
+                            stats.record_sent(text.len());
+                            if let Some(recorder) = &writer_recorder {
+                                recorder.outbound(&text);
+                            }
+                            let codec = writer_connection.session().codec;
+                            codec.frame(text)
+                        }
+                        // The notifier is closed or gave up on the client;
+                        // the reader still says why.
+                        None => {
+                            if let Ok(frame) = (&mut close_rx).await {
+                                let _ = sender.send(Message::Close(Some(frame))).await;
+                            }
+                            return;
+                        }
+                    },
+                    _ = ping.tick(), if ping_interval > 0 => {
+                        Message::Ping(stats.ping_payload().into())
+                    }
+                    Ok(frame) = &mut close_rx => {
+                        let _ = sender.send(Message::Close(Some(frame))).await;
+                        return;
+                    }
+                };
+                if let Err(e) = sender.send(message).await {
+                    warn!(connection_id = connection_id, error = %e, "Failed to send message");
+                    return; // Connection closed
+                }
+            }
+        }
+        .instrument(Span::current()),
+    );
+    let mut rate_limit = TokenBucket::new(
+        state.config.rate_limit.requests_per_second,
+        state.config.rate_limit.burst,
+    );
+
+    // A client that keeps sending after this many consecutive RATE_LIMITED
+    // replies is ignoring them and gets disconnected.
+    let max_rejections = state.config.rate_limit.burst.max(1);
+    let mut rejections = 0;
+    let mut shutdown = state.shutdown.subscribe();
+    let idle_timeout = Duration::from_secs(state.config.idle_timeout_secs);
+    let mut last_activity = Instant::now();
+    let mut close = None;
+    let overflow = connection.notifier.backlog();
+
+    loop {
+        let msg_result = tokio::select! {
+            msg = receiver.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = tokio::time::sleep_until(last_activity + idle_timeout), if !idle_timeout.is_zero() => {
+                info!(connection_id = connection_id, idle_secs = idle_timeout.as_secs(), "Closing idle connection");
+                close = Some(close_frame(IDLE_TIMEOUT_CLOSE_CODE, "Idle timeout"));
+                break;
+            }
+            _ = shutdown.wait_for(|stopping| *stopping) => {
+                info!(connection_id = connection_id, "Closing connection for shutdown");
+                close = Some(close_frame(close_code::AWAY, "Server shutting down"));
+                break;
+            }
+            _ = overflow.overflowed() => {
+                info!(connection_id = connection_id, "Closing connection that stopped reading");
+                close = Some(close_frame(BACKLOG_CLOSE_CODE, "Outbound queue full"));
+                break;
+            }
+            _ = connection.superseded.notified() => {
+                info!(connection_id = connection_id, "Handing session over to a new connection");
+                close = Some(close_frame(SUPERSEDED_CLOSE_CODE, "Session resumed elsewhere"));
+                break;
+            }
+        };
+        last_activity = Instant::now();
         let msg = match msg_result {
             Ok(msg) => msg,
             Err(e) => {
                 warn!(connection_id = connection_id, error = %e, "WebSocket message error");
-                return; // Connection error, close gracefully
+                close = Some(read_error_frame(e));
+                break; // Connection error, close gracefully
             }
         };
 
-        if let Message::Text(text) = msg {
-            let request_span = info_span!(
-                "process_request",
-                connection_id = connection_id,
-                request_size = text.len()
+        if let Message::Pong(payload) = &msg {
+            connection.stats.record_pong(payload);
+            continue;
+        }
+        let correlation_id = next_correlation_id();
+
+        let text = match msg {
+            Message::Text(text) => text,
+            Message::Binary(bytes) => {
+                let codec = connection.session().codec;
+                match codec.read_binary(&bytes) {
+                    Ok(text) => text.into(),
+                    Err(e) => {
+                        warn!(error = %e, "Failed to decode binary request");
+                        let response = create_error_response_with_data(
+                            PARSE_ERROR_CODE,
+                            "Parse error",
+                            Some(json!({ "reason": e })),
+                            serde_json::Value::Null,
+                        );
+                        if !send_response(
+                            &state,
+                            &connection,
+                            &response.correlated(&correlation_id),
+                        ) {
+                            break;
+                        }
+                        continue;
+                    }
+                }
+            }
+            // Pings are answered by the socket and a close ends the stream.
+            _ => continue,
+        };
+        connection.stats.record_received(text.len());
+        if let Some(recorder) = &recorder {
+            recorder.inbound(&text);
+        }
+        let request_span = info_span!(
+            "process_request",
+            connection_id = connection_id,
+            correlation_id = %correlation_id,
+            request_size = text.len()
+        );
+        let _enter = request_span.enter();
+        state
+            .request_log
+            .received(&correlation_id, connection_id, &text);
+
+        let limit = state.limits().max_message_bytes;
+        if text.len() > limit {
+            warn!(size = text.len(), limit, "Rejecting oversized message");
+            let response = create_error_response_with_data(
+                PAYLOAD_TOO_LARGE_CODE,
+                &format!("Message exceeds the {limit} byte limit"),
+                Some(json!({ "size": text.len(), "limit": limit })),
+                request_id(&text),
             );
-            let _enter = request_span.enter();
+            if !send_response(&state, &connection, &response.correlated(&correlation_id)) {
+                break;
+            }
+            continue;
+        }
+
+        debug!(request = %text, "Received JSON-RPC request");
 
-            debug!(request = %text, "Received JSON-RPC request");
+        // Rejected outright rather than queued, so a flooding client
+        // cannot build an unbounded backlog.
+        if !rate_limit.try_acquire() {
+            rejections += 1;
+            if rejections > max_rejections {
+                warn!(
+                    connection_id = connection_id,
+                    rejections, "Closing connection that ignores rate limiting"
+                );
+                close = Some(close_frame(
+                    close_code::POLICY,
+                    "Rate limit repeatedly exceeded",
+                ));
+                break;
+            }
+            let id = request_id(&text);
+            warn!(
+                connection_id = connection_id,
+                "Request rejected by rate limit"
+            );
+            let response = create_error_response(RATE_LIMITED_CODE, "Rate limit exceeded", id);
+            if !send_response(&state, &connection, &response.correlated(&correlation_id)) {
+                break;
+            }
+            continue;
+        }
+        rejections = 0;
 
-            let response = match serde_json::from_str(&text) {
-                Ok(request) => {
-                    debug!("Request parsed successfully");
-                    process_request(request)
+        match serde_json::from_str::<JsonRpcRequest>(&text) {
+            Ok(mut request) => {
+                debug!("Request parsed successfully");
+                connection.stats.record_method(&request.method);
+                // Malformed cancellations go through the lanes so
+                // process_request rejects them like any other request.
+                if request.method == "$/cancelRequest" && request.validate().is_ok() {
+                    let outcome = cancel::handle_cancel_request(&connection, request.params);
+                    // Sent as a notification, as clients normally do.
+                    let Some(id) = request.id else {
+                        continue;
+                    };
+                    let response = match outcome {
+                        Ok(result) => JsonRpcResponse {
+                            jsonrpc: "2.0".to_string(),
+                            result: Some(result),
+                            error: None,
+                            id,
+                            correlation_id: None,
+                        },
+                        Err(e) => e.to_jsonrpc_error(id),
+                    };
+                    if !send_response(&state, &connection, &response.correlated(&correlation_id)) {
+                        break;
+                    }
+                    continue;
                 }
-                Err(e) => {
-                    warn!(error = %e, "Failed to parse JSON-RPC request");
-                    crate::rpc::error::create_error_response(
-                        PARSE_ERROR_CODE,
-                        "Parse error",
-                        serde_json::Value::Null,
-                    )
+                if let Some(id) = &request.id
+                    && request.validate().is_ok()
+                {
+                    match connection.notifier.check_replay(id) {
+                        Replay::Run => {}
+                        Replay::Pending => {
+                            debug!("Ignoring resent request that is still running");
+                            continue;
+                        }
+                        Replay::Answered(text) => {
+                            debug!("Resending response to a resent request");
+                            if !connection.notifier.resend(text) {
+                                break;
+                            }
+                            continue;
+                        }
+                        Replay::Expired => {
+                            let response = HandlerError::InvalidRequest(format!(
+                                "request id {id} was already answered in this session"
+                            ))
+                            .to_jsonrpc_error(id.clone());
+                            if !send_response(
+                                &state,
+                                &connection,
+                                &response.correlated(&correlation_id),
+                            ) {
+                                break;
+                            }
+                            continue;
+                        }
+                    }
                 }
-            };
-
-            let response_text = match serde_json::to_string(&response) {
-                Ok(text) => {
-                    debug!(
-                        response_size = text.len(),
-                        "Response serialized successfully"
-                    );
-                    text
+                request.correlation_id = correlation_id;
+                lanes.dispatch(request, request_span.clone());
+            }
+            // Well-formed JSON that is not a request object at all.
+            Err(e) if serde_json::from_str::<serde_json::Value>(&text).is_ok() => {
+                warn!(error = %e, "Rejecting malformed JSON-RPC request");
+                let response = HandlerError::InvalidRequest(
+                    "request must be an object with jsonrpc, method and optional params and id"
+                        .to_string(),
+                )
+                .to_jsonrpc_error(serde_json::Value::Null);
+                if !send_response(&state, &connection, &response.correlated(&correlation_id)) {
+                    break;
                 }
-                Err(e) => {
-                    error!(error = %e, "Failed to serialize response");
-                    continue; // Skip if we can't serialize the response
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to parse JSON-RPC request");
+                let response = create_error_response_with_data(
+                    PARSE_ERROR_CODE,
+                    "Parse error",
+                    Some(json!({ "line": e.line(), "column": e.column() })),
+                    serde_json::Value::Null,
+                );
+                if !send_response(&state, &connection, &response.correlated(&correlation_id)) {
+                    break; // Writer task stopped, connection closed
                 }
-            };
+            }
+        }
+    }
 
-            if let Err(e) = sender.send(Message::Text(response_text.into())).await {
-                warn!(connection_id = connection_id, error = %e, "Failed to send response");
-                return; // Connection closed
+    // Shutdown closes resumable sessions too, as nobody could resume them.
+    let token = connection.session().resume_token.clone();
+    let grace = state.config.session_grace_secs;
+    match token.filter(|_| grace > 0 && !*state.shutdown.borrow()) {
+        Some(token) => {
+            connection.notifier.detach();
+            let generation = state
+                .sessions
+                .park(&token, Arc::clone(&connection), lanes, forwarder);
+            let state = Arc::clone(&state);
+            tokio::spawn(
+                async move {
+                    tokio::time::sleep(Duration::from_secs(grace)).await;
+                    if let Some(parked) = state.sessions.expire(&token, generation) {
+                        info!(connection_id, "Session expired without being resumed");
+                        release(&state, &parked.connection, parked.lanes, parked.forwarder);
+                    }
+                }
+                .instrument(Span::current()),
+            );
+        }
+        None => release(&state, &connection, lanes, forwarder),
+    }
+    if let Some(frame) = close
+        && close_tx.send(frame).is_ok()
+    {
+        let _ = tokio::time::timeout(CLOSE_TIMEOUT, &mut writer).await;
+    }
+    writer.abort();
+    info!(connection_id = connection_id, "WebSocket connection closed");
+}
+
+/// Stops everything a connection started, once it is gone for good.
+fn release(
+    state: &SharedState,
+    connection: &ConnectionContext,
+    lanes: ConnectionLanes,
+    forwarder: JoinHandle<()>,
+) {
+    lanes.close();
+    forwarder.abort();
+    connection.flow.close_all();
+    connection.notifier.close();
+    close_resources(state, connection);
+    state
+        .activity
+        .record("connection.closed", Some(connection.id), json!({}));
+}
+
+/// Closes the terminals, tasks, documents, locks and other state a
+/// connection opened.
+pub fn close_resources(state: &SharedState, connection: &ConnectionContext) {
+    state.terminals.close_connection(connection.id);
+    state.tasks.close_connection(connection.id);
+    state.lsp.close_connection(connection.id);
+    state.dap.close_connection(connection.id);
+    state.blobs.close_connection(connection.id);
+    state.presence.remove(connection.id);
+    state.watcher.close_connection(connection.id);
+    for path in state.locks.close_connection(connection.id) {
+        state.activity.record(
+            "file.unlocked",
+            Some(connection.id),
+            json!({ "path": path, "reason": "disconnected" }),
+        );
+    }
+    state
+        .documents
+        .close_connection(connection.id, state.config.auto_save.enabled());
+    if let Some(token) = &connection.session().resume_token {
+        state.sessions.remove(token);
+    }
+}
+
+/// Pushes workspace activity and other clients' presence to the client as
+/// `activity/event` and `presence/changed` notifications until the
+/// connection closes. A confined connection only hears about paths in its
+/// own workspace.
+fn forward_events(state: &SharedState, connection: &ConnectionContext) -> JoinHandle<()> {
+    let mut events = state.activity.subscribe();
+    let mut presence = state.presence.subscribe();
+    let connection_id = connection.id;
+    let tenant = connection.identity.tenant.clone();
+    // Peers whose presence a confined connection has been shown.
+    let mut shown = HashSet::new();
+    let notifier = connection.notifier.clone();
+    let state = Arc::clone(state);
+    tokio::spawn(
+        async move {
+            loop {
+                tokio::select! {
+                    event = events.recv() => match event {
+                        Ok(event) => {
+                            let fault = state.faults.for_event(&event.kind);
+                            if let Some(delay) = fault.delay {
+                                tokio::time::sleep(delay).await;
+                            }
+                            if fault.outcome == Outcome::Drop {
+                                continue;
+                            }
+                            if let Some(tenant) = &tenant
+                                && !tenant.sees(&event.details)
+                            {
+                                continue;
+                            }
+                            if !notifier.notify("activity/event", &event) {
+                                return;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            warn!(missed, "Client fell behind the activity feed");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    },
+                    change = presence.recv() => match change {
+                        Ok(change) if change.connection_id == connection_id => {}
+                        Ok(change) => {
+                            let Some(change) = confine_presence(tenant.as_deref(), &mut shown, change)
+                            else {
+                                continue;
+                            };
+                            if !notifier.notify("presence/changed", &change) {
+                                return;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            warn!(missed, "Client fell behind presence changes");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    },
+                }
             }
+        }
+        .instrument(Span::current()),
+    )
+}
+
+/// The presence change a connection confined to `tenant` may see. Peers
+/// seen in its workspace are reported gone once they leave it, rather than
+/// followed elsewhere.
+fn confine_presence(
+    tenant: Option<&Tenant>,
+    shown: &mut HashSet<u64>,
+    change: PresenceChange,
+) -> Option<PresenceChange> {
+    let Some(tenant) = tenant else {
+        return Some(change);
+    };
+    let visible = change
+        .presence
+        .as_ref()
+        .and_then(|presence| serde_json::to_value(presence).ok())
+        .is_some_and(|presence| tenant.sees(&presence));
+    if visible {
+        shown.insert(change.connection_id);
+        Some(change)
+    } else if shown.remove(&change.connection_id) {
+        Some(PresenceChange {
+            connection_id: change.connection_id,
+            presence: None,
+        })
+    } else {
+        None
+    }
+}
 
-            debug!("Response sent successfully");
+/// Serializes a response onto the connection's outbound queue, returning
+/// false once the writer has stopped.
+pub fn send_response(
+    state: &SharedState,
+    connection: &ConnectionContext,
+    response: &JsonRpcResponse,
+) -> bool {
+    let locale = connection.session().locale;
+    let localized = locale::localize_response(locale, response);
+    let response = localized.as_ref().unwrap_or(response);
+    let response_text = match serde_json::to_string(response) {
+        Ok(text) => {
+            debug!(
+                response_size = text.len(),
+                "Response serialized successfully"
+            );
+            text
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to serialize response");
+            return true; // Skip if we can't serialize the response
         }
+    };
+    state.request_log.answered(response, &response_text);
+
+    if !connection
+        .notifier
+        .send_response(&response.id, response_text)
+    {
+        warn!(connection_id = connection.id, "Failed to queue response");
+        return false;
     }
 
-    info!(connection_id = connection_id, "WebSocket connection closed");
+    debug!("Response queued successfully");
+    true
 }
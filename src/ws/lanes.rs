@@ -0,0 +1,398 @@
+use serde_json::{Value, json};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::{Semaphore, mpsc},
+    task::{JoinHandle, JoinSet},
+};
+use tracing::{Instrument, Span, debug, error, warn};
+
+use super::{
+    connection::send_response,
+    pool::{BlockingPool, Saturated, Task},
+};
+use crate::{
+    clock,
+    fault::Outcome,
+    logging,
+    rpc::{
+        cancel::CancelToken,
+        context::ConnectionContext,
+        error::{HandlerError, INTERNAL_ERROR_CODE, create_error_response_with_data},
+        handlers::process_request,
+        request::{JsonRpcRequest, JsonRpcResponse},
+    },
+    state::SharedState,
+};
+
+/// Methods that walk the workspace, shell out, or move bulk data. Anything
+/// not listed is treated as interactive.
+const BACKGROUND_METHODS: &[&str] = &[
+    "blob/get",
+    "blob/put",
+    "diskUsage",
+    "exportZip",
+    "extractText",
+    "findFiles",
+    "formatDocument",
+    "git/blame",
+    "hashFile",
+    "importZip",
+    "readFileDelta",
+    "replaceInFiles",
+    "scan/run",
+    "searchInFiles",
+    "snippet/run",
+    "table/read",
+    "workspace/export",
+];
+
+pub fn is_background(method: &str) -> bool {
+    BACKGROUND_METHODS.contains(&method)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Interactive,
+    Background,
+}
+
+impl Priority {
+    /// Deterministic builds run everything on one lane so requests are
+    /// handled, and answered, in the order they arrive.
+    pub fn for_method(method: &str) -> Self {
+        if !clock::DETERMINISTIC && is_background(method) {
+            Priority::Background
+        } else {
+            Priority::Interactive
+        }
+    }
+}
+
+/// Server-wide limits on how many requests of each class may run at once.
+/// Interactive requests share tokio's blocking threads; background ones
+/// run on a small pool of their own, so they can never occupy every
+/// blocking thread while an editor is waiting on a keystroke.
+pub struct RequestLanes {
+    interactive: Arc<Semaphore>,
+    pub background: BlockingPool,
+}
+
+impl RequestLanes {
+    pub fn new(interactive: usize, background: usize, background_queue: usize) -> Self {
+        Self {
+            interactive: Arc::new(Semaphore::new(interactive.max(1))),
+            background: BlockingPool::new(background, background_queue),
+        }
+    }
+
+    /// The permits a request must hold to start; `None` for background
+    /// requests, which the pool queues or refuses instead.
+    pub fn semaphore(&self, priority: Priority) -> Option<Arc<Semaphore>> {
+        match priority {
+            Priority::Interactive => Some(Arc::clone(&self.interactive)),
+            Priority::Background => None,
+        }
+    }
+}
+
+/// A queued request with the span it was received under and its
+/// cancellation token.
+type Queued = (JsonRpcRequest, Span, CancelToken);
+
+/// Per-connection queues, one per priority, each drained by its own worker
+/// so interactive requests never wait behind a queued background one.
+/// Workers start each request as soon as permits allow and reply when it
+/// finishes, so responses may arrive out of order; clients correlate them
+/// by request id.
+pub struct ConnectionLanes {
+    interactive: mpsc::UnboundedSender<Queued>,
+    background: mpsc::UnboundedSender<Queued>,
+    workers: Vec<JoinHandle<()>>,
+    connection: Arc<ConnectionContext>,
+}
+
+impl ConnectionLanes {
+    pub fn spawn(state: &SharedState, connection: &Arc<ConnectionContext>) -> Self {
+        let (interactive, interactive_rx) = mpsc::unbounded_channel();
+        let (background, background_rx) = mpsc::unbounded_channel();
+        let max_in_flight = if clock::DETERMINISTIC {
+            1
+        } else {
+            state.config.max_concurrent_requests.max(1)
+        };
+        // Each lane has slots of its own, so a connection's background
+        // requests cannot hold the slots its interactive ones need.
+        let workers = vec![
+            spawn_worker(
+                state,
+                connection,
+                Priority::Interactive,
+                max_in_flight,
+                interactive_rx,
+            ),
+            spawn_worker(
+                state,
+                connection,
+                Priority::Background,
+                max_in_flight,
+                background_rx,
+            ),
+        ];
+        Self {
+            interactive,
+            background,
+            workers,
+            connection: Arc::clone(connection),
+        }
+    }
+
+    pub fn dispatch(&self, request: JsonRpcRequest, span: Span) {
+        let priority = Priority::for_method(&request.method);
+        debug!(method = %request.method, priority = ?priority, "Queueing request");
+        let queue = match priority {
+            Priority::Interactive => &self.interactive,
+            Priority::Background => &self.background,
+        };
+        // Registered now so a request can be cancelled while still queued.
+        let cancel = self.connection.cancellations.register(request.id.as_ref());
+        if queue.send((request, span, cancel)).is_err() {
+            error!(priority = ?priority, "Request lane worker stopped");
+        }
+    }
+
+    /// Stops the workers and the requests they started; requests still
+    /// queued are dropped.
+    pub fn close(self) {
+        for worker in self.workers {
+            worker.abort();
+        }
+    }
+}
+
+/// Takes requests off a lane queue and runs each on its own task once both
+/// the server-wide lane permit, if any, and one of the lane's
+/// `max_in_flight` per-connection slots are available.
+fn spawn_worker(
+    state: &SharedState,
+    connection: &Arc<ConnectionContext>,
+    priority: Priority,
+    max_in_flight: usize,
+    mut queue: mpsc::UnboundedReceiver<Queued>,
+) -> JoinHandle<()> {
+    let state = Arc::clone(state);
+    let connection = Arc::clone(connection);
+    let in_flight = Arc::new(Semaphore::new(max_in_flight));
+    tokio::spawn(
+        async move {
+            let lane = state.lanes.semaphore(priority);
+            // Dropping the set on abort cancels the requests it holds.
+            let mut running = JoinSet::new();
+            loop {
+                let (request, span, cancel) = tokio::select! {
+                    queued = queue.recv() => match queued {
+                        Some(queued) => queued,
+                        None => break,
+                    },
+                    Some(_) = running.join_next() => continue,
+                };
+
+                let lane_permit = match &lane {
+                    Some(lane) => match Arc::clone(lane).acquire_owned().await {
+                        Ok(permit) => Some(permit),
+                        Err(_) => return,
+                    },
+                    None => None,
+                };
+                let Ok(connection_permit) = Arc::clone(&in_flight).acquire_owned().await else {
+                    return;
+                };
+
+                let state = Arc::clone(&state);
+                let connection = Arc::clone(&connection);
+                running.spawn(async move {
+                    let _permits = (lane_permit, connection_permit);
+                    run_request(state, connection, request, span, cancel).await;
+                });
+            }
+            while running.join_next().await.is_some() {}
+        }
+        .instrument(Span::current()),
+    )
+}
+
+async fn run_request(
+    state: SharedState,
+    connection: Arc<ConnectionContext>,
+    request: JsonRpcRequest,
+    span: Span,
+    cancel: CancelToken,
+) {
+    let response = execute(
+        Arc::clone(&state),
+        Arc::clone(&connection),
+        request,
+        span.clone(),
+        cancel,
+    )
+    .await;
+    if let Some(response) = response {
+        let _enter = span.enter();
+        send_response(&state, &connection, &response);
+    }
+}
+
+/// Runs a request with its injected faults and timeout, returning the
+/// response to send, if any.
+pub async fn execute(
+    state: SharedState,
+    connection: Arc<ConnectionContext>,
+    request: JsonRpcRequest,
+    span: Span,
+    cancel: CancelToken,
+) -> Option<JsonRpcResponse> {
+    let id = request.id.clone();
+    let correlation_id = request.correlation_id.clone();
+    let fault = state.faults.for_method(&request.method);
+    if let Some(delay) = fault.delay {
+        tokio::time::sleep(delay).await;
+    }
+    let response = if fault.outcome == Outcome::Drop {
+        Ok(None)
+    } else if fault.outcome == Outcome::Fail {
+        Ok(Some(create_error_response_with_data(
+            INTERNAL_ERROR_CODE,
+            "Injected fault",
+            Some(json!({ "injected": true })),
+            id.clone().unwrap_or(Value::Null),
+        )))
+    } else if cancel.is_cancelled() {
+        Ok(Some(
+            HandlerError::RequestCancelled.to_jsonrpc_error(id.clone().unwrap_or(Value::Null)),
+        ))
+    } else {
+        let timeout = state.config.timeouts.for_method(&request.method);
+        // Handlers do blocking file and process I/O.
+        let handler_state = Arc::clone(&state);
+        let handler_connection = Arc::clone(&connection);
+        let handler_span = span.clone();
+        let handler_cancel = cancel.clone();
+        let inject_panic = fault.outcome == Outcome::Panic;
+        let priority = Priority::for_method(&request.method);
+        let work = move || {
+            handler_span.in_scope(|| {
+                let id = request.id.clone();
+                let reference = request.correlation_id.clone();
+                // A panicking handler fails its own request only; the
+                // connection and every other request carry on.
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    if inject_panic {
+                        panic!("Injected fault");
+                    }
+                    process_request(
+                        &handler_state,
+                        &handler_connection,
+                        request,
+                        &handler_cancel,
+                    )
+                }))
+                .unwrap_or_else(|payload| {
+                    error!(
+                        reference = %reference,
+                        panic = logging::panic_message(&*payload),
+                        "Request handler panicked"
+                    );
+                    id.map(|id| HandlerError::Internal { reference }.to_jsonrpc_error(id))
+                })
+            })
+        };
+        let handler = match priority {
+            Priority::Interactive => Handler::Shared(tokio::task::spawn_blocking(work)),
+            Priority::Background => match state.lanes.background.spawn(work) {
+                Ok(task) => Handler::Pooled(task),
+                Err(Saturated(queued)) => {
+                    let _enter = span.enter();
+                    warn!(queued, "Background pool saturated; refusing request");
+                    Handler::Refused(Box::new(
+                        HandlerError::ServerBusy { queued }
+                            .to_jsonrpc_error(id.clone().unwrap_or(Value::Null)),
+                    ))
+                }
+            },
+        };
+        match (handler, timeout) {
+            (Handler::Refused(response), _) => Ok(Some(*response)),
+            (Handler::Shared(handle), None) => handle.await.map_err(|e| e.to_string()),
+            (Handler::Pooled(task), None) => task.await.ok_or_else(lost),
+            (Handler::Shared(mut handle), Some(limit)) => {
+                match tokio::time::timeout(limit, &mut handle).await {
+                    Ok(response) => response.map_err(|e| e.to_string()),
+                    Err(_) => Ok(timed_out(&span, &cancel, limit, &id)),
+                }
+            }
+            (Handler::Pooled(mut task), Some(limit)) => {
+                match tokio::time::timeout(limit, &mut task).await {
+                    Ok(response) => response.ok_or_else(lost),
+                    Err(_) => {
+                        // Its thread is replaced, so later background
+                        // requests need not wait for it to wind down.
+                        task.abandon();
+                        Ok(timed_out(&span, &cancel, limit, &id))
+                    }
+                }
+            }
+        }
+    };
+    connection.cancellations.finish(id.as_ref());
+
+    let _enter = span.enter();
+    // `None` when the handler replies asynchronously.
+    response
+        .unwrap_or_else(|e| {
+            error!(error = %e, "Request handler did not finish");
+            None
+        })
+        .map(|response| response.correlated(&correlation_id))
+}
+
+/// Where a request's handler runs.
+enum Handler {
+    Shared(JoinHandle<Option<JsonRpcResponse>>),
+    Pooled(Task<Option<JsonRpcResponse>>),
+    Refused(Box<JsonRpcResponse>),
+}
+
+/// The reply to a request that ran out of time. The blocking thread cannot
+/// be stopped from here; cancelling lets cooperative handlers wind down.
+fn timed_out(
+    span: &Span,
+    cancel: &CancelToken,
+    limit: Duration,
+    id: &Option<Value>,
+) -> Option<JsonRpcResponse> {
+    let _enter = span.enter();
+    warn!(limit_ms = limit.as_millis() as u64, "Request timed out");
+    cancel.cancel();
+    Some(HandlerError::Timeout(limit).to_jsonrpc_error(id.clone().unwrap_or(Value::Null)))
+}
+
+fn lost() -> String {
+    "background job dropped".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `maxSearchConcurrency` counts background requests, so searches must
+    /// be among them.
+    #[test]
+    fn searches_run_in_the_background() {
+        for method in ["findFiles", "replaceInFiles", "searchInFiles"] {
+            assert!(is_background(method), "{method} is not background");
+        }
+    }
+}
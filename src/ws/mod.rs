@@ -1,3 +1,9 @@
+pub mod codec;
 pub mod connection;
+pub mod lanes;
+pub mod pool;
+pub mod rate_limit;
+pub mod record;
+pub mod session;
 
 pub use connection::ws_handler;
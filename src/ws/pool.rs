@@ -0,0 +1,279 @@
+//! Threads of their own for background requests, the ones that hash, zip
+//! or walk the workspace, so they never hold tokio's blocking threads
+//! while an editor waits on a keystroke. The pool is bounded twice: by
+//! its threads and by how many jobs may wait for one. Jobs beyond that
+//! are refused straight away rather than piling up.
+//!
+//! A blocking thread cannot be stopped from outside, so a job that
+//! outlives its request's timeout is abandoned instead: its thread is
+//! written off and a fresh one takes its place, and the old thread exits
+//! once the job returns. At most as many threads as the pool has are
+//! written off at once; past that the pool runs short until they finish.
+
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    thread,
+};
+use tokio::sync::oneshot;
+use tracing::{debug, error, warn};
+
+type Work = Box<dyn FnOnce() + Send>;
+
+/// How far along a job is, shared between its thread and its [`Task`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Queued,
+    Running,
+    /// Abandoned while queued; the job is skipped.
+    Dropped,
+    /// Abandoned while running; `replaced` says whether a thread was
+    /// started in its place.
+    Abandoned {
+        replaced: bool,
+    },
+    Done,
+}
+
+struct Job {
+    work: Work,
+    stage: Arc<Mutex<Stage>>,
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<Job>>,
+    ready: Condvar,
+    threads: usize,
+    max_queued: usize,
+    busy: AtomicUsize,
+    written_off: AtomicUsize,
+    completed: AtomicU64,
+    rejected: AtomicU64,
+    recycled: AtomicU64,
+}
+
+/// Counters for `server/info`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolStats {
+    pub threads: usize,
+    /// Threads running a job, not counting written-off ones.
+    pub busy: usize,
+    pub queued: usize,
+    pub max_queued: usize,
+    /// Threads still running a job that was abandoned.
+    pub written_off: usize,
+    pub completed: u64,
+    /// Jobs refused because the queue was full.
+    pub rejected: u64,
+    /// Threads started in place of written-off ones.
+    pub recycled: u64,
+}
+
+/// The queue was full; carries its length.
+#[derive(Debug, Clone, Copy)]
+pub struct Saturated(pub usize);
+
+pub struct BlockingPool {
+    shared: Arc<Shared>,
+}
+
+impl BlockingPool {
+    pub fn new(threads: usize, max_queued: usize) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::new()),
+            ready: Condvar::new(),
+            threads: threads.max(1),
+            max_queued,
+            busy: AtomicUsize::new(0),
+            written_off: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            recycled: AtomicU64::new(0),
+        });
+        for _ in 0..shared.threads {
+            start_thread(&shared);
+        }
+        Self { shared }
+    }
+
+    /// Queues `work`, unless `max_queued` jobs are already waiting for a
+    /// thread. Jobs an idle thread will pick up straight away do not count
+    /// as waiting, so with `max_queued` at zero work runs only when a
+    /// thread is free for it.
+    pub fn spawn<T, F>(&self, work: F) -> Result<Task<T>, Saturated>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let mut queue = lock(&self.shared.queue);
+        let idle = self
+            .shared
+            .threads
+            .saturating_sub(self.shared.busy.load(Ordering::Relaxed));
+        if (queue.len() + 1).saturating_sub(idle) > self.shared.max_queued {
+            self.shared.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(Saturated(queue.len()));
+        }
+        let (sender, receiver) = oneshot::channel();
+        let stage = Arc::new(Mutex::new(Stage::Queued));
+        queue.push_back(Job {
+            work: Box::new(move || {
+                let _ = sender.send(work());
+            }),
+            stage: Arc::clone(&stage),
+        });
+        drop(queue);
+        self.shared.ready.notify_one();
+        Ok(Task {
+            receiver,
+            stage,
+            shared: Arc::clone(&self.shared),
+        })
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let shared = &self.shared;
+        PoolStats {
+            threads: shared.threads,
+            busy: shared.busy.load(Ordering::Relaxed),
+            queued: lock(&shared.queue).len(),
+            max_queued: shared.max_queued,
+            written_off: shared.written_off.load(Ordering::Relaxed),
+            completed: shared.completed.load(Ordering::Relaxed),
+            rejected: shared.rejected.load(Ordering::Relaxed),
+            recycled: shared.recycled.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A queued job's result. Resolves to `None` if the job panicked.
+pub struct Task<T> {
+    receiver: oneshot::Receiver<T>,
+    stage: Arc<Mutex<Stage>>,
+    shared: Arc<Shared>,
+}
+
+impl<T> Task<T> {
+    /// Gives up on the job: skips it if it has not started, and writes off
+    /// its thread if it has.
+    pub fn abandon(&self) {
+        let mut stage = lock(&self.stage);
+        match *stage {
+            Stage::Queued => *stage = Stage::Dropped,
+            Stage::Running => {
+                let shared = &self.shared;
+                let written_off = shared.written_off.fetch_add(1, Ordering::Relaxed);
+                let replaced = written_off < shared.threads;
+                *stage = Stage::Abandoned { replaced };
+                shared.busy.fetch_sub(1, Ordering::Relaxed);
+                if replaced {
+                    shared.recycled.fetch_add(1, Ordering::Relaxed);
+                    debug!("Replacing background thread of an abandoned job");
+                    start_thread(shared);
+                } else {
+                    warn!(
+                        threads = shared.threads,
+                        "Too many background threads written off; not replacing another"
+                    );
+                }
+            }
+            Stage::Dropped | Stage::Abandoned { .. } | Stage::Done => {}
+        }
+    }
+}
+
+impl<T> Future for Task<T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx).map(Result::ok)
+    }
+}
+
+fn start_thread(shared: &Arc<Shared>) {
+    let shared = Arc::clone(shared);
+    let started = thread::Builder::new()
+        .name("background".to_string())
+        .spawn(move || work(&shared));
+    if let Err(e) = started {
+        error!(error = %e, "Failed to start background thread");
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let job = {
+            let mut queue = lock(&shared.queue);
+            loop {
+                match queue.pop_front() {
+                    Some(job) => break job,
+                    None => queue = shared.ready.wait(queue).unwrap_or_else(|e| e.into_inner()),
+                }
+            }
+        };
+        {
+            let mut stage = lock(&job.stage);
+            if *stage == Stage::Dropped {
+                continue;
+            }
+            *stage = Stage::Running;
+            shared.busy.fetch_add(1, Ordering::Relaxed);
+        }
+        // The work catches its own panics; this keeps the thread alive
+        // should one slip through.
+        let _ = panic::catch_unwind(AssertUnwindSafe(job.work));
+        shared.completed.fetch_add(1, Ordering::Relaxed);
+
+        let mut stage = lock(&job.stage);
+        match *stage {
+            Stage::Abandoned { replaced } => {
+                shared.written_off.fetch_sub(1, Ordering::Relaxed);
+                if replaced {
+                    debug!("Abandoned background job finished; thread exiting");
+                    return;
+                }
+            }
+            _ => {
+                shared.busy.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+        *stage = Stage::Done;
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+
+    #[test]
+    fn zero_queue_runs_jobs_while_a_thread_is_free() {
+        let pool = BlockingPool::new(1, 0);
+        let (release, held) = mpsc::channel::<()>();
+        let (started, running) = mpsc::channel();
+        let task = pool
+            .spawn(move || {
+                started.send(()).unwrap();
+                held.recv().unwrap();
+            })
+            .expect("an idle thread takes the job");
+        running.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        assert!(pool.spawn(|| ()).is_err(), "no thread is free");
+        release.send(()).unwrap();
+        drop(task);
+    }
+}
@@ -0,0 +1,66 @@
+use serde_json::json;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+use tracing::{info, warn};
+
+use crate::clock;
+
+/// Writes every frame of one connection to `session-<id>-<unix secs>.jsonl`.
+///
+/// The first line describes the session (`type: "session"`, `root`); each
+/// following line is `{"t": <ms since start>, "direction": "in" | "out",
+/// "text": <raw frame>}`. Frames are stored verbatim so malformed requests
+/// replay exactly as received.
+pub struct SessionRecorder {
+    file: Mutex<BufWriter<File>>,
+    started: Instant,
+}
+
+impl SessionRecorder {
+    pub fn create(dir: &Path, connection_id: u64, root: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let started_at = clock::unix_secs();
+        let path = dir.join(format!("session-{connection_id}-{started_at}.jsonl"));
+        let mut file = BufWriter::new(File::create(&path)?);
+        let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let header = json!({
+            "type": "session",
+            "connectionId": connection_id,
+            "startedAt": started_at,
+            "root": root,
+        });
+        writeln!(file, "{header}")?;
+        file.flush()?;
+
+        info!(path = %path.display(), "Recording session");
+        Ok(Self {
+            file: Mutex::new(file),
+            started: clock::now(),
+        })
+    }
+
+    pub fn inbound(&self, text: &str) {
+        self.write("in", text);
+    }
+
+    pub fn outbound(&self, text: &str) {
+        self.write("out", text);
+    }
+
+    fn write(&self, direction: &str, text: &str) {
+        let entry = json!({
+            "t": clock::now().duration_since(self.started).as_millis() as u64,
+            "direction": direction,
+            "text": text,
+        });
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{entry}").and_then(|_| file.flush()) {
+            warn!(error = %e, "Failed to write session recording");
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{sync::Notify, task::JoinHandle};
+use tracing::{debug, info};
+
+use super::lanes::ConnectionLanes;
+use crate::rpc::context::ConnectionContext;
+
+/// How long a resume waits for the socket it replaces to let go.
+const HANDOVER_TIMEOUT: Duration = Duration::from_secs(2);
+const HANDOVER_POLL: Duration = Duration::from_millis(20);
+
+/// A session whose socket dropped, kept running for `sessionGraceSecs` so
+/// a reconnecting client finds its terminals, open documents and
+/// subscriptions as it left them.
+pub struct Parked {
+    pub connection: Arc<ConnectionContext>,
+    pub lanes: ConnectionLanes,
+    /// Task relaying activity and presence notifications.
+    pub forwarder: JoinHandle<()>,
+    /// Tells an expiry timer apart from the one of a later disconnect.
+    generation: u64,
+}
+
+enum Entry {
+    /// In use by a socket, which is woken through `superseded` to hand
+    /// the session over.
+    Attached {
+        identity: Option<String>,
+        superseded: Arc<Notify>,
+    },
+    Parked(Parked),
+}
+
+impl Entry {
+    fn attached(connection: &ConnectionContext) -> Self {
+        Entry::Attached {
+            identity: connection.identity.name.clone(),
+            superseded: Arc::clone(&connection.superseded),
+        }
+    }
+
+    fn identity(&self) -> Option<&str> {
+        match self {
+            Entry::Attached { identity, .. } => identity.as_deref(),
+            Entry::Parked(parked) => parked.connection.identity.name.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ResumeError {
+    Unknown,
+    /// The token belongs to another identity.
+    Forbidden,
+    /// The previous socket did not let go in time.
+    Busy,
+}
+
+/// Resumable sessions by token.
+#[derive(Default)]
+pub struct SessionRegistry {
+    sessions: Mutex<HashMap<String, Entry>>,
+    generations: AtomicU64,
+}
+
+impl SessionRegistry {
+    pub fn issue(&self, token: &str, connection: &ConnectionContext) {
+        debug!(connection_id = connection.id, "Issued session token");
+        self.lock()
+            .insert(token.to_string(), Entry::attached(connection));
+    }
+
+    /// Parks a session whose socket closed, returning the generation its
+    /// expiry timer passes to [`SessionRegistry::expire`].
+    pub fn park(
+        &self,
+        token: &str,
+        connection: Arc<ConnectionContext>,
+        lanes: ConnectionLanes,
+        forwarder: JoinHandle<()>,
+    ) -> u64 {
+        let generation = self.generations.fetch_add(1, Ordering::Relaxed);
+        info!(connection_id = connection.id, "Parking session for resume");
+        self.lock().insert(
+            token.to_string(),
+            Entry::Parked(Parked {
+                connection,
+                lanes,
+                forwarder,
+                generation,
+            }),
+        );
+        generation
+    }
+
+    /// Takes a parked session for a new socket. A session still attached to
+    /// another socket is asked to let go first, which the client needs when
+    /// it notices a dead connection before the server does.
+    pub async fn resume(&self, token: &str, identity: Option<&str>) -> Result<Parked, ResumeError> {
+        let deadline = tokio::time::Instant::now() + HANDOVER_TIMEOUT;
+        loop {
+            {
+                let mut sessions = self.lock();
+                let Some(entry) = sessions.remove(token) else {
+                    return Err(ResumeError::Unknown);
+                };
+                if entry.identity() != identity {
+                    sessions.insert(token.to_string(), entry);
+                    return Err(ResumeError::Forbidden);
+                }
+                if let Entry::Parked(parked) = entry {
+                    sessions.insert(token.to_string(), Entry::attached(&parked.connection));
+                    return Ok(parked);
+                }
+                if let Entry::Attached { superseded, .. } = &entry {
+                    superseded.notify_waiters();
+                }
+                sessions.insert(token.to_string(), entry);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(ResumeError::Busy);
+            }
+            tokio::time::sleep(HANDOVER_POLL).await;
+        }
+    }
+
+    /// Removes a session that was parked at `generation` and never resumed.
+    pub fn expire(&self, token: &str, generation: u64) -> Option<Parked> {
+        let mut sessions = self.lock();
+        match sessions.get(token) {
+            Some(Entry::Parked(parked)) if parked.generation == generation => {}
+            _ => return None,
+        }
+        match sessions.remove(token) {
+            Some(Entry::Parked(parked)) => Some(parked),
+            _ => None,
+        }
+    }
+
+    /// Forgets a session that closed for good.
+    pub fn remove(&self, token: &str) {
+        self.lock().remove(token);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Entry>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}